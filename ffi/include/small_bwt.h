@@ -0,0 +1,65 @@
+/*
+ * C bindings of small-bwt.
+ *
+ * Link with libsmall_bwt_ffi built by `cargo build --release -p small-bwt-ffi`.
+ * Every function returns a small_bwt_status_t and writes its outputs
+ * to buffers allocated by the caller.
+ */
+#ifndef SMALL_BWT_H
+#define SMALL_BWT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes, which are stable and never renumbered. */
+typedef enum small_bwt_status {
+    SMALL_BWT_OK = 0,
+    SMALL_BWT_NULL_POINTER = 1,
+    SMALL_BWT_INVALID_TEXT = 2,
+    SMALL_BWT_INVALID_BWT = 3,
+    SMALL_BWT_BUFFER_TOO_SMALL = 4,
+    SMALL_BWT_BUILD_FAILED = 5,
+    SMALL_BWT_PANIC = 6,
+} small_bwt_status_t;
+
+/* Returns the static description of status, which must not be freed. */
+const char *small_bwt_status_message(small_bwt_status_t status);
+
+/*
+ * Builds the BWT of text ending with a smallest unique terminator, such as '\0'.
+ * bwt must have at least text_len bytes. primary_index may be NULL.
+ */
+small_bwt_status_t small_bwt_build(const uint8_t *text, size_t text_len,
+                                   uint8_t *bwt, size_t bwt_len,
+                                   size_t *primary_index);
+
+/*
+ * Builds the BWT of arbitrary bytes followed by an implicit sentinel,
+ * written as placeholder. bwt must have at least text_len + 1 bytes.
+ */
+small_bwt_status_t small_bwt_build_with_sentinel(const uint8_t *text, size_t text_len,
+                                                 uint8_t placeholder,
+                                                 uint8_t *bwt, size_t bwt_len,
+                                                 size_t *sentinel_index);
+
+/* Decodes a BWT built by small_bwt_build. text must have at least bwt_len bytes. */
+small_bwt_status_t small_bwt_decode(const uint8_t *bwt, size_t bwt_len,
+                                    uint8_t *text, size_t text_len);
+
+/*
+ * Decodes a BWT built by small_bwt_build_with_sentinel.
+ * text must have at least bwt_len - 1 bytes.
+ */
+small_bwt_status_t small_bwt_decode_with_sentinel(const uint8_t *bwt, size_t bwt_len,
+                                                  size_t sentinel_index,
+                                                  uint8_t *text, size_t text_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SMALL_BWT_H */
@@ -0,0 +1,324 @@
+//! # C bindings of small-bwt
+//!
+//! `extern "C"` functions to build and decode the BWT over raw buffers,
+//! declared in `include/small_bwt.h` for C and C++ callers.
+//! The crate is built as `libsmall_bwt_ffi.a` and `libsmall_bwt_ffi.so` (or the platform equivalents).
+//!
+//! Every function returns a [`SmallBwtStatus`] and writes its outputs to buffers allocated by the caller,
+//! so that no memory is allocated across the boundary.
+//! A panic is caught and reported as [`SmallBwtStatus::Panic`] instead of unwinding into the caller.
+#![deny(missing_docs)]
+
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::slice;
+
+use small_bwt::BwtBuilder;
+
+/// Status code returned by the functions.
+///
+/// The values are stable and never renumbered, so that they can be compared across versions.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SmallBwtStatus {
+    /// Succeeded.
+    Ok = 0,
+    /// A required pointer is null.
+    NullPointer = 1,
+    /// The text does not end with a smallest unique terminator.
+    InvalidText = 2,
+    /// The BWT cannot be decoded.
+    InvalidBwt = 3,
+    /// The output buffer is shorter than the output.
+    BufferTooSmall = 4,
+    /// The construction failed for another reason.
+    BuildFailed = 5,
+    /// An internal panic was caught.
+    Panic = 6,
+}
+
+impl SmallBwtStatus {
+    const fn message(self) -> &'static [u8] {
+        match self {
+            Self::Ok => b"ok\0",
+            Self::NullPointer => b"a required pointer is null\0",
+            Self::InvalidText => b"the text does not end with a smallest unique terminator\0",
+            Self::InvalidBwt => b"the BWT cannot be decoded\0",
+            Self::BufferTooSmall => b"the output buffer is too small\0",
+            Self::BuildFailed => b"the construction failed\0",
+            Self::Panic => b"an internal panic was caught\0",
+        }
+    }
+}
+
+/// Returns the static null-terminated description of `status`, which must not be freed.
+#[no_mangle]
+pub const extern "C" fn small_bwt_status_message(status: SmallBwtStatus) -> *const c_char {
+    status.message().as_ptr().cast()
+}
+
+/// Builds the BWT of a text ending with a smallest unique terminator, such as `\0`.
+///
+/// The BWT of `text_len` bytes is written to `bwt`,
+/// and the primary index is written to `primary_index` unless it is null.
+///
+/// # Safety
+///
+/// `text` must point to `text_len` readable bytes,
+/// `bwt` must point to `bwt_len` writable bytes not overlapping `text`,
+/// and `primary_index` must be null or point to a writable `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn small_bwt_build(
+    text: *const u8,
+    text_len: usize,
+    bwt: *mut u8,
+    bwt_len: usize,
+    primary_index: *mut usize,
+) -> SmallBwtStatus {
+    if text.is_null() || bwt.is_null() {
+        return SmallBwtStatus::NullPointer;
+    }
+    if bwt_len < text_len {
+        return SmallBwtStatus::BufferTooSmall;
+    }
+    let text = slice::from_raw_parts(text, text_len);
+    let bwt = slice::from_raw_parts_mut(bwt, text_len);
+    guard(|| {
+        let builder = match BwtBuilder::new(text) {
+            Ok(builder) => builder,
+            Err(_) => return SmallBwtStatus::InvalidText,
+        };
+        match builder.build(&mut bwt[..]) {
+            Ok(stats) => {
+                if !primary_index.is_null() {
+                    *primary_index = stats.primary_index;
+                }
+                SmallBwtStatus::Ok
+            }
+            Err(_) => SmallBwtStatus::BuildFailed,
+        }
+    })
+}
+
+/// Builds the BWT of arbitrary bytes followed by an implicit sentinel smaller than every byte.
+///
+/// The BWT of `text_len + 1` bytes is written to `bwt`, where the sentinel is written as `placeholder`,
+/// and its row is written to `sentinel_index`.
+///
+/// # Safety
+///
+/// `text` must point to `text_len` readable bytes,
+/// `bwt` must point to `bwt_len` writable bytes not overlapping `text`,
+/// and `sentinel_index` must point to a writable `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn small_bwt_build_with_sentinel(
+    text: *const u8,
+    text_len: usize,
+    placeholder: u8,
+    bwt: *mut u8,
+    bwt_len: usize,
+    sentinel_index: *mut usize,
+) -> SmallBwtStatus {
+    if text.is_null() || bwt.is_null() || sentinel_index.is_null() {
+        return SmallBwtStatus::NullPointer;
+    }
+    if bwt_len <= text_len {
+        return SmallBwtStatus::BufferTooSmall;
+    }
+    let text = slice::from_raw_parts(text, text_len);
+    let bwt = slice::from_raw_parts_mut(bwt, text_len + 1);
+    guard(|| {
+        let built = BwtBuilder::new_unchecked(text)
+            .and_then(|builder| builder.implicit_sentinel(placeholder).build(&mut bwt[..]));
+        match built {
+            Ok(stats) => {
+                *sentinel_index = stats.primary_index;
+                SmallBwtStatus::Ok
+            }
+            Err(_) => SmallBwtStatus::BuildFailed,
+        }
+    })
+}
+
+/// Decodes the BWT built by [`small_bwt_build`] into the text of `bwt_len` bytes written to `text`.
+///
+/// # Safety
+///
+/// `bwt` must point to `bwt_len` readable bytes,
+/// and `text` must point to `text_len` writable bytes not overlapping `bwt`.
+#[no_mangle]
+pub unsafe extern "C" fn small_bwt_decode(
+    bwt: *const u8,
+    bwt_len: usize,
+    text: *mut u8,
+    text_len: usize,
+) -> SmallBwtStatus {
+    if bwt.is_null() || text.is_null() {
+        return SmallBwtStatus::NullPointer;
+    }
+    if text_len < bwt_len {
+        return SmallBwtStatus::BufferTooSmall;
+    }
+    let bwt = slice::from_raw_parts(bwt, bwt_len);
+    let text = slice::from_raw_parts_mut(text, bwt_len);
+    guard(|| {
+        small_bwt::decode_bwt(bwt).map_or(SmallBwtStatus::InvalidBwt, |decoded| {
+            text.copy_from_slice(&decoded);
+            SmallBwtStatus::Ok
+        })
+    })
+}
+
+/// Decodes the BWT built by [`small_bwt_build_with_sentinel`]
+/// into the text of `bwt_len - 1` bytes written to `text`.
+///
+/// # Safety
+///
+/// `bwt` must point to `bwt_len` readable bytes,
+/// and `text` must point to `text_len` writable bytes not overlapping `bwt`.
+#[no_mangle]
+pub unsafe extern "C" fn small_bwt_decode_with_sentinel(
+    bwt: *const u8,
+    bwt_len: usize,
+    sentinel_index: usize,
+    text: *mut u8,
+    text_len: usize,
+) -> SmallBwtStatus {
+    if bwt.is_null() || text.is_null() {
+        return SmallBwtStatus::NullPointer;
+    }
+    if bwt_len == 0 {
+        return SmallBwtStatus::InvalidBwt;
+    }
+    if text_len < bwt_len - 1 {
+        return SmallBwtStatus::BufferTooSmall;
+    }
+    let bwt = slice::from_raw_parts(bwt, bwt_len);
+    let text = slice::from_raw_parts_mut(text, bwt_len - 1);
+    guard(|| {
+        small_bwt::decode_bwt_with_sentinel(bwt, sentinel_index).map_or(
+            SmallBwtStatus::InvalidBwt,
+            |decoded| {
+                text.copy_from_slice(&decoded);
+                SmallBwtStatus::Ok
+            },
+        )
+    })
+}
+
+/// Runs `f`, reporting a panic as a status instead of unwinding across the boundary.
+fn guard<F: FnOnce() -> SmallBwtStatus>(f: F) -> SmallBwtStatus {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(SmallBwtStatus::Panic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CStr;
+    use std::ptr;
+
+    #[test]
+    fn test_build_and_decode() {
+        let text = b"abracadabra\0";
+        let mut bwt = [0u8; 12];
+        let mut primary_index = 0;
+        let status = unsafe {
+            small_bwt_build(
+                text.as_ptr(),
+                text.len(),
+                bwt.as_mut_ptr(),
+                bwt.len(),
+                &mut primary_index,
+            )
+        };
+        assert_eq!(status, SmallBwtStatus::Ok);
+        assert_eq!(&bwt, b"ard\0rcaaaabb");
+        assert_eq!(primary_index, 3);
+
+        let mut decoded = [0u8; 12];
+        let status = unsafe {
+            small_bwt_decode(bwt.as_ptr(), bwt.len(), decoded.as_mut_ptr(), decoded.len())
+        };
+        assert_eq!(status, SmallBwtStatus::Ok);
+        assert_eq!(&decoded, text);
+
+        let status = unsafe {
+            small_bwt_build(
+                text.as_ptr(),
+                text.len(),
+                bwt.as_mut_ptr(),
+                bwt.len() - 1,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(status, SmallBwtStatus::BufferTooSmall);
+        let status = unsafe {
+            small_bwt_build(
+                text.as_ptr(),
+                text.len() - 1,
+                bwt.as_mut_ptr(),
+                bwt.len(),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(status, SmallBwtStatus::InvalidText);
+        let status = unsafe {
+            small_bwt_build(ptr::null(), 0, bwt.as_mut_ptr(), bwt.len(), ptr::null_mut())
+        };
+        assert_eq!(status, SmallBwtStatus::NullPointer);
+        let status =
+            unsafe { small_bwt_decode(b"ab".as_ptr(), 2, decoded.as_mut_ptr(), decoded.len()) };
+        assert_eq!(status, SmallBwtStatus::InvalidBwt);
+    }
+
+    #[test]
+    fn test_build_and_decode_with_sentinel() {
+        let text: Vec<u8> = (0..=255).chain(0..=255).collect();
+        let mut bwt = vec![0u8; text.len() + 1];
+        let mut sentinel_index = 0;
+        let status = unsafe {
+            small_bwt_build_with_sentinel(
+                text.as_ptr(),
+                text.len(),
+                0,
+                bwt.as_mut_ptr(),
+                bwt.len(),
+                &mut sentinel_index,
+            )
+        };
+        assert_eq!(status, SmallBwtStatus::Ok);
+
+        let mut decoded = vec![0u8; text.len()];
+        let status = unsafe {
+            small_bwt_decode_with_sentinel(
+                bwt.as_ptr(),
+                bwt.len(),
+                sentinel_index,
+                decoded.as_mut_ptr(),
+                decoded.len(),
+            )
+        };
+        assert_eq!(status, SmallBwtStatus::Ok);
+        assert_eq!(decoded, text);
+
+        let status = unsafe {
+            small_bwt_build_with_sentinel(
+                text.as_ptr(),
+                text.len(),
+                0,
+                bwt.as_mut_ptr(),
+                text.len(),
+                &mut sentinel_index,
+            )
+        };
+        assert_eq!(status, SmallBwtStatus::BufferTooSmall);
+    }
+
+    #[test]
+    fn test_status_message() {
+        let message =
+            unsafe { CStr::from_ptr(small_bwt_status_message(SmallBwtStatus::InvalidBwt)) };
+        assert_eq!(message.to_str().unwrap(), "the BWT cannot be decoded");
+    }
+}
@@ -0,0 +1,226 @@
+//! # Python bindings of small-bwt
+//!
+//! A `small_bwt` Python module built with [maturin](https://www.maturin.rs/) by `maturin build --release`.
+//!
+//! ```python
+//! import small_bwt
+//!
+//! bwt = small_bwt.bwt(b"abracadabra\0")
+//! assert bwt == b"ard\0rcaaaabb"
+//! assert small_bwt.decode(bwt) == b"abracadabra\0"
+//!
+//! small_bwt.bwt_to_file(text, "text.bwt", progress=lambda done, total: print(done, total))
+//! small_bwt.decode_file("text.bwt", "text.txt")
+//! ```
+//!
+//! The GIL is released during the construction and the decoding,
+//! and is acquired only to call the progress callback.
+//! An invalid text or BWT raises `ValueError`, and a failed file operation raises `OSError`.
+#![deny(missing_docs)]
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use small_bwt::{BuildStats, BwtBuilder, ChunkProgress, ProgressHandler};
+
+/// Progress handler calling a Python callable with `(n_bytes_written, n_bytes)` after each chunk.
+///
+/// An exception raised by the callable cancels the construction and is re-raised after it.
+struct PyProgress {
+    callback: Py<PyAny>,
+    error: Mutex<Option<PyErr>>,
+    cancel: AtomicBool,
+}
+
+impl PyProgress {
+    const fn new(callback: Py<PyAny>) -> Self {
+        Self {
+            callback,
+            error: Mutex::new(None),
+            cancel: AtomicBool::new(false),
+        }
+    }
+}
+
+impl ProgressHandler for PyProgress {
+    fn on_chunk_done(&self, progress: &ChunkProgress) {
+        if self.cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let result = Python::attach(|py| {
+            self.callback
+                .call1(py, (progress.n_bytes_written, progress.n_bytes))
+                .map(|_| ())
+        });
+        if let Err(e) = result {
+            self.error.lock().unwrap().get_or_insert(e);
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Builds the BWT of `text` into `wrt` without the GIL, calling `progress` if given.
+fn build_to<W: Write + Send>(
+    py: Python<'_>,
+    text: &[u8],
+    wrt: W,
+    progress: Option<Py<PyAny>>,
+) -> PyResult<BuildStats> {
+    let handler = progress.map(PyProgress::new);
+    let built = py.detach(|| {
+        let mut builder = BwtBuilder::new(text)?;
+        if let Some(handler) = handler.as_ref() {
+            builder = builder
+                .progress_handler(handler)
+                .cancel_flag(&handler.cancel);
+        }
+        builder.build(wrt)
+    });
+    if let Some(e) = handler.and_then(|h| h.error.into_inner().unwrap()) {
+        return Err(e);
+    }
+    built.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Builds the BWT of a text ending with a smallest unique terminator, such as `\0`.
+///
+/// `progress`, if given, is called with `(n_bytes_written, n_bytes)` after each chunk.
+#[pyfunction]
+#[pyo3(signature = (text, progress=None))]
+fn bwt<'py>(
+    py: Python<'py>,
+    text: &[u8],
+    progress: Option<Py<PyAny>>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let mut bwt = Vec::with_capacity(text.len());
+    build_to(py, text, &mut bwt, progress)?;
+    Ok(PyBytes::new(py, &bwt))
+}
+
+/// Builds the BWT of a text ending with a smallest unique terminator into the file at `path`,
+/// returning the primary index.
+///
+/// `progress`, if given, is called with `(n_bytes_written, n_bytes)` after each chunk.
+#[pyfunction]
+#[pyo3(signature = (text, path, progress=None))]
+fn bwt_to_file(
+    py: Python<'_>,
+    text: &[u8],
+    path: std::path::PathBuf,
+    progress: Option<Py<PyAny>>,
+) -> PyResult<usize> {
+    let mut wrt = BufWriter::new(File::create(path)?);
+    let stats = build_to(py, text, &mut wrt, progress)?;
+    wrt.flush()?;
+    Ok(stats.primary_index)
+}
+
+/// Decodes the text from the BWT built by `bwt`.
+#[pyfunction]
+fn decode<'py>(py: Python<'py>, bwt: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let text = py
+        .detach(|| small_bwt::decode_bwt(bwt))
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(PyBytes::new(py, &text))
+}
+
+/// Decodes the text from the BWT in the file at `bwt_path` into the file at `text_path`,
+/// streaming the BWT instead of loading it.
+#[pyfunction]
+fn decode_file(
+    py: Python<'_>,
+    bwt_path: std::path::PathBuf,
+    text_path: std::path::PathBuf,
+) -> PyResult<()> {
+    let rdr = File::open(bwt_path)?;
+    let wrt = BufWriter::new(File::create(text_path)?);
+    py.detach(|| small_bwt::decode_bwt_from_reader(rdr, wrt))
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// The `small_bwt` Python module.
+#[pymodule]
+#[pyo3(name = "small_bwt")]
+fn small_bwt_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(bwt, m)?)?;
+    m.add_function(wrap_pyfunction!(bwt_to_file, m)?)?;
+    m.add_function(wrap_pyfunction!(decode, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_file, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pyo3::types::PyList;
+
+    #[test]
+    fn test_bwt_and_decode() {
+        Python::initialize();
+        Python::attach(|py| {
+            let bwt = bwt(py, b"abracadabra\0", None).unwrap();
+            assert_eq!(bwt.as_bytes(), b"ard\0rcaaaabb");
+            let decoded = decode(py, bwt.as_bytes()).unwrap();
+            assert_eq!(decoded.as_bytes(), b"abracadabra\0");
+
+            let e = super::bwt(py, b"abracadabra", None).unwrap_err();
+            assert!(e.is_instance_of::<PyValueError>(py));
+            let e = decode(py, b"ab").unwrap_err();
+            assert!(e.is_instance_of::<PyValueError>(py));
+        });
+    }
+
+    #[test]
+    fn test_bwt_to_file_and_decode_file() {
+        Python::initialize();
+        let dir = std::env::temp_dir().join(format!("small-bwt-python-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bwt_path = dir.join("text.bwt");
+        let text_path = dir.join("text.txt");
+        Python::attach(|py| {
+            let calls = PyList::empty(py);
+            let append = calls.getattr("append").unwrap();
+            let callback = py
+                .eval(c"lambda append: lambda *args: append(args)", None, None)
+                .unwrap()
+                .call1((append,))
+                .unwrap();
+            let primary_index = bwt_to_file(
+                py,
+                b"abracadabra\0",
+                bwt_path.clone(),
+                Some(callback.unbind()),
+            )
+            .unwrap();
+            assert_eq!(primary_index, 3);
+            assert!(!calls.is_empty());
+            let last: (usize, usize) = calls.get_item(calls.len() - 1).unwrap().extract().unwrap();
+            assert_eq!(last, (12, 12));
+
+            decode_file(py, bwt_path.clone(), text_path.clone()).unwrap();
+        });
+        assert_eq!(std::fs::read(&bwt_path).unwrap(), b"ard\0rcaaaabb");
+        assert_eq!(std::fs::read(&text_path).unwrap(), b"abracadabra\0");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_progress_error() {
+        Python::initialize();
+        Python::attach(|py| {
+            let callback = py
+                .eval(c"lambda done, total: 1 // 0", None, None)
+                .unwrap()
+                .unbind();
+            let e = bwt(py, b"abracadabra\0", Some(callback)).unwrap_err();
+            assert!(e.is_instance_of::<pyo3::exceptions::PyZeroDivisionError>(py));
+        });
+    }
+}
@@ -0,0 +1,302 @@
+use crate::error::{Error, Result};
+
+/// Effective alphabet of a text, i.e., its distinct bytes mapped to the dense codes `[0, σ)` in order.
+///
+/// Since the mapping preserves the order of bytes,
+/// the BWT of a remapped text is the remapped BWT of the original text.
+/// It is reported in [`BuildStats::alphabet`](crate::BuildStats::alphabet)
+/// if the text is remapped by [`BwtBuilder::remap_alphabet`](crate::BwtBuilder::remap_alphabet).
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::Alphabet;
+///
+/// let alphabet = Alphabet::from_text(b"ACGT$");
+/// assert_eq!(alphabet.sigma(), 5);
+/// assert_eq!(alphabet.bits_per_symbol(), 3);
+/// assert_eq!(alphabet.encode(b'$'), Some(0));
+/// assert_eq!(alphabet.encode(b'G'), Some(3));
+/// assert_eq!(alphabet.encode(b'N'), None);
+/// assert_eq!(alphabet.decode(3), b'G');
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alphabet {
+    // The distinct bytes in increasing order.
+    symbols: Vec<u8>,
+    // codes[c] is the code of byte c, or NONE if c does not occur.
+    codes: [u16; 256],
+}
+
+/// Code of the bytes not in the alphabet.
+const NONE: u16 = u16::MAX;
+
+impl Alphabet {
+    /// Computes the alphabet of the distinct bytes in `text`.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    pub fn from_text(text: &[u8]) -> Self {
+        let mut occurs = [false; 256];
+        for &c in text {
+            occurs[c as usize] = true;
+        }
+        let symbols: Vec<u8> = (0..=255u8).filter(|&c| occurs[c as usize]).collect();
+        let mut codes = [NONE; 256];
+        for (k, &c) in symbols.iter().enumerate() {
+            codes[c as usize] = k as u16;
+        }
+        Self { symbols, codes }
+    }
+
+    /// Returns the alphabet size `σ`.
+    pub fn sigma(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns the number of bits to represent a code, i.e., `ceil(log2(σ))`.
+    pub fn bits_per_symbol(&self) -> usize {
+        (usize::BITS - self.sigma().saturating_sub(1).leading_zeros()) as usize
+    }
+
+    /// Returns the distinct bytes in increasing order, where the `k`-th one has code `k`.
+    pub fn symbols(&self) -> &[u8] {
+        &self.symbols
+    }
+
+    /// Returns the code of byte `c`, or `None` if `c` is not in the alphabet.
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - The byte.
+    pub const fn encode(&self, c: u8) -> Option<u8> {
+        match self.codes[c as usize] {
+            NONE => None,
+            k => Some(k as u8),
+        }
+    }
+
+    /// Returns the byte of code `k`.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - The code, which must be less than `σ`.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `k` is not less than `σ`.
+    pub fn decode(&self, k: u8) -> u8 {
+        self.symbols[k as usize]
+    }
+
+    /// Checks that every byte of a sequence is in the alphabet.
+    ///
+    /// # Arguments
+    ///
+    /// * `seq` - The sequence.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `seq` has a byte not in the alphabet,
+    /// reporting the first one and its position.
+    pub fn validate(&self, seq: &[u8]) -> Result<()> {
+        if let Some(i) = seq.iter().position(|&c| self.encode(c).is_none()) {
+            return Err(Error::InvalidSymbol {
+                pos: i,
+                byte: seq[i],
+            });
+        }
+        Ok(())
+    }
+
+    /// Remaps a sequence of bytes in the alphabet to their codes, such as a text or its BWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `seq` - The sequence, whose bytes must be in the alphabet.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `seq` has a byte not in the alphabet.
+    pub fn encode_all(&self, seq: &[u8]) -> Vec<u8> {
+        seq.iter()
+            .map(|&c| {
+                self.encode(c)
+                    .unwrap_or_else(|| panic!("byte {c:?} must be in the alphabet."))
+            })
+            .collect()
+    }
+
+    /// Restores a sequence of codes to their bytes, such as the BWT of a remapped text.
+    ///
+    /// # Arguments
+    ///
+    /// * `seq` - The sequence, whose codes must be less than `σ`.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `seq` has a code not less than `σ`.
+    pub fn decode_all(&self, seq: &[u8]) -> Vec<u8> {
+        seq.iter().map(|&k| self.decode(k)).collect()
+    }
+}
+
+/// Presets of the amino-acid alphabets of protein sequences,
+/// with the bytes suggested to separate and terminate the sequences.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{AlphabetPreset, BwtBuilder};
+///
+/// let preset = AlphabetPreset::AminoAcids;
+/// let text = [&b"MKTAYIAK"[..], &[preset.separator()], b"MVLSPADK", &[preset.terminator()]].concat();
+/// preset.validate(&text)?;
+/// assert!(preset.validate(b"MKTXYIAK\0").is_err());
+///
+/// let mut bwt = vec![];
+/// let stats = BwtBuilder::new(&text)?
+///     .remap_with_alphabet(preset.alphabet())
+///     .build(&mut bwt)?;
+/// let alphabet = stats.alphabet.unwrap();
+/// assert_eq!(alphabet.sigma(), 22);
+/// assert_eq!(alphabet.bits_per_symbol(), 5);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlphabetPreset {
+    /// The 20 standard amino acids `ACDEFGHIKLMNPQRSTVWY`.
+    AminoAcids,
+    /// The IUPAC amino-acid letters, i.e., the standard ones with
+    /// selenocysteine `U`, pyrrolysine `O`, the ambiguity codes `B`, `J`, `Z`, and `X`,
+    /// and the translation stop `*`.
+    AminoAcidsIupac,
+}
+
+impl AlphabetPreset {
+    /// Returns the residues in increasing order.
+    pub const fn residues(self) -> &'static [u8] {
+        match self {
+            Self::AminoAcids => b"ACDEFGHIKLMNPQRSTVWY",
+            Self::AminoAcidsIupac => b"*ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+        }
+    }
+
+    /// Returns the suggested byte to separate sequences, such as for
+    /// [`parse_fasta`](crate::parse_fasta), which is `$`.
+    pub const fn separator(self) -> u8 {
+        b'$'
+    }
+
+    /// Returns the suggested terminator, which is the null byte as
+    /// [`FASTA_TERMINATOR`](crate::FASTA_TERMINATOR).
+    pub const fn terminator(self) -> u8 {
+        b'\0'
+    }
+
+    /// Returns the alphabet of the residues, the separator, and the terminator,
+    /// which can be given to [`BwtBuilder::remap_with_alphabet`](crate::BwtBuilder::remap_with_alphabet)
+    /// so that the codes are the same for any text.
+    pub fn alphabet(self) -> Alphabet {
+        let mut symbols = self.residues().to_vec();
+        symbols.extend([self.separator(), self.terminator()]);
+        Alphabet::from_text(&symbols)
+    }
+
+    /// Checks that a text consists of the residues, the separator, and the terminator.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text, such as the concatenated sequences of [`parse_fasta`](crate::parse_fasta).
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `text` has another byte, such as a lowercase letter,
+    /// reporting the first one and its position.
+    pub fn validate(self, text: &[u8]) -> Result<()> {
+        self.alphabet().validate(text)
+    }
+}
+
+/// Serializes only the distinct bytes, from which the codes are restored.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Alphabet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.symbols.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Alphabet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let symbols = Vec::<u8>::deserialize(deserializer)?;
+        Ok(Self::from_text(&symbols))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alphabet() {
+        let text = b"mississippi$";
+        let alphabet = Alphabet::from_text(text);
+        assert_eq!(alphabet.symbols(), b"$imps");
+        assert_eq!(alphabet.sigma(), 5);
+        let encoded = alphabet.encode_all(text);
+        assert_eq!(encoded, vec![2, 1, 4, 4, 1, 4, 4, 1, 3, 3, 1, 0]);
+        assert_eq!(alphabet.decode_all(&encoded), text);
+    }
+
+    #[test]
+    fn test_alphabet_bits_per_symbol() {
+        for (text, bits) in [
+            (&b""[..], 0),
+            (b"a", 0),
+            (b"ab", 1),
+            (b"abc", 2),
+            (b"abcd", 2),
+        ] {
+            assert_eq!(Alphabet::from_text(text).bits_per_symbol(), bits);
+        }
+        let all: Vec<u8> = (0..=255).collect();
+        let alphabet = Alphabet::from_text(&all);
+        assert_eq!(alphabet.bits_per_symbol(), 8);
+        assert_eq!(alphabet.encode(255), Some(255));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_alphabet_serde() {
+        let alphabet = Alphabet::from_text(b"mississippi$");
+        let json = serde_json::to_string(&alphabet).unwrap();
+        assert_eq!(json, "[36,105,109,112,115]");
+        assert_eq!(serde_json::from_str::<Alphabet>(&json).unwrap(), alphabet);
+    }
+
+    #[test]
+    fn test_alphabet_preset() {
+        for preset in [AlphabetPreset::AminoAcids, AlphabetPreset::AminoAcidsIupac] {
+            let residues = preset.residues();
+            assert!(residues.windows(2).all(|w| w[0] < w[1]));
+            let alphabet = preset.alphabet();
+            assert_eq!(alphabet.sigma(), residues.len() + 2);
+            // The terminator and the separator are the smallest ones.
+            assert_eq!(alphabet.encode(preset.terminator()), Some(0));
+            assert_eq!(alphabet.encode(preset.separator()), Some(1));
+            assert!(preset.validate(residues).is_ok());
+        }
+        assert_eq!(AlphabetPreset::AminoAcids.residues().len(), 20);
+        assert!(AlphabetPreset::AminoAcids.validate(b"MKX").is_err());
+        assert!(AlphabetPreset::AminoAcidsIupac.validate(b"MKX*").is_ok());
+        let e = AlphabetPreset::AminoAcidsIupac
+            .validate(b"MKtA")
+            .unwrap_err();
+        assert!(e.to_string().contains("position 2"));
+    }
+}
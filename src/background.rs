@@ -0,0 +1,398 @@
+use std::io::{ErrorKind, Write};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+
+use crate::error::{Error, Result};
+
+/// Number of blocks held by the queue of [`pipe_to_thread`], including the one being filled.
+const N_BLOCKS: usize = 4;
+
+/// Writer sending blocks of bytes to the consumer thread of [`pipe_to_thread`],
+/// blocking while the queue is full.
+pub struct QueueWriter {
+    tx: SyncSender<Vec<u8>>,
+    block: Vec<u8>,
+    block_size: usize,
+}
+
+impl QueueWriter {
+    fn send_block(&mut self) -> std::io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let block = std::mem::replace(&mut self.block, Vec::with_capacity(self.block_size));
+        // The receiver is dropped if the consumer thread has stopped.
+        self.tx
+            .send(block)
+            .map_err(|_| std::io::Error::new(ErrorKind::BrokenPipe, "the consumer thread stopped"))
+    }
+}
+
+impl Write for QueueWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.block_size - self.block.len());
+        self.block.extend_from_slice(&buf[..len]);
+        if self.block.len() == self.block_size {
+            self.send_block()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_block()
+    }
+}
+
+/// Iterator over the BWT in pieces, produced by
+/// [`BwtBuilder::build_streaming`](crate::BwtBuilder::build_streaming)
+/// while the construction is running.
+///
+/// Each piece has at most the chunk size of bytes.
+/// The iterator ends when the construction finishes or fails,
+/// which is told by the result of the construction.
+pub struct BwtChunks {
+    rx: Receiver<Vec<u8>>,
+}
+
+impl Iterator for BwtChunks {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.recv().ok()
+    }
+}
+
+/// Runs `f` with a [`QueueWriter`] in the calling thread and `consume` in a dedicated thread,
+/// which receives the bytes written by `f` in blocks of `block_size` bytes
+/// through a queue of [`N_BLOCKS`] blocks.
+pub fn pipe_to_thread<F, C, R, S>(block_size: usize, f: F, consume: C) -> (Result<R>, S)
+where
+    F: FnOnce(&mut QueueWriter) -> Result<R>,
+    C: FnOnce(BwtChunks) -> S + Send,
+    S: Send,
+{
+    std::thread::scope(|s| {
+        // One more block is being filled by the queue writer.
+        let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(N_BLOCKS - 1);
+        let handle = s.spawn(move || consume(BwtChunks { rx }));
+        let mut queue = QueueWriter {
+            tx,
+            block: Vec::with_capacity(block_size),
+            block_size,
+        };
+        let result = f(&mut queue).and_then(|r| {
+            queue.flush()?;
+            Ok(r)
+        });
+        // Closes the queue to let the consumer thread finish.
+        drop(queue);
+        let consumed = handle
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e));
+        (result, consumed)
+    })
+}
+
+/// Runs `f` with a [`QueueWriter`] whose bytes are written to `wrt` by a dedicated thread,
+/// holding up to about `queue_size` bytes in the queue.
+///
+/// An error of the writer thread takes precedence over that of `f`,
+/// which fails with [`ErrorKind::BrokenPipe`] once the writer thread stops.
+pub fn write_in_background<W, F, R>(wrt: W, queue_size: usize, f: F) -> Result<R>
+where
+    W: Write + Send,
+    F: FnOnce(&mut QueueWriter) -> Result<R>,
+{
+    if queue_size == 0 {
+        return Err(Error::invalid_argument("queue_size must be positive."));
+    }
+    let block_size = (queue_size / N_BLOCKS).max(1);
+    let (result, written) = pipe_to_thread(block_size, f, move |blocks| -> Result<()> {
+        let mut wrt = wrt;
+        for block in blocks {
+            wrt.write_all(&block)?;
+        }
+        wrt.flush()?;
+        Ok(())
+    });
+    written.and(result)
+}
+
+/// Writer sending blocks of bytes to the task of [`build_async`], blocking while the queue is full.
+#[cfg(feature = "tokio")]
+struct AsyncQueueWriter {
+    tx: tokio::sync::mpsc::Sender<Vec<u8>>,
+    block: Vec<u8>,
+    block_size: usize,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncQueueWriter {
+    fn send_block(&mut self) -> std::io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let block = std::mem::replace(&mut self.block, Vec::with_capacity(self.block_size));
+        // The receiver is dropped if the writing task has stopped.
+        self.tx
+            .blocking_send(block)
+            .map_err(|_| std::io::Error::new(ErrorKind::BrokenPipe, "the writing task stopped"))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Write for AsyncQueueWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.block_size - self.block.len());
+        self.block.extend_from_slice(&buf[..len]);
+        if self.block.len() == self.block_size {
+            self.send_block()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_block()
+    }
+}
+
+/// Runs a construction on a blocking thread of the tokio runtime
+/// and writes its output to an asynchronous writer,
+/// so that the BWT can be built inside an async service without blocking the runtime.
+///
+/// `build` is run by [`tokio::task::spawn_blocking`] with a writer
+/// sending the bytes in blocks through a bounded queue, and the construction waits while the queue is full.
+/// Meanwhile the calling task awaits the blocks and writes them to `wrt`,
+/// yielding to the runtime between them.
+/// Since [`BwtBuilder`](crate::BwtBuilder) holds its text and writers by reference,
+/// it is created in `build` from the owned values moved into it.
+///
+/// # Arguments
+///
+/// * `wrt` - The asynchronous writer to write the output.
+/// * `queue_size` - The maximum number of bytes in the queue, roughly.
+/// * `build` - The function writing the output to the given writer, such as [`BwtBuilder::build`](crate::BwtBuilder::build).
+///
+/// # Errors
+///
+/// An error is returned if `queue_size` is zero, or `build` or `wrt` returns an error.
+/// An error of `wrt` takes precedence over that of `build`,
+/// which fails with [`ErrorKind::BrokenPipe`] once the writing stops.
+///
+/// # Panics
+///
+/// It panics if called outside a tokio runtime, or resumes the panic of `build`.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{build_async, BwtBuilder};
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+/// let mut bwt = vec![];
+/// let text = b"abracadabra$".to_vec();
+/// let stats = runtime.block_on(build_async(&mut bwt, 1 << 20, move |wrt| {
+///     BwtBuilder::new(&text)?.build(wrt)
+/// }))?;
+/// assert_eq!(bwt, b"ard$rcaaaabb");
+/// assert_eq!(stats.primary_index, 3);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn build_async<W, F, R>(mut wrt: W, queue_size: usize, build: F) -> Result<R>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+    F: FnOnce(&mut dyn Write) -> Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    use tokio::io::AsyncWriteExt;
+
+    if queue_size == 0 {
+        return Err(Error::invalid_argument("queue_size must be positive."));
+    }
+    let block_size = (queue_size / N_BLOCKS).max(1);
+    // One more block is being filled by the queue writer.
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Vec<u8>>(N_BLOCKS - 1);
+    let handle = tokio::task::spawn_blocking(move || {
+        let mut queue = AsyncQueueWriter {
+            tx,
+            block: Vec::with_capacity(block_size),
+            block_size,
+        };
+        let r = build(&mut queue)?;
+        queue.flush()?;
+        Ok(r)
+    });
+    let written = async {
+        while let Some(block) = rx.recv().await {
+            wrt.write_all(&block).await?;
+        }
+        wrt.flush().await
+    }
+    .await;
+    // Closes the queue to let the construction finish.
+    drop(rx);
+    let result = match handle.await {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    };
+    written?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(ErrorKind::Other, "disk full"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_in_background() {
+        let bytes: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        for queue_size in [1, 3, 16, 10000] {
+            let mut out = vec![];
+            let n = write_in_background(&mut out, queue_size, |wrt| {
+                for chunk in bytes.chunks(7) {
+                    wrt.write_all(chunk)?;
+                }
+                Ok(bytes.len())
+            })
+            .unwrap();
+            assert_eq!(n, bytes.len());
+            assert_eq!(out, bytes);
+        }
+        assert!(write_in_background(vec![], 0, |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_write_in_background_error() {
+        let e = write_in_background(FailingWriter, 8, |wrt| {
+            for _ in 0..100 {
+                wrt.write_all(b"abracadabra$")?;
+            }
+            Ok(())
+        })
+        .unwrap_err();
+        match e {
+            Error::Io(e) => assert_eq!(e.to_string(), "disk full"),
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn test_pipe_to_thread() {
+        let (result, blocks) = pipe_to_thread(
+            4,
+            |wrt| {
+                wrt.write_all(b"abracadabra$")?;
+                Ok(())
+            },
+            |blocks| blocks.collect::<Vec<_>>(),
+        );
+        result.unwrap();
+        assert_eq!(blocks, [&b"abra"[..], b"cada", b"bra$"]);
+
+        // The writer fails if the consumer stops early.
+        let (result, first) = pipe_to_thread(
+            1,
+            |wrt| {
+                for _ in 0..100 {
+                    wrt.write_all(b"abracadabra$")?;
+                }
+                Ok(())
+            },
+            |mut blocks| blocks.next(),
+        );
+        assert!(result.is_err());
+        assert_eq!(first, Some(b"a".to_vec()));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_build_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let bytes: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        for queue_size in [1, 3, 16, 10000] {
+            let mut out = vec![];
+            let expected = bytes.clone();
+            let n = runtime
+                .block_on(build_async(&mut out, queue_size, move |wrt| {
+                    for chunk in expected.chunks(7) {
+                        wrt.write_all(chunk)?;
+                    }
+                    Ok(expected.len())
+                }))
+                .unwrap();
+            assert_eq!(n, bytes.len());
+            assert_eq!(out, bytes);
+        }
+        assert!(runtime
+            .block_on(build_async(vec![], 0, |_| Ok(())))
+            .is_err());
+        // The error of the construction.
+        let result = runtime.block_on(build_async(vec![], 8, |wrt| -> Result<()> {
+            wrt.write_all(b"abra")?;
+            Err(Error::invalid_argument("stopped"))
+        }));
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_build_async_error() {
+        struct FailingAsyncWriter;
+
+        impl tokio::io::AsyncWrite for FailingAsyncWriter {
+            fn poll_write(
+                self: std::pin::Pin<&mut Self>,
+                _: &mut std::task::Context<'_>,
+                _: &[u8],
+            ) -> std::task::Poll<std::io::Result<usize>> {
+                std::task::Poll::Ready(Err(std::io::Error::new(ErrorKind::Other, "disk full")))
+            }
+
+            fn poll_flush(
+                self: std::pin::Pin<&mut Self>,
+                _: &mut std::task::Context<'_>,
+            ) -> std::task::Poll<std::io::Result<()>> {
+                std::task::Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(
+                self: std::pin::Pin<&mut Self>,
+                _: &mut std::task::Context<'_>,
+            ) -> std::task::Poll<std::io::Result<()>> {
+                std::task::Poll::Ready(Ok(()))
+            }
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let e = runtime
+            .block_on(build_async(FailingAsyncWriter, 8, |wrt| {
+                for _ in 0..100 {
+                    wrt.write_all(b"abracadabra$")?;
+                }
+                Ok(())
+            }))
+            .unwrap_err();
+        match e {
+            Error::Io(e) => assert_eq!(e.to_string(), "disk full"),
+            e => panic!("unexpected error: {e}"),
+        }
+    }
+}
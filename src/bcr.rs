@@ -0,0 +1,138 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// Returns the path of the `k`-th part, i.e., `{prefix}-B{k:02}`.
+fn part_path(prefix: &Path, k: usize) -> PathBuf {
+    let mut path = OsString::from(prefix.as_os_str());
+    path.push(format!("-B{k:02}"));
+    PathBuf::from(path)
+}
+
+/// Writes the BWT to files split by the symbols of the first column, in the layout of BCR.
+///
+/// The `k`-th file `{prefix}-B{k:02}` holds the BWT of the rows starting with `symbols[k]`,
+/// which is empty if the symbol does not appear, so that the files of a DNA BWT over `$ACGNT`
+/// are `-B00` to `-B05` as written by BCR.
+///
+/// # Arguments
+///
+/// * `bwt` - The BWT.
+/// * `symbols` - The distinct symbols in increasing order, such as `b"$ACGNT"`.
+/// * `prefix` - The prefix of the paths of the files.
+///
+/// # Errors
+///
+/// An error is returned if `symbols` is not strictly increasing,
+/// if `bwt` has a byte not in `symbols`, or if a file cannot be written.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{read_bcr_parts, write_bcr_parts};
+///
+/// let prefix = std::env::temp_dir().join("small_bwt_bcr_doctest");
+/// write_bcr_parts(b"ACTGA$TA", b"$ACGNT", &prefix)?;
+/// assert_eq!(std::fs::read(prefix.with_file_name("small_bwt_bcr_doctest-B01"))?, b"CTG");
+/// assert_eq!(read_bcr_parts(&prefix)?, b"ACTGA$TA");
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_bcr_parts<P: AsRef<Path>>(bwt: &[u8], symbols: &[u8], prefix: P) -> Result<()> {
+    if symbols.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(Error::invalid_argument(
+            "symbols must be strictly increasing.",
+        ));
+    }
+    let mut codes = [None; 256];
+    for (k, &c) in symbols.iter().enumerate() {
+        codes[c as usize] = Some(k);
+    }
+    let mut counts = vec![0; symbols.len()];
+    for (pos, &byte) in bwt.iter().enumerate() {
+        let k = codes[byte as usize].ok_or(Error::InvalidSymbol { pos, byte })?;
+        counts[k] += 1;
+    }
+    let mut start = 0;
+    for (k, &count) in counts.iter().enumerate() {
+        let mut wrt = BufWriter::new(File::create(part_path(prefix.as_ref(), k))?);
+        wrt.write_all(&bwt[start..start + count])?;
+        wrt.flush()?;
+        start += count;
+    }
+    Ok(())
+}
+
+/// Reads the BWT from files split by the symbols of the first column,
+/// concatenating `{prefix}-B00`, `{prefix}-B01`, and so on until a file is missing.
+///
+/// # Arguments
+///
+/// * `prefix` - The prefix of the paths of the files.
+///
+/// # Errors
+///
+/// An error is returned if `{prefix}-B00` is missing or a file cannot be read.
+pub fn read_bcr_parts<P: AsRef<Path>>(prefix: P) -> Result<Vec<u8>> {
+    let mut bwt = vec![];
+    for k in 0.. {
+        let mut file = match File::open(part_path(prefix.as_ref(), k)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound && k != 0 => break,
+            Err(e) => return Err(e.into()),
+        };
+        file.read_to_end(&mut bwt)?;
+    }
+    Ok(bwt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{decode_bwt, BwtBuilder};
+
+    #[test]
+    fn test_bcr_parts_fixture() {
+        let prefix = std::env::temp_dir().join("small_bwt_test_bcr_fixture");
+        write_bcr_parts(b"ACTGA$TA", b"$ACGNT", &prefix).unwrap();
+        let parts: Vec<Vec<u8>> = (0..6)
+            .map(|k| std::fs::read(part_path(&prefix, k)).unwrap())
+            .collect();
+        assert_eq!(parts, [&b"A"[..], b"CTG", b"A", b"$", b"", b"TA"]);
+        assert!(!part_path(&prefix, 6).exists());
+        assert_eq!(read_bcr_parts(&prefix).unwrap(), b"ACTGA$TA");
+        for k in 0..6 {
+            std::fs::remove_file(part_path(&prefix, k)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_bcr_parts_roundtrip() {
+        let text = b"TTAGGGTTAGGGNNNACGTACGT$";
+        let mut bwt = vec![];
+        BwtBuilder::new(text).unwrap().build(&mut bwt).unwrap();
+        let prefix = std::env::temp_dir().join("small_bwt_test_bcr_roundtrip");
+        write_bcr_parts(&bwt, b"$ACGNT", &prefix).unwrap();
+        let read = read_bcr_parts(&prefix).unwrap();
+        assert_eq!(decode_bwt(&read).unwrap(), text);
+        for k in 0..6 {
+            std::fs::remove_file(part_path(&prefix, k)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_bcr_parts_invalid() {
+        let prefix = std::env::temp_dir().join("small_bwt_test_bcr_invalid");
+        assert!(matches!(
+            write_bcr_parts(b"ACXT", b"$ACGNT", &prefix),
+            Err(Error::InvalidSymbol { pos: 2, byte: b'X' })
+        ));
+        assert!(write_bcr_parts(b"ACGT", b"$CAGNT", &prefix).is_err());
+        assert!(read_bcr_parts(&prefix).is_err());
+    }
+}
@@ -0,0 +1,436 @@
+use std::io::{Read, Write};
+
+use crate::error::{Error, Result};
+
+use crate::decode::decode_bwt_with_index;
+use crate::intio::{read_varint, write_varint};
+
+/// Default number of bytes per block, as the largest block of bzip2.
+const DEFAULT_BLOCK_SIZE: usize = 900_000;
+
+/// Computes the BWT of the sorted rotations of a text, as used in bzip2,
+/// together with its primary index, i.e., the row of the text itself in the sorted rotations.
+///
+/// Unlike [`BwtBuilder`](crate::BwtBuilder), the text needs no terminator.
+/// The rotations are sorted by prefix doubling in `O(n log^2 n)` time and `12n` bytes of space,
+/// where `n` is the length of the text, so it is intended for blocks of moderate size.
+/// The BWT is decoded by [`decode_bwt_with_index`].
+///
+/// # Arguments
+///
+/// * `text` - The text, whose length must be less than 2^32.
+///
+/// # Errors
+///
+/// An error is returned if `text` is empty or too long.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::rotation_bwt;
+///
+/// let (bwt, primary_index) = rotation_bwt(b"banana")?;
+/// assert_eq!(bwt, b"nnbaaa");
+/// assert_eq!(primary_index, 3);
+/// # Ok(())
+/// # }
+/// ```
+pub fn rotation_bwt(text: &[u8]) -> Result<(Vec<u8>, usize)> {
+    if text.is_empty() {
+        return Err(Error::EmptyText);
+    }
+    if text.len() > u32::MAX as usize {
+        return Err(Error::invalid_argument(format!(
+            "text must be at most {} bytes, but got {}.",
+            u32::MAX,
+            text.len()
+        )));
+    }
+    let rotations = sort_rotations(text);
+    let n = text.len();
+    let mut bwt = Vec::with_capacity(n);
+    let mut primary_index = 0;
+    for (r, &i) in rotations.iter().enumerate() {
+        let i = i as usize;
+        if i == 0 {
+            primary_index = r;
+        }
+        bwt.push(text[(i + n - 1) % n]);
+    }
+    Ok((bwt, primary_index))
+}
+
+/// Sorts the rotations of `text`, where equal rotations are sorted by their starting positions.
+///
+/// The rotations are sorted by their first `2k` symbols from those sorted by their first `k` symbols,
+/// where only the groups of rotations with equal prefixes are sorted again, as in Manber and Myers.
+fn sort_rotations(text: &[u8]) -> Vec<u32> {
+    let n = text.len();
+    let mut rotations: Vec<u32> = (0..n as u32).collect();
+    rotations.sort_unstable_by_key(|&i| (text[i as usize], i));
+    // ranks[i] is the first row of the group of rotations sharing the first k symbols with the one at i.
+    let mut ranks = vec![0; n];
+    for w in 1..n {
+        let (i, j) = (rotations[w - 1] as usize, rotations[w] as usize);
+        ranks[j] = if text[i] == text[j] {
+            ranks[i]
+        } else {
+            w as u32
+        };
+    }
+    let mut next_ranks = ranks.clone();
+    let mut k = 1;
+    while k < n {
+        let mut sorted = true;
+        let mut w = 0;
+        while w < n {
+            let rank = ranks[rotations[w] as usize];
+            let mut end = w + 1;
+            while end < n && ranks[rotations[end] as usize] == rank {
+                end += 1;
+            }
+            if end - w > 1 {
+                let key = |i: u32| ranks[(i as usize + k) % n];
+                rotations[w..end].sort_unstable_by_key(|&i| (key(i), i));
+                for v in w..end {
+                    let (i, j) = (rotations[v.max(w + 1) - 1], rotations[v]);
+                    if v == w || key(i) != key(j) {
+                        next_ranks[j as usize] = v as u32;
+                    } else {
+                        next_ranks[j as usize] = next_ranks[i as usize];
+                        sorted = false;
+                    }
+                }
+            }
+            w = end;
+        }
+        ranks.copy_from_slice(&next_ranks);
+        // Stops if all the rotations are distinguished or are compared in their full lengths.
+        if sorted {
+            break;
+        }
+        k *= 2;
+    }
+    rotations
+}
+
+/// Writer adapter transforming a byte stream block by block, like bzip2.
+///
+/// The stream is split into blocks of a fixed size, and each block is emitted as a frame of
+/// the block length and the primary index in the unsigned LEB128 encoding,
+/// followed by the BWT of the block computed by [`rotation_bwt`].
+/// The end of the stream is marked by a frame of length zero,
+/// emitted by [`BlockBwtWriter::finish`] after the pending block,
+/// so it must be called after all bytes are written.
+///
+/// Since each block is held in memory and sorted independently,
+/// unbounded inputs can be transformed in space proportional to the block size.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::io::{Read, Write};
+/// use small_bwt::{BlockBwtReader, BlockBwtWriter};
+///
+/// let text = b"banana bandana";
+/// let mut wrt = BlockBwtWriter::new(vec![]).block_size(6)?;
+/// wrt.write_all(text)?;
+/// let framed = wrt.finish()?;
+/// assert_eq!(framed[..8], [6, 3, b'n', b'n', b'b', b'a', b'a', b'a']);
+///
+/// let mut decoded = vec![];
+/// BlockBwtReader::new(framed.as_slice()).read_to_end(&mut decoded)?;
+/// assert_eq!(decoded, text);
+/// # Ok(())
+/// # }
+/// ```
+pub struct BlockBwtWriter<W: Write> {
+    wrt: W,
+    block_size: usize,
+    n_threads: usize,
+    // The pending bytes of up to n_threads blocks.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> BlockBwtWriter<W> {
+    /// Creates a new writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the framed stream.
+    pub const fn new(wrt: W) -> Self {
+        Self {
+            wrt,
+            block_size: DEFAULT_BLOCK_SIZE,
+            n_threads: 1,
+            pending: vec![],
+        }
+    }
+
+    /// Sets the number of bytes per block.
+    ///
+    /// Larger blocks yield longer runs in the BWT at the cost of more memory,
+    /// about `14` bytes per byte of a block.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_size` - The number of bytes per block.
+    ///
+    /// # Default value
+    ///
+    /// `900000`
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `block_size` is zero or not less than 2^32.
+    pub fn block_size(mut self, block_size: usize) -> Result<Self> {
+        if block_size == 0 || block_size > u32::MAX as usize {
+            return Err(Error::invalid_format(format!(
+                "block_size must be in 1..={}, but got {block_size}.",
+                u32::MAX
+            )));
+        }
+        self.block_size = block_size;
+        Ok(self)
+    }
+
+    /// Sets whether to transform blocks in parallel
+    /// using the global thread pool of [rayon](https://docs.rs/rayon).
+    /// The frames are still written in the order of blocks.
+    ///
+    /// If `parallel` is `true`, as many blocks as the number of threads
+    /// are held in memory at once, so the working space grows proportionally.
+    ///
+    /// # Arguments
+    ///
+    /// * `parallel` - Whether to transform blocks in parallel.
+    ///
+    /// # Default value
+    ///
+    /// `false`
+    #[cfg(feature = "rayon")]
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.n_threads = if parallel {
+            rayon::current_num_threads()
+        } else {
+            1
+        };
+        self
+    }
+
+    /// Emits the pending blocks and the end marker, and returns the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the underlying writer returns an error.
+    pub fn finish(mut self) -> Result<W> {
+        self.write_blocks()?;
+        write_varint(&mut self.wrt, 0)?;
+        self.wrt.flush()?;
+        Ok(self.wrt)
+    }
+
+    fn write_blocks(&mut self) -> Result<()> {
+        let blocks: Vec<_> = self.pending.chunks(self.block_size).collect();
+        #[cfg(feature = "rayon")]
+        let transformed: Vec<_> = if self.n_threads > 1 {
+            use rayon::prelude::*;
+            blocks.into_par_iter().map(rotation_bwt).collect()
+        } else {
+            blocks.into_iter().map(rotation_bwt).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let transformed: Vec<_> = blocks.into_iter().map(rotation_bwt).collect();
+        for block in transformed {
+            let (bwt, primary_index) = block?;
+            write_varint(&mut self.wrt, bwt.len())?;
+            write_varint(&mut self.wrt, primary_index)?;
+            self.wrt.write_all(&bwt)?;
+        }
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BlockBwtWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let capacity = self.block_size * self.n_threads;
+        let mut buf = buf;
+        let len = buf.len();
+        while !buf.is_empty() {
+            let n = (capacity - self.pending.len()).min(buf.len());
+            self.pending.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+            if self.pending.len() == capacity {
+                self.write_blocks()?;
+            }
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.wrt.flush()
+    }
+}
+
+/// Reader adapter restoring the original byte stream from a stream written by [`BlockBwtWriter`].
+///
+/// The frames are read one by one, and each block is decoded by [`decode_bwt_with_index`].
+pub struct BlockBwtReader<R: Read> {
+    rdr: R,
+    // The decoded block and the number of bytes already read from it.
+    block: Vec<u8>,
+    pos: usize,
+    finished: bool,
+}
+
+impl<R: Read> BlockBwtReader<R> {
+    /// Creates a new reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `rdr` - The reader of the framed stream.
+    pub const fn new(rdr: R) -> Self {
+        Self {
+            rdr,
+            block: vec![],
+            pos: 0,
+            finished: false,
+        }
+    }
+
+    fn read_block(&mut self) -> Result<()> {
+        self.block.clear();
+        self.pos = 0;
+        let len = read_varint(&mut self.rdr)?;
+        if len == 0 {
+            self.finished = true;
+            return Ok(());
+        }
+        if len > u32::MAX as usize {
+            return Err(Error::invalid_format(format!(
+                "block length must be at most {}, but got {len}.",
+                u32::MAX
+            )));
+        }
+        let primary_index = read_varint(&mut self.rdr)?;
+        let mut bwt = vec![];
+        (&mut self.rdr).take(len as u64).read_to_end(&mut bwt)?;
+        if bwt.len() != len {
+            return Err(Error::invalid_format(format!(
+                "block must have {len} bytes, but got {} bytes.",
+                bwt.len()
+            )));
+        }
+        self.block = decode_bwt_with_index(&bwt, primary_index)?;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for BlockBwtReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.block.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.read_block()?;
+        }
+        let n = (self.block.len() - self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_rotation_bwt(text: &[u8]) -> (Vec<u8>, usize) {
+        let n = text.len();
+        let mut rotations: Vec<_> = (0..n).collect();
+        rotations.sort_by_key(|&i| [&text[i..], &text[..i]].concat());
+        let bwt = rotations.iter().map(|&i| text[(i + n - 1) % n]).collect();
+        let primary_index = rotations.iter().position(|&i| i == 0).unwrap();
+        (bwt, primary_index)
+    }
+
+    fn roundtrip(text: &[u8], wrt: BlockBwtWriter<Vec<u8>>) -> Vec<u8> {
+        let mut wrt = wrt;
+        for chunk in text.chunks(7) {
+            wrt.write_all(chunk).unwrap();
+        }
+        let framed = wrt.finish().unwrap();
+        let mut decoded = vec![];
+        BlockBwtReader::new(framed.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_rotation_bwt() {
+        let texts: Vec<Vec<u8>> = vec![
+            b"a".to_vec(),
+            b"banana".to_vec(),
+            b"abababab".to_vec(),
+            b"mississippi".to_vec(),
+            vec![0xff, 0, 0xff, 0, 0],
+            (0..1000u32)
+                .map(|i| b"ab"[(i.count_ones() % 2) as usize])
+                .collect(),
+        ];
+        for text in texts {
+            let (bwt, primary_index) = rotation_bwt(&text).unwrap();
+            let (expected_bwt, expected_index) = naive_rotation_bwt(&text);
+            assert_eq!(bwt, expected_bwt);
+            assert_eq!(primary_index, expected_index);
+            assert_eq!(decode_bwt_with_index(&bwt, primary_index).unwrap(), text);
+        }
+        assert!(rotation_bwt(b"").is_err());
+    }
+
+    #[test]
+    fn test_block_bwt_roundtrip() {
+        let text: Vec<u8> = (0..5000u32).map(|i| (i * i % 251) as u8).collect();
+        for block_size in [1, 100, 4999, 5000, 10000] {
+            let wrt = BlockBwtWriter::new(vec![]).block_size(block_size).unwrap();
+            assert_eq!(roundtrip(&text, wrt), text);
+        }
+        assert_eq!(roundtrip(b"", BlockBwtWriter::new(vec![])), b"");
+        assert!(BlockBwtWriter::new(vec![]).block_size(0).is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_block_bwt_parallel() {
+        let text: Vec<u8> = (0..5000u32).map(|i| (i * i % 251) as u8).collect();
+        let mut framed = vec![];
+        for parallel in [false, true] {
+            let mut wrt = BlockBwtWriter::new(vec![])
+                .block_size(300)
+                .unwrap()
+                .parallel(parallel);
+            wrt.write_all(&text).unwrap();
+            framed.push(wrt.finish().unwrap());
+        }
+        assert_eq!(framed[0], framed[1]);
+    }
+
+    #[test]
+    fn test_block_bwt_reader_invalid() {
+        let mut decoded = vec![];
+        // The primary index is out of bounds.
+        let framed = [3, 3, b'a', b'b', b'c', 0];
+        assert!(BlockBwtReader::new(&framed[..])
+            .read_to_end(&mut decoded)
+            .is_err());
+        // The block is truncated.
+        let framed = [3, 0, b'a', b'b'];
+        assert!(BlockBwtReader::new(&framed[..])
+            .read_to_end(&mut decoded)
+            .is_err());
+    }
+}
@@ -0,0 +1,505 @@
+use std::ops::Range;
+
+use crate::error::{Error, Result};
+
+use crate::indexed::primary_index;
+use crate::lf::{count_symbols, psi as psi_values};
+use crate::wavelet::{BitVector, BitVectorBuilder};
+use crate::WaveletTree;
+
+/// Succinct de Bruijn graph of Bowe, Onodera, Sadakane, and Shibuya (BOSS)
+/// of order `k` over a collection of reads.
+///
+/// The nodes are the `k`-mers of the reads and the edges are their `(k+1)`-mers.
+/// Each read is padded on the left with `k` separators so that every node has an incoming edge,
+/// and a node without outgoing edges has one labeled by the separator.
+/// The nodes are sorted in the colexicographic order of their labels,
+/// and their outgoing edges are listed in the order of their labels.
+/// An edge is flagged if an earlier node with the same last `k-1` symbols has an edge with the same label,
+/// so that the unflagged edges point to distinct nodes.
+///
+/// The graph is built from the BWT of the reversed reads, whose rows sharing their first `k` symbols
+/// are exactly the nodes in colexicographic order, with the outgoing labels in the BWT.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{concat_inputs, Boss};
+///
+/// let reads = [&b"ACG"[..], b"CGT"];
+/// let concat = concat_inputs(reads, b'$')?;
+/// let mut rev_bwt = vec![];
+/// concat
+///     .builder()?
+///     .reverse_writer(&mut rev_bwt)
+///     .build(&mut vec![])?;
+///
+/// let boss = Boss::new(&rev_bwt, 2, b'$')?;
+/// // $$, $A, $C, AC, CG, and GT.
+/// assert_eq!(boss.n_nodes(), 6);
+/// assert_eq!(boss.labels(), b"ACCGGT$");
+///
+/// let v = boss.find(b"AC").unwrap();
+/// assert_eq!(boss.label(v), b"AC");
+/// assert_eq!(boss.outgoing(v, b'G'), boss.find(b"CG"));
+/// assert_eq!(boss.outgoing(v, b'T'), None);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Boss {
+    k: usize,
+    separator: u8,
+    // The edge labels, often denoted by W.
+    labels: Vec<u8>,
+    // The edge labels with the flagged ones replaced by the separator,
+    // whose occurrences of each symbol correspond to the nodes ending with it.
+    targets: WaveletTree,
+    // Whether each edge is the last one of its node, often denoted by L.
+    last: BitVector,
+    flagged: BitVector,
+    // node_starts[c] is the number of nodes whose labels end with symbols smaller than c.
+    node_starts: Vec<usize>,
+}
+
+impl Boss {
+    /// Builds the BOSS of order `k` from the BWT of the reversed reads.
+    ///
+    /// The reads are expected to be concatenated with `separator` and terminated
+    /// as by [`concat_inputs`](crate::concat_inputs),
+    /// and the BWT of the reversed text is written by
+    /// [`BwtBuilder::reverse_writer`](crate::BwtBuilder::reverse_writer).
+    /// The terminator and the separators both end the contexts of the rows.
+    ///
+    /// It refines the groups of rows sharing their first `l` symbols for `l = 1..=k` through psi,
+    /// taking `O(kn)` time and two arrays of `n` integers.
+    ///
+    /// # Arguments
+    ///
+    /// * `rev_bwt` - The Burrows-Wheeler transform of the reversed reads.
+    /// * `k` - The order of the graph, i.e., the length of the node labels.
+    /// * `separator` - The separator of the reads.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if
+    ///
+    /// - `k` is zero,
+    /// - `rev_bwt` is empty or does not have exactly one terminator,
+    /// - `separator` is not larger than the terminator,
+    /// - `rev_bwt` has a symbol other than the terminator smaller than `separator`, or
+    /// - `rev_bwt` is not a valid BWT.
+    pub fn new(rev_bwt: &[u8], k: usize, separator: u8) -> Result<Self> {
+        if k == 0 {
+            return Err(Error::invalid_argument("k must be positive."));
+        }
+        let primary = primary_index(rev_bwt)?;
+        let terminator = rev_bwt[primary];
+        if separator <= terminator {
+            return Err(Error::invalid_argument(format!(
+                "separator must be larger than the terminator {terminator:x}, but got {separator:x}."
+            )));
+        }
+        let counts = count_symbols(rev_bwt);
+        if let Some(c) = (terminator as usize + 1..separator as usize).find(|&c| counts[c] != 0) {
+            return Err(Error::invalid_argument(format!(
+                "rev_bwt must not have symbols smaller than the separator {separator:x}, but found {c:x}."
+            )));
+        }
+
+        let n = rev_bwt.len();
+        let psi: Vec<_> = psi_values(rev_bwt).collect();
+        // Row 0 is the terminator, and psi walks the whole text from it.
+        let (mut r, mut steps) = (psi[0], 1);
+        while r != 0 && steps < n {
+            r = psi[r];
+            steps += 1;
+        }
+        if r != 0 || steps != n {
+            return Err(Error::invalid_bwt(
+                "rev_bwt is not a valid BWT, since the psi array has multiple cycles.",
+            ));
+        }
+
+        // The first symbols of the rows, with the terminator identified with the separator.
+        let mut firsts = Vec::with_capacity(n);
+        for (c, &count) in counts.iter().enumerate() {
+            let c = (c as u8).max(separator);
+            firsts.extend(std::iter::repeat(c).take(count));
+        }
+
+        // groups[r] is the group of row r by its first l symbols up to a separator,
+        // refined from l = 1 to k, keeping the ones of l = k - 1.
+        let mut prev_groups = vec![0; n];
+        let mut groups = vec![0; n];
+        for r in 1..n {
+            groups[r] = groups[r - 1] + usize::from(firsts[r] != firsts[r - 1]);
+        }
+        for _ in 1..k {
+            std::mem::swap(&mut prev_groups, &mut groups);
+            for r in 1..n {
+                let split = firsts[r] != firsts[r - 1]
+                    || (firsts[r] != separator && prev_groups[psi[r]] != prev_groups[psi[r - 1]]);
+                groups[r] = groups[r - 1] + usize::from(split);
+            }
+        }
+        drop(psi);
+
+        let mut labels = vec![];
+        let mut last = BitVectorBuilder::default();
+        let mut flagged = BitVectorBuilder::default();
+        let mut node_counts = [0; 256];
+        // Whether each label appears in the earlier nodes with the same last k - 1 symbols.
+        let mut seen = [false; 256];
+        let mut start = 0;
+        while start < n {
+            let end = (start..n)
+                .find(|&r| groups[r] != groups[start])
+                .unwrap_or(n);
+            if start != 0 && prev_groups[start] != prev_groups[start - 1] {
+                seen = [false; 256];
+            }
+            let mut outgoing = [false; 256];
+            for &c in &rev_bwt[start..end] {
+                outgoing[c.max(separator) as usize] = true;
+            }
+            if (separator as usize + 1..256).any(|c| outgoing[c]) {
+                outgoing[separator as usize] = false;
+            }
+            let degree = outgoing.iter().filter(|&&b| b).count();
+            for c in (0..256).filter(|&c| outgoing[c]) {
+                labels.push(c as u8);
+                flagged.push(c != separator as usize && seen[c]);
+                seen[c] = true;
+            }
+            for i in 0..degree {
+                last.push(i + 1 == degree);
+            }
+            node_counts[firsts[start] as usize] += 1;
+            start = end;
+        }
+
+        let mut node_starts = vec![0; 257];
+        for c in 0..256 {
+            node_starts[c + 1] = node_starts[c] + node_counts[c];
+        }
+        let flagged = flagged.finish();
+        let targets: Vec<_> = labels
+            .iter()
+            .enumerate()
+            .map(|(e, &c)| if flagged.get(e) { separator } else { c })
+            .collect();
+        Ok(Self {
+            k,
+            separator,
+            targets: WaveletTree::new(&targets),
+            labels,
+            last: last.finish(),
+            flagged,
+            node_starts,
+        })
+    }
+
+    /// Returns the order of the graph, i.e., the length of the node labels.
+    pub const fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the separator, which pads the node labels and labels the edges of nodes without successors.
+    pub const fn separator(&self) -> u8 {
+        self.separator
+    }
+
+    /// Returns the number of nodes.
+    pub fn n_nodes(&self) -> usize {
+        self.node_starts[256]
+    }
+
+    /// Returns the number of edges, including the ones labeled by the separator.
+    pub fn n_edges(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Returns the labels of the edges, often denoted by `W`.
+    pub fn labels(&self) -> &[u8] {
+        &self.labels
+    }
+
+    /// Returns `true` if edge `e` is the last one of its node.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `e` is out of bounds.
+    pub fn is_last(&self, e: usize) -> bool {
+        assert!(
+            e < self.n_edges(),
+            "edge {e} must be less than {}.",
+            self.n_edges()
+        );
+        self.last.get(e)
+    }
+
+    /// Returns `true` if edge `e` is flagged,
+    /// i.e., an earlier node with the same last `k-1` symbols has an edge with the same label.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `e` is out of bounds.
+    pub fn is_flagged(&self, e: usize) -> bool {
+        assert!(
+            e < self.n_edges(),
+            "edge {e} must be less than {}.",
+            self.n_edges()
+        );
+        self.flagged.get(e)
+    }
+
+    /// Returns the range of the outgoing edges of node `v`.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `v` is out of bounds.
+    pub fn edges(&self, v: usize) -> Range<usize> {
+        assert!(
+            v < self.n_nodes(),
+            "node {v} must be less than {}.",
+            self.n_nodes()
+        );
+        self.first_edge(v)..self.first_edge(v + 1)
+    }
+
+    /// Returns the first edge of node `v`, by binary search over the ranks of the last flags.
+    fn first_edge(&self, v: usize) -> usize {
+        let (mut lo, mut hi) = (0, self.n_edges());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.last.rank1(mid) < v {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Returns the number of successors of node `v`.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `v` is out of bounds.
+    pub fn outdegree(&self, v: usize) -> usize {
+        let edges = self.edges(v);
+        if self.labels[edges.start] == self.separator {
+            0
+        } else {
+            edges.len()
+        }
+    }
+
+    /// Returns the successor of node `v` by the edge labeled `c`, or `None` if it does not exist.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `v` is out of bounds.
+    pub fn outgoing(&self, v: usize, c: u8) -> Option<usize> {
+        if c == self.separator {
+            return None;
+        }
+        let e = self.edges(v).find(|&e| self.labels[e] == c)?;
+        // A flagged edge points to the node of the previous unflagged edge with the same label.
+        let rank = self.targets.rank(c, e);
+        let rank = if self.flagged.get(e) { rank - 1 } else { rank };
+        Some(self.node_starts[c as usize] + rank)
+    }
+
+    /// Returns the predecessor of node `v` by its unflagged incoming edge,
+    /// or `None` if `v` is the node of `k` separators.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `v` is out of bounds.
+    pub fn incoming(&self, v: usize) -> Option<usize> {
+        let c = self.last_symbol(v);
+        if c == self.separator {
+            return None;
+        }
+        let e = self.targets.select(c, v - self.node_starts[c as usize])?;
+        Some(self.last.rank1(e))
+    }
+
+    /// Returns the last symbol of the label of node `v`.
+    fn last_symbol(&self, v: usize) -> u8 {
+        assert!(
+            v < self.n_nodes(),
+            "node {v} must be less than {}.",
+            self.n_nodes()
+        );
+        (self.node_starts.partition_point(|&s| s <= v) - 1) as u8
+    }
+
+    /// Returns the label of node `v`, padded on the left with the separator.
+    ///
+    /// It follows the incoming edges `k` times at most.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `v` is out of bounds.
+    pub fn label(&self, mut v: usize) -> Vec<u8> {
+        let mut label = vec![self.separator; self.k];
+        for i in (0..self.k).rev() {
+            let c = self.last_symbol(v);
+            match self.incoming(v) {
+                Some(u) => {
+                    label[i] = c;
+                    v = u;
+                }
+                None => break,
+            }
+        }
+        label
+    }
+
+    /// Returns the node labeled `kmer`, or `None` if it does not exist,
+    /// by binary search over the nodes in the colexicographic order of their labels.
+    ///
+    /// It takes `O(k log n)` traversals of the incoming edges, where `n` is the number of nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `kmer` - The label of length `k`, which may be padded on the left with the separator.
+    pub fn find(&self, kmer: &[u8]) -> Option<usize> {
+        if kmer.len() != self.k {
+            return None;
+        }
+        let (mut lo, mut hi) = (0, self.n_nodes());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.label(mid).iter().rev().lt(kmer.iter().rev()) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        (lo < self.n_nodes() && self.label(lo) == kmer).then_some(lo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use crate::{concat_inputs, BwtBuilder};
+
+    fn rev_bwt(reads: &[&[u8]]) -> Vec<u8> {
+        let concat = concat_inputs(reads.iter().copied(), b'$').unwrap();
+        let mut rev_bwt = vec![];
+        concat
+            .builder()
+            .unwrap()
+            .reverse_writer(&mut rev_bwt)
+            .build(&mut vec![])
+            .unwrap();
+        rev_bwt
+    }
+
+    /// Returns the node labels in order and the labels, last flags, and flagged flags of the edges.
+    fn naive_boss(reads: &[&[u8]], k: usize) -> (Vec<Vec<u8>>, Vec<u8>, Vec<bool>, Vec<bool>) {
+        // Keyed by the reversed labels for the colexicographic order.
+        let mut nodes: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for read in reads {
+            let mut padded = vec![b'$'; k];
+            padded.extend_from_slice(read);
+            padded.push(b'$');
+            for j in 0..=read.len() {
+                let key: Vec<u8> = padded[j..j + k].iter().rev().copied().collect();
+                nodes.entry(key).or_default().push(padded[j + k]);
+            }
+        }
+        let (mut labels, mut last, mut flagged) = (vec![], vec![], vec![]);
+        let mut seen = BTreeSet::new();
+        let mut node_labels = vec![];
+        for (key, mut outgoing) in nodes {
+            outgoing.sort_unstable();
+            outgoing.dedup();
+            if outgoing.len() > 1 {
+                outgoing.retain(|&c| c != b'$');
+            }
+            for (i, &c) in outgoing.iter().enumerate() {
+                labels.push(c);
+                last.push(i + 1 == outgoing.len());
+                flagged.push(c != b'$' && !seen.insert((key[..k - 1].to_vec(), c)));
+            }
+            node_labels.push(key.into_iter().rev().collect());
+        }
+        (node_labels, labels, last, flagged)
+    }
+
+    #[test]
+    fn test_boss() {
+        let collections: &[&[&[u8]]] = &[
+            &[b"ACG", b"CGT"],
+            &[b"ACGTACGT", b"CGTTACG", b"GGTAC"],
+            &[b"AAAAAA", b"AAC", b""],
+            &[b"A"],
+            &[b"ACGACGACGTTTGCA", b"TTGCAACG", b"CAT", b"GCAC"],
+        ];
+        for &reads in collections {
+            let bwt = rev_bwt(reads);
+            for k in 1..=5 {
+                let boss = Boss::new(&bwt, k, b'$').unwrap();
+                let (nodes, labels, last, flagged) = naive_boss(reads, k);
+                assert_eq!(boss.n_nodes(), nodes.len(), "{reads:?} k={k}");
+                assert_eq!(boss.labels(), labels, "{reads:?} k={k}");
+                let boss_last: Vec<_> = (0..boss.n_edges()).map(|e| boss.is_last(e)).collect();
+                assert_eq!(boss_last, last, "{reads:?} k={k}");
+                let boss_flagged: Vec<_> =
+                    (0..boss.n_edges()).map(|e| boss.is_flagged(e)).collect();
+                assert_eq!(boss_flagged, flagged, "{reads:?} k={k}");
+
+                for (v, node) in nodes.iter().enumerate() {
+                    assert_eq!(boss.label(v), *node, "{reads:?} k={k}");
+                    assert_eq!(boss.find(node), Some(v));
+                    for e in boss.edges(v) {
+                        let c = boss.labels()[e];
+                        let u = match boss.outgoing(v, c) {
+                            Some(u) => u,
+                            None => {
+                                assert_eq!(c, b'$');
+                                assert_eq!(boss.outdegree(v), 0);
+                                continue;
+                            }
+                        };
+                        let mut expected = node[1..].to_vec();
+                        expected.push(c);
+                        assert_eq!(nodes[u], expected, "{reads:?} k={k}");
+                    }
+                }
+                assert_eq!(boss.incoming(0), None);
+                assert_eq!(boss.find(&vec![b'X'; k]), None);
+                assert_eq!(boss.find(&vec![b'A'; k + 1]), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_boss_invalid() {
+        let bwt = rev_bwt(&[b"ACG", b"CGT"]);
+        assert!(Boss::new(&bwt, 0, b'$').is_err());
+        assert!(Boss::new(&bwt, 2, b'\0').is_err());
+        assert!(Boss::new(&bwt, 2, b'B').is_err());
+        assert!(Boss::new(b"", 2, b'$').is_err());
+        assert!(Boss::new(b"\0\0A", 2, b'$').is_err());
+        // Two cycles of "A\0" and "C".
+        assert!(Boss::new(b"A\0C", 2, b'$').is_err());
+
+        let mut text_bwt = vec![];
+        BwtBuilder::new(b"AC\0")
+            .unwrap()
+            .build(&mut text_bwt)
+            .unwrap();
+        assert!(Boss::new(&text_bwt, 1, b'$').is_ok());
+    }
+}
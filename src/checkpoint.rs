@@ -0,0 +1,206 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+/// Magic bytes at the beginning of a checkpoint file.
+const MAGIC: &[u8; 4] = b"SBCK";
+/// Version of the file format.
+const VERSION: u8 = 1;
+
+/// State of an interrupted construction.
+///
+/// It is written by [`BwtBuilder::checkpoint`](crate::BwtBuilder::checkpoint) after each chunk
+/// and passed to [`BwtBuilder::resume`](crate::BwtBuilder::resume) to continue it.
+///
+/// The file is stored in little-endian order:
+///
+/// | Size | Content |
+/// |-----:|---------|
+/// | 4 | Magic `SBCK` |
+/// | 1 | Version `1` |
+/// | 8 | Length of the text |
+/// | 8 | Number of cuts whose chunks are written |
+/// | 8 | Number of bytes of the BWT written |
+/// | 8 | Number of cuts |
+///
+/// followed by the length in 8 bytes and the bytes of each cut.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The length of the text in symbols.
+    pub text_len: usize,
+    /// The cuts of the construction.
+    pub cuts: Vec<Vec<u8>>,
+    /// The number of cuts whose chunks are written, which is less than the number of cuts.
+    pub n_cuts_done: usize,
+    /// The number of bytes of the BWT written, i.e., the length of the output to be kept.
+    pub n_bytes_written: usize,
+}
+
+impl Checkpoint {
+    /// Reads a checkpoint from `rdr`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the checkpoint is malformed or `rdr` returns an error.
+    pub fn read_from<R: Read>(mut rdr: R) -> Result<Self> {
+        let mut head = [0; 5];
+        rdr.read_exact(&mut head)?;
+        if &head[..4] != MAGIC {
+            return Err(Error::invalid_format(
+                "not a checkpoint file, since the magic is missing.",
+            ));
+        }
+        if head[4] != VERSION {
+            return Err(Error::invalid_format(format!(
+                "version must be {VERSION}, but got {}.",
+                head[4]
+            )));
+        }
+        let text_len = read_usize(&mut rdr)?;
+        let n_cuts_done = read_usize(&mut rdr)?;
+        let n_bytes_written = read_usize(&mut rdr)?;
+        let cuts = read_cuts(&mut rdr)?;
+        let n_cuts = cuts.len();
+        if n_cuts_done >= n_cuts {
+            return Err(Error::invalid_format(format!(
+                "number of cuts done must be less than {n_cuts}, but got {n_cuts_done}."
+            )));
+        }
+        Ok(Self {
+            text_len,
+            cuts,
+            n_cuts_done,
+            n_bytes_written,
+        })
+    }
+
+    /// Writes the checkpoint to `wrt`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `wrt` returns an error.
+    pub fn write_to<W: Write>(&self, mut wrt: W) -> Result<()> {
+        wrt.write_all(MAGIC)?;
+        wrt.write_all(&[VERSION])?;
+        for x in [self.text_len, self.n_cuts_done, self.n_bytes_written] {
+            wrt.write_all(&(x as u64).to_le_bytes())?;
+        }
+        write_cuts(&mut wrt, &self.cuts)?;
+        wrt.flush()?;
+        Ok(())
+    }
+
+    /// Loads a checkpoint from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the file cannot be read or is malformed.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Saves the checkpoint to the file at `path`,
+    /// writing a temporary file next to it and renaming it,
+    /// so that the file is never left half-written.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the file cannot be written.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        save_atomically(path.as_ref(), |wrt| self.write_to(wrt))
+    }
+}
+
+/// Writes a file by `write` to a temporary file next to `path` and renames it.
+pub fn save_atomically<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+{
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut wrt = BufWriter::new(File::create(&tmp)?);
+    write(&mut wrt)?;
+    wrt.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Writes the number of cuts followed by the length in 8 bytes and the bytes of each cut.
+pub fn write_cuts<W: Write>(wrt: &mut W, cuts: &[Vec<u8>]) -> Result<()> {
+    wrt.write_all(&(cuts.len() as u64).to_le_bytes())?;
+    for cut in cuts {
+        wrt.write_all(&(cut.len() as u64).to_le_bytes())?;
+        wrt.write_all(cut)?;
+    }
+    Ok(())
+}
+
+/// Reads the cuts written by [`write_cuts`].
+pub fn read_cuts<R: Read>(rdr: &mut R) -> Result<Vec<Vec<u8>>> {
+    let n_cuts = read_usize(rdr)?;
+    let mut cuts = Vec::with_capacity(n_cuts.min(1 << 20));
+    for _ in 0..n_cuts {
+        let len = read_usize(rdr)?;
+        let mut cut = vec![];
+        rdr.by_ref().take(len as u64).read_to_end(&mut cut)?;
+        if cut.len() != len {
+            return Err(Error::invalid_format("unexpected end of the cuts."));
+        }
+        cuts.push(cut);
+    }
+    Ok(cuts)
+}
+
+pub fn read_usize<R: Read>(rdr: &mut R) -> Result<usize> {
+    let mut buf = [0; 8];
+    rdr.read_exact(&mut buf)?;
+    let x = u64::from_le_bytes(buf);
+    usize::try_from(x)
+        .map_err(|_| Error::invalid_format(format!("integer {x} does not fit in usize.")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint() -> Checkpoint {
+        Checkpoint {
+            text_len: 12,
+            cuts: vec![b"".to_vec(), b"ab".to_vec(), b"ad".to_vec(), b"c".to_vec()],
+            n_cuts_done: 2,
+            n_bytes_written: 5,
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let mut buf = vec![];
+        checkpoint().write_to(&mut buf).unwrap();
+        assert_eq!(&buf[..5], b"SBCK\x01");
+        assert_eq!(Checkpoint::read_from(buf.as_slice()).unwrap(), checkpoint());
+
+        let path = std::env::temp_dir().join(format!("small-bwt-{}.ckpt", std::process::id()));
+        checkpoint().save(&path).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap(), checkpoint());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_invalid() {
+        let mut buf = vec![];
+        checkpoint().write_to(&mut buf).unwrap();
+        for len in [0, 4, 20, buf.len() - 1] {
+            assert!(Checkpoint::read_from(&buf[..len]).is_err());
+        }
+        let mut broken = buf.clone();
+        broken[0] = b'X';
+        assert!(Checkpoint::read_from(broken.as_slice()).is_err());
+        let mut done = checkpoint();
+        done.n_cuts_done = done.cuts.len();
+        let mut buf = vec![];
+        done.write_to(&mut buf).unwrap();
+        assert!(Checkpoint::read_from(buf.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,623 @@
+//! Block-sorting compression stages applied to the BWT, like bzip2.
+//!
+//! Each stage consists of a writer adapter encoding a byte stream
+//! and a reader adapter decoding it, which can be chained in the order of
+//! [`MtfWriter`], [`ZeroRunWriter`], and [`HuffmanWriter`].
+use std::io::{Read, Write};
+
+use crate::error::{Error, Result};
+
+use crate::intio::{read_varint, write_varint};
+
+/// The maximum number of symbols per block of the Huffman coding.
+const HUFFMAN_BLOCK_LEN: usize = 1 << 20;
+
+/// The maximum length of Huffman codes.
+const MAX_CODE_LEN: usize = 24;
+
+/// Writer adapter applying the move-to-front transform to a byte stream such as the BWT.
+///
+/// Each byte is replaced with its position in the list of recently used bytes,
+/// so that runs of equal bytes in the BWT become runs of zeros.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::io::Read;
+/// use small_bwt::{BwtBuilder, HuffmanReader, HuffmanWriter, MtfReader, MtfWriter};
+/// use small_bwt::{ZeroRunReader, ZeroRunWriter};
+///
+/// let text = "abracadabra$";
+/// let mut wrt = MtfWriter::new(ZeroRunWriter::new(HuffmanWriter::new(vec![])));
+/// BwtBuilder::new(text.as_bytes())?.build(&mut wrt)?;
+/// let compressed = wrt.finish()?.finish()?.finish()?;
+///
+/// let mut rdr = MtfReader::new(ZeroRunReader::new(HuffmanReader::new(compressed.as_slice())));
+/// let mut bwt = vec![];
+/// rdr.read_to_end(&mut bwt)?;
+/// assert_eq!(bwt, b"ard$rcaaaabb");
+/// # Ok(())
+/// # }
+/// ```
+pub struct MtfWriter<W: Write> {
+    wrt: W,
+    table: MtfTable,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> MtfWriter<W> {
+    /// Creates a new writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the transformed stream.
+    pub fn new(wrt: W) -> Self {
+        Self {
+            wrt,
+            table: MtfTable::new(),
+            buf: vec![],
+        }
+    }
+
+    /// Flushes and returns the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the underlying writer returns an error.
+    pub fn finish(mut self) -> Result<W> {
+        self.wrt.flush()?;
+        Ok(self.wrt)
+    }
+}
+
+impl<W: Write> Write for MtfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.clear();
+        self.buf.extend(buf.iter().map(|&c| self.table.encode(c)));
+        self.wrt.write_all(&self.buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.wrt.flush()
+    }
+}
+
+/// Reader adapter inverting the move-to-front transform of a stream written by [`MtfWriter`].
+pub struct MtfReader<R: Read> {
+    rdr: R,
+    table: MtfTable,
+}
+
+impl<R: Read> MtfReader<R> {
+    /// Creates a new reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `rdr` - The reader of the transformed stream.
+    pub fn new(rdr: R) -> Self {
+        Self {
+            rdr,
+            table: MtfTable::new(),
+        }
+    }
+}
+
+impl<R: Read> Read for MtfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.rdr.read(buf)?;
+        for c in &mut buf[..len] {
+            *c = self.table.decode(*c);
+        }
+        Ok(len)
+    }
+}
+
+/// List of bytes in the order of recent use.
+struct MtfTable {
+    list: [u8; 256],
+}
+
+impl MtfTable {
+    fn new() -> Self {
+        let mut list = [0; 256];
+        for (i, c) in list.iter_mut().enumerate() {
+            *c = i as u8;
+        }
+        Self { list }
+    }
+
+    fn encode(&mut self, c: u8) -> u8 {
+        let i = self.list.iter().position(|&d| d == c).unwrap();
+        self.list.copy_within(..i, 1);
+        self.list[0] = c;
+        i as u8
+    }
+
+    fn decode(&mut self, i: u8) -> u8 {
+        let i = i as usize;
+        let c = self.list[i];
+        self.list.copy_within(..i, 1);
+        self.list[0] = c;
+        c
+    }
+}
+
+/// Writer adapter run-length encoding zeros in a byte stream such as the output of [`MtfWriter`].
+///
+/// Each maximal run of zeros is emitted as a zero byte followed by
+/// the run length in the unsigned LEB128 encoding, and the other bytes are emitted as they are.
+/// The pending run is emitted only by [`ZeroRunWriter::finish`],
+/// so it must be called after all bytes are written.
+pub struct ZeroRunWriter<W: Write> {
+    wrt: W,
+    // The length of the pending run of zeros.
+    run: usize,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> ZeroRunWriter<W> {
+    /// Creates a new writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the encoded stream.
+    pub const fn new(wrt: W) -> Self {
+        Self {
+            wrt,
+            run: 0,
+            buf: vec![],
+        }
+    }
+
+    /// Emits the pending run and returns the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the underlying writer returns an error.
+    pub fn finish(mut self) -> Result<W> {
+        if self.run != 0 {
+            self.wrt.write_all(&[0])?;
+            write_varint(&mut self.wrt, self.run)?;
+        }
+        self.wrt.flush()?;
+        Ok(self.wrt)
+    }
+}
+
+impl<W: Write> Write for ZeroRunWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.clear();
+        for &c in buf {
+            if c == 0 {
+                self.run += 1;
+                continue;
+            }
+            if self.run != 0 {
+                self.buf.push(0);
+                write_varint(&mut self.buf, self.run)?;
+                self.run = 0;
+            }
+            self.buf.push(c);
+        }
+        self.wrt.write_all(&self.buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.wrt.flush()
+    }
+}
+
+/// Reader adapter decoding a stream written by [`ZeroRunWriter`].
+pub struct ZeroRunReader<R: Read> {
+    rdr: R,
+    // The number of zeros to be read.
+    run: usize,
+}
+
+impl<R: Read> ZeroRunReader<R> {
+    /// Creates a new reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `rdr` - The reader of the encoded stream.
+    pub const fn new(rdr: R) -> Self {
+        Self { rdr, run: 0 }
+    }
+}
+
+impl<R: Read> Read for ZeroRunReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() {
+            if self.run != 0 {
+                let n = self.run.min(buf.len() - len);
+                buf[len..len + n].fill(0);
+                self.run -= n;
+                len += n;
+                continue;
+            }
+            let mut c = [0; 1];
+            if self.rdr.read(&mut c)? == 0 {
+                break;
+            }
+            if c[0] == 0 {
+                self.run = read_varint(&mut self.rdr)?;
+                if self.run == 0 {
+                    return Err(Error::invalid_format("run length must be positive.").into());
+                }
+            } else {
+                buf[len] = c[0];
+                len += 1;
+            }
+        }
+        Ok(len)
+    }
+}
+
+/// Writer adapter compressing a byte stream with the Huffman coding.
+///
+/// The stream is split into blocks of up to 2^20 bytes, each of which is emitted as
+/// the block length in the unsigned LEB128 encoding, the code lengths of the 256 bytes,
+/// and the canonical Huffman codes of the bytes packed from the most significant bit.
+/// The end of the stream is marked by a block of length zero,
+/// emitted by [`HuffmanWriter::finish`] after the pending block,
+/// so it must be called after all bytes are written.
+pub struct HuffmanWriter<W: Write> {
+    wrt: W,
+    block: Vec<u8>,
+}
+
+impl<W: Write> HuffmanWriter<W> {
+    /// Creates a new writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the compressed stream.
+    pub const fn new(wrt: W) -> Self {
+        Self { wrt, block: vec![] }
+    }
+
+    /// Emits the pending block and the end marker, and returns the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the underlying writer returns an error.
+    pub fn finish(mut self) -> Result<W> {
+        self.write_block()?;
+        write_varint(&mut self.wrt, 0)?;
+        self.wrt.flush()?;
+        Ok(self.wrt)
+    }
+
+    fn write_block(&mut self) -> std::io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let mut freqs = [0; 256];
+        for &c in &self.block {
+            freqs[c as usize] += 1;
+        }
+        let lens = code_lengths(&freqs);
+        let codes = canonical_codes(&lens);
+
+        let mut out = Vec::with_capacity(self.block.len() / 2 + 266);
+        write_varint(&mut out, self.block.len())?;
+        out.extend_from_slice(&lens);
+        let mut acc = 0u64;
+        let mut n_bits = 0;
+        for &c in &self.block {
+            let len = lens[c as usize] as usize;
+            acc = (acc << len) | codes[c as usize] as u64;
+            n_bits += len;
+            while n_bits >= 8 {
+                n_bits -= 8;
+                out.push((acc >> n_bits) as u8);
+            }
+        }
+        if n_bits != 0 {
+            out.push((acc << (8 - n_bits)) as u8);
+        }
+        self.wrt.write_all(&out)?;
+        self.block.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HuffmanWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut buf = buf;
+        let len = buf.len();
+        while !buf.is_empty() {
+            let n = (HUFFMAN_BLOCK_LEN - self.block.len()).min(buf.len());
+            self.block.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+            if self.block.len() == HUFFMAN_BLOCK_LEN {
+                self.write_block()?;
+            }
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.wrt.flush()
+    }
+}
+
+/// Reader adapter decompressing a stream written by [`HuffmanWriter`].
+///
+/// The compressed stream is read byte by byte, so `rdr` should be buffered.
+pub struct HuffmanReader<R: Read> {
+    rdr: R,
+    // The decoded block and the number of bytes already read from it.
+    block: Vec<u8>,
+    pos: usize,
+    finished: bool,
+}
+
+impl<R: Read> HuffmanReader<R> {
+    /// Creates a new reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `rdr` - The reader of the compressed stream.
+    pub const fn new(rdr: R) -> Self {
+        Self {
+            rdr,
+            block: vec![],
+            pos: 0,
+            finished: false,
+        }
+    }
+
+    fn read_block(&mut self) -> Result<()> {
+        self.block.clear();
+        self.pos = 0;
+        let len = read_varint(&mut self.rdr)?;
+        if len == 0 {
+            self.finished = true;
+            return Ok(());
+        }
+        if len > HUFFMAN_BLOCK_LEN {
+            return Err(Error::invalid_format(format!(
+                "block length must be at most {HUFFMAN_BLOCK_LEN}, but got {len}."
+            )));
+        }
+        let mut lens = [0; 256];
+        self.rdr.read_exact(&mut lens)?;
+        let decoder = CanonicalDecoder::new(&lens)?;
+
+        let mut byte = [0; 1];
+        let mut n_bits = 0;
+        for _ in 0..len {
+            let mut code = 0;
+            let mut first = 0;
+            let mut index = 0;
+            let mut symbol = None;
+            for l in 1..=MAX_CODE_LEN {
+                if n_bits == 0 {
+                    self.rdr.read_exact(&mut byte)?;
+                    n_bits = 8;
+                }
+                n_bits -= 1;
+                code |= ((byte[0] >> n_bits) & 1) as usize;
+                let count = decoder.counts[l];
+                if code < first + count {
+                    symbol = Some(decoder.symbols[index + code - first]);
+                    break;
+                }
+                index += count;
+                first = (first + count) << 1;
+                code <<= 1;
+            }
+            match symbol {
+                Some(c) => self.block.push(c),
+                None => return Err(Error::invalid_format("invalid Huffman code.")),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for HuffmanReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.block.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.read_block()?;
+        }
+        let n = (self.block.len() - self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Decoding table of canonical Huffman codes.
+struct CanonicalDecoder {
+    // counts[l] is the number of codes of length l.
+    counts: [usize; MAX_CODE_LEN + 1],
+    // The symbols sorted by their code lengths and values.
+    symbols: Vec<u8>,
+}
+
+impl CanonicalDecoder {
+    fn new(lens: &[u8; 256]) -> Result<Self> {
+        let mut counts = [0; MAX_CODE_LEN + 1];
+        for &l in lens {
+            let l = l as usize;
+            if l > MAX_CODE_LEN {
+                return Err(Error::invalid_format(format!(
+                    "code length must be at most {MAX_CODE_LEN}, but got {l}."
+                )));
+            }
+            counts[l] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<_> = (0..=255u8).filter(|&c| lens[c as usize] != 0).collect();
+        symbols.sort_by_key(|&c| lens[c as usize]);
+        Ok(Self { counts, symbols })
+    }
+}
+
+/// Computes the Huffman code lengths of at most [`MAX_CODE_LEN`] bits for the frequencies.
+///
+/// If the lengths exceed the limit, the frequencies are halved until they fit.
+fn code_lengths(freqs: &[usize; 256]) -> [u8; 256] {
+    let mut freqs = *freqs;
+    loop {
+        let lens = huffman_lengths(&freqs);
+        if lens.iter().all(|&l| l as usize <= MAX_CODE_LEN) {
+            return lens;
+        }
+        for f in freqs.iter_mut().filter(|f| **f != 0) {
+            *f = (*f + 1) / 2;
+        }
+    }
+}
+
+/// Computes the optimal code lengths with the Huffman algorithm,
+/// where a single symbol is given a code of length one.
+fn huffman_lengths(freqs: &[usize; 256]) -> [u8; 256] {
+    let mut lens = [0; 256];
+    // Nodes as pairs of the weight and the symbols in the subtree.
+    let mut nodes: Vec<(usize, Vec<u8>)> = (0..=255u8)
+        .filter(|&c| freqs[c as usize] != 0)
+        .map(|c| (freqs[c as usize], vec![c]))
+        .collect();
+    if nodes.len() == 1 {
+        lens[nodes[0].1[0] as usize] = 1;
+        return lens;
+    }
+    while nodes.len() > 1 {
+        // Keeps the nodes sorted in decreasing order of weight to pop the two lightest ones.
+        nodes.sort_by_key(|node| std::cmp::Reverse(node.0));
+        let (w1, s1) = nodes.pop().unwrap();
+        let (w2, s2) = nodes.pop().unwrap();
+        for &c in s1.iter().chain(&s2) {
+            lens[c as usize] += 1;
+        }
+        nodes.push((w1 + w2, [s1, s2].concat()));
+    }
+    lens
+}
+
+/// Assigns the canonical codes to the code lengths.
+fn canonical_codes(lens: &[u8; 256]) -> [u32; 256] {
+    let mut codes = [0; 256];
+    let mut symbols: Vec<_> = (0..=255u8).filter(|&c| lens[c as usize] != 0).collect();
+    symbols.sort_by_key(|&c| lens[c as usize]);
+    let mut code = 0u32;
+    let mut prev_len = 0;
+    for c in symbols {
+        let len = lens[c as usize];
+        code <<= len - prev_len;
+        codes[c as usize] = code;
+        code += 1;
+        prev_len = len;
+    }
+    codes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        (0..100000u32)
+            .map(|i| b"abcd"[((i.count_ones() + i / 1000) % 4) as usize])
+            .collect()
+    }
+
+    #[test]
+    fn test_mtf() {
+        let mut wrt = MtfWriter::new(vec![]);
+        wrt.write_all(b"bbaac").unwrap();
+        let encoded = wrt.finish().unwrap();
+        assert_eq!(encoded, vec![b'b', 0, b'b', 0, b'c']);
+        let mut decoded = vec![];
+        MtfReader::new(encoded.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, b"bbaac");
+    }
+
+    #[test]
+    fn test_zero_run() {
+        let data = [&[0; 300][..], b"ab", &[0], b"c", &[0; 2]].concat();
+        let mut wrt = ZeroRunWriter::new(vec![]);
+        wrt.write_all(&data[..100]).unwrap();
+        wrt.write_all(&data[100..]).unwrap();
+        let encoded = wrt.finish().unwrap();
+        assert_eq!(encoded, vec![0, 0xac, 0x02, b'a', b'b', 0, 1, b'c', 0, 2]);
+        let mut decoded = vec![];
+        ZeroRunReader::new(encoded.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_huffman() {
+        let data = sample();
+        let mut wrt = HuffmanWriter::new(vec![]);
+        wrt.write_all(&data).unwrap();
+        let encoded = wrt.finish().unwrap();
+        assert!(encoded.len() < data.len() / 3);
+        let mut decoded = vec![];
+        HuffmanReader::new(encoded.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_huffman_single_symbol() {
+        for data in [vec![], vec![7; 10]] {
+            let mut wrt = HuffmanWriter::new(vec![]);
+            wrt.write_all(&data).unwrap();
+            let encoded = wrt.finish().unwrap();
+            let mut decoded = vec![];
+            HuffmanReader::new(encoded.as_slice())
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn test_huffman_truncated() {
+        let mut wrt = HuffmanWriter::new(vec![]);
+        wrt.write_all(b"abracadabra").unwrap();
+        let encoded = wrt.finish().unwrap();
+        let mut decoded = vec![];
+        let e = HuffmanReader::new(&encoded[..encoded.len() - 2]).read_to_end(&mut decoded);
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn test_code_lengths_limited() {
+        // Fibonacci frequencies give the deepest Huffman tree.
+        let mut freqs = [0; 256];
+        let (mut a, mut b) = (1, 1);
+        for f in freqs.iter_mut().take(40) {
+            *f = a;
+            (a, b) = (b, a + b);
+        }
+        assert!(huffman_lengths(&freqs)
+            .iter()
+            .any(|&l| l as usize > MAX_CODE_LEN));
+        let lens = code_lengths(&freqs);
+        assert!(lens.iter().all(|&l| l as usize <= MAX_CODE_LEN));
+        // Kraft's inequality holds with equality.
+        let kraft: u64 = lens
+            .iter()
+            .filter(|&&l| l != 0)
+            .map(|&l| 1 << (MAX_CODE_LEN - l as usize))
+            .sum();
+        assert_eq!(kraft, 1 << MAX_CODE_LEN);
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::Read;
+use std::ops::Range;
+
+use crate::error::{Error, Result};
+
+use crate::BwtBuilder;
+
+/// Terminator appended to the end of a text concatenated by [`concat_inputs`].
+pub const CONCAT_TERMINATOR: u8 = b'\0';
+
+/// Text concatenating several inputs, such as the files of a corpus, with the boundaries of the inputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConcatText {
+    /// The concatenated text ending with [`CONCAT_TERMINATOR`].
+    pub text: Vec<u8>,
+    /// The range of each input in the text, in the order given.
+    pub inputs: Vec<Range<usize>>,
+}
+
+impl ConcatText {
+    /// Creates a builder of the BWT of the concatenated text.
+    ///
+    /// # Errors
+    ///
+    /// See [`BwtBuilder::new`].
+    pub fn builder(&self) -> Result<BwtBuilder<'_>> {
+        BwtBuilder::new(&self.text)
+    }
+
+    /// Returns the index of the input containing the text position `pos`,
+    /// or `None` if `pos` is at a separator or the terminator.
+    pub fn input_of(&self, pos: usize) -> Option<usize> {
+        let i = self.inputs.partition_point(|r| r.start <= pos);
+        let i = i.checked_sub(1)?;
+        self.inputs[i].contains(&pos).then_some(i)
+    }
+}
+
+/// Concatenates the contents of several inputs into a text for [`BwtBuilder`],
+/// recording the range of each input so that the BWT can be related back to the inputs.
+///
+/// The inputs are concatenated with `separator` between consecutive ones,
+/// and [`CONCAT_TERMINATOR`] is appended to the end.
+/// An empty input has an empty range between separators.
+///
+/// # Arguments
+///
+/// * `inputs` - The readers of the inputs.
+/// * `separator` - The byte to separate inputs, which must be larger than [`CONCAT_TERMINATOR`].
+///
+/// # Errors
+///
+/// An error is returned if
+///
+/// - `separator` is [`CONCAT_TERMINATOR`],
+/// - an input contains `separator` or [`CONCAT_TERMINATOR`],
+/// - no inputs are given, or
+/// - a reader returns an error.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::concat_inputs;
+///
+/// let inputs = [&b"abra"[..], b"cadabra"];
+/// let concat = concat_inputs(inputs, b'$')?;
+/// assert_eq!(concat.text, b"abra$cadabra\0");
+/// assert_eq!(concat.inputs, [0..4, 5..12]);
+/// assert_eq!(concat.input_of(6), Some(1));
+/// assert_eq!(concat.input_of(4), None);
+///
+/// let mut bwt = vec![];
+/// concat.builder()?.build(&mut bwt)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn concat_inputs<I, R>(inputs: I, separator: u8) -> Result<ConcatText>
+where
+    I: IntoIterator<Item = R>,
+    R: Read,
+{
+    if separator == CONCAT_TERMINATOR {
+        return Err(Error::invalid_argument(format!(
+            "separator must be different from the terminator {CONCAT_TERMINATOR:?}."
+        )));
+    }
+    let mut text = vec![];
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for mut input in inputs {
+        if !ranges.is_empty() {
+            text.push(separator);
+        }
+        let start = text.len();
+        input.read_to_end(&mut text)?;
+        if let Some(pos) = text[start..]
+            .iter()
+            .position(|&c| c == separator || c == CONCAT_TERMINATOR)
+        {
+            return Err(Error::invalid_format(format!(
+                "input must not contain the separator or terminator, but input {} has {:?} at position {pos}.",
+                ranges.len(),
+                text[start + pos]
+            )));
+        }
+        ranges.push(start..text.len());
+    }
+    if ranges.is_empty() {
+        return Err(Error::invalid_argument("inputs must not be empty."));
+    }
+    text.push(CONCAT_TERMINATOR);
+    Ok(ConcatText {
+        text,
+        inputs: ranges,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat_inputs() {
+        let inputs = [&b"abra"[..], b"", b"cad", b"abra"];
+        let concat = concat_inputs(inputs, b'$').unwrap();
+        assert_eq!(concat.text, b"abra$$cad$abra\0");
+        assert_eq!(concat.inputs, [0..4, 5..5, 6..9, 10..14]);
+        for (pos, i) in [
+            (0, Some(0)),
+            (3, Some(0)),
+            (4, None),
+            (5, None),
+            (6, Some(2)),
+            (9, None),
+            (13, Some(3)),
+            (14, None),
+        ] {
+            assert_eq!(concat.input_of(pos), i, "pos = {pos}");
+        }
+        let mut bwt = vec![];
+        concat.builder().unwrap().build(&mut bwt).unwrap();
+        assert_eq!(crate::decode_bwt(&bwt).unwrap(), concat.text);
+    }
+
+    #[test]
+    fn test_concat_inputs_invalid() {
+        assert!(concat_inputs([&b"abra"[..]], b'\0').is_err());
+        assert!(concat_inputs([&b"abra"[..], b"ca$"], b'$').is_err());
+        assert!(concat_inputs([&b"ab\0ra"[..]], b'$').is_err());
+        assert!(concat_inputs(Vec::<&[u8]>::new(), b'$').is_err());
+    }
+}
@@ -0,0 +1,717 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::error::{Error, Result};
+use crate::lf::c_array_from_counts;
+use crate::{decode_bwt, decode_bwt_with_sentinel, decode_context_bwt, Alphabet, BuildStats};
+
+/// Magic bytes at the beginning of a BWT file.
+const MAGIC: &[u8; 4] = b"SBWT";
+/// Version of the file format.
+const VERSION: u8 = 2;
+/// Length of the header of version 1, which has no symbol counts.
+const V1_HEADER_LEN: usize = 288;
+/// Length of the header in bytes, ending with the count of each byte.
+const HEADER_LEN: usize = V1_HEADER_LEN + 256 * 8;
+
+const FLAG_SENTINEL: u8 = 1 << 0;
+const FLAG_ALPHABET: u8 = 1 << 1;
+const FLAG_CHECKSUM: u8 = 1 << 2;
+const FLAG_CONTEXT_ORDER: u8 = 1 << 3;
+
+/// Header of a BWT file, describing how to interpret the BWT following it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BwtHeader {
+    /// The length of the BWT.
+    pub len: usize,
+    /// The primary index, i.e., the position of the terminator or the implicit sentinel in the BWT.
+    pub primary_index: usize,
+    /// The terminator of the text, or the placeholder written at the implicit sentinel.
+    pub terminator: u8,
+    /// Whether the text is followed by an implicit sentinel,
+    /// as set by [`BwtBuilder::implicit_sentinel`](crate::BwtBuilder::implicit_sentinel).
+    pub implicit_sentinel: bool,
+    /// The effective alphabet to which the text is remapped, if any.
+    pub alphabet: Option<Alphabet>,
+    /// The CRC-32 checksum of the BWT, if recorded.
+    pub checksum: Option<u32>,
+    /// The number of occurrences of each byte in the BWT,
+    /// which is recorded since version 2 of the file format.
+    pub counts: Option<Vec<usize>>,
+    /// The number of the symbols by which the rotations are sorted if the BWT is context-bounded,
+    /// as set by [`BwtBuilder::context_order`](crate::BwtBuilder::context_order).
+    pub context_order: Option<usize>,
+}
+
+impl BwtHeader {
+    /// The length of the header in bytes, which is followed by the BWT in a file.
+    pub const LEN: usize = HEADER_LEN;
+
+    /// Returns the C array computed from [`Self::counts`], if recorded,
+    /// as [`c_array`](crate::c_array).
+    pub fn c_array(&self) -> Option<Vec<usize>> {
+        self.counts.as_deref().map(c_array_from_counts)
+    }
+
+    /// Decodes the original text from the BWT described by the header,
+    /// following the implicit sentinel, the context order, and the alphabet.
+    ///
+    /// # Arguments
+    ///
+    /// * `bwt` - The BWT following the header.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `bwt` is not a valid BWT, its length differs from [`Self::len`],
+    /// or it has a code out of the alphabet.
+    pub fn decode(&self, bwt: &[u8]) -> Result<Vec<u8>> {
+        if bwt.len() != self.len {
+            return Err(Error::invalid_bwt(format!(
+                "bwt must have {} bytes, but got {} bytes.",
+                self.len,
+                bwt.len()
+            )));
+        }
+        let text = if self.implicit_sentinel {
+            decode_bwt_with_sentinel(bwt, self.primary_index)?
+        } else if let Some(k) = self.context_order {
+            decode_context_bwt(bwt, self.primary_index, k)?
+        } else {
+            decode_bwt(bwt)?
+        };
+        Ok(match &self.alphabet {
+            Some(alphabet) => {
+                // The codes are unchecked in a corrupted file whose alphabet is too small.
+                if let Some(pos) = text.iter().position(|&k| k as usize >= alphabet.sigma()) {
+                    return Err(Error::InvalidSymbol {
+                        pos,
+                        byte: text[pos],
+                    });
+                }
+                alphabet.decode_all(&text)
+            }
+            None => text,
+        })
+    }
+
+    fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut buf = [0; HEADER_LEN];
+        let mut flags = 0;
+        if self.implicit_sentinel {
+            flags |= FLAG_SENTINEL;
+        }
+        if let Some(alphabet) = &self.alphabet {
+            flags |= FLAG_ALPHABET;
+            let symbols = alphabet.symbols();
+            buf[28..30].copy_from_slice(&(symbols.len() as u16).to_le_bytes());
+            buf[32..32 + symbols.len()].copy_from_slice(symbols);
+        }
+        if let Some(checksum) = self.checksum {
+            flags |= FLAG_CHECKSUM;
+            buf[24..28].copy_from_slice(&checksum.to_le_bytes());
+        }
+        if let Some(k) = self.context_order {
+            flags |= FLAG_CONTEXT_ORDER;
+            buf[30..32].copy_from_slice(&(k as u16).to_le_bytes());
+        }
+        buf[..4].copy_from_slice(MAGIC);
+        buf[4] = VERSION;
+        buf[5] = flags;
+        buf[6] = self.terminator;
+        buf[8..16].copy_from_slice(&(self.len as u64).to_le_bytes());
+        buf[16..24].copy_from_slice(&(self.primary_index as u64).to_le_bytes());
+        if let Some(counts) = &self.counts {
+            for (c, &count) in counts.iter().enumerate() {
+                let i = V1_HEADER_LEN + c * 8;
+                buf[i..i + 8].copy_from_slice(&(count as u64).to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    /// Parses the header of version 1 or 2, whose length is [`V1_HEADER_LEN`] or [`HEADER_LEN`].
+    fn from_bytes(buf: &[u8]) -> Result<Self> {
+        if &buf[..4] != MAGIC {
+            return Err(Error::invalid_format(
+                "not a BWT file, since the magic is missing.",
+            ));
+        }
+        if buf[4] != 1 && buf[4] != VERSION {
+            return Err(Error::invalid_format(format!(
+                "version must be 1 or {VERSION}, but got {}.",
+                buf[4]
+            )));
+        }
+        let flags = buf[5];
+        if flags & !(FLAG_SENTINEL | FLAG_ALPHABET | FLAG_CHECKSUM | FLAG_CONTEXT_ORDER) != 0 {
+            return Err(Error::invalid_format(format!("unknown flags {flags:#x}.")));
+        }
+        let len = u64_from_le(&buf[8..16])?;
+        let primary_index = u64_from_le(&buf[16..24])?;
+        if primary_index >= len {
+            return Err(Error::invalid_format(format!(
+                "primary index must be less than {len}, but got {primary_index}."
+            )));
+        }
+        let alphabet = if flags & FLAG_ALPHABET != 0 {
+            let sigma = u16::from_le_bytes([buf[28], buf[29]]) as usize;
+            let symbols = &buf[32..32 + sigma.min(256)];
+            if sigma == 0 || sigma > 256 || symbols.windows(2).any(|w| w[0] >= w[1]) {
+                return Err(Error::invalid_format(
+                    "alphabet must have 1 to 256 distinct bytes in increasing order.",
+                ));
+            }
+            Some(Alphabet::from_text(symbols))
+        } else {
+            None
+        };
+        let checksum = if flags & FLAG_CHECKSUM != 0 {
+            Some(u32::from_le_bytes([buf[24], buf[25], buf[26], buf[27]]))
+        } else {
+            None
+        };
+        let context_order = if flags & FLAG_CONTEXT_ORDER != 0 {
+            let k = u16::from_le_bytes([buf[30], buf[31]]) as usize;
+            if k == 0 || flags & FLAG_SENTINEL != 0 {
+                return Err(Error::invalid_format(
+                    "context order must be positive without an implicit sentinel.",
+                ));
+            }
+            Some(k)
+        } else {
+            None
+        };
+        let counts = if buf[4] == VERSION {
+            let counts = buf[V1_HEADER_LEN..HEADER_LEN]
+                .chunks(8)
+                .map(u64_from_le)
+                .collect::<Result<Vec<_>>>()?;
+            if counts.iter().try_fold(0usize, |a, &c| a.checked_add(c)) != Some(len) {
+                return Err(Error::invalid_format(format!(
+                    "symbol counts must sum to the length {len}."
+                )));
+            }
+            Some(counts)
+        } else {
+            None
+        };
+        Ok(Self {
+            len,
+            primary_index,
+            terminator: buf[6],
+            implicit_sentinel: flags & FLAG_SENTINEL != 0,
+            alphabet,
+            checksum,
+            counts,
+            context_order,
+        })
+    }
+}
+
+fn u64_from_le(bytes: &[u8]) -> Result<usize> {
+    let x = u64::from_le_bytes(bytes.try_into().unwrap());
+    usize::try_from(x)
+        .map_err(|_| Error::invalid_format(format!("integer {x} does not fit in usize.")))
+}
+
+/// Writer adapter that stores the BWT in a self-describing file with a [`BwtHeader`].
+///
+/// The file begins with a header of 2336 bytes in little-endian order:
+///
+/// | Offset | Size | Content |
+/// |-------:|-----:|---------|
+/// | 0 | 4 | Magic `SBWT` |
+/// | 4 | 1 | Version `2` |
+/// | 5 | 1 | Flags: bit 0 for the implicit sentinel, bit 1 for the alphabet, bit 2 for the checksum, bit 3 for the context order |
+/// | 6 | 1 | Terminator or placeholder of the implicit sentinel |
+/// | 8 | 8 | Length of the BWT |
+/// | 16 | 8 | Primary index |
+/// | 24 | 4 | CRC-32 checksum of the BWT |
+/// | 28 | 2 | Size of the alphabet |
+/// | 30 | 2 | Context order of the context-bounded BWT |
+/// | 32 | 256 | Bytes of the alphabet in increasing order |
+/// | 288 | 2048 | Number of occurrences of each byte in the BWT as 8-byte integers |
+///
+/// followed by the BWT.
+/// The symbol counts give the C array of the BWT by [`BwtHeader::c_array`] without scanning it.
+/// A file of version 1, whose header ends before the symbol counts, is also read.
+/// Since the header is known only after the construction,
+/// it is written by [`BwtFileWriter::finish`] seeking back to the beginning.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::io::{Cursor, Read};
+/// use small_bwt::{BwtBuilder, BwtFileReader, BwtFileWriter};
+///
+/// let text = "abracadabra$";
+/// let mut wrt = BwtFileWriter::new(Cursor::new(vec![]))?;
+/// let stats = BwtBuilder::new(text.as_bytes())?.build(&mut wrt)?;
+/// let file = wrt.finish(&stats)?.into_inner();
+///
+/// let mut rdr = BwtFileReader::new(file.as_slice())?;
+/// assert_eq!(rdr.header().primary_index, 3);
+/// assert_eq!(rdr.header().terminator, b'$');
+/// let mut bwt = vec![];
+/// rdr.read_to_end(&mut bwt)?;
+/// assert_eq!(bwt, b"ard$rcaaaabb");
+/// assert_eq!(rdr.header().decode(&bwt)?, text.as_bytes());
+/// # Ok(())
+/// # }
+/// ```
+pub struct BwtFileWriter<W: Write + Seek> {
+    wrt: W,
+    // The position of the header in wrt.
+    start: u64,
+    len: usize,
+    // The smallest byte written, i.e., the terminator of a valid BWT.
+    smallest: u8,
+    crc: Option<u32>,
+    counts: Vec<usize>,
+}
+
+impl<W: Write + Seek> BwtFileWriter<W> {
+    /// Creates a new writer, reserving the header at the current position of `wrt`.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the file.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `wrt` returns an error.
+    pub fn new(mut wrt: W) -> Result<Self> {
+        let start = wrt.stream_position()?;
+        wrt.write_all(&[0; HEADER_LEN])?;
+        Ok(Self {
+            wrt,
+            start,
+            len: 0,
+            smallest: u8::MAX,
+            crc: Some(0),
+            counts: vec![0; 256],
+        })
+    }
+
+    /// Creates a writer continuing a file whose construction was interrupted,
+    /// such as the one resumed by [`BwtBuilder::resume`](crate::BwtBuilder::resume),
+    /// where `wrt` is positioned right after the BWT written so far.
+    ///
+    /// The BWT written so far is read from `written` to restore the checksum.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to continue the file.
+    /// * `written` - The reader of the BWT written so far, following the header.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `wrt` or `written` returns an error,
+    /// or if `wrt` is positioned before the end of the BWT written so far.
+    pub fn resume<R: Read>(mut wrt: W, mut written: R) -> Result<Self> {
+        let (mut len, mut smallest, mut crc) = (0, u8::MAX, 0);
+        let mut counts = vec![0; 256];
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            let n = written.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            len += n;
+            smallest = buf[..n].iter().fold(smallest, |a, &c| a.min(c));
+            crc = crc32_update(crc, &buf[..n]);
+            count_bytes(&mut counts, &buf[..n]);
+        }
+        let end = wrt.stream_position()?;
+        let start = end.checked_sub((HEADER_LEN + len) as u64).ok_or_else(|| {
+            Error::invalid_argument("wrt must be positioned after the BWT written so far.")
+        })?;
+        Ok(Self {
+            wrt,
+            start,
+            len,
+            smallest,
+            crc: Some(crc),
+            counts,
+        })
+    }
+
+    /// Sets whether to record the CRC-32 checksum of the BWT, which is verified by [`BwtFileReader`].
+    ///
+    /// # Arguments
+    ///
+    /// * `checksum` - Whether to record the checksum.
+    ///
+    /// # Default value
+    ///
+    /// `true`.
+    pub const fn checksum(mut self, checksum: bool) -> Self {
+        self.crc = if checksum { Some(0) } else { None };
+        self
+    }
+
+    /// Writes the header from the statistics of the construction and returns the underlying writer,
+    /// positioned at the end of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `stats` - The statistics returned by [`BwtBuilder::build`](crate::BwtBuilder::build).
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `stats` does not describe the written BWT,
+    /// if the context order is greater than 65535,
+    /// or if the underlying writer returns an error.
+    pub fn finish(mut self, stats: &BuildStats) -> Result<W> {
+        if stats.primary_index >= self.len {
+            return Err(Error::invalid_argument(format!(
+                "primary index must be less than {}, but got {}.",
+                self.len, stats.primary_index
+            )));
+        }
+        if let Some(k) = stats.context_order.filter(|&k| k > u16::MAX as usize) {
+            return Err(Error::invalid_argument(format!(
+                "context order must be at most {}, but got {k}.",
+                u16::MAX
+            )));
+        }
+        let header = BwtHeader {
+            len: self.len,
+            primary_index: stats.primary_index,
+            terminator: stats.sentinel.unwrap_or(self.smallest),
+            implicit_sentinel: stats.sentinel.is_some(),
+            alphabet: stats.alphabet.clone(),
+            checksum: self.crc,
+            counts: Some(self.counts.clone()),
+            context_order: stats.context_order,
+        };
+        let end = self.wrt.stream_position()?;
+        self.wrt.seek(SeekFrom::Start(self.start))?;
+        self.wrt.write_all(&header.to_bytes())?;
+        self.wrt.seek(SeekFrom::Start(end))?;
+        self.wrt.flush()?;
+        Ok(self.wrt)
+    }
+}
+
+impl<W: Write + Seek> Write for BwtFileWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.wrt.write(buf)?;
+        let buf = &buf[..n];
+        self.len += n;
+        self.smallest = buf.iter().fold(self.smallest, |a, &c| a.min(c));
+        if let Some(crc) = self.crc.as_mut() {
+            *crc = crc32_update(*crc, buf);
+        }
+        count_bytes(&mut self.counts, buf);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.wrt.flush()
+    }
+}
+
+/// Reader of a BWT file written by [`BwtFileWriter`].
+///
+/// The header is parsed by [`BwtFileReader::new`], and the BWT is read through [`Read`].
+/// If the checksum and the symbol counts are recorded, they are verified when the last byte is read.
+pub struct BwtFileReader<R: Read> {
+    rdr: R,
+    header: BwtHeader,
+    pos: usize,
+    crc: u32,
+    counts: Vec<usize>,
+}
+
+impl<R: Read> BwtFileReader<R> {
+    /// Creates a new reader, parsing the header.
+    ///
+    /// # Arguments
+    ///
+    /// * `rdr` - The reader of the file.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the header is malformed or `rdr` returns an error.
+    pub fn new(mut rdr: R) -> Result<Self> {
+        let mut buf = vec![0; V1_HEADER_LEN];
+        rdr.read_exact(&mut buf)?;
+        if buf[4] == VERSION {
+            buf.resize(HEADER_LEN, 0);
+            rdr.read_exact(&mut buf[V1_HEADER_LEN..])?;
+        }
+        let header = BwtHeader::from_bytes(&buf)?;
+        Ok(Self {
+            rdr,
+            header,
+            pos: 0,
+            crc: 0,
+            counts: vec![0; 256],
+        })
+    }
+
+    /// Returns the header.
+    pub const fn header(&self) -> &BwtHeader {
+        &self.header
+    }
+}
+
+impl<R: Read> Read for BwtFileReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let rest = self.header.len - self.pos;
+        if rest == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = rest.min(buf.len());
+        let n = self.rdr.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(Error::invalid_format(format!(
+                "BWT file must have {} bytes of the BWT, but got {} bytes.",
+                self.header.len, self.pos
+            ))
+            .into());
+        }
+        self.pos += n;
+        if let Some(checksum) = self.header.checksum {
+            self.crc = crc32_update(self.crc, &buf[..n]);
+            if self.pos == self.header.len && self.crc != checksum {
+                return Err(Error::invalid_format(format!(
+                    "checksum must be {checksum:#010x}, but got {:#010x}.",
+                    self.crc
+                ))
+                .into());
+            }
+        }
+        if let Some(counts) = &self.header.counts {
+            count_bytes(&mut self.counts, &buf[..n]);
+            if self.pos == self.header.len && &self.counts != counts {
+                return Err(
+                    Error::invalid_format("symbol counts must match those of the BWT.").into(),
+                );
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Adds the number of occurrences of each byte in `buf` to `counts`.
+fn count_bytes(counts: &mut [usize], buf: &[u8]) {
+    for &c in buf {
+        counts[c as usize] += 1;
+    }
+}
+
+/// Table of the CRC-32 (IEEE 802.3) for each byte.
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 0 {
+                c >> 1
+            } else {
+                0xedb8_8320 ^ (c >> 1)
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+/// Returns the CRC-32 of the concatenation of the bytes with checksum `crc` and `buf`.
+pub fn crc32_update(crc: u32, buf: &[u8]) -> u32 {
+    let crc = buf.iter().fold(!crc, |crc, &c| {
+        CRC_TABLE[((crc ^ c as u32) & 0xff) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::BwtBuilder;
+
+    fn build_file(builder: &BwtBuilder, checksum: bool) -> Vec<u8> {
+        let mut wrt = BwtFileWriter::new(Cursor::new(vec![]))
+            .unwrap()
+            .checksum(checksum);
+        let stats = builder.build(&mut wrt).unwrap();
+        wrt.finish(&stats).unwrap().into_inner()
+    }
+
+    fn read_file(file: &[u8]) -> Result<(BwtHeader, Vec<u8>)> {
+        let mut rdr = BwtFileReader::new(file)?;
+        let mut bwt = vec![];
+        rdr.read_to_end(&mut bwt)?;
+        Ok((rdr.header, bwt))
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32_update(0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32_update(crc32_update(0, b"1234"), b"56789"),
+            0xcbf4_3926
+        );
+    }
+
+    #[test]
+    fn test_bwt_file_roundtrip() {
+        let text = b"abracadabra$";
+        let file = build_file(&BwtBuilder::new(text).unwrap(), true);
+        assert_eq!(file.len(), HEADER_LEN + text.len());
+        let (header, bwt) = read_file(&file).unwrap();
+        assert_eq!(
+            header,
+            BwtHeader {
+                len: 12,
+                primary_index: 3,
+                terminator: b'$',
+                implicit_sentinel: false,
+                alphabet: None,
+                checksum: Some(crc32_update(0, b"ard$rcaaaabb")),
+                counts: Some(crate::lf::count_symbols(b"ard$rcaaaabb").to_vec()),
+                context_order: None,
+            }
+        );
+        assert_eq!(header.c_array(), Some(crate::c_array(text)));
+        assert_eq!(bwt, b"ard$rcaaaabb");
+        assert_eq!(header.decode(&bwt).unwrap(), text);
+
+        let file = build_file(&BwtBuilder::new(text).unwrap(), false);
+        let (header, _) = read_file(&file).unwrap();
+        assert_eq!(header.checksum, None);
+    }
+
+    #[test]
+    fn test_bwt_file_version_1() {
+        let text = b"abracadabra$";
+        let file = build_file(&BwtBuilder::new(text).unwrap(), true);
+        // The file of version 1 has no symbol counts.
+        let mut v1 = file[..V1_HEADER_LEN].to_vec();
+        v1[4] = 1;
+        v1.extend_from_slice(&file[HEADER_LEN..]);
+        let (header, bwt) = read_file(&v1).unwrap();
+        assert_eq!(header.counts, None);
+        assert_eq!(header.c_array(), None);
+        assert_eq!(header.decode(&bwt).unwrap(), text);
+    }
+
+    #[test]
+    fn test_bwt_file_resume() {
+        let text = b"abracadabra$";
+        let expected = build_file(&BwtBuilder::new(text).unwrap(), true);
+
+        // The construction is interrupted after five bytes of the BWT.
+        let mut file = Cursor::new(expected[..HEADER_LEN + 5].to_vec());
+        file.seek(SeekFrom::End(0)).unwrap();
+        let written = expected[HEADER_LEN..HEADER_LEN + 5].to_vec();
+        let mut wrt = BwtFileWriter::resume(file, written.as_slice()).unwrap();
+        wrt.write_all(b"caaaabb").unwrap();
+        let stats = BwtBuilder::new(text).unwrap().build(vec![]).unwrap();
+        assert_eq!(wrt.finish(&stats).unwrap().into_inner(), expected);
+
+        let file = Cursor::new(vec![0; HEADER_LEN]);
+        assert!(BwtFileWriter::resume(file, written.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_bwt_file_context_order() {
+        let text = b"mississippi";
+        let builder = BwtBuilder::new_unchecked(text)
+            .unwrap()
+            .context_order(2)
+            .unwrap();
+        let file = build_file(&builder, true);
+        let (header, bwt) = read_file(&file).unwrap();
+        assert_eq!(header.context_order, Some(2));
+        assert_eq!(bwt, crate::context_bwt(text, 2).unwrap().0);
+        assert_eq!(header.decode(&bwt).unwrap(), text);
+
+        // Remapped to the alphabet.
+        let builder = BwtBuilder::new(b"GATTACA$")
+            .unwrap()
+            .remap_alphabet(true)
+            .context_order(3)
+            .unwrap();
+        let (header, bwt) = read_file(&build_file(&builder, true)).unwrap();
+        assert_eq!(header.decode(&bwt).unwrap(), b"GATTACA$");
+
+        // The context order with an implicit sentinel.
+        let mut corrupted = file;
+        corrupted[5] |= FLAG_SENTINEL;
+        assert!(read_file(&corrupted).is_err());
+        corrupted[5] &= !FLAG_SENTINEL;
+        corrupted[30..32].fill(0);
+        assert!(read_file(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_bwt_file_sentinel_and_alphabet() {
+        let text: Vec<u8> = (0..=255).chain(0..=255).collect();
+        let builder = BwtBuilder::new_unchecked(&text)
+            .unwrap()
+            .implicit_sentinel(0);
+        let (header, bwt) = read_file(&build_file(&builder, true)).unwrap();
+        assert!(header.implicit_sentinel);
+        assert_eq!(header.len, text.len() + 1);
+        assert_eq!(header.decode(&bwt).unwrap(), text);
+
+        let text = b"GATTACA$";
+        let builder = BwtBuilder::new(text).unwrap().remap_alphabet(true);
+        let (header, bwt) = read_file(&build_file(&builder, true)).unwrap();
+        assert_eq!(header.alphabet, Some(Alphabet::from_text(text)));
+        assert_eq!(header.terminator, 0);
+        assert_eq!(header.decode(&bwt).unwrap(), text);
+
+        // A corrupted alphabet too small for the codes.
+        let small = BwtHeader {
+            alphabet: Some(Alphabet::from_text(b"$AC")),
+            ..header
+        };
+        assert!(matches!(
+            small.decode(&bwt),
+            Err(Error::InvalidSymbol { byte: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bwt_file_invalid() {
+        let file = build_file(&BwtBuilder::new(b"abracadabra$").unwrap(), true);
+        assert!(read_file(&file[..HEADER_LEN - 1]).is_err());
+        assert!(read_file(&file[..file.len() - 1]).is_err());
+
+        let mut corrupted = file.clone();
+        corrupted[HEADER_LEN] ^= 1;
+        assert!(read_file(&corrupted).is_err());
+        let mut corrupted = file.clone();
+        corrupted[0] = b'X';
+        assert!(read_file(&corrupted).is_err());
+        let mut corrupted = file.clone();
+        corrupted[16] = 12;
+        assert!(read_file(&corrupted).is_err());
+        // The count of 'a' is changed from 5 to 4 and that of 'b' from 2 to 3.
+        let mut corrupted = file.clone();
+        corrupted[V1_HEADER_LEN + 8 * b'a' as usize] = 4;
+        corrupted[V1_HEADER_LEN + 8 * b'b' as usize] = 3;
+        assert!(read_file(&corrupted).is_err());
+        let mut corrupted = file;
+        corrupted[V1_HEADER_LEN] = 1;
+        assert!(read_file(&corrupted).is_err());
+
+        let wrt = BwtFileWriter::new(Cursor::new(vec![])).unwrap();
+        let stats = BwtBuilder::new(b"ab$").unwrap().build(&mut vec![]).unwrap();
+        assert!(wrt.finish(&stats).is_err());
+    }
+}
@@ -0,0 +1,471 @@
+use std::cmp::Ordering;
+
+use crate::error::{Error, Result};
+
+use crate::decode::symbol_ends;
+use crate::indexed::primary_index;
+use crate::lf::{count_symbols, psi as psi_values};
+use crate::wavelet::{BitVector, BitVectorBuilder};
+
+/// Number of rows per absolute sample of the psi array.
+const PSI_SAMPLE_RATE: usize = 64;
+
+/// Compressed suffix array in the style of Sadakane,
+/// supporting pattern counting, locating, and substring extraction without the BWT.
+///
+/// The psi array is stored as Elias delta codes of the gaps between consecutive values,
+/// which are increasing within the rows of each symbol,
+/// with an absolute value sampled at every 64 rows.
+/// The suffix array and its inverse are sampled at every `sample_rate` text positions,
+/// and the sampled rows are marked in a bit vector with rank support.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BwtBuilder, CompressedSuffixArray};
+///
+/// let mut bwt = vec![];
+/// BwtBuilder::new(b"abracadabra$")?.build(&mut bwt)?;
+///
+/// let csa = CompressedSuffixArray::new(&bwt, 4)?;
+/// assert_eq!(csa.len(), 12);
+/// assert_eq!(csa.count(b"abra"), 2);
+/// assert_eq!(csa.locate(b"a"), vec![0, 3, 5, 7, 10]);
+/// assert_eq!(csa.extract(4, 3)?, b"cad");
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressedSuffixArray {
+    len: usize,
+    // ends[c] is the number of symbols no greater than c.
+    ends: Vec<usize>,
+    // The delta codes of psi except at the sampled rows,
+    // which are the gaps from the previous values, or the values plus one at the first rows of symbols.
+    psi_codes: Vec<u64>,
+    // psi_samples[k] is psi[k * PSI_SAMPLE_RATE] and the bit offset of the code of the next row.
+    psi_samples: Vec<(usize, usize)>,
+    sample_rate: usize,
+    // The rows of the suffixes at positions of multiples of sample_rate and at position n - 1.
+    sampled_rows: BitVector,
+    // sa_samples[k] is the starting position of the suffix at the k-th sampled row.
+    sa_samples: Vec<usize>,
+    // isa_samples[k] is the row of the suffix at position k * sample_rate.
+    isa_samples: Vec<usize>,
+}
+
+impl CompressedSuffixArray {
+    /// Builds the compressed suffix array from a BWT.
+    ///
+    /// The psi array is encoded from the values streamed by [`psi`](crate::psi),
+    /// and the samples are taken by walking the encoded psi array over the whole text once,
+    /// so no array of `n` integers is materialized.
+    ///
+    /// # Arguments
+    ///
+    /// * `bwt` - The Burrows-Wheeler transform of a text with a unique terminator.
+    /// * `sample_rate` - The sampling rate of the suffix array and its inverse.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if
+    ///
+    /// - `bwt` is empty or does not have exactly one terminator,
+    /// - `sample_rate` is zero, or
+    /// - `bwt` is not a valid BWT.
+    pub fn new(bwt: &[u8], sample_rate: usize) -> Result<Self> {
+        if sample_rate == 0 {
+            return Err(Error::invalid_argument("sample_rate must be positive."));
+        }
+        primary_index(bwt)?;
+        let n = bwt.len();
+        let ends = symbol_ends(&count_symbols(bwt));
+
+        let mut codes = DeltaCodes::default();
+        let mut psi_samples = Vec::with_capacity((n - 1) / PSI_SAMPLE_RATE + 1);
+        let mut c = 0;
+        let mut prev = 0;
+        for (r, p) in psi_values(bwt).enumerate() {
+            let first = advance_symbol(&ends, &mut c, r);
+            if r % PSI_SAMPLE_RATE == 0 {
+                psi_samples.push((p, codes.len));
+            } else if first {
+                codes.push(p + 1);
+            } else {
+                codes.push(p - prev);
+            }
+            prev = p;
+        }
+        let mut csa = Self {
+            len: n,
+            ends,
+            psi_codes: codes.words,
+            psi_samples,
+            sample_rate,
+            sampled_rows: BitVectorBuilder::default().finish(),
+            sa_samples: vec![],
+            isa_samples: Vec::with_capacity((n - 1) / sample_rate + 1),
+        };
+
+        // Walks psi from the row of position 0, i.e., psi[0] since row 0 is position n - 1.
+        let mut samples = Vec::with_capacity((n - 1) / sample_rate + 2);
+        let mut r = csa.psi(0);
+        for pos in 0..n {
+            if (r == 0) != (pos == n - 1) {
+                return Err(Error::invalid_bwt(
+                    "bwt is not a valid BWT, since the psi array has multiple cycles.",
+                ));
+            }
+            if pos % sample_rate == 0 {
+                csa.isa_samples.push(r);
+                samples.push((r, pos));
+            } else if pos == n - 1 {
+                samples.push((r, pos));
+            }
+            r = csa.psi(r);
+        }
+        samples.sort_unstable();
+        let mut sampled_rows = BitVectorBuilder::default();
+        let mut iter = samples.iter().peekable();
+        for r in 0..n {
+            sampled_rows.push(iter.next_if(|&&(s, _)| s == r).is_some());
+        }
+        csa.sampled_rows = sampled_rows.finish();
+        csa.sa_samples = samples.into_iter().map(|(_, pos)| pos).collect();
+        Ok(csa)
+    }
+
+    /// Returns the length of the text, including the terminator.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the text is empty, which never happens.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `psi[r]`, i.e., the row of the suffix following the one at row `r` in text order.
+    ///
+    /// It decodes at most 63 delta codes from the previous absolute sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `r` - The row, which must be less than [`Self::len`].
+    ///
+    /// # Panics
+    ///
+    /// It panics if `r` is out of bounds.
+    pub fn psi(&self, r: usize) -> usize {
+        assert!(r < self.len, "row {r} must be less than {}.", self.len);
+        let k = r / PSI_SAMPLE_RATE;
+        let (mut p, mut offset) = self.psi_samples[k];
+        let mut c = self.first_symbol(k * PSI_SAMPLE_RATE) as usize;
+        for i in k * PSI_SAMPLE_RATE + 1..=r {
+            let x = read_delta(&self.psi_codes, &mut offset);
+            if advance_symbol(&self.ends, &mut c, i) {
+                p = x - 1;
+            } else {
+                p += x;
+            }
+        }
+        p
+    }
+
+    /// Returns the first symbol of the suffix at row `r`.
+    fn first_symbol(&self, r: usize) -> u8 {
+        self.ends.partition_point(|&e| e <= r) as u8
+    }
+
+    /// Returns the number of occurrences of `pattern` in the text.
+    ///
+    /// It runs in `O(m log n)` psi accesses, where `m` is the length of `pattern`.
+    /// The empty pattern occurs at every position of the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        let (lo, hi) = self.search(pattern);
+        hi - lo
+    }
+
+    /// Returns the starting positions of the occurrences of `pattern` in the text in increasing order.
+    ///
+    /// Each occurrence is located by walking psi until a sampled row,
+    /// which takes less than `sample_rate` steps.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    pub fn locate(&self, pattern: &[u8]) -> Vec<usize> {
+        let (lo, hi) = self.search(pattern);
+        let mut positions: Vec<_> = (lo..hi).map(|r| self.suffix_position(r)).collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    /// Returns the starting position of the suffix at row `r`.
+    fn suffix_position(&self, mut r: usize) -> usize {
+        let mut steps = 0;
+        while !self.sampled_rows.get(r) {
+            r = self.psi(r);
+            steps += 1;
+        }
+        self.sa_samples[self.sampled_rows.rank1(r)] - steps
+    }
+
+    /// Returns the range of rows `lo..hi` whose suffixes start with `pattern`,
+    /// by binary search over the suffixes extracted with psi.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    pub fn search(&self, pattern: &[u8]) -> (usize, usize) {
+        let lo = self.partition_point(|r| self.compare_prefix(r, pattern) == Ordering::Less);
+        let hi = self.partition_point(|r| self.compare_prefix(r, pattern) != Ordering::Greater);
+        (lo, hi)
+    }
+
+    /// Returns the first row for which `pred` is false, assuming that it is monotone.
+    fn partition_point<P: Fn(usize) -> bool>(&self, pred: P) -> usize {
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if pred(mid) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Compares the prefix of the suffix at row `r` with `pattern`,
+    /// where a suffix shorter than `pattern` is smaller if it is a prefix of it.
+    fn compare_prefix(&self, mut r: usize, pattern: &[u8]) -> Ordering {
+        for (i, &p) in pattern.iter().enumerate() {
+            let c = self.first_symbol(r);
+            if c != p {
+                return c.cmp(&p);
+            }
+            if i + 1 < pattern.len() {
+                // The suffix at row 0 is the terminator, which ends the text.
+                if r == 0 {
+                    return Ordering::Less;
+                }
+                r = self.psi(r);
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Extracts the substring of the text of length `len` starting at `start`.
+    ///
+    /// It walks psi from the last sampled position not after `start`,
+    /// taking `len + sample_rate` psi accesses at most.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The starting position of the substring.
+    /// * `len` - The length of the substring.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the substring exceeds the text.
+    pub fn extract(&self, start: usize, len: usize) -> Result<Vec<u8>> {
+        start
+            .checked_add(len)
+            .filter(|&end| end <= self.len)
+            .ok_or_else(|| {
+                Error::invalid_argument(format!(
+                    "substring [{start}, {start}+{len}) must be within the text of length {}.",
+                    self.len
+                ))
+            })?;
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        let k = start / self.sample_rate;
+        let mut r = self.isa_samples[k];
+        for _ in k * self.sample_rate..start {
+            r = self.psi(r);
+        }
+        let mut substr = Vec::with_capacity(len);
+        for i in 0..len {
+            substr.push(self.first_symbol(r));
+            if i + 1 < len {
+                r = self.psi(r);
+            }
+        }
+        Ok(substr)
+    }
+}
+
+/// Advances `c` to the first symbol of row `r` given the previous row,
+/// returning `true` if `r` is the first row of the symbol.
+fn advance_symbol(ends: &[usize], c: &mut usize, r: usize) -> bool {
+    let mut first = r == 0;
+    while ends[*c] <= r {
+        *c += 1;
+        first = true;
+    }
+    first
+}
+
+/// Elias delta codes of positive integers appended from the least significant bit of each word.
+#[derive(Default)]
+struct DeltaCodes {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl DeltaCodes {
+    /// Appends the code of `x`, which must be positive.
+    fn push(&mut self, x: usize) {
+        debug_assert!(x > 0);
+        let l = usize::BITS - x.leading_zeros();
+        let ll = u32::BITS - 1 - l.leading_zeros();
+        for _ in 0..ll {
+            self.push_bit(false);
+        }
+        for k in (0..=ll).rev() {
+            self.push_bit(l >> k & 1 == 1);
+        }
+        for k in (0..l - 1).rev() {
+            self.push_bit(x >> k & 1 == 1);
+        }
+    }
+
+    fn push_bit(&mut self, bit: bool) {
+        if self.len % 64 == 0 {
+            self.words.push(0);
+        }
+        if bit {
+            *self.words.last_mut().unwrap() |= 1 << (self.len % 64);
+        }
+        self.len += 1;
+    }
+}
+
+/// Reads the Elias delta code at bit `offset` of `words`, advancing `offset` past it.
+fn read_delta(words: &[u64], offset: &mut usize) -> usize {
+    let mut read_bit = || {
+        let bit = words[*offset / 64] >> (*offset % 64) & 1 == 1;
+        *offset += 1;
+        bit
+    };
+    let mut ll = 0;
+    while !read_bit() {
+        ll += 1;
+    }
+    // The leading one of the length has been read.
+    let mut l = 1;
+    for _ in 0..ll {
+        l = l << 1 | usize::from(read_bit());
+    }
+    let mut x = 1;
+    for _ in 0..l - 1 {
+        x = x << 1 | usize::from(read_bit());
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BwtBuilder;
+
+    fn naive_locate(text: &[u8], pattern: &[u8]) -> Vec<usize> {
+        (0..text.len())
+            .filter(|&i| text[i..].starts_with(pattern))
+            .collect()
+    }
+
+    #[test]
+    fn test_delta_codes() {
+        let values: Vec<usize> = (1..300)
+            .chain([1 << 31, (1 << 40) + 7, usize::MAX])
+            .collect();
+        let mut codes = DeltaCodes::default();
+        for &x in &values {
+            codes.push(x);
+        }
+        let mut offset = 0;
+        for &x in &values {
+            assert_eq!(read_delta(&codes.words, &mut offset), x);
+        }
+        assert_eq!(offset, codes.len);
+    }
+
+    #[test]
+    fn test_compressed_suffix_array() {
+        let mut text: Vec<u8> = (0..1000u32)
+            .map(|i| b"ab"[(i * i % 7 % 2) as usize])
+            .collect();
+        text.extend_from_slice(b"abracadabra$");
+        let mut bwt = vec![];
+        BwtBuilder::new(&text).unwrap().build(&mut bwt).unwrap();
+        let mut sa: Vec<usize> = (0..text.len()).collect();
+        sa.sort_by_key(|&i| &text[i..]);
+        for sample_rate in [1, 3, 64, text.len() + 1] {
+            let csa = CompressedSuffixArray::new(&bwt, sample_rate).unwrap();
+            assert_eq!(csa.len(), text.len());
+            for (r, &i) in sa.iter().enumerate() {
+                assert_eq!(sa[csa.psi(r)], (i + 1) % text.len());
+                assert_eq!(csa.suffix_position(r), i);
+            }
+            for pattern in [
+                &b""[..],
+                b"a",
+                b"ab",
+                b"bab",
+                b"abra",
+                b"abracadabra$",
+                b"c",
+                b"dab",
+                b"ra$",
+                b"$",
+                b"$a",
+                b"abc",
+                b"z",
+            ] {
+                let expected = naive_locate(&text, pattern);
+                assert_eq!(csa.count(pattern), expected.len(), "{pattern:?}");
+                assert_eq!(csa.locate(pattern), expected, "{pattern:?}");
+            }
+            for (start, len) in [(0, 0), (0, text.len()), (500, 30), (text.len() - 5, 5)] {
+                assert_eq!(csa.extract(start, len).unwrap(), &text[start..start + len]);
+            }
+            assert!(csa.extract(text.len(), 1).is_err());
+            assert!(csa.extract(1, usize::MAX).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compressed_suffix_array_single() {
+        let csa = CompressedSuffixArray::new(b"$", 2).unwrap();
+        assert_eq!(csa.len(), 1);
+        assert_eq!(csa.locate(b"$"), vec![0]);
+        assert_eq!(csa.extract(0, 1).unwrap(), b"$");
+    }
+
+    #[test]
+    fn test_compressed_suffix_array_invalid() {
+        assert!(CompressedSuffixArray::new(b"", 1).is_err());
+        assert!(CompressedSuffixArray::new(b"ard$rcaaaabb", 0).is_err());
+        assert!(CompressedSuffixArray::new(b"a$$", 1).is_err());
+        // The psi array consists of two cycles.
+        assert!(CompressedSuffixArray::new(b"bb$a", 1).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_compressed_suffix_array_serde() {
+        let csa = CompressedSuffixArray::new(b"ard$rcaaaabb", 4).unwrap();
+        let json = serde_json::to_string(&csa).unwrap();
+        let csa: CompressedSuffixArray = serde_json::from_str(&json).unwrap();
+        assert_eq!(csa.locate(b"abra"), vec![0, 7]);
+    }
+}
@@ -0,0 +1,370 @@
+use crate::text::{suffix_positions, TextAccess};
+
+/// Sentinel of missing children in [`FreqTrie`].
+const NONE: usize = usize::MAX;
+
+/// The maximum length of k-mers whose frequencies can be precomputed.
+pub const MAX_KMER_LEN: usize = 3;
+
+/// The maximum length of cuts, since each level of the cuts takes a scan of the text.
+/// A prefix of this length occurring more than `chunk_size` times is a cut of its own oversized chunk.
+pub const MAX_CUT_LEN: usize = 64;
+
+/// The maximum ratio of the size of an oversized chunk to `chunk_size`,
+/// beyond which the cut generation is regarded as degenerate.
+pub const MAX_OVERSIZE_RATIO: usize = 4;
+
+pub struct CutGenerator<'a> {
+    trie: &'a FreqTrie,
+    chunk_size: usize,
+    cuts: Vec<Vec<u8>>,
+    lens: Vec<usize>,
+    // The size of the largest oversized chunk.
+    max_oversize: usize,
+}
+
+impl<'a> CutGenerator<'a> {
+    /// Generates the cuts, answering the frequencies of prefixes shorter than `kmer_len`
+    /// from a precomputed [`KmerTable`].
+    /// Only the suffixes starting at multiples of `stride` are counted.
+    ///
+    /// The cuts are at most [`MAX_CUT_LEN`] long, where the chunk of a cut of that length
+    /// may have more than `chunk_size` suffixes.
+    /// `None` is returned if such a chunk has more than [`MAX_OVERSIZE_RATIO`] times `chunk_size` suffixes,
+    /// as in a periodic text such as `a^n$`.
+    pub fn generate<T: TextAccess + ?Sized>(
+        text: &T,
+        chunk_size: usize,
+        kmer_len: usize,
+        stride: usize,
+    ) -> Option<Vec<Vec<u8>>> {
+        let trie = FreqTrie::build(text, chunk_size, kmer_len, stride);
+        let mut builder = CutGenerator {
+            trie: &trie,
+            chunk_size,
+            cuts: vec![vec![]],
+            lens: vec![],
+            max_oversize: 0,
+        };
+        builder.expand(0, vec![]);
+        if builder.max_oversize > chunk_size.saturating_mul(MAX_OVERSIZE_RATIO) {
+            return None;
+        }
+        Some(builder.cuts)
+    }
+
+    fn expand(&mut self, node: usize, mut cut: Vec<u8>) {
+        let freqs = self.trie.freqs(node);
+        cut.push(0); // dummy last symbol
+        for (symbol, &freq) in freqs.iter().enumerate() {
+            if freq == 0 {
+                continue;
+            }
+            *cut.last_mut().unwrap() = symbol as u8;
+            let child = self.trie.child(node, symbol as u8);
+            if freq <= self.chunk_size || child == NONE {
+                // The suffixes starting with the cut are greater than it, so a chunk is closed
+                // by the cut of the first prefix of the next chunk.
+                // An oversized chunk of a capped prefix is never shared with the neighbors,
+                // since its length exceeds chunk_size.
+                if self.lens.is_empty() {
+                    self.lens.push(0);
+                } else if *self.lens.last().unwrap() + freq > self.chunk_size {
+                    self.cuts.push(cut.clone());
+                    self.lens.push(0);
+                }
+                *self.lens.last_mut().unwrap() += freq;
+                if freq > self.chunk_size {
+                    self.max_oversize = self.max_oversize.max(freq);
+                }
+            } else {
+                self.expand(child, cut.clone());
+            }
+        }
+    }
+}
+
+/// Trie of the cut prefixes to be expanded, i.e., those occurring more than `chunk_size` times,
+/// storing the frequencies of the symbols following each prefix.
+///
+/// The trie is built level by level, computing the frequencies for all the prefixes
+/// of the same length in a single scan of the text shared by the siblings.
+/// The levels shorter than `kmer_len` are filled from a [`KmerTable`] without scanning the text.
+/// The scan visits only the positions whose prefix matched a node in the previous level,
+/// which are marked in a bit vector of `n` bits.
+/// The prefixes are not expanded beyond [`MAX_CUT_LEN`].
+struct FreqTrie {
+    // children[v * 256 + c] is the child of node v with symbol c, or NONE.
+    children: Vec<usize>,
+    // freqs[v * 256 + c] is the number of occurrences of node v's prefix followed by c.
+    freqs: Vec<usize>,
+}
+
+impl FreqTrie {
+    fn build<T: TextAccess + ?Sized>(
+        text: &T,
+        chunk_size: usize,
+        kmer_len: usize,
+        stride: usize,
+    ) -> Self {
+        let table = KmerTable::new(text, kmer_len, stride);
+        let mut trie = Self {
+            children: vec![],
+            freqs: vec![],
+        };
+        // Pairs of a node and its prefix encoded in base 256, which is valid only while depth < kmer_len.
+        let mut frontier = vec![(trie.add_node(), 0)];
+        let mut depth = 0;
+        // alive[i / 64] >> (i % 64) & 1 is whether the prefix at i matched a node in the previous level.
+        let mut alive = vec![];
+        while !frontier.is_empty() {
+            if depth < kmer_len {
+                for &(v, code) in &frontier {
+                    trie.freqs[v * 256..(v + 1) * 256].copy_from_slice(table.freqs(depth, code));
+                }
+            } else {
+                if alive.is_empty() {
+                    alive = vec![0u64; (text.len() + 63) / 64];
+                    for i in suffix_positions(text, stride) {
+                        alive[i / 64] |= 1 << (i % 64);
+                    }
+                }
+                trie.count(text, depth, &mut alive);
+            }
+            let mut next = vec![];
+            for &(v, code) in &frontier {
+                for c in 0..256 {
+                    if trie.freqs[v * 256 + c] > chunk_size && depth + 1 < MAX_CUT_LEN {
+                        let u = trie.add_node();
+                        trie.children[v * 256 + c] = u;
+                        let code = if depth + 1 < kmer_len {
+                            code * 256 + c
+                        } else {
+                            0
+                        };
+                        next.push((u, code));
+                    }
+                }
+            }
+            frontier = next;
+            depth += 1;
+        }
+        trie
+    }
+
+    fn add_node(&mut self) -> usize {
+        let v = self.freqs.len() / 256;
+        self.children.resize(self.children.len() + 256, NONE);
+        self.freqs.resize(self.freqs.len() + 256, 0);
+        v
+    }
+
+    /// Counts the symbols following the prefixes of length `depth` in a single scan
+    /// of the positions marked in `alive`, unmarking those matching no node.
+    fn count<T: TextAccess + ?Sized>(&mut self, text: &T, depth: usize, alive: &mut [u64]) {
+        for (w, word) in alive.iter_mut().enumerate() {
+            let mut bits = *word;
+            while bits != 0 {
+                let b = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                let i = w * 64 + b;
+                let mut v = if i + depth < text.len() { 0 } else { NONE };
+                for j in i..i + depth {
+                    if v == NONE {
+                        break;
+                    }
+                    v = self.children[v * 256 + text.get(j) as usize];
+                }
+                if v == NONE {
+                    *word &= !(1 << b);
+                } else {
+                    self.freqs[v * 256 + text.get(i + depth) as usize] += 1;
+                }
+            }
+        }
+    }
+
+    fn freqs(&self, v: usize) -> &[usize] {
+        &self.freqs[v * 256..(v + 1) * 256]
+    }
+
+    fn child(&self, v: usize, c: u8) -> usize {
+        self.children[v * 256 + c as usize]
+    }
+}
+
+/// Frequencies of all the k-mers of length up to `kmer_len`, computed in a single scan of the text.
+struct KmerTable {
+    // counts[l][x] is the number of occurrences of the (l + 1)-mer encoded as x in base 256.
+    counts: Vec<Vec<usize>>,
+}
+
+impl KmerTable {
+    fn new<T: TextAccess + ?Sized>(text: &T, kmer_len: usize, stride: usize) -> Self {
+        assert!(kmer_len <= MAX_KMER_LEN);
+        let mut counts: Vec<_> = (1..=kmer_len).map(|l| vec![0; 1 << (8 * l)]).collect();
+        for i in suffix_positions(text, stride) {
+            let mut code = 0;
+            for (l, k) in (i..text.len().min(i + kmer_len)).enumerate() {
+                code = code * 256 + text.get(k) as usize;
+                counts[l][code] += 1;
+            }
+        }
+        Self { counts }
+    }
+
+    /// Returns the frequencies of symbols following the `depth`-mer encoded as `code`.
+    fn freqs(&self, depth: usize, code: usize) -> &[usize] {
+        &self.counts[depth][code * 256..(code + 1) * 256]
+    }
+}
+
+/// Computes the frequencies of symbols following cut in text.
+#[cfg(test)]
+fn symbol_freqs(text: &[u8], cut: &[u8]) -> Vec<usize> {
+    let mut freqs = vec![0; 256];
+    for j in cut.len()..text.len() {
+        let i = j - cut.len();
+        if cut == &text[i..j] {
+            freqs[text[j] as usize] += 1;
+        }
+    }
+    freqs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cut_generator() {
+        let text: &[u8] = b"abracadabra$";
+        let cuts = CutGenerator::generate(text, 3, 0, 1).unwrap();
+        assert_eq!(
+            cuts,
+            vec![
+                b"".to_vec(),
+                b"ab".to_vec(),
+                b"ad".to_vec(),
+                b"c".to_vec(),
+                b"r".to_vec(),
+            ]
+        );
+        assert_eq!(chunk_lens(text, &cuts), vec![2, 3, 3, 2, 2]);
+        let cuts = CutGenerator::generate(text, 4, 0, 1).unwrap();
+        assert_eq!(cuts, vec![b"".to_vec(), b"ac".to_vec(), b"c".to_vec()]);
+        assert_eq!(chunk_lens(text, &cuts), vec![4, 4, 4]);
+    }
+
+    #[test]
+    fn test_cut_generator_kmer_table() {
+        let text: &[u8] = b"abracadabra$";
+        for chunk_size in 1..=text.len() {
+            let expected = CutGenerator::generate(text, chunk_size, 0, 1).unwrap();
+            for kmer_len in 1..=MAX_KMER_LEN {
+                let cuts = CutGenerator::generate(text, chunk_size, kmer_len, 1).unwrap();
+                assert_eq!(
+                    cuts, expected,
+                    "chunk_size={chunk_size}, kmer_len={kmer_len}"
+                );
+            }
+        }
+    }
+
+    /// Returns the sizes of the chunks, where the `q`-th one is between the `q`-th and `q+1`-th cuts.
+    fn chunk_lens(text: &[u8], cuts: &[Vec<u8>]) -> Vec<usize> {
+        let mut lens = vec![0; cuts.len()];
+        for i in 0..text.len() {
+            let q = cuts.partition_point(|cut| text.cmp_suffix(i, cut).is_gt());
+            lens[q - 1] += 1;
+        }
+        lens
+    }
+
+    #[test]
+    fn test_cut_generator_capped() {
+        // A block repeated 20 times, whose prefixes of MAX_CUT_LEN bytes occur 20 times.
+        let block: Vec<u8> = (0..100u32)
+            .map(|i| b"acgt"[(i.wrapping_mul(2654435761) >> 30) as usize])
+            .collect();
+        let text = [&block.repeat(20)[..], b"$"].concat();
+        let chunk_size = 8;
+        let cuts = CutGenerator::generate(&text[..], chunk_size, 0, 1).unwrap();
+        assert!(cuts.windows(2).all(|w| w[0] < w[1]));
+        assert!(cuts.iter().all(|cut| cut.len() <= MAX_CUT_LEN));
+        let lens = chunk_lens(&text, &cuts);
+        assert_eq!(lens.iter().sum::<usize>(), text.len());
+        let mut n_oversized = 0;
+        for (q, &len) in lens.iter().enumerate() {
+            // A suffix equal to the next cut may be added to a chunk.
+            if len > chunk_size + 1 {
+                // The oversized chunk consists only of the suffixes starting with its capped cut.
+                assert_eq!(cuts[q].len(), MAX_CUT_LEN);
+                assert!(len <= chunk_size * MAX_OVERSIZE_RATIO);
+                n_oversized += 1;
+            }
+        }
+        assert!(n_oversized > 0);
+
+        // The chunks are too large to be sorted at once.
+        assert_eq!(CutGenerator::generate(&text[..], 4, 0, 1), None);
+        let text = [&b"a".repeat(1000)[..], b"$"].concat();
+        assert_eq!(CutGenerator::generate(&text[..], 100, 0, 1), None);
+        assert!(CutGenerator::generate(&text[..], 300, 0, 1).is_some());
+    }
+
+    #[test]
+    fn test_kmer_table() {
+        let text: &[u8] = b"abracadabra$";
+        let table = KmerTable::new(text, 2, 1);
+        assert_eq!(table.freqs(0, 0), symbol_freqs(text, b""));
+        assert_eq!(table.freqs(1, b'r' as usize), symbol_freqs(text, b"r"));
+        assert_eq!(table.freqs(1, b'$' as usize), symbol_freqs(text, b"$"));
+    }
+
+    #[test]
+    fn test_freq_trie() {
+        let text: &[u8] = b"abracadabra$";
+        let trie = FreqTrie::build(text, 1, 0, 1);
+        let mut stack = vec![(0, vec![])];
+        let mut n_nodes = 0;
+        while let Some((v, prefix)) = stack.pop() {
+            n_nodes += 1;
+            assert_eq!(trie.freqs(v), symbol_freqs(text, &prefix));
+            for c in 0..=255u8 {
+                let u = trie.child(v, c);
+                if u != NONE {
+                    stack.push((u, [&prefix[..], &[c]].concat()));
+                }
+            }
+        }
+        // "", "a", "ab", "abr", "abra", "b", "br", "bra", "r", "ra"
+        assert_eq!(n_nodes, 10);
+    }
+
+    #[test]
+    fn test_symbol_freqs() {
+        let text: &[u8] = b"abracadabra$";
+        let cut = b"ra";
+        let freqs = symbol_freqs(text, cut);
+        let mut expected = vec![0; 256];
+        expected[b'$' as usize] = 1;
+        expected[b'c' as usize] = 1;
+        assert_eq!(freqs, expected);
+    }
+
+    #[test]
+    fn test_symbol_freqs_empty() {
+        let text: &[u8] = b"abracadabra$";
+        let cut = b"";
+        let freqs = symbol_freqs(text, cut);
+        let mut expected = vec![0; 256];
+        expected[b'$' as usize] = 1;
+        expected[b'a' as usize] = 5;
+        expected[b'b' as usize] = 2;
+        expected[b'c' as usize] = 1;
+        expected[b'd' as usize] = 1;
+        expected[b'r' as usize] = 2;
+        assert_eq!(freqs, expected);
+    }
+}
@@ -0,0 +1,867 @@
+use std::io::{BufReader, Read, Write};
+
+use crate::error::{Error, Result};
+
+use crate::index::{SuffixIndex, U40};
+use crate::rank::OccTable;
+use crate::STR_SENTINEL;
+
+/// Length of the buffer to write decoded texts.
+const DECODE_BUF_LEN: usize = 1 << 16;
+
+/// Decodes the original text from a given BWT.
+///
+/// It runs in `O(n)` time and `O(n log n)` bits of space,
+/// where `n` is the length of the text.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+///
+/// # Errors
+///
+/// An error is returned if the Burrows-Wheeler transform is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::decode_bwt;
+///
+/// let bwt = "ard$rcaaaabb";
+/// let decoded = decode_bwt(bwt.as_bytes())?;
+/// assert_eq!(decoded, "abracadabra$".as_bytes());
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_bwt(bwt: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(bwt.len());
+    decode_bwt_to(bwt, &mut decoded)?;
+    Ok(decoded)
+}
+
+/// Decodes the original string from the BWT built by [`BwtBuilder::from_str`](crate::BwtBuilder),
+/// removing the terminator or the implicit sentinel.
+///
+/// If `bwt` contains [`STR_SENTINEL`], which never occurs in UTF-8, its position is the sentinel.
+/// Otherwise, the smallest byte is the terminator as in [`decode_bwt`].
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a string.
+///
+/// # Errors
+///
+/// An error is returned if the Burrows-Wheeler transform is invalid
+/// or the decoded text is not in UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::decode_bwt_string;
+///
+/// assert_eq!(decode_bwt_string(b"ard\0rcaaaabb")?, "abracadabra");
+/// // The BWT of "a\0b" followed by the sentinel.
+/// assert_eq!(decode_bwt_string(b"ba\xff\0")?, "a\0b");
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_bwt_string(bwt: &[u8]) -> Result<String> {
+    let decoded = match bwt.iter().position(|&c| c == STR_SENTINEL) {
+        Some(sentinel_index) => decode_bwt_with_sentinel(bwt, sentinel_index)?,
+        None => {
+            let mut decoded = decode_bwt(bwt)?;
+            decoded.pop();
+            decoded
+        }
+    };
+    String::from_utf8(decoded)
+        .map_err(|e| Error::invalid_bwt(format!("decoded text must be in UTF-8, but {e}.")))
+}
+
+/// Decodes the original text from a given BWT and writes it to `wrt` from the beginning,
+/// without holding the decoded text in memory.
+///
+/// It runs in `O(n)` time and `O(n log n)` bits of space,
+/// where `n` is the length of the text.
+/// The positions are stored in 4 bytes if `n` is less than `2^32`.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+/// * `wrt` - The writer to write the decoded text.
+///
+/// # Errors
+///
+/// An error is returned if the Burrows-Wheeler transform is invalid or `wrt` returns an error.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::decode_bwt_to;
+///
+/// let bwt = "ard$rcaaaabb";
+/// let mut decoded = vec![];
+/// decode_bwt_to(bwt.as_bytes(), &mut decoded)?;
+/// assert_eq!(decoded, "abracadabra$".as_bytes());
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_bwt_to<W: Write>(bwt: &[u8], wrt: W) -> Result<()> {
+    if bwt.len() <= u32::MAX_POS {
+        decode_bwt_with::<u32, W>(bwt, wrt)
+    } else if bwt.len() <= U40::MAX_POS {
+        decode_bwt_with::<U40, W>(bwt, wrt)
+    } else {
+        decode_bwt_with::<usize, W>(bwt, wrt)
+    }
+}
+
+/// Decodes the original text from a BWT read from `rdr` and writes it to `wrt`,
+/// without holding the BWT nor the decoded text in memory.
+///
+/// The BWT is consumed in a single pass, and only the positions of the symbols are stored,
+/// taking 4 bytes per symbol if `n` is less than `2^32` or 8 bytes otherwise.
+/// It is suitable for decoding a BWT from a file, pipe, or network.
+///
+/// # Arguments
+///
+/// * `rdr` - The reader of the Burrows-Wheeler transform of a text.
+/// * `wrt` - The writer to write the decoded text.
+///
+/// # Errors
+///
+/// An error is returned if the Burrows-Wheeler transform is invalid or `rdr` or `wrt` returns an error.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::decode_bwt_from_reader;
+///
+/// let bwt = "ard$rcaaaabb";
+/// let mut decoded = vec![];
+/// decode_bwt_from_reader(bwt.as_bytes(), &mut decoded)?;
+/// assert_eq!(decoded, "abracadabra$".as_bytes());
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_bwt_from_reader<R: Read, W: Write>(rdr: R, wrt: W) -> Result<()> {
+    let mut bytes = BufReader::new(rdr).bytes();
+    let mut n = 0;
+    // lists[c] is the sorted positions of c in the BWT.
+    let mut lists: Vec<Vec<u32>> = vec![vec![]; 256];
+    if read_positions(&mut bytes, &mut lists, &mut n)? {
+        let counts: Vec<usize> = lists.iter().map(Vec::len).collect();
+        return walk_psi(&concat_lists(lists), &counts, wrt);
+    }
+    // Widens the positions since the BWT is too long.
+    let mut lists: Vec<Vec<usize>> = lists
+        .into_iter()
+        .map(|list| list.into_iter().map(|i| i as usize).collect())
+        .collect();
+    read_positions(&mut bytes, &mut lists, &mut n)?;
+    let counts: Vec<usize> = lists.iter().map(Vec::len).collect();
+    walk_psi(&concat_lists(lists), &counts, wrt)
+}
+
+/// Appends the positions of the symbols read from `bytes` to `lists`, starting from position `n`.
+/// Returns `false` if a position cannot be represented by `I` before reaching the end.
+fn read_positions<I: SuffixIndex>(
+    bytes: &mut impl Iterator<Item = std::io::Result<u8>>,
+    lists: &mut [Vec<I>],
+    n: &mut usize,
+) -> Result<bool> {
+    loop {
+        if *n > I::MAX_POS {
+            return Ok(false);
+        }
+        match bytes.next() {
+            Some(c) => {
+                lists[c? as usize].push(I::from_usize(*n));
+                *n += 1;
+            }
+            None => return Ok(true),
+        }
+    }
+}
+
+/// Concatenates the position lists into the psi array, releasing each list once copied.
+fn concat_lists<I: SuffixIndex>(lists: Vec<Vec<I>>) -> Vec<I> {
+    let mut psi = Vec::with_capacity(lists.iter().map(Vec::len).sum());
+    for list in lists {
+        psi.extend_from_slice(&list);
+    }
+    psi
+}
+
+fn decode_bwt_with<I: SuffixIndex, W: Write>(bwt: &[u8], wrt: W) -> Result<()> {
+    let (psi, counts) = build_psi::<I>(bwt);
+    walk_psi(&psi, &counts, wrt)
+}
+
+/// Computes the psi array and the symbol counts of the BWT.
+fn build_psi<I: SuffixIndex>(bwt: &[u8]) -> (Vec<I>, Vec<usize>) {
+    let mut counts = vec![0; 256];
+    for &c in bwt {
+        counts[c as usize] += 1;
+    }
+    let mut occ = vec![0; 256];
+    let mut rank = 0;
+    for c in 0..256 {
+        occ[c] = rank;
+        rank += counts[c];
+    }
+    let mut psi = vec![I::default(); bwt.len()];
+    for (i, &c) in bwt.iter().enumerate() {
+        psi[occ[c as usize]] = I::from_usize(i);
+        occ[c as usize] += 1;
+    }
+    (psi, counts)
+}
+
+/// Decodes the text by walking `psi`, where `psi[r]` is the row following row `r` in text order,
+/// i.e., the inverse of the LF mapping, and `counts[c]` is the number of occurrences of `c`.
+fn walk_psi<I: SuffixIndex, W: Write>(psi: &[I], counts: &[usize], wrt: W) -> Result<()> {
+    let (_, ends) = validate_counts(counts)?;
+    // Starts from the row of the whole text, i.e., the position of the terminator.
+    walk_psi_from(psi, &ends, psi[0].to_usize(), wrt)
+}
+
+/// Decodes the text by walking `psi` from row `start`,
+/// where `ends` is the one computed by [`symbol_ends`].
+fn walk_psi_from<I: SuffixIndex, W: Write>(
+    psi: &[I],
+    ends: &[usize],
+    start: usize,
+    mut wrt: W,
+) -> Result<()> {
+    let mut r = start;
+    let mut buf = Vec::with_capacity(DECODE_BUF_LEN);
+    for k in 0..psi.len() {
+        buf.push(ends.partition_point(|&e| e <= r) as u8);
+        if buf.len() == DECODE_BUF_LEN {
+            wrt.write_all(&buf)?;
+            buf.clear();
+        }
+        r = psi[r].to_usize();
+        if r == start && k != psi.len() - 1 {
+            return Err(Error::invalid_bwt(format!(
+                "bwt is not a valid BWT, since the text ends at position {k}."
+            )));
+        }
+    }
+    wrt.write_all(&buf)?;
+    wrt.flush()?;
+    Ok(())
+}
+
+/// Properties of a BWT verified by [`validate_bwt`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BwtProperties {
+    /// The length of the BWT, i.e., of the text including the terminator.
+    pub len: usize,
+    /// The terminator, i.e., the smallest byte of the BWT.
+    pub terminator: u8,
+    /// The position of the terminator in the BWT, i.e., the row of the whole text.
+    pub primary_index: usize,
+    /// The number of distinct bytes.
+    pub sigma: usize,
+    /// The number of runs of equal bytes.
+    pub n_runs: usize,
+}
+
+/// Verifies that a given sequence is a valid BWT without decoding the text,
+/// i.e., it has exactly one terminator and its LF mapping forms a single cycle of length `n`.
+///
+/// It runs in `O(n)` time and `O(n log n)` bits of space for the psi array as [`decode_bwt`],
+/// but the decoded text is neither stored nor written.
+///
+/// # Arguments
+///
+/// * `bwt` - The sequence to be verified.
+///
+/// # Errors
+///
+/// An error is returned if `bwt` is not a valid BWT, in the same cases as [`decode_bwt`].
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::validate_bwt;
+///
+/// let props = validate_bwt(b"ard$rcaaaabb")?;
+/// assert_eq!(props.len, 12);
+/// assert_eq!(props.terminator, b'$');
+/// assert_eq!(props.primary_index, 3);
+/// assert_eq!(props.sigma, 6);
+/// assert_eq!(props.n_runs, 8);
+///
+/// // The LF mapping consists of two cycles.
+/// assert!(validate_bwt(b"bb$a").is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub fn validate_bwt(bwt: &[u8]) -> Result<BwtProperties> {
+    if bwt.len() <= u32::MAX_POS {
+        validate_bwt_with::<u32>(bwt)
+    } else if bwt.len() <= U40::MAX_POS {
+        validate_bwt_with::<U40>(bwt)
+    } else {
+        validate_bwt_with::<usize>(bwt)
+    }
+}
+
+fn validate_bwt_with<I: SuffixIndex>(bwt: &[u8]) -> Result<BwtProperties> {
+    let (psi, counts) = build_psi::<I>(bwt);
+    let (terminator, _) = validate_counts(&counts)?;
+    // Walks the cycle from row 0, which returns to row 0 after exactly n steps for a valid BWT.
+    let mut r = psi[0].to_usize();
+    for k in 1..psi.len() {
+        if r == 0 {
+            return Err(Error::invalid_bwt(format!(
+                "bwt is not a valid BWT, since the text ends at position {k}."
+            )));
+        }
+        r = psi[r].to_usize();
+    }
+    let n_runs = 1 + bwt.windows(2).filter(|w| w[0] != w[1]).count();
+    Ok(BwtProperties {
+        len: bwt.len(),
+        terminator: terminator as u8,
+        primary_index: psi[0].to_usize(),
+        sigma: counts.iter().filter(|&&c| c != 0).count(),
+        n_runs,
+    })
+}
+
+/// Verifies that the BWT is not empty and has exactly one terminator,
+/// returning the terminator and [`symbol_ends`].
+fn validate_counts(counts: &[usize]) -> Result<(usize, Vec<usize>)> {
+    let terminator = match counts.iter().position(|&c| c != 0) {
+        Some(terminator) => terminator,
+        None => return Err(Error::invalid_bwt("bwt must not be empty.")),
+    };
+    if counts[terminator] != 1 {
+        return Err(Error::invalid_bwt(format!(
+            "bwt must have exactly one terminator character, but found {:x} {} times.",
+            terminator, counts[terminator]
+        )));
+    }
+    Ok((terminator, symbol_ends(counts)))
+}
+
+/// Returns `ends`, where `ends[c]` is the number of symbols no greater than `c`.
+/// The symbol of the first column at row `r` is the first `c` such that `r < ends[c]`.
+pub fn symbol_ends(counts: &[usize]) -> Vec<usize> {
+    let mut ends = vec![0; 256];
+    let mut rank = 0;
+    for c in 0..256 {
+        rank += counts[c];
+        ends[c] = rank;
+    }
+    ends
+}
+
+/// Decodes the original text from a given BWT without a unique terminator,
+/// such as the BWT of the sorted rotations of a text used in bzip2.
+///
+/// The text is identified by `primary_index`, the row of the text itself in the sorted rotations.
+/// It runs in `O(n)` time and `O(n log n)` bits of space, as [`decode_bwt`].
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of the rotations of a text.
+/// * `primary_index` - The row of the text in the sorted rotations.
+///
+/// # Errors
+///
+/// An error is returned if
+///
+/// - `bwt` is empty,
+/// - `primary_index` is out of bounds, or
+/// - the text cannot be restored from `primary_index`, i.e., `bwt` is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::decode_bwt_with_index;
+///
+/// // The sorted rotations of "banana" are
+/// // "abanan", "anaban", "ananab", "banana", "nabana", and "nanaba".
+/// let decoded = decode_bwt_with_index(b"nnbaaa", 3)?;
+/// assert_eq!(decoded, b"banana");
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_bwt_with_index(bwt: &[u8], primary_index: usize) -> Result<Vec<u8>> {
+    if bwt.is_empty() {
+        return Err(Error::invalid_bwt("bwt must not be empty."));
+    }
+    if primary_index >= bwt.len() {
+        return Err(Error::invalid_argument(format!(
+            "primary_index must be less than {}, but got {primary_index}.",
+            bwt.len()
+        )));
+    }
+    if bwt.len() <= u32::MAX_POS {
+        decode_rotations_with::<u32>(bwt, primary_index)
+    } else if bwt.len() <= U40::MAX_POS {
+        decode_rotations_with::<U40>(bwt, primary_index)
+    } else {
+        decode_rotations_with::<usize>(bwt, primary_index)
+    }
+}
+
+fn decode_rotations_with<I: SuffixIndex>(bwt: &[u8], primary_index: usize) -> Result<Vec<u8>> {
+    let (psi, counts) = build_psi::<I>(bwt);
+    let ends = symbol_ends(&counts);
+    let n = bwt.len();
+
+    // Decodes the cycle of the inverse LF mapping from primary_index.
+    let mut decoded = Vec::with_capacity(n);
+    let mut r = primary_index;
+    loop {
+        decoded.push(ends.partition_point(|&e| e <= r) as u8);
+        r = psi[r].to_usize();
+        if r == primary_index {
+            break;
+        }
+    }
+
+    // For a valid BWT, the cycle is shorter than the text only if the text is periodic,
+    // in which case the cycle is repeated.
+    let period = decoded.len();
+    let mut period_counts = vec![0; 256];
+    for &c in &decoded {
+        period_counts[c as usize] += 1;
+    }
+    if n % period != 0 || (0..256).any(|c| period_counts[c] * (n / period) != counts[c]) {
+        return Err(Error::invalid_bwt(
+            "bwt is not a valid BWT, since the rotations cannot be restored from primary_index.",
+        ));
+    }
+    for i in period..n {
+        decoded.push(decoded[i - period]);
+    }
+    Ok(decoded)
+}
+
+/// Decodes the original text from a given BWT built with an implicit sentinel
+/// by [`BwtBuilder::implicit_sentinel`](crate::BwtBuilder::implicit_sentinel).
+///
+/// The symbol at `sentinel_index` is regarded as the sentinel smaller than every byte,
+/// whatever its value is, and the text is returned without it.
+/// It runs in `O(n)` time and `O(n log n)` bits of space, as [`decode_bwt`].
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text followed by the sentinel.
+/// * `sentinel_index` - The position of the sentinel in `bwt`, i.e., the primary index.
+///
+/// # Errors
+///
+/// An error is returned if
+///
+/// - `bwt` is empty,
+/// - `sentinel_index` is out of bounds, or
+/// - the text cannot be restored, i.e., `bwt` is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::decode_bwt_with_sentinel;
+///
+/// // The BWT of "abab" followed by the sentinel, which is written as 0xff.
+/// let decoded = decode_bwt_with_sentinel(b"bb\xffaa", 2)?;
+/// assert_eq!(decoded, b"abab");
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_bwt_with_sentinel(bwt: &[u8], sentinel_index: usize) -> Result<Vec<u8>> {
+    if bwt.is_empty() {
+        return Err(Error::invalid_bwt("bwt must not be empty."));
+    }
+    if sentinel_index >= bwt.len() {
+        return Err(Error::invalid_argument(format!(
+            "sentinel_index must be less than {}, but got {sentinel_index}.",
+            bwt.len()
+        )));
+    }
+    if bwt.len() <= u32::MAX_POS {
+        decode_sentinel_with::<u32>(bwt, sentinel_index)
+    } else if bwt.len() <= U40::MAX_POS {
+        decode_sentinel_with::<U40>(bwt, sentinel_index)
+    } else {
+        decode_sentinel_with::<usize>(bwt, sentinel_index)
+    }
+}
+
+fn decode_sentinel_with<I: SuffixIndex>(bwt: &[u8], sentinel_index: usize) -> Result<Vec<u8>> {
+    let n = bwt.len() - 1;
+    let mut counts = vec![0; 256];
+    for (i, &c) in bwt.iter().enumerate() {
+        if i != sentinel_index {
+            counts[c as usize] += 1;
+        }
+    }
+    // Row 0 is the empty suffix, followed by the rows starting with every byte.
+    let mut occ = vec![0; 256];
+    let mut rank = 1;
+    for c in 0..256 {
+        occ[c] = rank;
+        rank += counts[c];
+    }
+    let mut psi = vec![I::default(); bwt.len()];
+    psi[0] = I::from_usize(sentinel_index);
+    for (i, &c) in bwt.iter().enumerate() {
+        if i != sentinel_index {
+            psi[occ[c as usize]] = I::from_usize(i);
+            occ[c as usize] += 1;
+        }
+    }
+    let ends = symbol_ends(&counts);
+
+    // Walks from the row of the whole text, which precedes the sentinel.
+    let mut decoded = Vec::with_capacity(n);
+    let mut r = sentinel_index;
+    for k in 0..n {
+        if r == 0 {
+            return Err(Error::invalid_bwt(format!(
+                "bwt is not a valid BWT, since the text ends at position {k}."
+            )));
+        }
+        decoded.push(ends.partition_point(|&e| e < r) as u8);
+        r = psi[r].to_usize();
+    }
+    Ok(decoded)
+}
+
+/// Decodes the original text from a given BWT in small space, using a rank structure instead of the psi array.
+///
+/// In addition to the BWT and the decoded text, it takes `σn/32` bytes of sampled occurrence counts,
+/// where `σ` is the number of distinct symbols.
+/// For small alphabets such as DNA, this is much smaller than the `4n` or more bytes of [`decode_bwt`],
+/// at the cost of slower decoding: every step of the LF mapping is answered by a rank query over the BWT.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+///
+/// # Errors
+///
+/// An error is returned if the Burrows-Wheeler transform is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::decode_bwt_small_space;
+///
+/// let bwt = "ard$rcaaaabb";
+/// let decoded = decode_bwt_small_space(bwt.as_bytes())?;
+/// assert_eq!(decoded, "abracadabra$".as_bytes());
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_bwt_small_space(bwt: &[u8]) -> Result<Vec<u8>> {
+    let mut counts = vec![0; 256];
+    for &c in bwt {
+        counts[c as usize] += 1;
+    }
+    let (terminator, _) = validate_counts(&counts)?;
+    let terminator = terminator as u8;
+    let occ = OccTable::new(bwt);
+
+    // Walks the LF mapping backward from row 0, i.e., the suffix of the terminator alone.
+    let n = bwt.len();
+    let mut decoded = vec![terminator; n];
+    let mut i = 0;
+    for k in (0..n - 1).rev() {
+        let c = bwt[i];
+        if c == terminator {
+            return Err(Error::invalid_bwt(format!(
+                "bwt is not a valid BWT, since the text starts at position {}.",
+                k + 1
+            )));
+        }
+        decoded[k] = c;
+        i = occ.c(c) + occ.rank(bwt, c, i);
+    }
+    Ok(decoded)
+}
+
+/// Number of segments per thread in [`decode_bwt_parallel`], to balance their random lengths.
+#[cfg(feature = "rayon")]
+const SEGMENTS_PER_THREAD: usize = 16;
+
+/// Decodes the original text from a given BWT in parallel
+/// using the global thread pool of [rayon](https://docs.rs/rayon).
+///
+/// The cycle of the inverse LF mapping is split at sampled rows into segments,
+/// which are decoded concurrently and stitched together in text order.
+/// It takes `O(n log n)` bits of space in addition to the decoded text, as [`decode_bwt`].
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+///
+/// # Errors
+///
+/// An error is returned if the Burrows-Wheeler transform is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::decode_bwt_parallel;
+///
+/// let bwt = "ard$rcaaaabb";
+/// let decoded = decode_bwt_parallel(bwt.as_bytes())?;
+/// assert_eq!(decoded, "abracadabra$".as_bytes());
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "rayon")]
+pub fn decode_bwt_parallel(bwt: &[u8]) -> Result<Vec<u8>> {
+    if bwt.len() <= u32::MAX_POS {
+        decode_bwt_parallel_with::<u32>(bwt)
+    } else if bwt.len() <= U40::MAX_POS {
+        decode_bwt_parallel_with::<U40>(bwt)
+    } else {
+        decode_bwt_parallel_with::<usize>(bwt)
+    }
+}
+
+#[cfg(feature = "rayon")]
+fn decode_bwt_parallel_with<I: SuffixIndex>(bwt: &[u8]) -> Result<Vec<u8>> {
+    use rayon::prelude::*;
+
+    let (psi, counts) = build_psi::<I>(bwt);
+    let (_, ends) = validate_counts(&counts)?;
+    let n = bwt.len();
+
+    // Samples evenly spaced rows, including the row of the whole text, as the starts of segments.
+    let primary = psi[0].to_usize();
+    let n_segments = (rayon::current_num_threads() * SEGMENTS_PER_THREAD).min(n);
+    let mut starts: Vec<usize> = (0..n_segments).map(|i| i * n / n_segments).collect();
+    starts.push(primary);
+    starts.sort_unstable();
+    starts.dedup();
+    let mut is_start = vec![false; n];
+    for &r in &starts {
+        is_start[r] = true;
+    }
+
+    // Decodes each segment until reaching the start of another segment.
+    let segments: Vec<(Vec<u8>, usize)> = starts
+        .par_iter()
+        .map(|&start| {
+            let mut segment = vec![];
+            let mut r = start;
+            loop {
+                segment.push(ends.partition_point(|&e| e <= r) as u8);
+                r = psi[r].to_usize();
+                if is_start[r] || segment.len() == n {
+                    return (segment, r);
+                }
+            }
+        })
+        .collect();
+
+    // Stitches the segments from the row of the whole text.
+    let mut decoded = Vec::with_capacity(n);
+    let mut r = primary;
+    while let Ok(k) = starts.binary_search(&r) {
+        let (segment, next) = &segments[k];
+        decoded.extend_from_slice(segment);
+        r = *next;
+        if r == primary || decoded.len() >= n {
+            break;
+        }
+    }
+    if r != primary || decoded.len() != n {
+        return Err(Error::invalid_bwt(
+            "bwt is not a valid BWT, since the inverse LF mapping has multiple cycles.",
+        ));
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BwtBuilder;
+
+    #[test]
+    fn test_decode_bwt_single() {
+        let bwt = "$";
+        let decoded = decode_bwt(bwt.as_bytes()).unwrap();
+        assert_eq!(decoded, "$".as_bytes());
+    }
+
+    #[test]
+    fn test_decode_bwt_empty() {
+        let bwt = "";
+        let e = decode_bwt(bwt.as_bytes());
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn test_decode_bwt_to() {
+        let text: Vec<u8> = (0..100000u32)
+            .map(|i| b"ab"[(i.count_ones() % 2) as usize])
+            .chain([b'$'])
+            .collect();
+        let mut bwt = vec![];
+        BwtBuilder::new(&text).unwrap().build(&mut bwt).unwrap();
+        let mut decoded = vec![];
+        decode_bwt_to(&bwt, &mut decoded).unwrap();
+        assert_eq!(decoded, text);
+    }
+
+    #[test]
+    fn test_decode_bwt_multiple_cycles() {
+        // The LF mapping consists of two cycles, "ab$" and "b".
+        let bwt = "bb$a";
+        let e = decode_bwt(bwt.as_bytes());
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn test_validate_bwt() {
+        // Every sequence over "$ab" with a unique terminator, compared with the decoding.
+        for n in 1..=7 {
+            for code in 0..3usize.pow(n) {
+                let bwt: Vec<u8> = (0..n).map(|i| b"$ab"[code / 3usize.pow(i) % 3]).collect();
+                let expected = decode_bwt(&bwt);
+                match validate_bwt(&bwt) {
+                    Ok(props) => {
+                        assert!(expected.is_ok(), "bwt = {bwt:?}");
+                        assert_eq!(props.len, bwt.len());
+                        assert_eq!(props.terminator, bwt[props.primary_index]);
+                    }
+                    Err(_) => assert!(expected.is_err(), "bwt = {bwt:?}"),
+                }
+            }
+        }
+        assert!(validate_bwt(b"").is_err());
+        assert!(validate_bwt(b"ard$rcaaa$bb").is_err());
+    }
+
+    #[test]
+    fn test_decode_bwt_invalid_terminator() {
+        let bwt = "ard$rcaaa$bb";
+        let e = decode_bwt(bwt.as_bytes());
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn test_decode_bwt_from_reader() {
+        let texts: &[&[u8]] = &[b"$", b"abracadabra$", b"mississippi$", b"aaaa$"];
+        for &text in texts {
+            let mut bwt = vec![];
+            BwtBuilder::new(text).unwrap().build(&mut bwt).unwrap();
+            let mut decoded = vec![];
+            decode_bwt_from_reader(&bwt[..], &mut decoded).unwrap();
+            assert_eq!(decoded, text);
+        }
+        assert!(decode_bwt_from_reader(&b""[..], vec![]).is_err());
+        assert!(decode_bwt_from_reader(&b"ard$rcaaa$bb"[..], vec![]).is_err());
+        assert!(decode_bwt_from_reader(&b"bb$a"[..], vec![]).is_err());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_decode_bwt_parallel() {
+        let text: Vec<u8> = (0..100000u32)
+            .map(|i| b"ab"[(i.count_ones() % 2) as usize])
+            .chain([b'$'])
+            .collect();
+        let texts: &[&[u8]] = &[b"$", b"a$", b"abracadabra$", &text];
+        for &text in texts {
+            let mut bwt = vec![];
+            BwtBuilder::new(text).unwrap().build(&mut bwt).unwrap();
+            assert_eq!(decode_bwt_parallel(&bwt).unwrap(), text);
+        }
+        assert!(decode_bwt_parallel(b"").is_err());
+        assert!(decode_bwt_parallel(b"ard$rcaaa$bb").is_err());
+        assert!(decode_bwt_parallel(b"bb$a").is_err());
+    }
+
+    #[test]
+    fn test_decode_bwt_small_space() {
+        let text: Vec<u8> = (0..10000u32)
+            .map(|i| b"ab"[(i.count_ones() % 2) as usize])
+            .chain([b'$'])
+            .collect();
+        let texts: &[&[u8]] = &[b"$", b"a$", b"abracadabra$", &text];
+        for &text in texts {
+            let mut bwt = vec![];
+            BwtBuilder::new(text).unwrap().build(&mut bwt).unwrap();
+            assert_eq!(decode_bwt_small_space(&bwt).unwrap(), text);
+        }
+        assert!(decode_bwt_small_space(b"").is_err());
+        assert!(decode_bwt_small_space(b"ard$rcaaa$bb").is_err());
+        assert!(decode_bwt_small_space(b"bb$a").is_err());
+    }
+
+    #[test]
+    fn test_decode_bwt_with_index() {
+        assert_eq!(decode_bwt_with_index(b"nnbaaa", 3).unwrap(), b"banana");
+        // Periodic texts have identical rotations.
+        assert_eq!(decode_bwt_with_index(b"bbaa", 0).unwrap(), b"abab");
+        assert_eq!(decode_bwt_with_index(b"bbaa", 1).unwrap(), b"abab");
+        assert_eq!(decode_bwt_with_index(b"a", 0).unwrap(), b"a");
+        // The BWT with a terminator can be also decoded from its primary index.
+        assert_eq!(
+            decode_bwt_with_index(b"ard$rcaaaabb", 3).unwrap(),
+            b"abracadabra$"
+        );
+        assert!(decode_bwt_with_index(b"", 0).is_err());
+        assert!(decode_bwt_with_index(b"nnbaaa", 6).is_err());
+        // "bb$a" consists of two cycles of different rotations.
+        assert!(decode_bwt_with_index(b"bb$a", 2).is_err());
+    }
+
+    #[test]
+    fn test_decode_bwt_with_sentinel() {
+        // Every byte value occurs in the text, so no byte can be a terminator.
+        let text: Vec<u8> = (0..1000u32).map(|i| (i * 37 % 256) as u8).collect();
+        let mut bwt = vec![];
+        let stats = BwtBuilder::new_unchecked(&text)
+            .unwrap()
+            .implicit_sentinel(0)
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt.len(), text.len() + 1);
+        assert_eq!(
+            decode_bwt_with_sentinel(&bwt, stats.primary_index).unwrap(),
+            text
+        );
+        // The sentinel alone is the BWT of the empty text.
+        assert_eq!(decode_bwt_with_sentinel(b"x", 0).unwrap(), b"");
+        assert!(decode_bwt_with_sentinel(b"", 0).is_err());
+        assert!(decode_bwt_with_sentinel(b"ab", 2).is_err());
+        // The sentinel must be at the row of the whole text.
+        assert!(decode_bwt_with_sentinel(b"ard$rcaaaabb", 0).is_err());
+    }
+
+    #[test]
+    fn test_read_positions() {
+        let mut bytes = b"ba$".bytes();
+        let mut n = 0;
+        let mut lists: Vec<Vec<u32>> = vec![vec![]; 256];
+        assert!(read_positions(&mut bytes, &mut lists, &mut n).unwrap());
+        assert_eq!(n, 3);
+        assert_eq!(lists[b'$' as usize], vec![2]);
+        assert_eq!(lists[b'a' as usize], vec![1]);
+        assert_eq!(lists[b'b' as usize], vec![0]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+use std::io;
+
+/// Result type of the fallible functions in this crate.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error of the fallible functions in this crate.
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::{verify_terminator, Error};
+///
+/// let e = verify_terminator(b"abrac$dabra$").unwrap_err();
+/// assert!(matches!(e, Error::InvalidTerminator { pos: 5, byte: b'$' }));
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The text is empty.
+    EmptyText,
+    /// The smallest byte of the text appears before its end.
+    InvalidTerminator {
+        /// The position of the first such byte.
+        pos: usize,
+        /// The byte.
+        byte: u8,
+    },
+    /// The text has a byte out of the expected alphabet, such as a non-nucleotide base.
+    InvalidSymbol {
+        /// The position of the first such byte.
+        pos: usize,
+        /// The byte.
+        byte: u8,
+    },
+    /// The BWT is empty or cannot be decoded, with the reason.
+    InvalidBwt(String),
+    /// An argument is out of its valid range or unsupported in the configuration, with the reason.
+    InvalidArgument(String),
+    /// An input stream is malformed, such as a compressed BWT or a FASTA file, with the reason.
+    InvalidFormat(String),
+    /// The construction is cancelled by [`BwtBuilder::cancel_flag`](crate::BwtBuilder::cancel_flag),
+    /// in which case the output written so far is incomplete.
+    Cancelled,
+    /// An I/O error of a reader or writer.
+    Io(io::Error),
+}
+
+impl Error {
+    pub(crate) fn invalid_bwt<S: Into<String>>(msg: S) -> Self {
+        Self::InvalidBwt(msg.into())
+    }
+
+    pub(crate) fn invalid_argument<S: Into<String>>(msg: S) -> Self {
+        Self::InvalidArgument(msg.into())
+    }
+
+    pub(crate) fn invalid_format<S: Into<String>>(msg: S) -> Self {
+        Self::InvalidFormat(msg.into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyText => write!(f, "text must not be empty."),
+            Self::InvalidTerminator { pos, byte } => write!(
+                f,
+                "text must have the smallest special character only at the end, but found {byte:?} at position {pos}."
+            ),
+            Self::InvalidSymbol { pos, byte } => write!(
+                f,
+                "byte {:?} at position {pos} is not in the alphabet.",
+                *byte as char
+            ),
+            Self::InvalidBwt(msg) | Self::InvalidArgument(msg) | Self::InvalidFormat(msg) => {
+                write!(f, "{msg}")
+            }
+            Self::Cancelled => write!(f, "the construction was cancelled."),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Converts an error to be returned from [`io::Read`] and [`io::Write`] implementations,
+/// unwrapping [`Error::Io`] and reporting the others as [`io::ErrorKind::InvalidData`].
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            e => Self::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_roundtrip() {
+        let e = io::Error::from(Error::from(io::Error::from(io::ErrorKind::UnexpectedEof)));
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        let e = io::Error::from(Error::EmptyText);
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), "text must not be empty.");
+    }
+}
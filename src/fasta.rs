@@ -0,0 +1,325 @@
+use std::io::BufRead;
+
+use crate::error::{Error, Result};
+
+use crate::BwtBuilder;
+
+/// Terminator appended to the end of a text parsed by [`parse_fasta`].
+pub const FASTA_TERMINATOR: u8 = b'\0';
+
+/// Text concatenating the sequences of FASTA records.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FastaText {
+    /// The concatenated text ending with [`FASTA_TERMINATOR`].
+    pub text: Vec<u8>,
+    /// The records in the order of appearance.
+    pub records: Vec<FastaRecord>,
+}
+
+/// Metadata of a FASTA record in [`FastaText`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FastaRecord {
+    /// The header line without the leading `>`.
+    pub name: String,
+    /// The starting position of the sequence in the text.
+    pub start: usize,
+    /// The length of the sequence.
+    pub len: usize,
+}
+
+impl FastaText {
+    /// Creates a builder of the BWT of the concatenated text.
+    ///
+    /// # Errors
+    ///
+    /// See [`BwtBuilder::new`].
+    pub fn builder(&self) -> Result<BwtBuilder<'_>> {
+        BwtBuilder::new(&self.text)
+    }
+
+    /// Returns the index of the record containing the text position `pos`,
+    /// or `None` if `pos` is at a separator or the terminator.
+    pub fn record_of(&self, pos: usize) -> Option<usize> {
+        let i = self.records.partition_point(|r| r.start <= pos);
+        let i = i.checked_sub(1)?;
+        let r = &self.records[i];
+        if pos < r.start + r.len {
+            Some(i)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses FASTA records and concatenates the sequences into a text for [`BwtBuilder`].
+///
+/// Header lines starting with `>` are stripped and recorded,
+/// empty lines and comment lines starting with `;` are skipped,
+/// and line breaks inside sequences are removed.
+/// The sequences are concatenated with `separator` between consecutive records,
+/// and [`FASTA_TERMINATOR`] is appended to the end.
+///
+/// # Arguments
+///
+/// * `rdr` - The reader of the FASTA data.
+/// * `separator` - The byte to separate records, which must be larger than [`FASTA_TERMINATOR`].
+///
+/// # Errors
+///
+/// An error is returned if
+///
+/// - `separator` is [`FASTA_TERMINATOR`],
+/// - a sequence line appears before any header,
+/// - a sequence contains `separator` or [`FASTA_TERMINATOR`],
+/// - no records are found, or
+/// - `rdr` returns an error.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::parse_fasta;
+///
+/// let fasta = ">seq1\nACGT\nAC\n>seq2\nGGT\n";
+/// let parsed = parse_fasta(fasta.as_bytes(), b'$')?;
+/// assert_eq!(parsed.text, b"ACGTAC$GGT\0");
+/// assert_eq!(parsed.records[1].name, "seq2");
+/// assert_eq!(parsed.records[1].start, 7);
+/// assert_eq!(parsed.record_of(8), Some(1));
+///
+/// let mut bwt = vec![];
+/// parsed.builder()?.build(&mut bwt)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_fasta<R: BufRead>(rdr: R, separator: u8) -> Result<FastaText> {
+    if separator == FASTA_TERMINATOR {
+        return Err(Error::invalid_format(format!(
+            "separator must be different from the terminator {FASTA_TERMINATOR:?}."
+        )));
+    }
+    let mut text = vec![];
+    let mut records: Vec<FastaRecord> = vec![];
+    for (lineno, line) in rdr.split(b'\n').enumerate() {
+        let mut line = line?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if line.is_empty() || line[0] == b';' {
+            continue;
+        }
+        if line[0] == b'>' {
+            if let Some(r) = records.last_mut() {
+                r.len = text.len() - r.start;
+                text.push(separator);
+            }
+            records.push(FastaRecord {
+                name: String::from_utf8_lossy(&line[1..]).into_owned(),
+                start: text.len(),
+                len: 0,
+            });
+            continue;
+        }
+        if records.is_empty() {
+            return Err(Error::invalid_format(format!(
+                "sequence must follow a header, but found at line {}.",
+                lineno + 1
+            )));
+        }
+        if let Some(&c) = line
+            .iter()
+            .find(|&&c| c == separator || c == FASTA_TERMINATOR)
+        {
+            return Err(Error::invalid_format(format!(
+                "sequence must not contain the separator or terminator, but found {c:?} at line {}.",
+                lineno + 1
+            )));
+        }
+        text.extend_from_slice(&line);
+    }
+    let last = records
+        .last_mut()
+        .ok_or_else(|| Error::invalid_format("no FASTA records are found."))?;
+    last.len = text.len() - last.start;
+    text.push(FASTA_TERMINATOR);
+    Ok(FastaText { text, records })
+}
+
+/// Parses FASTA records into the text of the FMD-index layout used by aligners such as BWA,
+/// where each sequence is followed by its reverse complement.
+///
+/// The text concatenates `S1`, `rc(S1)`, `S2`, `rc(S2)`, ... with `separator` between them
+/// and [`FASTA_TERMINATOR`] at the end,
+/// so that the BWT covers both strands of every sequence.
+/// Then, `records[2 * i]` is the `i`-th sequence and `records[2 * i + 1]` is its reverse complement,
+/// which have the same name.
+///
+/// # Arguments
+///
+/// * `rdr` - The reader of the FASTA data.
+/// * `separator` - The byte to separate sequences, which must be larger than [`FASTA_TERMINATOR`].
+///
+/// # Errors
+///
+/// An error is returned if [`parse_fasta`] returns an error,
+/// or if a sequence contains a byte other than nucleotide bases and `N`.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::parse_fasta_fmd;
+///
+/// let fasta = ">seq1\nAACG\n>seq2\nGT\n";
+/// let parsed = parse_fasta_fmd(fasta.as_bytes(), b'$')?;
+/// assert_eq!(parsed.text, b"AACG$CGTT$GT$AC\0");
+/// assert_eq!(parsed.records[1].name, "seq1");
+/// assert_eq!(parsed.records[1].start, 5);
+/// assert_eq!(parsed.record_of(11), Some(2));
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_fasta_fmd<R: BufRead>(rdr: R, separator: u8) -> Result<FastaText> {
+    let parsed = parse_fasta(rdr, separator)?;
+    let mut text = Vec::with_capacity(2 * parsed.text.len());
+    let mut records = Vec::with_capacity(2 * parsed.records.len());
+    for r in parsed.records {
+        if !text.is_empty() {
+            text.push(separator);
+        }
+        let seq = &parsed.text[r.start..r.start + r.len];
+        let rc = reverse_complement(seq).map_err(|e| {
+            Error::invalid_format(format!(
+                "record {:?} cannot be reverse-complemented: {e}",
+                r.name
+            ))
+        })?;
+        records.push(FastaRecord {
+            name: r.name.clone(),
+            start: text.len(),
+            len: r.len,
+        });
+        text.extend_from_slice(seq);
+        text.push(separator);
+        records.push(FastaRecord {
+            name: r.name,
+            start: text.len(),
+            len: r.len,
+        });
+        text.extend_from_slice(&rc);
+    }
+    text.push(FASTA_TERMINATOR);
+    Ok(FastaText { text, records })
+}
+
+/// Returns the reverse complement of a nucleotide sequence,
+/// where the case is preserved and `N` is its own complement.
+///
+/// # Arguments
+///
+/// * `seq` - The sequence of `A`, `C`, `G`, `T`, and `N`, case-insensitively.
+///
+/// # Errors
+///
+/// An error is returned if `seq` contains another byte.
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::reverse_complement;
+///
+/// assert_eq!(reverse_complement(b"AACgtN").unwrap(), b"NacGTT");
+/// assert!(reverse_complement(b"ACXT").is_err());
+/// ```
+pub fn reverse_complement(seq: &[u8]) -> Result<Vec<u8>> {
+    seq.iter()
+        .enumerate()
+        .rev()
+        .map(|(pos, &c)| match c {
+            b'A' => Ok(b'T'),
+            b'C' => Ok(b'G'),
+            b'G' => Ok(b'C'),
+            b'T' => Ok(b'A'),
+            b'N' => Ok(b'N'),
+            b'a' => Ok(b't'),
+            b'c' => Ok(b'g'),
+            b'g' => Ok(b'c'),
+            b't' => Ok(b'a'),
+            b'n' => Ok(b'n'),
+            _ => Err(Error::InvalidSymbol { pos, byte: c }),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fasta() {
+        let fasta = ">a desc\r\nAC\r\n\r\n;comment\nGT\n>b\n>c\nTT";
+        let parsed = parse_fasta(fasta.as_bytes(), b'#').unwrap();
+        assert_eq!(parsed.text, b"ACGT##TT\0");
+        assert_eq!(
+            parsed.records,
+            vec![
+                FastaRecord {
+                    name: "a desc".to_string(),
+                    start: 0,
+                    len: 4
+                },
+                FastaRecord {
+                    name: "b".to_string(),
+                    start: 5,
+                    len: 0
+                },
+                FastaRecord {
+                    name: "c".to_string(),
+                    start: 6,
+                    len: 2
+                },
+            ]
+        );
+        assert_eq!(parsed.record_of(3), Some(0));
+        assert_eq!(parsed.record_of(4), None);
+        assert_eq!(parsed.record_of(7), Some(2));
+        assert_eq!(parsed.record_of(8), None);
+    }
+
+    #[test]
+    fn test_parse_fasta_no_header() {
+        assert!(parse_fasta("ACGT\n".as_bytes(), b'$').is_err());
+    }
+
+    #[test]
+    fn test_parse_fasta_empty() {
+        assert!(parse_fasta("".as_bytes(), b'$').is_err());
+    }
+
+    #[test]
+    fn test_parse_fasta_separator_in_sequence() {
+        assert!(parse_fasta(">a\nAC$GT\n".as_bytes(), b'$').is_err());
+    }
+
+    #[test]
+    fn test_parse_fasta_fmd() {
+        let fasta = ">a\nACCN\n>b\n>c\ngt\n";
+        let parsed = parse_fasta_fmd(fasta.as_bytes(), b'#').unwrap();
+        assert_eq!(parsed.text, b"ACCN#NGGT###gt#ac\0");
+        let starts: Vec<_> = parsed.records.iter().map(|r| r.start).collect();
+        assert_eq!(starts, [0, 5, 10, 11, 12, 15]);
+        let names: Vec<_> = parsed.records.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["a", "a", "b", "b", "c", "c"]);
+        assert_eq!(parsed.record_of(7), Some(1));
+
+        // Both strands of every sequence are in the BWT.
+        let mut bwt = vec![];
+        parsed.builder().unwrap().build(&mut bwt).unwrap();
+        let index = crate::FmIndex::new(bwt).unwrap();
+        assert_eq!(index.count(b"GGT"), index.count(b"ACC"));
+
+        assert!(parse_fasta_fmd(">a\nACRT\n".as_bytes(), b'#').is_err());
+    }
+}
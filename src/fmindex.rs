@@ -0,0 +1,1182 @@
+use crate::error::{Error, Result};
+
+use crate::indexed::primary_index;
+use crate::rank::OccTable;
+
+/// FM-index over a BWT, supporting pattern counting by backward search
+/// and pattern locating with a sampled suffix array.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BwtBuilder, FmIndex};
+///
+/// let mut bwt = vec![];
+/// BwtBuilder::new(b"abracadabra$")?.build(&mut bwt)?;
+///
+/// let index = FmIndex::new(bwt)?;
+/// assert_eq!(index.count(b"abra"), 2);
+/// assert_eq!(index.count(b"a"), 5);
+/// assert_eq!(index.count(b"bra$"), 1);
+/// assert_eq!(index.count(b"abc"), 0);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FmIndex {
+    bwt: Vec<u8>,
+    occ: OccTable,
+    primary: usize,
+    sample_rate: usize,
+    // sa_samples[k] is the starting position of the suffix at row k * sample_rate,
+    // which is empty if the samples are not given.
+    sa_samples: Vec<usize>,
+}
+
+/// Matching statistic at a position of a query, computed by [`FmIndex::matching_statistics`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchingStatistic {
+    /// The length of the longest prefix of the query from the position occurring in the text.
+    pub len: usize,
+    /// The range of rows `lo..hi` whose suffixes start with the prefix,
+    /// whose size is the number of its occurrences.
+    pub rows: (usize, usize),
+}
+
+/// Approximate match of a pattern, found by [`FmIndex::approximate_search`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApproximateMatch {
+    /// The range of rows `lo..hi` whose suffixes start with the matched string.
+    pub rows: (usize, usize),
+    /// The number of positions where the matched string differs from the pattern.
+    pub n_mismatches: usize,
+}
+
+impl FmIndex {
+    /// Builds the index from a BWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `bwt` - The Burrows-Wheeler transform of a text with a unique terminator.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `bwt` is empty or does not have exactly one terminator.
+    pub fn new(bwt: Vec<u8>) -> Result<Self> {
+        let primary = primary_index(&bwt)?;
+        let occ = OccTable::new(&bwt);
+        Ok(Self {
+            bwt,
+            occ,
+            primary,
+            sample_rate: 0,
+            sa_samples: vec![],
+        })
+    }
+
+    /// Builds the index from a BWT and its suffix array sampled at every `sample_rate` rows,
+    /// as written by [`BwtBuilder::sampled_suffix_array_writer`](crate::BwtBuilder::sampled_suffix_array_writer).
+    ///
+    /// # Arguments
+    ///
+    /// * `bwt` - The Burrows-Wheeler transform of a text with a unique terminator.
+    /// * `sa_samples` - The starting positions of the suffixes at rows `0, sample_rate, 2 * sample_rate, ...`.
+    /// * `sample_rate` - The sampling rate of the suffix array.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if
+    ///
+    /// - `bwt` is empty or does not have exactly one terminator,
+    /// - `sample_rate` is zero, or
+    /// - the number of samples is not `ceil(n / sample_rate)`, where `n` is the length of `bwt`.
+    ///
+    /// Unlike [`FmIndex::with_sample_rate`], the LF mapping is not walked to validate `bwt`,
+    /// so an invalid BWT is reported by [`FmIndex::locate`] instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, FmIndex, IntWidth};
+    ///
+    /// let mut bwt = vec![];
+    /// let mut samples = vec![];
+    /// BwtBuilder::new(b"abracadabra$")?
+    ///     .sampled_suffix_array_writer(&mut samples, IntWidth::U32, 4)?
+    ///     .build(&mut bwt)?;
+    ///
+    /// let samples = samples
+    ///     .chunks(4)
+    ///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+    ///     .collect();
+    /// let index = FmIndex::with_sa_samples(bwt, samples, 4)?;
+    /// assert_eq!(index.locate(b"abra")?, vec![0, 7]);
+    /// assert_eq!(index.locate(b"a")?, vec![0, 3, 5, 7, 10]);
+    /// assert_eq!(index.locate(b"abc")?, vec![]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_sa_samples(
+        bwt: Vec<u8>,
+        sa_samples: Vec<usize>,
+        sample_rate: usize,
+    ) -> Result<Self> {
+        if sample_rate == 0 {
+            return Err(Error::invalid_argument("sample_rate must be positive."));
+        }
+        let index = Self::new(bwt)?;
+        let n_samples = (index.len() - 1) / sample_rate + 1;
+        if sa_samples.len() != n_samples {
+            return Err(Error::invalid_argument(format!(
+                "sa_samples must have {n_samples} values, but got {}.",
+                sa_samples.len()
+            )));
+        }
+        Ok(Self {
+            sample_rate,
+            sa_samples,
+            ..index
+        })
+    }
+
+    /// Builds the index from a BWT, sampling the suffix array at every `sample_rate` rows
+    /// by walking the LF mapping over the whole BWT once.
+    ///
+    /// If the BWT is built by [`BwtBuilder`](crate::BwtBuilder),
+    /// [`FmIndex::with_sa_samples`] avoids the walk by taking the samples written by the builder.
+    ///
+    /// # Arguments
+    ///
+    /// * `bwt` - The Burrows-Wheeler transform of a text with a unique terminator.
+    /// * `sample_rate` - The sampling rate of the suffix array.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if
+    ///
+    /// - `bwt` is empty or does not have exactly one terminator,
+    /// - `sample_rate` is zero, or
+    /// - `bwt` is not a valid BWT.
+    pub fn with_sample_rate(bwt: Vec<u8>, sample_rate: usize) -> Result<Self> {
+        if sample_rate == 0 {
+            return Err(Error::invalid_argument("sample_rate must be positive."));
+        }
+        let index = Self::new(bwt)?;
+
+        // Walks the LF mapping from row 0, i.e., the suffix at position n - 1.
+        let n = index.len();
+        let mut sa_samples = vec![usize::MAX; (n - 1) / sample_rate + 1];
+        let mut r = 0;
+        for pos in (0..n).rev() {
+            if (r == index.primary) != (pos == 0) {
+                return Err(Error::invalid_bwt(
+                    "bwt is not a valid BWT, since the LF mapping has multiple cycles.",
+                ));
+            }
+            if r % sample_rate == 0 {
+                sa_samples[r / sample_rate] = pos;
+            }
+            r = index.occ.lf(&index.bwt, r);
+        }
+        Ok(Self {
+            sample_rate,
+            sa_samples,
+            ..index
+        })
+    }
+
+    /// Returns the length of the text, including the terminator.
+    pub fn len(&self) -> usize {
+        self.bwt.len()
+    }
+
+    /// Returns `true` if the text is empty, which never happens.
+    pub fn is_empty(&self) -> bool {
+        self.bwt.is_empty()
+    }
+
+    /// Returns the BWT.
+    pub fn bwt(&self) -> &[u8] {
+        &self.bwt
+    }
+
+    /// Returns the number of occurrences of `pattern` in the text.
+    ///
+    /// It runs in `O(m)` rank queries, where `m` is the length of `pattern`.
+    /// The empty pattern occurs at every position of the text,
+    /// and a pattern with the terminator other than at its end occurs nowhere.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        let (lo, hi) = self.backward_search(pattern);
+        hi - lo
+    }
+
+    /// Returns the starting positions of the occurrences of `pattern` in the text in increasing order.
+    ///
+    /// Each occurrence is located by walking the LF mapping until a sampled row,
+    /// which takes `sample_rate` steps on average for typical texts.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if
+    ///
+    /// - the index is built without suffix array samples, or
+    /// - the LF mapping does not reach a sampled row, i.e., the BWT given to
+    ///   [`FmIndex::with_sa_samples`] is not valid.
+    pub fn locate(&self, pattern: &[u8]) -> Result<Vec<usize>> {
+        if self.sa_samples.is_empty() {
+            return Err(Error::invalid_argument(
+                "the index must be built with suffix array samples to locate patterns.",
+            ));
+        }
+        let (lo, hi) = self.backward_search(pattern);
+        let mut positions = (lo..hi)
+            .map(|r| self.suffix_position(r))
+            .collect::<Result<Vec<_>>>()?;
+        positions.sort_unstable();
+        Ok(positions)
+    }
+
+    /// Returns the starting position of the suffix at row `r`.
+    ///
+    /// The walk is bounded by the length of the text,
+    /// since the LF mapping of an invalid BWT can cycle without reaching a sampled row.
+    fn suffix_position(&self, mut r: usize) -> Result<usize> {
+        let mut steps = 0;
+        while r % self.sample_rate != 0 {
+            if r == self.primary {
+                // The suffix at the primary row starts at position 0.
+                return Ok(steps);
+            }
+            if steps == self.len() {
+                return Err(Error::invalid_bwt(
+                    "bwt is not a valid BWT, since the LF mapping has multiple cycles.",
+                ));
+            }
+            r = self.occ.lf(&self.bwt, r);
+            steps += 1;
+        }
+        Ok(self.sa_samples[r / self.sample_rate] + steps)
+    }
+
+    /// Computes the matching statistics of `query` against the text,
+    /// i.e., for each position `i` of `query`, the length of the longest prefix of `query[i..]`
+    /// occurring in the text and the range of rows of its occurrences.
+    ///
+    /// The statistics are computed from the end of `query` by [`FmIndex::backward_step`],
+    /// using that the length at `i` is at most one more than that at `i + 1`.
+    /// If the match cannot be extended to the left, it is shortened from the right
+    /// and the range of the shortened match is searched again,
+    /// so it takes `O(mL)` rank queries, where `m` is the length of `query`
+    /// and `L` is the largest length of the statistics.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query sequence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, FmIndex};
+    ///
+    /// let mut bwt = vec![];
+    /// BwtBuilder::new(b"abracadabra$")?.build(&mut bwt)?;
+    /// let index = FmIndex::new(bwt)?;
+    ///
+    /// let ms = index.matching_statistics(b"cabrx");
+    /// let lens: Vec<_> = ms.iter().map(|s| s.len).collect();
+    /// assert_eq!(lens, vec![2, 3, 2, 1, 0]);
+    /// // "abr" occurs twice.
+    /// assert_eq!(ms[1].rows.1 - ms[1].rows.0, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn matching_statistics(&self, query: &[u8]) -> Vec<MatchingStatistic> {
+        let all = (0, self.len());
+        let mut stats = vec![MatchingStatistic { len: 0, rows: all }; query.len()];
+        let terminator = self.terminator();
+        let (mut len, mut rows) = (0, all);
+        for i in (0..query.len()).rev() {
+            loop {
+                // The terminator is followed by no symbols in the text.
+                let (lo, hi) = if query[i] == terminator && len != 0 {
+                    (0, 0)
+                } else {
+                    self.backward_step(rows.0, rows.1, query[i])
+                };
+                if lo < hi {
+                    len += 1;
+                    rows = (lo, hi);
+                    break;
+                }
+                if len == 0 {
+                    break;
+                }
+                len -= 1;
+                rows = self.backward_search(&query[i + 1..i + 1 + len]);
+            }
+            stats[i] = MatchingStatistic { len, rows };
+        }
+        stats
+    }
+
+    /// Returns the ranges of rows whose suffixes start with a string
+    /// differing from `pattern` in at most `k` positions, in increasing order of rows.
+    ///
+    /// The strings are enumerated by backtracking from the end of `pattern`,
+    /// substituting each symbol of the text other than the terminator while the budget remains,
+    /// and pruning the strings that do not occur.
+    /// The terminator is also tried at the end of `pattern`, the only position it can occur.
+    /// Since the matched strings have the same length, their ranges are disjoint.
+    /// The number of explored strings grows as `O((mσ)^k)` in the worst case,
+    /// where `m` is the length of `pattern` and `σ` is the alphabet size,
+    /// so it is intended for small `k`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    /// * `k` - The maximum number of mismatches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, FmIndex};
+    ///
+    /// let mut bwt = vec![];
+    /// BwtBuilder::new(b"abracadabra$")?.build(&mut bwt)?;
+    /// let index = FmIndex::with_sample_rate(bwt, 4)?;
+    ///
+    /// // "aca", "ada", and "abr" differ from "aba" in one position.
+    /// assert_eq!(index.count_approximate(b"aba", 0), 0);
+    /// assert_eq!(index.count_approximate(b"aba", 1), 4);
+    /// assert_eq!(index.locate_approximate(b"aba", 1)?, vec![0, 3, 5, 7]);
+    /// let matches = index.approximate_search(b"aba", 1);
+    /// assert_eq!(matches.len(), 3);
+    /// assert!(matches.iter().all(|m| m.n_mismatches == 1));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn approximate_search(&self, pattern: &[u8], k: usize) -> Vec<ApproximateMatch> {
+        let symbols = self.body_symbols();
+        let terminator = self.terminator();
+        let mut matches = vec![];
+        // The suffix of pattern matched so far, given by its length, range, and number of mismatches.
+        let mut stack = vec![(0, (0, self.len()), 0)];
+        while let Some((matched, (lo, hi), n_mismatches)) = stack.pop() {
+            if matched == pattern.len() {
+                matches.push(ApproximateMatch {
+                    rows: (lo, hi),
+                    n_mismatches,
+                });
+                continue;
+            }
+            let expected = pattern[pattern.len() - matched - 1];
+            let last = (matched == 0).then_some(terminator);
+            for &c in symbols.iter().chain(&last) {
+                let n_mismatches = n_mismatches + usize::from(c != expected);
+                if n_mismatches > k {
+                    continue;
+                }
+                let (lo, hi) = self.backward_step(lo, hi, c);
+                if lo < hi {
+                    stack.push((matched + 1, (lo, hi), n_mismatches));
+                }
+            }
+        }
+        matches.sort_unstable_by_key(|m| m.rows);
+        matches
+    }
+
+    /// Returns the terminator of the text, i.e., its last symbol.
+    fn terminator(&self) -> u8 {
+        self.bwt[self.primary]
+    }
+
+    /// Returns the symbols occurring in the text other than the terminator in increasing order.
+    fn body_symbols(&self) -> Vec<u8> {
+        let terminator = self.terminator();
+        (0..=255u8)
+            .filter(|&c| c != terminator && self.count(&[c]) != 0)
+            .collect()
+    }
+
+    /// Returns the number of occurrences of the strings
+    /// differing from `pattern` in at most `k` positions, as found by [`Self::approximate_search`].
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    /// * `k` - The maximum number of mismatches.
+    pub fn count_approximate(&self, pattern: &[u8], k: usize) -> usize {
+        self.approximate_search(pattern, k)
+            .iter()
+            .map(|m| m.rows.1 - m.rows.0)
+            .sum()
+    }
+
+    /// Returns the starting positions of the occurrences of the strings
+    /// differing from `pattern` in at most `k` positions in increasing order,
+    /// as found by [`Self::approximate_search`].
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    /// * `k` - The maximum number of mismatches.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if
+    ///
+    /// - the index is built without suffix array samples, or
+    /// - the LF mapping does not reach a sampled row, i.e., the BWT given to
+    ///   [`FmIndex::with_sa_samples`] is not valid.
+    pub fn locate_approximate(&self, pattern: &[u8], k: usize) -> Result<Vec<usize>> {
+        if self.sa_samples.is_empty() {
+            return Err(Error::invalid_argument(
+                "the index must be built with suffix array samples to locate patterns.",
+            ));
+        }
+        let mut positions = self
+            .approximate_search(pattern, k)
+            .iter()
+            .flat_map(|m| m.rows.0..m.rows.1)
+            .map(|r| self.suffix_position(r))
+            .collect::<Result<Vec<_>>>()?;
+        positions.sort_unstable();
+        Ok(positions)
+    }
+
+    /// Returns the number of distinct substrings of length `k` in the text except the terminator,
+    /// as computed by [`Self::for_each_distinct_kmer_count`].
+    /// It is one for `k = 0`, i.e., the empty string.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - The length of the substrings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, FmIndex};
+    ///
+    /// let mut bwt = vec![];
+    /// BwtBuilder::new(b"abracadabra$")?.build(&mut bwt)?;
+    /// let index = FmIndex::new(bwt)?;
+    ///
+    /// assert_eq!(index.count_distinct_kmers(1), 5);
+    /// // "abra" occurs twice.
+    /// assert_eq!(index.count_distinct_kmers(4), 7);
+    /// assert_eq!(index.count_distinct_kmers(12), 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn count_distinct_kmers(&self, k: usize) -> usize {
+        let mut count = 1;
+        self.for_each_distinct_kmer_count(k, |_, c| count = c);
+        count
+    }
+
+    /// Computes the number of distinct substrings of each length from 1 to `max_k`
+    /// in the text except the terminator, calling `f` with the length and the number
+    /// as soon as each length is done.
+    ///
+    /// The distinct substrings of length `k` are the nonempty ranges of rows
+    /// obtained by extending those of length `k - 1` to the left by each symbol other than the terminator,
+    /// so the ranges are traversed level by level,
+    /// holding at most `n` ranges of a single length at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_k` - The maximum length of the substrings.
+    /// * `f` - The callback receiving the length and the number of distinct substrings.
+    pub fn for_each_distinct_kmer_count<F: FnMut(usize, usize)>(&self, max_k: usize, mut f: F) {
+        let symbols = self.body_symbols();
+        let mut ranges = vec![(0, self.len())];
+        for k in 1..=max_k {
+            let mut next = vec![];
+            for &(lo, hi) in &ranges {
+                for &c in &symbols {
+                    let (lo, hi) = self.backward_step(lo, hi, c);
+                    if lo < hi {
+                        next.push((lo, hi));
+                    }
+                }
+            }
+            ranges = next;
+            f(k, ranges.len());
+        }
+    }
+
+    /// Returns the range of rows `lo..hi` whose suffixes start with `pattern`,
+    /// by repeating [`FmIndex::backward_step`] from the range of all the rows.
+    ///
+    /// The range is empty if `pattern` has the terminator other than at its end,
+    /// since no suffix continues after the terminator.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    pub fn backward_search(&self, pattern: &[u8]) -> (usize, usize) {
+        if let Some((_, init)) = pattern.split_last() {
+            if init.contains(&self.terminator()) {
+                return (0, 0);
+            }
+        }
+        let mut range = (0, self.bwt.len());
+        for &c in pattern.iter().rev() {
+            range = self.backward_step(range.0, range.1, c);
+            if range.0 == range.1 {
+                break;
+            }
+        }
+        range
+    }
+
+    /// Extends the range of rows `lo..hi` by one symbol to the left,
+    /// i.e., returns the range of rows whose suffixes are `c` followed by the suffixes at rows `lo..hi`.
+    ///
+    /// The range of all the rows is `0..n`, where `n` is the length of the text.
+    /// The returned range is empty if no such suffix exists.
+    /// The rows are those of the rotations of the text, so prepending the terminator
+    /// to a non-empty suffix gives the rotation wrapping around the end,
+    /// which [`FmIndex::backward_search`] excludes.
+    /// A range exceeding `0..n` is clamped to it, and an empty range results in an empty range.
+    ///
+    /// # Arguments
+    ///
+    /// * `lo` - The first row of the range.
+    /// * `hi` - The row following the last row of the range.
+    /// * `c` - The symbol to prepend.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, FmIndex};
+    ///
+    /// let mut bwt = vec![];
+    /// BwtBuilder::new(b"abracadabra$")?.build(&mut bwt)?;
+    /// let index = FmIndex::new(bwt)?;
+    ///
+    /// // Rows of the suffixes starting with "a".
+    /// let (lo, hi) = index.backward_step(0, index.len(), b'a');
+    /// assert_eq!(hi - lo, 5);
+    /// // Rows of the suffixes starting with "ra".
+    /// let (lo, hi) = index.backward_step(lo, hi, b'r');
+    /// assert_eq!(hi - lo, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn backward_step(&self, lo: usize, hi: usize, c: u8) -> (usize, usize) {
+        self.occ.backward_step(&self.bwt, lo, hi, c)
+    }
+}
+
+/// Bidirectional FM-index over the BWTs of a text and its reverse,
+/// supporting extension of a pattern to both the left and the right
+/// and enumeration of maximal exact matches with a query.
+///
+/// The reverse is the text except the terminator in reverse order, followed by the terminator,
+/// whose BWT is written by [`BwtBuilder::reverse_writer`](crate::BwtBuilder::reverse_writer).
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BidirectionalFmIndex, BwtBuilder, FmIndex};
+///
+/// let mut bwt = vec![];
+/// let mut rev_bwt = vec![];
+/// BwtBuilder::new(b"abracadabra$")?
+///     .reverse_writer(&mut rev_bwt)
+///     .build(&mut bwt)?;
+/// let index = BidirectionalFmIndex::new(FmIndex::new(bwt)?, rev_bwt)?;
+///
+/// // Extends "c" to "ca" and then to "aca".
+/// let range = index.extend_left(index.extend_right(index.search(b"c"), b'a'), b'a');
+/// assert_eq!(range.size, 1);
+/// assert_eq!(range, index.search(b"aca"));
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BidirectionalFmIndex {
+    fwd: FmIndex,
+    rev: FmIndex,
+}
+
+/// Range of rows of a pattern in a [`BidirectionalFmIndex`],
+/// synchronized between the BWT of the text and that of its reverse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BiRange {
+    /// The first row of the suffixes starting with the pattern in the text.
+    pub fwd: usize,
+    /// The first row of the suffixes starting with the reversed pattern in the reverse.
+    pub rev: usize,
+    /// The number of rows, i.e., the number of occurrences of the pattern.
+    pub size: usize,
+}
+
+/// Maximal exact match between a query and the text,
+/// reported by [`BidirectionalFmIndex::for_each_mem`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mem {
+    /// The starting position of the match in the query.
+    pub start: usize,
+    /// The length of the match.
+    pub len: usize,
+    /// The range of rows of the match, giving its occurrences in the text.
+    pub range: BiRange,
+}
+
+impl BidirectionalFmIndex {
+    /// Builds the index from the FM-index of a text and the BWT of its reverse.
+    ///
+    /// # Arguments
+    ///
+    /// * `fwd` - The FM-index of the text, which may have suffix array samples to locate matches.
+    /// * `rev_bwt` - The Burrows-Wheeler transform of the reverse.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if
+    ///
+    /// - `rev_bwt` is empty or does not have exactly one terminator, or
+    /// - `rev_bwt` differs from the BWT of `fwd` in length or terminator.
+    pub fn new(fwd: FmIndex, rev_bwt: Vec<u8>) -> Result<Self> {
+        let rev = FmIndex::new(rev_bwt)?;
+        if rev.len() != fwd.len() {
+            return Err(Error::invalid_argument(format!(
+                "rev_bwt must have the same length as the BWT {}, but got {}.",
+                fwd.len(),
+                rev.len()
+            )));
+        }
+        if rev.bwt[rev.primary] != fwd.bwt[fwd.primary] {
+            return Err(Error::invalid_argument(
+                "rev_bwt must have the same terminator as the BWT.",
+            ));
+        }
+        Ok(Self { fwd, rev })
+    }
+
+    /// Returns the length of the text, including the terminator.
+    pub fn len(&self) -> usize {
+        self.fwd.len()
+    }
+
+    /// Returns `true` if the text is empty, which never happens.
+    pub fn is_empty(&self) -> bool {
+        self.fwd.is_empty()
+    }
+
+    /// Returns the FM-index of the text, such as to locate the occurrences of a match.
+    pub const fn forward(&self) -> &FmIndex {
+        &self.fwd
+    }
+
+    /// Returns the range of all the rows, i.e., that of the empty pattern.
+    pub fn full_range(&self) -> BiRange {
+        BiRange {
+            fwd: 0,
+            rev: 0,
+            size: self.len(),
+        }
+    }
+
+    /// Returns the range of `pattern`, extending the empty pattern to the right.
+    ///
+    /// The range is empty if `pattern` has the terminator other than at its end.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The pattern to search for.
+    pub fn search(&self, pattern: &[u8]) -> BiRange {
+        if let Some((_, init)) = pattern.split_last() {
+            if init.contains(&self.fwd.terminator()) {
+                return BiRange {
+                    size: 0,
+                    ..self.full_range()
+                };
+            }
+        }
+        let mut range = self.full_range();
+        for &c in pattern {
+            range = self.extend_right(range, c);
+            if range.size == 0 {
+                break;
+            }
+        }
+        range
+    }
+
+    /// Extends the pattern of `range` by `c` to the left.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The range of the pattern.
+    /// * `c` - The symbol to prepend.
+    pub fn extend_left(&self, range: BiRange, c: u8) -> BiRange {
+        let (fwd, rev) = extend(&self.fwd, range.fwd, range.rev, range.size, c);
+        BiRange {
+            fwd: fwd.0,
+            rev,
+            size: fwd.1,
+        }
+    }
+
+    /// Extends the pattern of `range` by `c` to the right.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The range of the pattern.
+    /// * `c` - The symbol to append.
+    pub fn extend_right(&self, range: BiRange, c: u8) -> BiRange {
+        let (rev, fwd) = extend(&self.rev, range.rev, range.fwd, range.size, c);
+        BiRange {
+            fwd,
+            rev: rev.0,
+            size: rev.1,
+        }
+    }
+
+    /// Enumerates the maximal exact matches (MEMs) between `query` and the text
+    /// of length at least `min_len` in increasing order of their starting positions,
+    /// calling `f` for each of them.
+    ///
+    /// A MEM is a substring of `query` occurring in the text
+    /// that cannot be extended to the left or the right with an occurrence in the text.
+    /// From each position of `query`, the longest match is found by extending it to the right,
+    /// and it is a MEM unless the match from the previous position is longer.
+    /// It takes `O(mL)` rank queries, where `m` is the length of `query`
+    /// and `L` is the length of the longest match.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query sequence.
+    /// * `min_len` - The minimum length of the MEMs to report.
+    /// * `f` - The callback receiving each MEM.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BidirectionalFmIndex, BwtBuilder, FmIndex};
+    ///
+    /// let mut bwt = vec![];
+    /// let mut rev_bwt = vec![];
+    /// BwtBuilder::new(b"abracadabra$")?
+    ///     .reverse_writer(&mut rev_bwt)
+    ///     .build(&mut bwt)?;
+    /// let index = BidirectionalFmIndex::new(FmIndex::with_sample_rate(bwt, 4)?, rev_bwt)?;
+    ///
+    /// let query = b"cabrx";
+    /// let mut mems = vec![];
+    /// index.for_each_mem(query, 2, |mem| mems.push(mem));
+    /// let found: Vec<_> = mems.iter().map(|mem| &query[mem.start..mem.start + mem.len]).collect();
+    /// assert_eq!(found, vec![&b"ca"[..], b"abr"]);
+    /// assert_eq!(index.forward().locate(found[1])?, vec![0, 7]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn for_each_mem<F: FnMut(Mem)>(&self, query: &[u8], min_len: usize, mut f: F) {
+        let mut prev_len = 0;
+        for start in 0..query.len() {
+            let mut range = self.full_range();
+            let mut len = 0;
+            // No match continues after the terminator.
+            while start + len < query.len()
+                && (len == 0 || query[start + len - 1] != self.fwd.terminator())
+            {
+                let next = self.extend_right(range, query[start + len]);
+                if next.size == 0 {
+                    break;
+                }
+                range = next;
+                len += 1;
+            }
+            if len != 0 && len >= min_len && prev_len <= len {
+                f(Mem { start, len, range });
+            }
+            prev_len = len;
+        }
+    }
+}
+
+/// Extends a pattern to the left in `index` by `c`,
+/// where `lo` and `other` are the first rows of the pattern in `index` and the other direction,
+/// and `size` is the number of rows.
+/// Returns the first row and the number of rows in `index`, and the first row in the other direction.
+fn extend(index: &FmIndex, lo: usize, other: usize, size: usize, c: u8) -> ((usize, usize), usize) {
+    let hi = lo + size;
+    let (new_lo, new_hi) = index.backward_step(lo, hi, c);
+    // The occurrences followed by smaller symbols precede in the other direction.
+    let smaller = index.occ.rank_less(&index.bwt, c, hi) - index.occ.rank_less(&index.bwt, c, lo);
+    ((new_lo, new_hi - new_lo), other + smaller)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BwtBuilder, IntWidth};
+
+    fn naive_count(text: &[u8], pattern: &[u8]) -> usize {
+        (0..=text.len() - pattern.len())
+            .filter(|&i| &text[i..i + pattern.len()] == pattern)
+            .count()
+    }
+
+    #[test]
+    fn test_fm_index_count() {
+        let text: Vec<u8> = (0..1000u32)
+            .map(|i| b"ab"[(i.count_ones() % 2) as usize])
+            .chain([b'$'])
+            .collect();
+        let mut bwt = vec![];
+        BwtBuilder::new(&text).unwrap().build(&mut bwt).unwrap();
+        let index = FmIndex::new(bwt).unwrap();
+        for len in 1..=8 {
+            for start in (0..text.len() - len).step_by(37) {
+                let pattern = &text[start..start + len];
+                assert_eq!(index.count(pattern), naive_count(&text, pattern));
+            }
+        }
+        assert_eq!(index.count(b""), text.len());
+        assert_eq!(index.count(b"aaa"), 0);
+        assert_eq!(index.count(b"c"), 0);
+        assert_eq!(index.count(&text), 1);
+    }
+
+    fn naive_locate(text: &[u8], pattern: &[u8]) -> Vec<usize> {
+        (0..=text.len() - pattern.len())
+            .filter(|&i| &text[i..i + pattern.len()] == pattern)
+            .collect()
+    }
+
+    #[test]
+    fn test_fm_index_locate() {
+        let text: Vec<u8> = (0..1000u32)
+            .map(|i| b"ab"[(i.count_ones() % 2) as usize])
+            .chain([b'$'])
+            .collect();
+        for sample_rate in [1, 3, 16] {
+            let mut bwt = vec![];
+            let mut samples = vec![];
+            BwtBuilder::new(&text)
+                .unwrap()
+                .sampled_suffix_array_writer(&mut samples, IntWidth::U32, sample_rate)
+                .unwrap()
+                .build(&mut bwt)
+                .unwrap();
+            let samples: Vec<usize> = samples
+                .chunks(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+                .collect();
+            let index =
+                FmIndex::with_sa_samples(bwt.clone(), samples.clone(), sample_rate).unwrap();
+            let walked = FmIndex::with_sample_rate(bwt, sample_rate).unwrap();
+            assert_eq!(walked.sa_samples, samples);
+            for len in 1..=8 {
+                for start in (0..text.len() - len).step_by(37) {
+                    let pattern = &text[start..start + len];
+                    assert_eq!(index.locate(pattern).unwrap(), naive_locate(&text, pattern));
+                }
+            }
+            assert_eq!(
+                index.locate(b"").unwrap(),
+                (0..text.len()).collect::<Vec<_>>()
+            );
+            assert_eq!(index.locate(b"aaa").unwrap(), Vec::<usize>::new());
+            assert_eq!(index.locate(&text).unwrap(), vec![0]);
+        }
+    }
+
+    #[test]
+    fn test_fm_index_terminator() {
+        let text = b"aaaa$";
+        let mut bwt = vec![];
+        let mut samples = vec![];
+        BwtBuilder::new(text)
+            .unwrap()
+            .sampled_suffix_array_writer(&mut samples, IntWidth::U32, 1)
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        let samples: Vec<usize> = samples
+            .chunks(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+            .collect();
+        let index = FmIndex::with_sa_samples(bwt, samples, 1).unwrap();
+        for pattern in [&b"$"[..], b"a$", b"aa$", b"$a", b"a$a", b"$$", b"aa$a"] {
+            assert_eq!(
+                index.count(pattern),
+                naive_count(text, pattern),
+                "{pattern:?}"
+            );
+            assert_eq!(
+                index.locate(pattern).unwrap(),
+                naive_locate(text, pattern),
+                "{pattern:?}"
+            );
+        }
+        assert_eq!(index.locate(b"a$").unwrap(), vec![3]);
+
+        // The rotation "$" followed by the text is not an occurrence.
+        let index = FmIndex::new(b"$".to_vec()).unwrap();
+        assert_eq!(index.count(b"$"), 1);
+        assert_eq!(index.count(b"$$"), 0);
+        assert_eq!(index.count(b"$$$"), 0);
+        assert_eq!(index.count_approximate(b"$$", 1), 0);
+    }
+
+    #[test]
+    fn test_fm_index_matching_statistics() {
+        let text: Vec<u8> = (0..500u32)
+            .map(|i| b"abc"[(i * i % 11 % 3) as usize])
+            .chain([b'$'])
+            .collect();
+        let mut bwt = vec![];
+        BwtBuilder::new(&text).unwrap().build(&mut bwt).unwrap();
+        let index = FmIndex::new(bwt).unwrap();
+        let queries: &[&[u8]] = &[
+            b"",
+            b"d",
+            &text[100..180],
+            b"abcabcabcdabcaaaaaccccbbbbacbacbcabca",
+            b"cccccccccccccccccccccccccccdcccccc",
+            b"ab$abc$$cab$",
+        ];
+        for &query in queries {
+            let stats = index.matching_statistics(query);
+            assert_eq!(stats.len(), query.len());
+            for (i, stat) in stats.iter().enumerate() {
+                let len = (0..=query.len() - i)
+                    .rev()
+                    .find(|&l| naive_count(&text, &query[i..i + l]) != 0)
+                    .unwrap();
+                assert_eq!(stat.len, len, "i = {i}");
+                assert_eq!(stat.rows, index.backward_search(&query[i..i + len]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_fm_index_approximate_search() {
+        let text: Vec<u8> = (0..300u32)
+            .map(|i| b"acgt"[(i * i % 13 % 4) as usize])
+            .chain([b'$'])
+            .collect();
+        let mut bwt = vec![];
+        BwtBuilder::new(&text).unwrap().build(&mut bwt).unwrap();
+        let index = FmIndex::with_sample_rate(bwt, 5).unwrap();
+        let patterns = [
+            &b"acgtac"[..],
+            b"ttttt",
+            &text[40..52],
+            b"g",
+            b"",
+            b"gt$",
+            b"a$a",
+        ];
+        for pattern in patterns {
+            for k in 0..=2 {
+                // The terminator can be matched only at the end of the text.
+                let expected: Vec<usize> = (0..text.len())
+                    .filter(|&i| i + pattern.len() <= text.len())
+                    .filter(|&i| {
+                        let window = &text[i..i + pattern.len()];
+                        window.iter().zip(pattern).filter(|(a, b)| a != b).count() <= k
+                    })
+                    .collect();
+                let matches = index.approximate_search(pattern, k);
+                assert!(matches.windows(2).all(|w| w[0].rows.1 <= w[1].rows.0));
+                assert!(matches.iter().all(|m| m.n_mismatches <= k));
+                assert_eq!(index.count_approximate(pattern, k), expected.len());
+                assert_eq!(index.locate_approximate(pattern, k).unwrap(), expected);
+            }
+        }
+        assert!(FmIndex::new(b"ard$rcaaaabb".to_vec())
+            .unwrap()
+            .locate_approximate(b"a", 1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_fm_index_distinct_kmers() {
+        let text: Vec<u8> = (0..300u32)
+            .map(|i| b"acgt"[(i * i % 13 % 4) as usize])
+            .chain([b'$'])
+            .collect();
+        let mut bwt = vec![];
+        BwtBuilder::new(&text).unwrap().build(&mut bwt).unwrap();
+        let index = FmIndex::new(bwt).unwrap();
+        let body = &text[..text.len() - 1];
+        let mut counts = vec![];
+        index.for_each_distinct_kmer_count(20, |k, count| counts.push((k, count)));
+        let expected: Vec<(usize, usize)> = (1..=20)
+            .map(|k| {
+                let kmers: std::collections::HashSet<_> = body.windows(k).collect();
+                (k, kmers.len())
+            })
+            .collect();
+        assert_eq!(counts, expected);
+        assert_eq!(index.count_distinct_kmers(7), expected[6].1);
+        assert_eq!(index.count_distinct_kmers(0), 1);
+        assert_eq!(index.count_distinct_kmers(body.len()), 1);
+        assert_eq!(index.count_distinct_kmers(text.len()), 0);
+    }
+
+    #[test]
+    fn test_bidirectional_fm_index() {
+        let text: Vec<u8> = (0..500u32)
+            .map(|i| b"abc"[(i * i % 11 % 3) as usize])
+            .chain([b'$'])
+            .collect();
+        let mut bwt = vec![];
+        let mut rev_bwt = vec![];
+        BwtBuilder::new(&text)
+            .unwrap()
+            .reverse_writer(&mut rev_bwt)
+            .build(&mut bwt)
+            .unwrap();
+        let index = BidirectionalFmIndex::new(FmIndex::new(bwt).unwrap(), rev_bwt).unwrap();
+        for len in 1..=8 {
+            for start in (0..text.len() - 1 - len).step_by(29) {
+                let pattern = &text[start..start + len];
+                let range = index.search(pattern);
+                assert_eq!(range.size, naive_count(&text, pattern));
+                let fwd = index.forward().backward_search(pattern);
+                assert_eq!((range.fwd, range.fwd + range.size), fwd);
+                let reversed: Vec<u8> = pattern.iter().rev().copied().collect();
+                let rev = index.rev.backward_search(&reversed);
+                assert_eq!((range.rev, range.rev + range.size), rev);
+                let left = pattern
+                    .iter()
+                    .rev()
+                    .fold(index.full_range(), |r, &c| index.extend_left(r, c));
+                assert_eq!(left, range);
+            }
+        }
+        assert_eq!(index.search(b"abd").size, 0);
+        assert_eq!(index.search(&text[text.len() - 4..]).size, 1);
+        assert_eq!(index.search(b"$a").size, 0);
+        assert_eq!(index.search(b"a$$").size, 0);
+    }
+
+    #[test]
+    fn test_bidirectional_fm_index_mems() {
+        let text: Vec<u8> = (0..500u32)
+            .map(|i| b"abc"[(i * i % 11 % 3) as usize])
+            .chain([b'$'])
+            .collect();
+        let mut bwt = vec![];
+        let mut rev_bwt = vec![];
+        BwtBuilder::new(&text)
+            .unwrap()
+            .reverse_writer(&mut rev_bwt)
+            .build(&mut bwt)
+            .unwrap();
+        let index = BidirectionalFmIndex::new(FmIndex::new(bwt).unwrap(), rev_bwt).unwrap();
+        let queries: &[&[u8]] = &[
+            b"",
+            b"d",
+            &text[100..180],
+            b"abcabcabcdabcaaaaaccccbbbbacbacbcabca",
+            b"ab$abc$$cab$",
+        ];
+        for &query in queries {
+            let lens: Vec<usize> = (0..query.len())
+                .map(|i| {
+                    (0..=query.len() - i)
+                        .rev()
+                        .find(|&l| naive_count(&text, &query[i..i + l]) != 0)
+                        .unwrap()
+                })
+                .collect();
+            for min_len in [0, 1, 3, 10] {
+                let mut mems = vec![];
+                index.for_each_mem(query, min_len, |mem| mems.push(mem));
+                let expected: Vec<(usize, usize)> = (0..query.len())
+                    .filter(|&i| lens[i] != 0 && lens[i] >= min_len)
+                    .filter(|&i| i == 0 || lens[i - 1] <= lens[i])
+                    .map(|i| (i, lens[i]))
+                    .collect();
+                let found: Vec<(usize, usize)> = mems.iter().map(|m| (m.start, m.len)).collect();
+                assert_eq!(found, expected);
+                for mem in mems {
+                    let pattern = &query[mem.start..mem.start + mem.len];
+                    assert_eq!(mem.range, index.search(pattern));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bidirectional_fm_index_invalid() {
+        let fwd = || FmIndex::new(b"ard$rcaaaabb".to_vec()).unwrap();
+        assert!(BidirectionalFmIndex::new(fwd(), vec![]).is_err());
+        assert!(BidirectionalFmIndex::new(fwd(), b"a$b".to_vec()).is_err());
+        assert!(BidirectionalFmIndex::new(fwd(), b"ard#rcaaaabb".to_vec()).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_fm_index_serde() {
+        let index = FmIndex::with_sample_rate(b"ard$rcaaaabb".to_vec(), 2).unwrap();
+        let json = serde_json::to_string(&index).unwrap();
+        let index: FmIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(index.count(b"abra"), 2);
+        assert_eq!(index.locate(b"abra").unwrap(), vec![0, 7]);
+    }
+
+    #[test]
+    fn test_fm_index_backward_step() {
+        let index = FmIndex::new(b"ard$rcaaaabb".to_vec()).unwrap();
+        assert_eq!(index.backward_step(0, 12, b'a'), (1, 6));
+        assert_eq!(index.backward_step(1, 6, b'r'), (10, 12));
+        assert_eq!(index.backward_step(10, 12, b'b'), (6, 8));
+        assert_eq!(index.backward_step(1, 6, b'a'), (2, 2));
+        assert_eq!(index.backward_step(3, 3, b'a'), (2, 2));
+        assert_eq!(index.backward_step(0, usize::MAX, b'a'), (1, 6));
+        assert_eq!(index.backward_step(13, 20, b'a'), (6, 6));
+        assert_eq!(index.backward_step(8, 4, b'a'), (2, 2));
+        assert_eq!(index.backward_search(b"bra"), (6, 8));
+        assert_eq!(index.backward_search(b""), (0, 12));
+    }
+
+    #[test]
+    fn test_fm_index_invalid() {
+        assert!(FmIndex::new(vec![]).is_err());
+        assert!(FmIndex::new(b"ard$rcaaa$bb".to_vec()).is_err());
+        assert!(FmIndex::new(b"ard$rcaaaabb".to_vec())
+            .unwrap()
+            .locate(b"a")
+            .is_err());
+        assert!(FmIndex::with_sa_samples(b"ard$rcaaaabb".to_vec(), vec![11, 3, 4], 0).is_err());
+        assert!(FmIndex::with_sa_samples(b"ard$rcaaaabb".to_vec(), vec![11, 3], 4).is_err());
+        assert!(FmIndex::with_sample_rate(b"bb$a".to_vec(), 1).is_err());
+        // The LF mapping cycles over rows 1 and 3, reaching neither a sample nor the primary row.
+        let index = FmIndex::with_sa_samples(b"bb$a".to_vec(), vec![3, 1], 2).unwrap();
+        assert!(matches!(index.locate(b"a"), Err(Error::InvalidBwt(_))));
+        assert!(matches!(
+            index.locate_approximate(b"b", 1),
+            Err(Error::InvalidBwt(_))
+        ));
+    }
+}
@@ -0,0 +1,84 @@
+/// Integer type to store suffix positions in the chunks,
+/// so that texts shorter than 4 GiB can be processed with 4 bytes per position.
+pub trait SuffixIndex: Copy + Default + Ord + Send + Sync {
+    /// The largest representable position.
+    const MAX_POS: usize;
+
+    /// Converts a position, which must be no greater than [`SuffixIndex::MAX_POS`].
+    fn from_usize(x: usize) -> Self;
+
+    /// Converts to a position.
+    fn to_usize(self) -> usize;
+}
+
+impl SuffixIndex for u32 {
+    const MAX_POS: usize = Self::MAX as usize;
+
+    #[inline(always)]
+    fn from_usize(x: usize) -> Self {
+        debug_assert!(x <= Self::MAX_POS);
+        x as Self
+    }
+
+    #[inline(always)]
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+impl SuffixIndex for usize {
+    const MAX_POS: Self = Self::MAX;
+
+    #[inline(always)]
+    fn from_usize(x: usize) -> Self {
+        x
+    }
+
+    #[inline(always)]
+    fn to_usize(self) -> usize {
+        self
+    }
+}
+
+/// 40-bit integer packed in 5 bytes, for texts between 4 GiB and 1 TiB.
+///
+/// The bytes are stored in big-endian order so that the derived ordering is numeric.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct U40([u8; 5]);
+
+impl SuffixIndex for U40 {
+    // On 32-bit targets, the truncation results in usize::MAX.
+    const MAX_POS: usize = ((1u64 << 40) - 1) as usize;
+
+    #[inline(always)]
+    fn from_usize(x: usize) -> Self {
+        debug_assert!(x <= Self::MAX_POS);
+        let bytes = (x as u64).to_be_bytes();
+        Self([bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]])
+    }
+
+    #[inline(always)]
+    fn to_usize(self) -> usize {
+        let [b0, b1, b2, b3, b4] = self.0;
+        u64::from_be_bytes([0, 0, 0, b0, b1, b2, b3, b4]) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_u40() {
+        assert_eq!(std::mem::size_of::<U40>(), 5);
+        let xs = [0, 1, 255, 256, 1 << 32, (1 << 40) - 1];
+        for &x in &xs {
+            assert_eq!(U40::from_usize(x).to_usize(), x);
+        }
+        for &x in &xs {
+            for &y in &xs {
+                assert_eq!(U40::from_usize(x).cmp(&U40::from_usize(y)), x.cmp(&y));
+            }
+        }
+    }
+}
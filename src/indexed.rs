@@ -0,0 +1,213 @@
+use crate::error::{Error, Result};
+
+use crate::rank::OccTable;
+
+/// BWT with rank support and inverse suffix array samples,
+/// supporting extraction of arbitrary substrings of the text without decoding the whole text.
+///
+/// The row of every `sample_rate`-th suffix is sampled,
+/// so a substring of length `len` is extracted with `len + sample_rate` steps of the LF mapping at most.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BwtBuilder, IndexedBwt};
+///
+/// let mut bwt = vec![];
+/// BwtBuilder::new(b"abracadabra$")?.build(&mut bwt)?;
+///
+/// let index = IndexedBwt::new(bwt, 4)?;
+/// assert_eq!(index.len(), 12);
+/// assert_eq!(index.extract(4, 3)?, b"cad");
+/// assert_eq!(index.extract(7, 5)?, b"abra$");
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexedBwt {
+    bwt: Vec<u8>,
+    occ: OccTable,
+    sample_rate: usize,
+    // isa_samples[k] is the row of the suffix at position k * sample_rate.
+    isa_samples: Vec<usize>,
+}
+
+impl IndexedBwt {
+    /// Builds the index from a BWT.
+    ///
+    /// It walks the LF mapping over the whole BWT once, taking `O(n)` rank queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `bwt` - The Burrows-Wheeler transform of a text with a unique terminator.
+    /// * `sample_rate` - The sampling rate of the inverse suffix array.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if
+    ///
+    /// - `bwt` is empty or does not have exactly one terminator, or
+    /// - `sample_rate` is zero.
+    pub fn new(bwt: Vec<u8>, sample_rate: usize) -> Result<Self> {
+        if sample_rate == 0 {
+            return Err(Error::invalid_argument("sample_rate must be positive."));
+        }
+        let primary = primary_index(&bwt)?;
+        let occ = OccTable::new(&bwt);
+
+        // Walks the LF mapping from row 0, i.e., the suffix at position n - 1.
+        let n = bwt.len();
+        let mut isa_samples = vec![0; (n - 1) / sample_rate + 1];
+        let mut r = 0;
+        for pos in (0..n).rev() {
+            if pos % sample_rate == 0 {
+                isa_samples[pos / sample_rate] = r;
+            }
+            if (r == primary) != (pos == 0) {
+                return Err(Error::invalid_bwt(
+                    "bwt is not a valid BWT, since the LF mapping has multiple cycles.",
+                ));
+            }
+            if pos != 0 {
+                r = occ.lf(&bwt, r);
+            }
+        }
+        Ok(Self {
+            bwt,
+            occ,
+            sample_rate,
+            isa_samples,
+        })
+    }
+
+    /// Returns the length of the text, including the terminator.
+    pub fn len(&self) -> usize {
+        self.bwt.len()
+    }
+
+    /// Returns `true` if the text is empty, which never happens.
+    pub fn is_empty(&self) -> bool {
+        self.bwt.is_empty()
+    }
+
+    /// Returns the BWT.
+    pub fn bwt(&self) -> &[u8] {
+        &self.bwt
+    }
+
+    /// Extends the range of rows `lo..hi` by one symbol to the left,
+    /// as [`FmIndex::backward_step`](crate::FmIndex::backward_step).
+    ///
+    /// # Arguments
+    ///
+    /// * `lo` - The first row of the range.
+    /// * `hi` - The row following the last row of the range.
+    /// * `c` - The symbol to prepend.
+    pub fn backward_step(&self, lo: usize, hi: usize, c: u8) -> (usize, usize) {
+        self.occ.backward_step(&self.bwt, lo, hi, c)
+    }
+
+    /// Extracts the substring of the text of length `len` starting at `start`.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The starting position of the substring.
+    /// * `len` - The length of the substring.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the substring exceeds the text.
+    pub fn extract(&self, start: usize, len: usize) -> Result<Vec<u8>> {
+        let end = start
+            .checked_add(len)
+            .filter(|&end| end <= self.len())
+            .ok_or_else(|| {
+                Error::invalid_argument(format!(
+                    "substring [{start}, {start}+{len}) must be within the text of length {}.",
+                    self.len()
+                ))
+            })?;
+        if len == 0 {
+            return Ok(vec![]);
+        }
+
+        // Walks the LF mapping backward from the first sampled suffix not before the last symbol.
+        let k = (end - 1) / self.sample_rate;
+        let (mut pos, mut r) = if k * self.sample_rate == end - 1 {
+            (end - 1, self.isa_samples[k])
+        } else if k + 1 < self.isa_samples.len() {
+            ((k + 1) * self.sample_rate, self.isa_samples[k + 1])
+        } else {
+            // The suffix at position n - 1 is always at row 0.
+            (self.len() - 1, 0)
+        };
+        let mut substr = vec![0; len];
+        loop {
+            if pos < end {
+                substr[pos - start] = self.occ.first_symbol(r);
+            }
+            if pos == start {
+                break;
+            }
+            r = self.occ.lf(&self.bwt, r);
+            pos -= 1;
+        }
+        Ok(substr)
+    }
+}
+
+/// Returns the position of the unique terminator in the BWT.
+pub fn primary_index(bwt: &[u8]) -> Result<usize> {
+    let terminator = match bwt.iter().min() {
+        Some(&terminator) => terminator,
+        None => return Err(Error::invalid_bwt("bwt must not be empty.")),
+    };
+    let primary = bwt.iter().position(|&c| c == terminator).unwrap();
+    if bwt[primary + 1..].contains(&terminator) {
+        return Err(Error::invalid_bwt(format!(
+            "bwt must have exactly one terminator character, but found {terminator:x} more than once."
+        )));
+    }
+    Ok(primary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BwtBuilder;
+
+    #[test]
+    fn test_indexed_bwt_extract() {
+        let text = b"abracadabra$";
+        let mut bwt = vec![];
+        BwtBuilder::new(text).unwrap().build(&mut bwt).unwrap();
+        for sample_rate in 1..=text.len() + 1 {
+            let index = IndexedBwt::new(bwt.clone(), sample_rate).unwrap();
+            for start in 0..=text.len() {
+                for end in start..=text.len() {
+                    let substr = index.extract(start, end - start).unwrap();
+                    assert_eq!(substr, &text[start..end], "sample_rate={sample_rate}");
+                }
+            }
+            assert!(index.extract(0, text.len() + 1).is_err());
+            assert!(index.extract(text.len(), 1).is_err());
+            assert!(index.extract(1, usize::MAX).is_err());
+        }
+    }
+
+    #[test]
+    fn test_indexed_bwt_backward_step() {
+        let index = IndexedBwt::new(b"ard$rcaaaabb".to_vec(), 4).unwrap();
+        assert_eq!(index.backward_step(0, 12, b'a'), (1, 6));
+        assert_eq!(index.backward_step(1, 6, b'b'), (6, 6));
+    }
+
+    #[test]
+    fn test_indexed_bwt_invalid() {
+        assert!(IndexedBwt::new(vec![], 1).is_err());
+        assert!(IndexedBwt::new(b"ard$rcaaaabb".to_vec(), 0).is_err());
+        assert!(IndexedBwt::new(b"ard$rcaaa$bb".to_vec(), 1).is_err());
+        assert!(IndexedBwt::new(b"bb$a".to_vec(), 1).is_err());
+    }
+}
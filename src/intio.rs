@@ -0,0 +1,218 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::error::{Error, Result};
+
+/// Byte width of integers written to auxiliary outputs.
+///
+/// Integers are written in little-endian order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntWidth {
+    /// 4 bytes per integer.
+    U32,
+    /// 8 bytes per integer.
+    U64,
+}
+
+impl IntWidth {
+    /// Returns the number of bytes per integer.
+    pub const fn bytes(self) -> usize {
+        match self {
+            Self::U32 => 4,
+            Self::U64 => 8,
+        }
+    }
+
+    /// Encodes `x` into little-endian bytes, whose first [`Self::bytes()`] bytes are valid.
+    fn encode(self, x: usize) -> Result<[u8; 8]> {
+        let mut buf = [0; 8];
+        match self {
+            Self::U32 => {
+                let x = u32::try_from(x).map_err(|_| {
+                    Error::invalid_argument(format!("integer {x} does not fit in 32 bits."))
+                })?;
+                buf[..4].copy_from_slice(&x.to_le_bytes());
+            }
+            Self::U64 => {
+                buf.copy_from_slice(&(x as u64).to_le_bytes());
+            }
+        }
+        Ok(buf)
+    }
+}
+
+/// Writer of fixed-width integers.
+pub struct IntWriter<'a> {
+    wrt: Box<dyn Write + 'a>,
+    width: IntWidth,
+}
+
+impl<'a> IntWriter<'a> {
+    pub fn new<W: Write + 'a>(wrt: W, width: IntWidth) -> Self {
+        Self {
+            wrt: Box::new(wrt),
+            width,
+        }
+    }
+
+    pub const fn width(&self) -> IntWidth {
+        self.width
+    }
+
+    pub fn write(&mut self, x: usize) -> Result<()> {
+        let buf = self.width.encode(x)?;
+        self.wrt.write_all(&buf[..self.width.bytes()])?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.wrt.flush()?;
+        Ok(())
+    }
+}
+
+/// Writer supporting random access.
+pub trait WriteSeek: Write + Seek {}
+
+impl<T: Write + Seek> WriteSeek for T {}
+
+/// Writer of fixed-width integers at arbitrary positions of an array.
+pub struct IntArrayWriter<'a> {
+    wrt: Box<dyn WriteSeek + 'a>,
+    width: IntWidth,
+    // The current position of the writer in bytes, if known.
+    pos: Option<u64>,
+}
+
+impl<'a> IntArrayWriter<'a> {
+    pub fn new<W: Write + Seek + 'a>(wrt: W, width: IntWidth) -> Self {
+        Self {
+            wrt: Box::new(wrt),
+            width,
+            pos: None,
+        }
+    }
+
+    pub const fn width(&self) -> IntWidth {
+        self.width
+    }
+
+    /// Writes `x` as the `i`-th integer of the array.
+    pub fn write_at(&mut self, i: usize, x: usize) -> Result<()> {
+        let buf = self.width.encode(x)?;
+        let bytes = self.width.bytes();
+        let target = (i * bytes) as u64;
+        if self.pos != Some(target) {
+            self.wrt.seek(SeekFrom::Start(target))?;
+        }
+        self.wrt.write_all(&buf[..bytes])?;
+        self.pos = Some(target + bytes as u64);
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.pos = None;
+        self.wrt.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes `x` in the unsigned LEB128 encoding.
+pub fn write_varint<W: Write>(wrt: &mut W, mut x: usize) -> std::io::Result<()> {
+    let mut buf = [0; 10];
+    let mut i = 0;
+    loop {
+        let b = (x & 0x7f) as u8;
+        x >>= 7;
+        if x == 0 {
+            buf[i] = b;
+            i += 1;
+            break;
+        }
+        buf[i] = b | 0x80;
+        i += 1;
+    }
+    wrt.write_all(&buf[..i])
+}
+
+/// Reads an integer in the unsigned LEB128 encoding.
+pub fn read_varint<R: Read>(rdr: &mut R) -> Result<usize> {
+    let mut buf = [0; 1];
+    let mut x = 0usize;
+    let mut shift = 0;
+    loop {
+        if rdr.read(&mut buf)? == 0 {
+            return Err(Error::invalid_format(
+                "unexpected end of the stream in an integer.",
+            ));
+        }
+        if shift >= usize::BITS {
+            return Err(Error::invalid_format("integer overflows."));
+        }
+        x |= ((buf[0] & 0x7f) as usize) << shift;
+        shift += 7;
+        if buf[0] & 0x80 == 0 {
+            return Ok(x);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int_writer_u32() {
+        let mut buf = vec![];
+        {
+            let mut wrt = IntWriter::new(&mut buf, IntWidth::U32);
+            wrt.write(1).unwrap();
+            wrt.write(0x01020304).unwrap();
+        }
+        assert_eq!(buf, vec![1, 0, 0, 0, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_int_writer_u32_overflow() {
+        let mut buf = vec![];
+        let mut wrt = IntWriter::new(&mut buf, IntWidth::U32);
+        assert!(wrt.write(1 << 32).is_err());
+    }
+
+    #[test]
+    fn test_int_array_writer() {
+        let mut buf = std::io::Cursor::new(vec![]);
+        {
+            let mut wrt = IntArrayWriter::new(&mut buf, IntWidth::U32);
+            wrt.write_at(2, 7).unwrap();
+            wrt.write_at(0, 5).unwrap();
+            wrt.write_at(1, 6).unwrap();
+        }
+        assert_eq!(buf.into_inner(), vec![5, 0, 0, 0, 6, 0, 0, 0, 7, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_int_writer_u64() {
+        let mut buf = vec![];
+        {
+            let mut wrt = IntWriter::new(&mut buf, IntWidth::U64);
+            wrt.write(1 << 32).unwrap();
+        }
+        assert_eq!(buf, vec![0, 0, 0, 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut buf = vec![];
+        for x in [0, 1, 127, 128, 300, usize::MAX] {
+            write_varint(&mut buf, x).unwrap();
+        }
+        assert_eq!(buf[..6], [0, 1, 127, 0x80, 0x01, 0xac]);
+        let mut rdr = buf.as_slice();
+        for x in [0, 1, 127, 128, 300, usize::MAX] {
+            assert_eq!(read_varint(&mut rdr).unwrap(), x);
+        }
+        assert!(read_varint(&mut rdr).is_err());
+        assert!(read_varint(&mut &[0x80][..]).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use crate::error::{Error, Result};
+
+use crate::lf::{count_symbols, psi as psi_values};
+
+/// Computes the context-bounded BWT of a text, often called the k-BWT or the Schindler transform,
+/// together with its primary index, i.e., the row of the text itself.
+///
+/// The rotations of the text are sorted only by their first `k` symbols,
+/// where the rotations with the same first `k` symbols are sorted by their starting positions.
+/// It is sorted by `k` passes of the counting sort from the `k`-th symbol,
+/// in `O(kn)` time and `16n` bytes of space, where `n` is the length of the text,
+/// which is faster than the full BWT for a small `k`, at the cost of compressibility.
+/// As in [`rotation_bwt`](crate::rotation_bwt), the text needs no terminator,
+/// and the order larger than `n` is the same as `n`, giving the BWT of the sorted rotations.
+/// The transform is inverted by [`decode_context_bwt`].
+///
+/// # Arguments
+///
+/// * `text` - The text.
+/// * `k` - The number of the symbols by which the rotations are sorted.
+///
+/// # Errors
+///
+/// An error is returned if `text` is empty or `k` is zero.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{context_bwt, decode_context_bwt};
+///
+/// let (bwt, primary_index) = context_bwt(b"banana", 1)?;
+/// // The rotations starting with "a" are at positions 1, 3, and 5.
+/// assert_eq!(bwt, b"bnnaaa");
+/// assert_eq!(primary_index, 3);
+/// assert_eq!(decode_context_bwt(&bwt, primary_index, 1)?, b"banana");
+/// # Ok(())
+/// # }
+/// ```
+pub fn context_bwt(text: &[u8], k: usize) -> Result<(Vec<u8>, usize)> {
+    if text.is_empty() {
+        return Err(Error::EmptyText);
+    }
+    if k == 0 {
+        return Err(Error::invalid_argument("k must be positive."));
+    }
+    let n = text.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    let mut sorted = vec![0; n];
+    for d in (0..k.min(n)).rev() {
+        let mut starts = [0; 256];
+        for &j in &order {
+            starts[text[(j + d) % n] as usize] += 1;
+        }
+        let mut sum = 0;
+        for start in starts.iter_mut() {
+            (*start, sum) = (sum, sum + *start);
+        }
+        for &j in &order {
+            let c = text[(j + d) % n] as usize;
+            sorted[starts[c]] = j;
+            starts[c] += 1;
+        }
+        std::mem::swap(&mut order, &mut sorted);
+    }
+    let mut bwt = Vec::with_capacity(n);
+    let mut primary_index = 0;
+    for (r, &j) in order.iter().enumerate() {
+        if j == 0 {
+            primary_index = r;
+        }
+        bwt.push(text[(j + n - 1) % n]);
+    }
+    Ok((bwt, primary_index))
+}
+
+/// Decodes the original text from the context-bounded BWT computed by [`context_bwt`].
+///
+/// The rows sharing their first `k` symbols are grouped by `k` refinements through psi,
+/// and the text is decoded from the end, taking the rows in each group from the last one,
+/// since the occurrences of a context are met in decreasing order of their positions.
+/// It takes `O(kn)` time and four arrays of `n` integers, where `n` is the length of the BWT.
+///
+/// # Arguments
+///
+/// * `bwt` - The context-bounded BWT of a text.
+/// * `primary_index` - The row of the text itself.
+/// * `k` - The number of the symbols by which the rotations are sorted.
+///
+/// # Errors
+///
+/// An error is returned if
+///
+/// - `bwt` is empty,
+/// - `primary_index` is out of bounds,
+/// - `k` is zero, or
+/// - `bwt` is detected not to be a context-bounded BWT with the primary index.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{context_bwt, decode_context_bwt};
+///
+/// let text = b"mississippi";
+/// for k in 1..=4 {
+///     let (bwt, primary_index) = context_bwt(text, k)?;
+///     assert_eq!(decode_context_bwt(&bwt, primary_index, k)?, text);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn decode_context_bwt(bwt: &[u8], primary_index: usize, k: usize) -> Result<Vec<u8>> {
+    if bwt.is_empty() {
+        return Err(Error::invalid_bwt("bwt must not be empty."));
+    }
+    let n = bwt.len();
+    if primary_index >= n {
+        return Err(Error::invalid_argument(format!(
+            "primary index must be less than {n}, but got {primary_index}."
+        )));
+    }
+    if k == 0 {
+        return Err(Error::invalid_argument("k must be positive."));
+    }
+
+    let counts = count_symbols(bwt);
+    let mut firsts = Vec::with_capacity(n);
+    for (c, &count) in counts.iter().enumerate() {
+        firsts.extend(std::iter::repeat(c as u8).take(count));
+    }
+    let psi: Vec<_> = psi_values(bwt).collect();
+    // groups[r] is the group of row r by its first l symbols, refined from l = 1 to k,
+    // since the l-th prefix of row r is its first symbol followed by the (l-1)-th prefix of row psi[r].
+    let mut groups = vec![0; n];
+    let mut prev_groups = vec![0; n];
+    for r in 1..n {
+        groups[r] = groups[r - 1] + usize::from(firsts[r] != firsts[r - 1]);
+    }
+    for _ in 1..k.min(n) {
+        std::mem::swap(&mut prev_groups, &mut groups);
+        for r in 1..n {
+            let split =
+                firsts[r] != firsts[r - 1] || prev_groups[psi[r]] != prev_groups[psi[r - 1]];
+            groups[r] = groups[r - 1] + usize::from(split);
+        }
+    }
+    drop(firsts);
+    // The LF mapping into prev_groups, reusing the buffer.
+    let mut lf = prev_groups;
+    for (r, &p) in psi.iter().enumerate() {
+        lf[p] = r;
+    }
+    drop(psi);
+
+    // bounds[g] is the first row of group g and the row next to the last unused one.
+    let mut bounds = vec![(0, 0); groups[n - 1] + 1];
+    for (r, &g) in groups.iter().enumerate().rev() {
+        bounds[g] = (r, bounds[g].1.max(r + 1));
+    }
+    if bounds[groups[primary_index]].0 != primary_index {
+        return Err(Error::invalid_bwt(
+            "bwt is not a context-bounded BWT, since the text is not the first rotation of its context.",
+        ));
+    }
+    let mut text = vec![0; n];
+    let mut r = primary_index;
+    for t in (0..n).rev() {
+        text[t] = bwt[r];
+        if t == 0 {
+            break;
+        }
+        // The rotation at t is the last unused one in the group of the context of row LF(r).
+        let (start, next) = &mut bounds[groups[lf[r]]];
+        if *next <= *start + usize::from(*start == primary_index) {
+            return Err(Error::invalid_bwt(
+                "bwt is not a context-bounded BWT, since a context occurs more often than its rows.",
+            ));
+        }
+        *next -= 1;
+        r = *next;
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::rotation_bwt;
+
+    fn naive_context_bwt(text: &[u8], k: usize) -> (Vec<u8>, usize) {
+        let n = text.len();
+        let context = |j: usize| -> Vec<u8> { (0..k).map(|d| text[(j + d) % n]).collect() };
+        let mut rotations: Vec<usize> = (0..n).collect();
+        rotations.sort_by_key(|&j| context(j));
+        let bwt = rotations.iter().map(|&j| text[(j + n - 1) % n]).collect();
+        let primary_index = rotations.iter().position(|&j| j == 0).unwrap();
+        (bwt, primary_index)
+    }
+
+    #[test]
+    fn test_context_bwt() {
+        let texts: &[&[u8]] = &[
+            b"a",
+            b"ab",
+            b"banana",
+            b"abracadabra$",
+            b"mississippi",
+            b"abababab",
+            b"aaaaaaaa",
+            b"cabcabcabxcab",
+        ];
+        for &text in texts {
+            for k in 1..=text.len() + 2 {
+                let (bwt, primary_index) = context_bwt(text, k).unwrap();
+                assert_eq!(
+                    (bwt.clone(), primary_index),
+                    naive_context_bwt(text, k),
+                    "{text:?} k={k}"
+                );
+                let decoded = decode_context_bwt(&bwt, primary_index, k).unwrap();
+                assert_eq!(decoded, text, "{text:?} k={k}");
+            }
+            // The order of n gives the BWT of the sorted rotations.
+            assert_eq!(
+                context_bwt(text, text.len()).unwrap(),
+                rotation_bwt(text).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_context_bwt_random() {
+        let mut x = 12345u64;
+        for len in [10, 100, 1000] {
+            let text: Vec<u8> = (0..len)
+                .map(|_| {
+                    x ^= x << 13;
+                    x ^= x >> 7;
+                    x ^= x << 17;
+                    b"acgt"[(x % 4) as usize]
+                })
+                .collect();
+            for k in [1, 2, 3, 5, 8, 16] {
+                let (bwt, primary_index) = context_bwt(&text, k).unwrap();
+                assert_eq!(decode_context_bwt(&bwt, primary_index, k).unwrap(), text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_context_bwt_invalid() {
+        assert!(context_bwt(b"", 1).is_err());
+        assert!(context_bwt(b"abc", 0).is_err());
+        assert!(decode_context_bwt(b"", 0, 1).is_err());
+        assert!(decode_context_bwt(b"abc", 3, 1).is_err());
+        assert!(decode_context_bwt(b"abc", 0, 0).is_err());
+        // "banana" has "a" at rows 1 to 3, so the text cannot be at row 2.
+        assert!(decode_context_bwt(b"bnnaaa", 2, 1).is_err());
+    }
+}
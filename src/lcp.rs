@@ -0,0 +1,818 @@
+use crate::error::{Error, Result};
+use crate::{BwtBuilder, IntWidth};
+
+/// Repeated substring of a text, found by [`longest_repeated_substrings`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Repeat {
+    /// The length of the substring.
+    pub len: usize,
+    /// The starting positions of the occurrences in increasing order.
+    pub positions: Vec<usize>,
+}
+
+/// Finds the longest substrings occurring at least twice in a text from its suffix and LCP arrays.
+///
+/// The arrays can be written by
+/// [`BwtBuilder::suffix_array_writer`](crate::BwtBuilder::suffix_array_writer) and
+/// [`BwtBuilder::lcp_array_writer`](crate::BwtBuilder::lcp_array_writer).
+///
+/// The longest repeated length is the largest LCP value,
+/// and each maximal run of rows whose LCP values equal it is a distinct repeated substring.
+/// The arrays are consumed in a single pass, holding only the occurrences of the longest ones so far.
+///
+/// # Arguments
+///
+/// * `sa` - The suffix array.
+/// * `lcp` - The LCP array, whose `i`-th value is the LCP between the `i-1`-th and `i`-th suffixes.
+///
+/// # Errors
+///
+/// An error is returned if `sa` and `lcp` have different lengths.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{longest_repeated_substrings, BwtBuilder, IntWidth};
+///
+/// let (mut sa, mut lcp) = (vec![], vec![]);
+/// BwtBuilder::new(b"abracadabra$")?
+///     .suffix_array_writer(&mut sa, IntWidth::U32)
+///     .lcp_array_writer(&mut lcp, IntWidth::U32)
+///     .build(&mut vec![])?;
+/// let values = |bytes: Vec<u8>| {
+///     bytes
+///         .chunks(4)
+///         .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+///         .collect::<Vec<_>>()
+/// };
+///
+/// let repeats = longest_repeated_substrings(values(sa), values(lcp))?;
+/// assert_eq!(repeats.len(), 1);
+/// // "abra" at positions 0 and 7.
+/// assert_eq!(repeats[0].len, 4);
+/// assert_eq!(repeats[0].positions, vec![0, 7]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn longest_repeated_substrings<S, L>(sa: S, lcp: L) -> Result<Vec<Repeat>>
+where
+    S: IntoIterator<Item = usize>,
+    L: IntoIterator<Item = usize>,
+{
+    let mut lcp = lcp.into_iter();
+    let mut best = 0;
+    // The occurrences of the longest repeated substrings so far.
+    let mut groups: Vec<Vec<usize>> = vec![];
+    // Whether the previous row is in the last group.
+    let mut in_group = false;
+    let mut prev = None;
+    let mut len = 0;
+    for pos in sa {
+        let l = lcp.next().ok_or_else(|| {
+            Error::invalid_argument(format!(
+                "lcp must have the same length as sa, but has {len}."
+            ))
+        })?;
+        len += 1;
+        // The first value has no previous suffix.
+        let prev_pos = match prev.replace(pos) {
+            Some(prev_pos) => prev_pos,
+            None => continue,
+        };
+        if l > best {
+            best = l;
+            groups.clear();
+            groups.push(vec![prev_pos, pos]);
+            in_group = true;
+        } else if l == best && best != 0 {
+            if in_group {
+                groups.last_mut().unwrap().push(pos);
+            } else {
+                groups.push(vec![prev_pos, pos]);
+                in_group = true;
+            }
+        } else {
+            in_group = false;
+        }
+    }
+    if lcp.next().is_some() {
+        return Err(Error::invalid_argument(format!(
+            "lcp must have the same length as sa, but has more than {len}."
+        )));
+    }
+    let mut repeats: Vec<_> = groups
+        .into_iter()
+        .map(|mut positions| {
+            positions.sort_unstable();
+            Repeat {
+                len: best,
+                positions,
+            }
+        })
+        .collect();
+    repeats.sort_unstable_by_key(|r| r.positions[0]);
+    Ok(repeats)
+}
+
+/// Counts the distinct non-empty substrings of a text from its LCP array,
+/// such as the one written by [`BwtBuilder::lcp_array_writer`](crate::BwtBuilder::lcp_array_writer).
+///
+/// Each suffix contributes its length minus its LCP with the previous suffix,
+/// so the count is `n(n+1)/2` minus the sum of the LCP values.
+/// Substrings including the terminator are counted; when the text ends with a unique terminator,
+/// they are exactly its `n` suffixes, so subtract `n` to exclude them.
+///
+/// The LCP array may include the empty suffix at its head, as it does
+/// with [`BwtBuilder::implicit_sentinel`](crate::BwtBuilder::implicit_sentinel).
+///
+/// # Arguments
+///
+/// * `text_len` - The length of the text, `n`.
+/// * `lcp` - The LCP array.
+///
+/// # Errors
+///
+/// An error is returned if
+///
+/// - `lcp` does not have `n` or `n+1` values, or
+/// - the LCP values sum to more than `n(n+1)/2`.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{count_distinct_substrings, BwtBuilder, IntWidth};
+///
+/// let text = b"abab$";
+/// let mut lcp = vec![];
+/// BwtBuilder::new(text)?
+///     .lcp_array_writer(&mut lcp, IntWidth::U32)
+///     .build(&mut vec![])?;
+/// let lcp = lcp
+///     .chunks(4)
+///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize);
+///
+/// // a, b, ab, ba, aba, bab, abab, and the 5 suffixes with $.
+/// assert_eq!(count_distinct_substrings(text.len(), lcp)?, 12);
+/// # Ok(())
+/// # }
+/// ```
+pub fn count_distinct_substrings<L>(text_len: usize, lcp: L) -> Result<u128>
+where
+    L: IntoIterator<Item = usize>,
+{
+    let n = text_len as u128;
+    let mut sum = 0u128;
+    let mut len = 0;
+    for l in lcp {
+        sum += l as u128;
+        len += 1;
+    }
+    if len != text_len && len != text_len + 1 {
+        return Err(Error::invalid_argument(format!(
+            "lcp must have {text_len} or {} values, but got {len}.",
+            text_len + 1
+        )));
+    }
+    let total = n * (n + 1) / 2;
+    if sum > total {
+        return Err(Error::invalid_argument(format!(
+            "lcp must sum to at most {total}, but got {sum}."
+        )));
+    }
+    Ok(total - sum)
+}
+
+/// Factor of the LZ77 parse of a text, computed by [`lz77_factorize`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LzFactor {
+    /// A symbol occurring for the first time.
+    Literal(u8),
+    /// A copy of `len` symbols from the earlier position `source`,
+    /// which may overlap the factor itself.
+    Copy {
+        /// The starting position of the previous occurrence.
+        source: usize,
+        /// The length of the factor.
+        len: usize,
+    },
+}
+
+impl LzFactor {
+    /// Returns the number of symbols of the text covered by the factor.
+    pub const fn len(&self) -> usize {
+        match self {
+            Self::Literal(_) => 1,
+            Self::Copy { len, .. } => *len,
+        }
+    }
+
+    /// Returns `true` if the factor covers no symbols, which never happens.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Computes the longest previous factor (LPF) array of a text from its suffix and LCP arrays.
+///
+/// The arrays can be written by
+/// [`BwtBuilder::suffix_array_writer`](crate::BwtBuilder::suffix_array_writer) and
+/// [`BwtBuilder::lcp_array_writer`](crate::BwtBuilder::lcp_array_writer).
+///
+/// The `i`-th value is the length of the longest prefix of the suffix at `i`
+/// that also starts at an earlier position, possibly overlapping it.
+/// It is the larger LCP with the nearest rows above and below whose positions are smaller,
+/// which are found by two scans with a stack, in `O(n)` time.
+///
+/// # Arguments
+///
+/// * `sa` - The suffix array.
+/// * `lcp` - The LCP array, whose `i`-th value is the LCP between the `i-1`-th and `i`-th suffixes.
+///
+/// # Errors
+///
+/// An error is returned if `sa` is not a permutation of `[0, n)` or `lcp` has a different length.
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::lpf_array;
+///
+/// // The suffix and LCP arrays of "abaabab$".
+/// let sa = [7, 2, 5, 0, 3, 6, 1, 4];
+/// let lcp = [0, 0, 1, 2, 3, 0, 1, 2];
+/// assert_eq!(lpf_array(&sa, &lcp).unwrap(), [0, 0, 1, 3, 2, 2, 1, 0]);
+/// ```
+pub fn lpf_array(sa: &[usize], lcp: &[usize]) -> Result<Vec<usize>> {
+    Ok(previous_factors(sa, lcp)?.0)
+}
+
+/// Computes the LZ77 parse of a text from its suffix and LCP arrays.
+///
+/// The arrays can be written by
+/// [`BwtBuilder::suffix_array_writer`](crate::BwtBuilder::suffix_array_writer) and
+/// [`BwtBuilder::lcp_array_writer`](crate::BwtBuilder::lcp_array_writer).
+///
+/// Each factor is the longest previous factor at its position, as given by [`lpf_array`],
+/// or a literal if the symbol occurs for the first time.
+/// It takes `O(n)` time and two arrays of `n` integers in addition to the inputs.
+/// The parse is decoded by [`decode_lz77`].
+///
+/// # Arguments
+///
+/// * `text` - The text.
+/// * `sa` - The suffix array of `text`.
+/// * `lcp` - The LCP array of `text`.
+///
+/// # Errors
+///
+/// An error is returned if `sa` is not a permutation of `[0, n)`
+/// or `lcp` has a different length, where `n` is the length of `text`.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{decode_lz77, lz77_factorize, BwtBuilder, IntWidth, LzFactor};
+///
+/// let text = b"abaabab$";
+/// let (mut sa, mut lcp) = (vec![], vec![]);
+/// BwtBuilder::new(text)?
+///     .suffix_array_writer(&mut sa, IntWidth::U32)
+///     .lcp_array_writer(&mut lcp, IntWidth::U32)
+///     .build(&mut vec![])?;
+/// let values = |bytes: Vec<u8>| {
+///     bytes
+///         .chunks(4)
+///         .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+///         .collect::<Vec<_>>()
+/// };
+///
+/// let factors = lz77_factorize(text, &values(sa), &values(lcp))?;
+/// assert_eq!(
+///     factors,
+///     [
+///         LzFactor::Literal(b'a'),
+///         LzFactor::Literal(b'b'),
+///         LzFactor::Copy { source: 0, len: 1 },
+///         LzFactor::Copy { source: 0, len: 3 },
+///         LzFactor::Copy { source: 1, len: 1 },
+///         LzFactor::Literal(b'$'),
+///     ]
+/// );
+/// assert_eq!(decode_lz77(&factors)?, text);
+/// # Ok(())
+/// # }
+/// ```
+pub fn lz77_factorize(text: &[u8], sa: &[usize], lcp: &[usize]) -> Result<Vec<LzFactor>> {
+    if sa.len() != text.len() {
+        return Err(Error::invalid_argument(format!(
+            "sa must have {} values, but got {}.",
+            text.len(),
+            sa.len()
+        )));
+    }
+    let (lpf, sources) = previous_factors(sa, lcp)?;
+    let mut factors = vec![];
+    let mut i = 0;
+    while i < text.len() {
+        let factor = match lpf[i] {
+            0 => LzFactor::Literal(text[i]),
+            len => LzFactor::Copy {
+                source: sources[i],
+                len,
+            },
+        };
+        i += factor.len();
+        factors.push(factor);
+    }
+    Ok(factors)
+}
+
+/// Decodes the text from its LZ77 parse computed by [`lz77_factorize`].
+///
+/// # Arguments
+///
+/// * `factors` - The factors of the parse.
+///
+/// # Errors
+///
+/// An error is returned if a copy has no symbols or does not start at an earlier position.
+pub fn decode_lz77(factors: &[LzFactor]) -> Result<Vec<u8>> {
+    let mut text = vec![];
+    for &factor in factors {
+        match factor {
+            LzFactor::Literal(c) => text.push(c),
+            LzFactor::Copy { source, len } => {
+                if len == 0 || source >= text.len() {
+                    return Err(Error::invalid_argument(format!(
+                        "copy must have symbols from a position less than {}, but got {len} from {source}.",
+                        text.len()
+                    )));
+                }
+                // The copy may overlap itself, so the symbols are copied one by one.
+                for j in source..source + len {
+                    text.push(text[j]);
+                }
+            }
+        }
+    }
+    Ok(text)
+}
+
+/// Longest common substring of two texts, found by [`longest_common_substrings`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommonSubstring {
+    /// The starting positions of the occurrences in the first text in increasing order.
+    pub positions1: Vec<usize>,
+    /// The starting positions of the occurrences in the second text in increasing order.
+    pub positions2: Vec<usize>,
+}
+
+/// Substrings shared by two texts, computed by [`longest_common_substrings`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommonSubstrings {
+    /// The length of the longest common substrings.
+    pub len: usize,
+    /// The distinct longest common substrings, in increasing order of their first positions in the first text,
+    /// which is empty if the texts share no symbols.
+    pub substrings: Vec<CommonSubstring>,
+    /// The number of distinct non-empty substrings occurring in both texts.
+    pub n_distinct: u128,
+}
+
+/// Finds the longest common substrings of two texts and counts their distinct common substrings,
+/// from the generalized suffix and LCP arrays of their concatenation.
+///
+/// The texts are concatenated with a separator, the smallest byte absent from both,
+/// and followed by an implicit sentinel, so that no common prefix of two suffixes crosses a text boundary.
+/// The lcp-intervals are traversed bottom-up with the texts in which their suffixes start,
+/// where an interval with suffixes of both texts and an LCP value `l` greater than its parent's `p`
+/// gives `l - p` distinct common substrings, and the deepest ones are the longest common substrings.
+/// It takes `O(n)` time and three arrays of `n` integers in addition to the construction,
+/// where `n` is the total length.
+///
+/// # Arguments
+///
+/// * `text1` - The first text.
+/// * `text2` - The second text.
+///
+/// # Errors
+///
+/// An error is returned if the texts contain all the 256 byte values together.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::longest_common_substrings;
+///
+/// let common = longest_common_substrings(b"abracadabra", b"cadabras")?;
+/// // "cadabra" at position 4 of the first text and 0 of the second.
+/// assert_eq!(common.len, 7);
+/// assert_eq!(common.substrings.len(), 1);
+/// assert_eq!(common.substrings[0].positions1, vec![4]);
+/// assert_eq!(common.substrings[0].positions2, vec![0]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn longest_common_substrings(text1: &[u8], text2: &[u8]) -> Result<CommonSubstrings> {
+    let mut used = [false; 256];
+    for &c in text1.iter().chain(text2) {
+        used[c as usize] = true;
+    }
+    let separator = used.iter().position(|&u| !u).ok_or_else(|| {
+        Error::invalid_argument("texts must leave a byte value unused for the separator.")
+    })? as u8;
+    let mut text = Vec::with_capacity(text1.len() + text2.len() + 1);
+    text.extend_from_slice(text1);
+    text.push(separator);
+    text.extend_from_slice(text2);
+
+    let (mut sa_bytes, mut lcp_bytes) = (vec![], vec![]);
+    BwtBuilder::new_unchecked(&text)?
+        .implicit_sentinel(separator)
+        .suffix_array_writer(&mut sa_bytes, IntWidth::U64)
+        .lcp_array_writer(&mut lcp_bytes, IntWidth::U64)
+        .build(std::io::sink())?;
+    let values = |bytes: Vec<u8>| -> Vec<usize> {
+        bytes
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as usize)
+            .collect()
+    };
+    let (sa, lcp) = (values(sa_bytes), values(lcp_bytes));
+
+    // The bit of the text in which each suffix starts, where the separator and sentinel are in neither.
+    let boundary = text1.len();
+    let color = |pos: usize| -> u8 {
+        match pos.cmp(&boundary) {
+            std::cmp::Ordering::Less => 1,
+            std::cmp::Ordering::Equal => 0,
+            std::cmp::Ordering::Greater => u8::from(pos < text.len()) << 1,
+        }
+    };
+    let mut common = CommonSubstrings::default();
+    // The rows of the longest common substrings.
+    let mut intervals = vec![];
+    // The open lcp-intervals with their LCP values, left bounds, and colors of their suffixes so far.
+    let mut stack = vec![(0, 0, 0u8)];
+    for i in 1..=sa.len() {
+        let l = lcp.get(i).copied().unwrap_or(0);
+        let leaf = color(sa[i - 1]);
+        let top = stack.last_mut().unwrap();
+        if l > top.0 {
+            stack.push((l, i - 1, leaf));
+            continue;
+        }
+        top.2 |= leaf;
+        let mut lb = i - 1;
+        let mut colors = 0;
+        while l < stack.last().unwrap().0 {
+            let (depth, start, node_colors) = stack.pop().unwrap();
+            let parent = stack.last_mut().unwrap();
+            if node_colors == 3 {
+                common.n_distinct += (depth - l.max(parent.0)) as u128;
+                if depth > common.len {
+                    common.len = depth;
+                    intervals.clear();
+                }
+                if depth == common.len {
+                    intervals.push(start..i);
+                }
+            }
+            if l <= parent.0 {
+                parent.2 |= node_colors;
+            } else {
+                colors = node_colors;
+            }
+            lb = start;
+        }
+        if l > stack.last().unwrap().0 {
+            stack.push((l, lb, colors));
+        }
+    }
+
+    for rows in intervals {
+        let (mut positions1, mut positions2) = (vec![], vec![]);
+        for &pos in &sa[rows] {
+            match color(pos) {
+                1 => positions1.push(pos),
+                2 => positions2.push(pos - boundary - 1),
+                _ => {}
+            }
+        }
+        positions1.sort_unstable();
+        positions2.sort_unstable();
+        common.substrings.push(CommonSubstring {
+            positions1,
+            positions2,
+        });
+    }
+    common.substrings.sort_unstable_by_key(|s| s.positions1[0]);
+    Ok(common)
+}
+
+/// Returns the LPF array and the starting positions of the previous occurrences of the factors.
+///
+/// For each row, the nearest rows above and below with smaller positions are found with a stack,
+/// where each entry holds the minimum LCP between it and the next entry, or the current row for the top.
+fn previous_factors(sa: &[usize], lcp: &[usize]) -> Result<(Vec<usize>, Vec<usize>)> {
+    let n = sa.len();
+    if lcp.len() != n {
+        return Err(Error::invalid_argument(format!(
+            "lcp must have the same length as sa {n}, but has {}.",
+            lcp.len()
+        )));
+    }
+    let mut seen = vec![false; n];
+    for (i, &pos) in sa.iter().enumerate() {
+        if pos >= n || seen[pos] {
+            return Err(Error::invalid_argument(format!(
+                "sa must be a permutation of [0, {n}), but got {pos} at index {i}."
+            )));
+        }
+        seen[pos] = true;
+    }
+    drop(seen);
+
+    let mut lpf = vec![0; n];
+    let mut sources = vec![0; n];
+    let mut stack: Vec<(usize, usize)> = vec![];
+    // The rows above, where lcp[r] extends the range from the top to row r.
+    for r in 0..n {
+        if let Some(top) = stack.last_mut() {
+            top.1 = top.1.min(lcp[r]);
+        }
+        pop_larger(&mut stack, sa, sa[r]);
+        if let Some(&(t, l)) = stack.last() {
+            (lpf[sa[r]], sources[sa[r]]) = (l, sa[t]);
+        }
+        stack.push((r, usize::MAX));
+    }
+    stack.clear();
+    // The rows below, where lcp[r + 1] extends the range from row r to the top.
+    for r in (0..n).rev() {
+        if let Some(top) = stack.last_mut() {
+            top.1 = top.1.min(lcp[r + 1]);
+        }
+        pop_larger(&mut stack, sa, sa[r]);
+        if let Some(&(t, l)) = stack.last() {
+            if l > lpf[sa[r]] {
+                (lpf[sa[r]], sources[sa[r]]) = (l, sa[t]);
+            }
+        }
+        stack.push((r, usize::MAX));
+    }
+    Ok((lpf, sources))
+}
+
+/// Pops the entries whose positions are larger than `pos`, merging their minimum LCPs into the new top.
+fn pop_larger(stack: &mut Vec<(usize, usize)>, sa: &[usize], pos: usize) {
+    while let Some(&(t, l)) = stack.last() {
+        if sa[t] < pos {
+            break;
+        }
+        stack.pop();
+        if let Some(top) = stack.last_mut() {
+            top.1 = top.1.min(l);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn sa_lcp(text: &[u8]) -> (Vec<usize>, Vec<usize>) {
+        let mut sa: Vec<usize> = (0..text.len()).collect();
+        sa.sort_by_key(|&i| &text[i..]);
+        let mut lcp = vec![0; sa.len()];
+        for r in 1..sa.len() {
+            let (a, b) = (&text[sa[r - 1]..], &text[sa[r]..]);
+            lcp[r] = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+        }
+        (sa, lcp)
+    }
+
+    fn naive_longest_repeats(text: &[u8]) -> Vec<Repeat> {
+        for len in (1..text.len()).rev() {
+            let mut occs: HashMap<&[u8], Vec<usize>> = HashMap::new();
+            for (i, w) in text.windows(len).enumerate() {
+                occs.entry(w).or_default().push(i);
+            }
+            let mut repeats: Vec<_> = occs
+                .into_values()
+                .filter(|positions| positions.len() > 1)
+                .map(|positions| Repeat { len, positions })
+                .collect();
+            if !repeats.is_empty() {
+                repeats.sort_unstable_by_key(|r| r.positions[0]);
+                return repeats;
+            }
+        }
+        vec![]
+    }
+
+    #[test]
+    fn test_longest_repeated_substrings() {
+        let texts: &[&[u8]] = &[
+            b"$",
+            b"abc$",
+            b"abracadabra$",
+            b"mississippi$",
+            b"abcxabcyabdzabd$",
+            b"aaaaaaaa$",
+        ];
+        for &text in texts {
+            let (sa, lcp) = sa_lcp(text);
+            let repeats = longest_repeated_substrings(sa, lcp).unwrap();
+            assert_eq!(repeats, naive_longest_repeats(text), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn test_count_distinct_substrings() {
+        let texts: &[&[u8]] = &[
+            b"$",
+            b"abc$",
+            b"abracadabra$",
+            b"mississippi$",
+            b"aaaaaaaa$",
+        ];
+        for &text in texts {
+            let mut substrings = std::collections::HashSet::new();
+            for i in 0..text.len() {
+                for j in i + 1..=text.len() {
+                    substrings.insert(&text[i..j]);
+                }
+            }
+            let (_, lcp) = sa_lcp(text);
+            let count = count_distinct_substrings(text.len(), lcp.iter().copied()).unwrap();
+            assert_eq!(count, substrings.len() as u128, "{text:?}");
+            // With the empty suffix first.
+            let lcp = [0].into_iter().chain(lcp.iter().copied());
+            assert_eq!(count_distinct_substrings(text.len(), lcp).unwrap(), count);
+        }
+        assert_eq!(count_distinct_substrings(0, []).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_count_distinct_substrings_invalid() {
+        assert!(count_distinct_substrings(3, [0, 0]).is_err());
+        assert!(count_distinct_substrings(1, [0, 0, 0]).is_err());
+        assert!(count_distinct_substrings(2, [0, 4]).is_err());
+    }
+
+    fn naive_lpf(text: &[u8]) -> Vec<usize> {
+        (0..text.len())
+            .map(|i| {
+                (0..i)
+                    .map(|j| {
+                        let (a, b) = (&text[i..], &text[j..]);
+                        a.iter().zip(b).take_while(|(x, y)| x == y).count()
+                    })
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lz77_factorize() {
+        let texts: &[&[u8]] = &[
+            b"$",
+            b"abc$",
+            b"abracadabra$",
+            b"mississippi$",
+            b"aaaaaaaa$",
+            b"abaababaabaababaababa$",
+            b"abcxabcyabdzabdabcxabc$",
+        ];
+        for &text in texts {
+            let (sa, lcp) = sa_lcp(text);
+            let lpf = lpf_array(&sa, &lcp).unwrap();
+            assert_eq!(lpf, naive_lpf(text), "{text:?}");
+
+            let factors = lz77_factorize(text, &sa, &lcp).unwrap();
+            let mut i = 0;
+            for &factor in &factors {
+                match factor {
+                    LzFactor::Literal(c) => {
+                        assert_eq!(lpf[i], 0);
+                        assert_eq!(c, text[i]);
+                    }
+                    LzFactor::Copy { source, len } => {
+                        assert_eq!(len, lpf[i]);
+                        assert!(source < i);
+                        assert_eq!(text[source..source + len], text[i..i + len]);
+                    }
+                }
+                i += factor.len();
+            }
+            assert_eq!(i, text.len());
+            assert_eq!(decode_lz77(&factors).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_lz77_factorize_invalid() {
+        assert!(lpf_array(&[0, 0], &[0, 0]).is_err());
+        assert!(lpf_array(&[0, 2], &[0, 0]).is_err());
+        assert!(lpf_array(&[1, 0], &[0]).is_err());
+        assert!(lz77_factorize(b"ab", &[0], &[0]).is_err());
+        assert!(lpf_array(&[], &[]).unwrap().is_empty());
+
+        assert!(decode_lz77(&[LzFactor::Copy { source: 0, len: 1 }]).is_err());
+        let factors = [
+            LzFactor::Literal(b'a'),
+            LzFactor::Copy { source: 0, len: 0 },
+        ];
+        assert!(decode_lz77(&factors).is_err());
+    }
+
+    fn naive_common_substrings(text1: &[u8], text2: &[u8]) -> CommonSubstrings {
+        let substrings = |text: &[u8]| -> HashMap<Vec<u8>, Vec<usize>> {
+            let mut occs: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+            for i in 0..text.len() {
+                for j in i + 1..=text.len() {
+                    occs.entry(text[i..j].to_vec()).or_default().push(i);
+                }
+            }
+            occs
+        };
+        let (occs1, occs2) = (substrings(text1), substrings(text2));
+        let mut common = CommonSubstrings::default();
+        for (s, positions1) in occs1 {
+            let positions2 = match occs2.get(&s) {
+                Some(positions2) => positions2,
+                None => continue,
+            };
+            common.n_distinct += 1;
+            if s.len() > common.len {
+                common.len = s.len();
+                common.substrings.clear();
+            }
+            if s.len() == common.len {
+                common.substrings.push(CommonSubstring {
+                    positions1,
+                    positions2: positions2.clone(),
+                });
+            }
+        }
+        common.substrings.sort_unstable_by_key(|s| s.positions1[0]);
+        common
+    }
+
+    #[test]
+    fn test_longest_common_substrings() {
+        let texts: &[&[u8]] = &[
+            b"",
+            b"a",
+            b"b",
+            b"abracadabra",
+            b"cadabras",
+            b"mississippi",
+            b"missouri",
+            b"aaaa",
+            b"abab",
+            b"xyz",
+            b"abcxabcyab",
+        ];
+        for &text1 in texts {
+            for &text2 in texts {
+                assert_eq!(
+                    longest_common_substrings(text1, text2).unwrap(),
+                    naive_common_substrings(text1, text2),
+                    "{text1:?} {text2:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_longest_common_substrings_all_bytes() {
+        let text1: Vec<u8> = (0..=255).collect();
+        assert!(longest_common_substrings(&text1, b"abc").is_err());
+        // The zero byte is left for the separator.
+        let common = longest_common_substrings(&text1[1..], &[5, 6, 9]).unwrap();
+        assert_eq!(common.len, 2);
+        assert_eq!(common.substrings[0].positions1, vec![4]);
+        assert_eq!(common.substrings[0].positions2, vec![0]);
+        assert_eq!(common.n_distinct, 4);
+    }
+
+    #[test]
+    fn test_longest_repeated_substrings_invalid() {
+        assert!(longest_repeated_substrings([0, 1], [0]).is_err());
+        assert!(longest_repeated_substrings([0], [0, 1]).is_err());
+        assert_eq!(longest_repeated_substrings([], []).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,629 @@
+use std::io::{Read, Write};
+use std::iter::FusedIterator;
+use std::ops::Range;
+
+use crate::error::Result;
+use crate::intio::{IntWidth, IntWriter};
+
+/// Length of the buffer to read symbols or write bit-packed values.
+const PACKED_BUF_LEN: usize = 1 << 16;
+
+/// Iterator over the LF mapping of a BWT, created by [`lf_mapping`].
+///
+/// The `i`-th value is `C[c] + rank_c(i)` for `c = bwt[i]`,
+/// i.e., the row of the rotation starting one position before that of row `i`.
+#[derive(Clone, Debug)]
+pub struct LfMapping<'a> {
+    bwt: std::slice::Iter<'a, u8>,
+    // The next LF value of each symbol, starting from the number of smaller symbols.
+    next: [usize; 256],
+}
+
+impl Iterator for LfMapping<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let c = *self.bwt.next()? as usize;
+        let r = self.next[c];
+        self.next[c] += 1;
+        Some(r)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.bwt.size_hint()
+    }
+}
+
+impl ExactSizeIterator for LfMapping<'_> {}
+
+impl FusedIterator for LfMapping<'_> {}
+
+/// Returns an iterator over the LF mapping of a given BWT in row order.
+///
+/// It takes one pass over `bwt` to count the symbols and another to produce the values,
+/// using only `O(σ)` words of memory besides the BWT,
+/// so the LF mapping can be streamed without the occurrence or rank tables.
+/// Any sequence of bytes is accepted, since the LF mapping is a permutation regardless of its validity.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::lf_mapping;
+///
+/// let lf: Vec<usize> = lf_mapping(b"ard$rcaaaabb").collect();
+/// assert_eq!(lf, vec![1, 10, 9, 0, 11, 8, 2, 3, 4, 5, 6, 7]);
+/// ```
+pub fn lf_mapping(bwt: &[u8]) -> LfMapping<'_> {
+    let counts = count_symbols(bwt);
+    let mut next = [0; 256];
+    let mut rank = 0;
+    for c in 0..256 {
+        next[c] = rank;
+        rank += counts[c];
+    }
+    LfMapping {
+        bwt: bwt.iter(),
+        next,
+    }
+}
+
+/// Writes the LF mapping of a given BWT, computed by [`lf_mapping`],
+/// as little-endian integers of `width`.
+///
+/// Each value is written directly to `wrt`,
+/// so a buffered writer such as [`std::io::BufWriter`] is recommended.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+/// * `wrt` - The writer to write the LF mapping.
+/// * `width` - The byte width of each integer.
+///
+/// # Errors
+///
+/// An error is returned if a value does not fit in `width` or `wrt` returns an error.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{write_lf_mapping, IntWidth};
+///
+/// let mut lf = vec![];
+/// write_lf_mapping(b"ard$rcaaaabb", &mut lf, IntWidth::U32)?;
+///
+/// let lf: Vec<u32> = lf
+///     .chunks(4)
+///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+///     .collect();
+/// assert_eq!(lf, vec![1, 10, 9, 0, 11, 8, 2, 3, 4, 5, 6, 7]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_lf_mapping<W: Write>(bwt: &[u8], wrt: W, width: IntWidth) -> Result<()> {
+    let mut wrt = IntWriter::new(wrt, width);
+    for r in lf_mapping(bwt) {
+        wrt.write(r)?;
+    }
+    wrt.flush()
+}
+
+/// Writes the LF mapping of a given BWT, computed by [`lf_mapping`],
+/// bit-packed in the smallest number of bits that can represent the rows,
+/// and returns the number of bits per value.
+///
+/// The `i`-th value occupies bits `[i * b, (i + 1) * b)` of the output,
+/// where `b` is the returned number of bits and bit `k` is bit `k % 8` of byte `k / 8`.
+/// The last byte is padded with zeros.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+/// * `wrt` - The writer to write the LF mapping.
+///
+/// # Errors
+///
+/// An error is returned if `wrt` returns an error.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::write_lf_mapping_packed;
+///
+/// let mut lf = vec![];
+/// let bits = write_lf_mapping_packed(b"nnbaa$a", &mut lf)?;
+/// assert_eq!(bits, 3);
+/// assert_eq!(lf.len(), 3);
+///
+/// let lf: Vec<u32> = (0..7)
+///     .map(|i| {
+///         let word = u32::from_le_bytes([lf[0], lf[1], lf[2], 0]);
+///         word >> (i * bits) & 0b111
+///     })
+///     .collect();
+/// assert_eq!(lf, vec![5, 6, 4, 1, 2, 0, 3]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_lf_mapping_packed<W: Write>(bwt: &[u8], wrt: W) -> Result<u32> {
+    write_packed(lf_mapping(bwt), bwt.len(), wrt)
+}
+
+/// Iterator over the psi array of a BWT, i.e., the inverse of the LF mapping, created by [`psi`].
+///
+/// The `r`-th value is the position of the `k`-th occurrence of `c` in the BWT,
+/// where `c` is the first symbol of row `r` and `k = r - C[c]`.
+#[derive(Clone, Debug)]
+pub struct Psi<'a> {
+    bwt: &'a [u8],
+    // The number of occurrences of each symbol not yet visited.
+    counts: [usize; 256],
+    // The current symbol and the position to search it from.
+    c: usize,
+    pos: usize,
+    len: usize,
+}
+
+impl Iterator for Psi<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        while self.counts[self.c] == 0 {
+            self.c += 1;
+            self.pos = 0;
+        }
+        let c = self.c as u8;
+        // The occurrence exists since it has been counted.
+        let p = self.pos + self.bwt[self.pos..].iter().position(|&b| b == c).unwrap();
+        self.counts[self.c] -= 1;
+        self.pos = p + 1;
+        self.len -= 1;
+        Some(p)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl ExactSizeIterator for Psi<'_> {}
+
+impl FusedIterator for Psi<'_> {}
+
+/// Returns an iterator over the psi array of a given BWT in row order,
+/// i.e., the inverse of the LF mapping, as used in compressed suffix arrays.
+///
+/// The values are produced by scanning `bwt` for each distinct symbol in increasing order,
+/// so it runs in `O(σn)` time, using only `O(σ)` words of memory besides the BWT,
+/// where `σ` is the number of distinct symbols.
+/// Since the values of each symbol are increasing, the psi array is suited to gap encoding.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::{lf_mapping, psi};
+///
+/// let bwt = b"ard$rcaaaabb";
+/// let psi: Vec<usize> = psi(bwt).collect();
+/// assert_eq!(psi, vec![3, 0, 6, 7, 8, 9, 10, 11, 5, 2, 1, 4]);
+/// for (i, r) in lf_mapping(bwt).enumerate() {
+///     assert_eq!(psi[r], i);
+/// }
+/// ```
+pub fn psi(bwt: &[u8]) -> Psi<'_> {
+    Psi {
+        bwt,
+        counts: count_symbols(bwt),
+        c: 0,
+        pos: 0,
+        len: bwt.len(),
+    }
+}
+
+/// Writes the psi array of a given BWT, computed by [`psi`],
+/// as little-endian integers of `width`.
+///
+/// Each value is written directly to `wrt`,
+/// so a buffered writer such as [`std::io::BufWriter`] is recommended.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+/// * `wrt` - The writer to write the psi array.
+/// * `width` - The byte width of each integer.
+///
+/// # Errors
+///
+/// An error is returned if a value does not fit in `width` or `wrt` returns an error.
+pub fn write_psi<W: Write>(bwt: &[u8], wrt: W, width: IntWidth) -> Result<()> {
+    let mut wrt = IntWriter::new(wrt, width);
+    for p in psi(bwt) {
+        wrt.write(p)?;
+    }
+    wrt.flush()
+}
+
+/// Writes the psi array of a given BWT, computed by [`psi`],
+/// bit-packed in `ceil(log2 n)` bits per value as [`write_lf_mapping_packed`],
+/// and returns the number of bits per value.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text.
+/// * `wrt` - The writer to write the psi array.
+///
+/// # Errors
+///
+/// An error is returned if `wrt` returns an error.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::write_psi_packed;
+///
+/// let mut psi = vec![];
+/// let bits = write_psi_packed(b"nnbaa$a", &mut psi)?;
+/// assert_eq!(bits, 3);
+///
+/// let word = u32::from_le_bytes([psi[0], psi[1], psi[2], 0]);
+/// let psi: Vec<u32> = (0..7).map(|i| word >> (i * bits) & 0b111).collect();
+/// assert_eq!(psi, vec![5, 3, 4, 6, 2, 0, 1]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_psi_packed<W: Write>(bwt: &[u8], wrt: W) -> Result<u32> {
+    write_packed(psi(bwt), bwt.len(), wrt)
+}
+
+/// Returns the C array of a given text or BWT, i.e., `C[c]` is the number of symbols smaller than `c`.
+///
+/// The array has 257 values, whose last one is the length of `seq`,
+/// so the rows of the suffixes starting with `c` are `C[c]..C[c + 1]`.
+/// Since the BWT is a permutation of the text, both give the same C array.
+///
+/// # Arguments
+///
+/// * `seq` - The text or its Burrows-Wheeler transform.
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::c_array;
+///
+/// let c = c_array(b"ard$rcaaaabb");
+/// assert_eq!(c.len(), 257);
+/// assert_eq!(c[b'$' as usize], 0);
+/// assert_eq!(c[b'a' as usize], 1);
+/// assert_eq!(c[b'b' as usize], 6);
+/// assert_eq!(c[b'r' as usize + 1], 12);
+/// assert_eq!(c, c_array(b"abracadabra$"));
+/// ```
+pub fn c_array(seq: &[u8]) -> Vec<usize> {
+    c_array_from_counts(&count_symbols(seq))
+}
+
+/// Returns the C array of a text or BWT read from a stream, as [`c_array`].
+///
+/// # Arguments
+///
+/// * `rdr` - The reader of the text or its Burrows-Wheeler transform.
+///
+/// # Errors
+///
+/// An error is returned if `rdr` returns an error.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{c_array, c_array_from_reader};
+///
+/// let bwt = b"ard$rcaaaabb";
+/// assert_eq!(c_array_from_reader(&bwt[..])?, c_array(bwt));
+/// # Ok(())
+/// # }
+/// ```
+pub fn c_array_from_reader<R: Read>(mut rdr: R) -> Result<Vec<usize>> {
+    let mut counts = [0; 256];
+    let mut buf = vec![0; PACKED_BUF_LEN];
+    loop {
+        let n = rdr.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        for &c in &buf[..n] {
+            counts[c as usize] += 1;
+        }
+    }
+    Ok(c_array_from_counts(&counts))
+}
+
+/// Iterator over the runs of the first column of the sorted rotations, created by [`f_column_runs`].
+#[derive(Clone, Debug)]
+pub struct FColumnRuns<'a> {
+    windows: std::iter::Enumerate<std::slice::Windows<'a, usize>>,
+}
+
+impl Iterator for FColumnRuns<'_> {
+    type Item = (u8, Range<usize>);
+
+    fn next(&mut self) -> Option<(u8, Range<usize>)> {
+        self.windows
+            .by_ref()
+            .find(|(_, w)| w[0] < w[1])
+            .map(|(c, w)| (c as u8, w[0]..w[1]))
+    }
+}
+
+impl FusedIterator for FColumnRuns<'_> {}
+
+/// Returns an iterator over the runs of the first column of the sorted rotations,
+/// i.e., each symbol occurring in the text with the range of its rows, in increasing order.
+///
+/// The first column is determined by the symbol counts alone,
+/// so it is produced from the C array without the BWT or the text.
+///
+/// # Arguments
+///
+/// * `c` - The C array of 257 values, as returned by [`c_array`].
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::{c_array, f_column_runs};
+///
+/// let c = c_array(b"ard$rcaaaabb");
+/// let runs: Vec<_> = f_column_runs(&c).collect();
+/// assert_eq!(
+///     runs,
+///     vec![(b'$', 0..1), (b'a', 1..6), (b'b', 6..8), (b'c', 8..9), (b'd', 9..10), (b'r', 10..12)]
+/// );
+/// ```
+pub fn f_column_runs(c: &[usize]) -> FColumnRuns<'_> {
+    FColumnRuns {
+        windows: c[..c.len().min(257)].windows(2).enumerate(),
+    }
+}
+
+/// Iterator over the first column of the sorted rotations, created by [`f_column`].
+#[derive(Clone, Debug)]
+pub struct FColumn<'a> {
+    runs: FColumnRuns<'a>,
+    // The symbol of the current run and the rows left in it.
+    run: Option<(u8, Range<usize>)>,
+    len: usize,
+}
+
+impl Iterator for FColumn<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        loop {
+            if let Some((c, rows)) = self.run.as_mut() {
+                if rows.next().is_some() {
+                    self.len -= 1;
+                    return Some(*c);
+                }
+            }
+            self.run = Some(self.runs.next()?);
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl ExactSizeIterator for FColumn<'_> {}
+
+impl FusedIterator for FColumn<'_> {}
+
+/// Returns an iterator over the first column of the sorted rotations, one symbol per row,
+/// expanding [`f_column_runs`] without materializing `n` bytes.
+///
+/// # Arguments
+///
+/// * `c` - The C array of 257 values, as returned by [`c_array`].
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::{c_array, f_column};
+///
+/// let c = c_array(b"ard$rcaaaabb");
+/// let f: Vec<u8> = f_column(&c).collect();
+/// assert_eq!(f, b"$aaaaabbcdrr");
+/// ```
+pub fn f_column(c: &[usize]) -> FColumn<'_> {
+    let runs = f_column_runs(c);
+    let len = runs.clone().map(|(_, rows)| rows.len()).sum();
+    FColumn {
+        runs,
+        run: None,
+        len,
+    }
+}
+
+/// Returns the C array from the number of occurrences of each symbol.
+pub fn c_array_from_counts(counts: &[usize]) -> Vec<usize> {
+    let mut c = Vec::with_capacity(257);
+    let mut rank = 0;
+    c.push(rank);
+    for &count in counts {
+        rank += count;
+        c.push(rank);
+    }
+    c
+}
+
+/// Returns the number of occurrences of each symbol in `bwt`.
+pub fn count_symbols(bwt: &[u8]) -> [usize; 256] {
+    let mut counts = [0; 256];
+    for &c in bwt {
+        counts[c as usize] += 1;
+    }
+    counts
+}
+
+/// Writes `n` values bit-packed in the smallest number of bits that can represent `n - 1`,
+/// returning the number of bits per value.
+fn write_packed<I, W>(values: I, n: usize, mut wrt: W) -> Result<u32>
+where
+    I: Iterator<Item = usize>,
+    W: Write,
+{
+    let bits = packed_bits(n);
+    let mut buf = Vec::with_capacity(PACKED_BUF_LEN);
+    // The pending bits not yet written, which are fewer than 8 after each value.
+    let mut acc = 0u128;
+    let mut n_acc = 0;
+    for x in values {
+        acc |= (x as u128) << n_acc;
+        n_acc += bits;
+        while n_acc >= 8 {
+            buf.push(acc as u8);
+            acc >>= 8;
+            n_acc -= 8;
+        }
+        if buf.len() >= PACKED_BUF_LEN {
+            wrt.write_all(&buf)?;
+            buf.clear();
+        }
+    }
+    if n_acc != 0 {
+        buf.push(acc as u8);
+    }
+    wrt.write_all(&buf)?;
+    wrt.flush()?;
+    Ok(bits)
+}
+
+/// Returns the number of bits to represent the rows of a BWT of length `n`, which is at least one.
+fn packed_bits(n: usize) -> u32 {
+    (usize::BITS - n.saturating_sub(1).leading_zeros()).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::rank::OccTable;
+
+    fn unpack(bytes: &[u8], bits: u32, n: usize) -> Vec<usize> {
+        (0..n)
+            .map(|i| {
+                (0..bits as usize).fold(0, |x, b| {
+                    let k = i * bits as usize + b;
+                    x | usize::from(bytes[k / 8] >> (k % 8) & 1) << b
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lf_mapping() {
+        let bwts: &[&[u8]] = &[b"", b"$", b"ard$rcaaaabb", b"ipssm$pissii", &[b'a'; 300]];
+        for &bwt in bwts {
+            let occ = OccTable::new(bwt);
+            let expected: Vec<usize> = (0..bwt.len()).map(|r| occ.lf(bwt, r)).collect();
+            let lf = lf_mapping(bwt);
+            assert_eq!(lf.len(), bwt.len());
+            assert_eq!(lf.collect::<Vec<_>>(), expected);
+
+            let mut bytes = vec![];
+            write_lf_mapping(bwt, &mut bytes, IntWidth::U64).unwrap();
+            let values: Vec<usize> = bytes
+                .chunks(8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as usize)
+                .collect();
+            assert_eq!(values, expected);
+
+            let mut packed = vec![];
+            let bits = write_lf_mapping_packed(bwt, &mut packed).unwrap();
+            assert_eq!(bits, packed_bits(bwt.len()));
+            assert_eq!(packed.len(), (bwt.len() * bits as usize + 7) / 8);
+            assert_eq!(unpack(&packed, bits, bwt.len()), expected);
+        }
+    }
+
+    #[test]
+    fn test_psi() {
+        let bwts: &[&[u8]] = &[b"", b"$", b"ard$rcaaaabb", b"ipssm$pissii", &[b'a'; 300]];
+        for &bwt in bwts {
+            let mut expected = vec![0; bwt.len()];
+            for (i, r) in lf_mapping(bwt).enumerate() {
+                expected[r] = i;
+            }
+            let psi = psi(bwt);
+            assert_eq!(psi.len(), bwt.len());
+            assert_eq!(psi.collect::<Vec<_>>(), expected);
+
+            let mut bytes = vec![];
+            write_psi(bwt, &mut bytes, IntWidth::U32).unwrap();
+            let values: Vec<usize> = bytes
+                .chunks(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+                .collect();
+            assert_eq!(values, expected);
+
+            let mut packed = vec![];
+            let bits = write_psi_packed(bwt, &mut packed).unwrap();
+            assert_eq!(unpack(&packed, bits, bwt.len()), expected);
+        }
+    }
+
+    #[test]
+    fn test_c_array() {
+        let bwts: &[&[u8]] = &[b"", b"$", b"ard$rcaaaabb", &[0xff; 300]];
+        for &bwt in bwts {
+            let c = c_array(bwt);
+            assert_eq!(c.len(), 257);
+            assert_eq!(c[256], bwt.len());
+            for (s, &x) in c.iter().enumerate() {
+                assert_eq!(x, bwt.iter().filter(|&&b| (b as usize) < s).count());
+            }
+            assert_eq!(c_array_from_reader(bwt).unwrap(), c);
+        }
+    }
+
+    #[test]
+    fn test_f_column() {
+        let bwts: &[&[u8]] = &[b"", b"$", b"ard$rcaaaabb", &[0xff; 300], &[0, 0xff]];
+        for &bwt in bwts {
+            let c = c_array(bwt);
+            let mut sorted = bwt.to_vec();
+            sorted.sort_unstable();
+            let f = f_column(&c);
+            assert_eq!(f.len(), bwt.len());
+            assert_eq!(f.collect::<Vec<_>>(), sorted);
+            for (s, rows) in f_column_runs(&c) {
+                assert!(!rows.is_empty());
+                assert!(sorted[rows].iter().all(|&x| x == s));
+            }
+        }
+    }
+
+    #[test]
+    fn test_packed_bits() {
+        assert_eq!(packed_bits(0), 1);
+        assert_eq!(packed_bits(1), 1);
+        assert_eq!(packed_bits(2), 1);
+        assert_eq!(packed_bits(3), 2);
+        assert_eq!(packed_bits(256), 8);
+        assert_eq!(packed_bits(257), 9);
+        assert_eq!(packed_bits(usize::MAX), usize::BITS);
+    }
+}
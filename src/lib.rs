@@ -18,25 +18,196 @@
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! use small_bwt::BwtBuilder;
 //!
-//! // The text must end with a smallest terminal character.
+//! // The text must end with a smallest terminal character,
+//! // unless `BwtBuilder::implicit_sentinel` is set.
 //! let text = "abracadabra$";
 //!
 //! // Build the BWT.
 //! let mut bwt = vec![];
-//! BwtBuilder::new(text.as_bytes())?.build(&mut bwt)?;
+//! let stats = BwtBuilder::new(text.as_bytes())?.build(&mut bwt)?;
 //! let bwt_str = String::from_utf8_lossy(&bwt);
 //! assert_eq!(bwt_str, "ard$rcaaaabb");
+//!
+//! // The primary index is the row of the text itself in the sorted rotations.
+//! assert_eq!(stats.primary_index, 3);
+//! // The number of runs of equal symbols in the BWT.
+//! assert_eq!(stats.runs.n_runs, 8);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Arbitrary byte content
+//!
+//! A text containing every byte value, such as a binary file, has no byte available as a terminator.
+//! Such a text is supported by [`BwtBuilder::implicit_sentinel`],
+//! which transforms the text as if it were followed by a virtual terminator smaller than every byte.
+//! The BWT is decoded by [`decode_bwt_with_sentinel`].
+//!
+//! ```
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use small_bwt::{decode_bwt_with_sentinel, BwtBuilder};
+//!
+//! let text: Vec<u8> = (0..=255).chain(0..=255).collect();
+//! let mut bwt = vec![];
+//! let stats = BwtBuilder::new_unchecked(&text)?.implicit_sentinel(0).build(&mut bwt)?;
+//! assert_eq!(bwt.len(), text.len() + 1);
+//!
+//! let decoded = decode_bwt_with_sentinel(&bwt, stats.primary_index)?;
+//! assert_eq!(decoded, text);
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! If the text has an unused byte smaller than all of its bytes,
+//! [`BwtBuilder::with_terminator`] appends it as an ordinary terminator instead.
+//!
+//! ## Malformed input
+//!
+//! Malformed input, such as a corrupted BWT file or a sequence that is not a valid BWT,
+//! is reported as an [`Error`] rather than a panic,
+//! so that user-supplied data can be processed in a long-running process such as a server.
+//! The exceptions are the accessors documented with a `# Panics` section,
+//! such as [`WaveletTree::access`], whose positions must be bounded by the caller as in slice indexing.
+//!
+//! ## Features
+//!
+//! - `fasta`: Enables [`parse_fasta`] to prepare a text from FASTA records.
+//! - `rayon`: Enables [`BwtBuilder::parallel`] to process cuts in parallel,
+//!   [`BlockBwtWriter::parallel`] to transform blocks in parallel,
+//!   and [`decode_bwt_parallel`] to decode a BWT in parallel.
+//! - `mmap`: Enables [`BwtBuilder::from_mmap`] to transform a memory-mapped file,
+//!   [`BwtBuilder::from_reader`] to transform a stream through a temporary one,
+//!   and [`BwtBuilder::build_into_file`] to build the BWT into one.
+//! - `tracing`: Emits the progress and statistics as [tracing](https://docs.rs/tracing) events,
+//!   with spans per phase of the construction.
+//! - `serde`: Derives [serde](https://docs.rs/serde) traits for [`BuildStats`] and the index structures,
+//!   such as [`FmIndex`] with its suffix array samples, to persist them in any format.
+//! - `sais`: Enables [`BwtBuilder::sais`] to build the suffix array by SA-IS when the memory is plentiful,
+//!   which also cross-validates the small-space algorithm in tests.
 #![deny(missing_docs)]
+mod alphabet;
+mod background;
+mod bcr;
+mod block;
+mod boss;
+mod checkpoint;
+mod compress;
+mod concat;
+mod container;
+mod csa;
+mod cuts;
+mod decode;
+mod error;
+#[cfg(feature = "fasta")]
+mod fasta;
+mod fmindex;
+mod index;
+mod indexed;
+mod intio;
+mod kbwt;
+mod lcp;
+mod lf;
+mod merge;
+mod moves;
+mod nrun;
+mod packed;
+mod partial;
+mod pfp;
+mod prepare;
+mod progress;
 mod radixsort;
+mod rank;
+mod reader;
+mod rlbwt;
+mod ropebwt;
+mod sais;
+mod sorter;
+mod spill;
+mod stats;
+mod symbol;
+mod text;
+mod tunnel;
+mod verify;
+mod wavelet;
 
-use std::io::Write;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::io::{Seek, Write};
+use std::ops::{Range, RangeInclusive};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Result};
+use cuts::{CutGenerator, MAX_CUT_LEN, MAX_KMER_LEN, MAX_OVERSIZE_RATIO};
+use index::{SuffixIndex, U40};
+use intio::{IntArrayWriter, IntWriter};
+use nrun::{NRunText, SpliceCursor};
+use pfp::{PfpParams, PrefixFreeParse};
+use progress::Progress;
+use radixsort::{MsdRadixSorter, SortWorkspace};
+use sorter::TextView;
+use spill::SpilledChunks;
+use stats::RunCounter;
+use text::{suffix_positions, TextAccess};
 
-use radixsort::MsdRadixSorter;
+pub use alphabet::{Alphabet, AlphabetPreset};
+#[cfg(feature = "tokio")]
+pub use background::build_async;
+pub use background::BwtChunks;
+pub use bcr::{read_bcr_parts, write_bcr_parts};
+pub use block::{rotation_bwt, BlockBwtReader, BlockBwtWriter};
+pub use boss::Boss;
+pub use checkpoint::Checkpoint;
+pub use compress::{
+    HuffmanReader, HuffmanWriter, MtfReader, MtfWriter, ZeroRunReader, ZeroRunWriter,
+};
+pub use concat::{concat_inputs, ConcatText, CONCAT_TERMINATOR};
+pub use container::{BwtFileReader, BwtFileWriter, BwtHeader};
+pub use csa::CompressedSuffixArray;
+#[cfg(feature = "rayon")]
+pub use decode::decode_bwt_parallel;
+pub use decode::{
+    decode_bwt, decode_bwt_from_reader, decode_bwt_small_space, decode_bwt_string, decode_bwt_to,
+    decode_bwt_with_index, decode_bwt_with_sentinel, validate_bwt, BwtProperties,
+};
+pub use error::{Error, Result};
+#[cfg(feature = "fasta")]
+pub use fasta::{
+    parse_fasta, parse_fasta_fmd, reverse_complement, FastaRecord, FastaText, FASTA_TERMINATOR,
+};
+pub use fmindex::{
+    ApproximateMatch, BiRange, BidirectionalFmIndex, FmIndex, MatchingStatistic, Mem,
+};
+pub use indexed::IndexedBwt;
+pub use intio::IntWidth;
+pub use kbwt::{context_bwt, decode_context_bwt};
+pub use lcp::{
+    count_distinct_substrings, decode_lz77, longest_common_substrings, longest_repeated_substrings,
+    lpf_array, lz77_factorize, CommonSubstring, CommonSubstrings, LzFactor, Repeat,
+};
+pub use lf::{
+    c_array, c_array_from_reader, f_column, f_column_runs, lf_mapping, psi, write_lf_mapping,
+    write_lf_mapping_packed, write_psi, write_psi_packed, FColumn, FColumnRuns, LfMapping, Psi,
+};
+pub use merge::merge_bwts;
+pub use moves::{MoveStructure, MoveStructureBuilder};
+pub use packed::{PackedDna, PACKED_DNA_TERMINATOR};
+pub use partial::{assemble_partials, CutSet, PartialBwtWriter, PartialHeader};
+pub use prepare::TextPreparer;
+pub use progress::{ChunkProgress, ProgressHandler};
+pub use reader::BwtReader;
+pub use rlbwt::{RlbwtReader, RlbwtWriter};
+pub use ropebwt::{RopebwtRleReader, RopebwtRleWriter};
+pub use sorter::{MsdRadixSort, MultikeyQuicksort, SuffixSorter, SuffixText};
+pub use stats::{
+    bwt_run_histogram, BuildStats, EntropyStats, RunHistogram, RunHistogramBuilder, RunStats,
+};
+pub use symbol::Symbol;
+pub use tunnel::{tunnel_bwt, TunneledBwt};
+pub use verify::verify_suffix_array;
+pub use wavelet::{WaveletTree, WaveletTreeBuilder};
 
 /// BWT builder in small space.
 ///
@@ -47,39 +218,332 @@ use radixsort::MsdRadixSorter;
 /// # Requirements
 ///
 /// This assumes that the smallest character appears only at the end of the text.
+/// [`BwtBuilder::new`] verifies it with [`verify_terminator`],
+/// while the other constructors such as [`BwtBuilder::new_unchecked`] do not.
 /// Given an unexpected text, the behavior is undefined.
-/// If you want to verify the text, use [`verify_terminator`].
+/// If the text may contain the terminator, use [`TextPreparer`] to escape it.
 ///
 /// # Examples
 ///
 /// See [the top page](crate).
 pub struct BwtBuilder<'a> {
-    text: &'a [u8],
+    text: Text<'a>,
     chunk_size: usize,
-    progress: Progress,
+    progress: Progress<'a>,
+    aux: RefCell<AuxWriters<'a>>,
+    reverse_wrt: Option<RefCell<Box<dyn Write + Send + 'a>>>,
+    chunk_opts: ChunkOptions<'a>,
+    kmer_len: usize,
+    // The terminator appended by Self::with_terminator.
+    terminator: Option<u8>,
+    remap_alphabet: bool,
+    // The alphabet given by Self::remap_with_alphabet.
+    remap_to: Option<Alphabet>,
+    // The minimum length of the runs of N handled separately.
+    min_n_run_len: Option<usize>,
+    // The cuts given by Self::cuts.
+    cuts: Option<Vec<Vec<u8>>>,
+    // The number of symbols by which the rotations are sorted, given by Self::context_order.
+    context_order: Option<usize>,
 }
 
 impl<'a> BwtBuilder<'a> {
-    /// Creates a new builder.
+    /// Creates a new builder, verifying the text with [`verify_terminator`].
+    ///
+    /// The verification is a single scan of the text, which is cheap relative to the construction.
+    /// Use [`Self::new_unchecked`] if the text has already been verified
+    /// or [`Self::implicit_sentinel`] is set.
     ///
     /// # Arguments
     ///
-    /// * `text` - The text to be transformed, which should satisfy [`verify_terminator`].
+    /// * `text` - The text to be transformed.
     ///
     /// # Errors
     ///
-    /// An error is returned if `text` is empty.
+    /// An [`Error::EmptyText`] error is returned if `text` is empty,
+    /// and an [`Error::InvalidTerminator`] error is returned if
+    /// the smallest character does not appear only at the end of `text`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use small_bwt::{BwtBuilder, Error};
+    ///
+    /// assert!(BwtBuilder::new(b"abracadabra$").is_ok());
+    /// assert!(matches!(
+    ///     BwtBuilder::new(b"abrac$dabra$"),
+    ///     Err(Error::InvalidTerminator { pos: 5, byte: b'$' })
+    /// ));
+    /// ```
     pub fn new(text: &'a [u8]) -> Result<Self> {
+        verify_terminator(text)?;
+        Self::new_unchecked(text)
+    }
+
+    /// Creates a new builder without verifying the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to be transformed, which should satisfy [`verify_terminator`]
+    ///   unless [`Self::implicit_sentinel`] is set.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `text` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::new_unchecked(b"abracadabra")?
+    ///     .implicit_sentinel(b'$')
+    ///     .build(&mut bwt)?;
+    /// assert_eq!(stats.primary_index, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_unchecked(text: &'a [u8]) -> Result<Self> {
+        Self::from_text(Text::Borrowed(text), 1)
+    }
+
+    /// Creates a new builder over a text of integer symbols, such as a tokenized corpus,
+    /// whose BWT is written as the big-endian bytes of its symbols.
+    ///
+    /// The symbols are copied to their big-endian bytes in additional `n * S::BYTES` bytes,
+    /// whose suffixes starting at symbol boundaries are sorted as the suffixes of the symbols.
+    /// The positions written to the auxiliary writers are in symbols.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to be transformed, which should end with a unique smallest symbol
+    ///   unless [`Self::implicit_sentinel`] is set.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `text` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, Symbol};
+    ///
+    /// // "abracadabra$" as tokens larger than bytes.
+    /// let text: Vec<u16> = vec![300, 301, 317, 300, 302, 300, 303, 300, 301, 317, 300, 0];
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::from_symbols(&text)?.build(&mut bwt)?;
+    /// let bwt: Vec<u16> = bwt.chunks(2).map(u16::from_be_slice).collect();
+    /// assert_eq!(bwt, [300, 317, 303, 0, 317, 302, 300, 300, 300, 300, 301, 301]);
+    /// assert_eq!(stats.text_len, 12);
+    /// assert_eq!(stats.primary_index, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_symbols<S: Symbol>(text: &[S]) -> Result<Self> {
+        let mut bytes = Vec::with_capacity(text.len() * S::BYTES);
+        for &c in text {
+            c.extend_be_bytes(&mut bytes);
+        }
+        Self::from_text(Text::Owned(bytes), S::BYTES)
+    }
+
+    /// Creates a new builder over a nucleotide text packed in 2 bits per base,
+    /// which is transformed without unpacking it.
+    ///
+    /// The text ends with [`PACKED_DNA_TERMINATOR`], so [`Self::implicit_sentinel`] and
+    /// [`Self::remap_alphabet`] cannot be set.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The packed text to be transformed.
+    ///
+    /// # Errors
+    ///
+    /// This never returns an error for now, since a packed text is never empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, PackedDna};
+    ///
+    /// let text = PackedDna::from_acgt(b"GATTACA")?;
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::from_packed_dna(&text)?.build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ACTGA\0TA");
+    /// assert_eq!(stats.primary_index, 5);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_packed_dna(text: &'a PackedDna) -> Result<Self> {
+        Self::from_text(Text::Packed(text), 1)
+    }
+
+    /// Creates a new builder over a copy of `text` followed by a terminator chosen by [`choose_terminator`],
+    /// for a text that does not end with its own terminator.
+    ///
+    /// The text is copied in additional `n` bytes to append the terminator,
+    /// which is reported in [`BuildStats::terminator`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to be transformed without a terminator.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `text` is empty or has no byte smaller than all of its bytes,
+    /// i.e., it contains `0x00`, in which case [`Self::implicit_sentinel`] can be used instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let text = "abracadabra";
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::with_terminator(text.as_bytes())?.build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard\0rcaaaabb");
+    /// assert_eq!(stats.terminator, Some(0));
+    /// assert_eq!(stats.text_len, 12);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_terminator(text: &[u8]) -> Result<Self> {
         if text.is_empty() {
-            return Err(anyhow!("text must not be empty."));
+            return Err(Error::EmptyText);
+        }
+        let terminator = choose_terminator(text).ok_or_else(|| {
+            Error::invalid_argument(
+                "text must have a byte smaller than all of its bytes, but contains 0x00.",
+            )
+        })?;
+        let mut owned = Vec::with_capacity(text.len() + 1);
+        owned.extend_from_slice(text);
+        owned.push(terminator);
+        let mut builder = Self::from_text(Text::Owned(owned), 1)?;
+        builder.terminator = Some(terminator);
+        Ok(builder)
+    }
+
+    /// Creates a new builder over a memory-mapped file,
+    /// so that a text larger than the main memory can be transformed with the OS paging it.
+    ///
+    /// The text is mostly accessed by sequential scans, which suits memory mapping.
+    /// The file must not be modified while the builder is alive.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the text file, which should satisfy [`verify_terminator`].
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the file cannot be mapped or the file is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let path = std::env::temp_dir().join("small_bwt_from_mmap_doctest.txt");
+    /// std::fs::write(&path, "abracadabra$")?;
+    ///
+    /// let mut bwt = vec![];
+    /// BwtBuilder::from_mmap(&path)?.build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: The file is assumed not to be modified during the construction, as documented.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Self::from_text(Text::Mapped(MappedText { mmap, _temp: None }), 1)
+    }
+
+    /// Creates a new builder over the text read from `rdr`, such as a pipe or an object store,
+    /// without holding the text in memory.
+    ///
+    /// The text is streamed into a temporary file in `dir` in a single pass,
+    /// which is memory-mapped as in [`Self::from_mmap`] for the scans of cut generation and chunk gathering,
+    /// and removed when the builder is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `rdr` - The reader of the text, which should satisfy [`verify_terminator`]
+    ///   unless [`Self::implicit_sentinel`] is set.
+    /// * `dir` - The directory to write the temporary file.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `rdr` returns an error, the temporary file cannot be written or mapped,
+    /// or the text is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let rdr = "abracadabra$".as_bytes();
+    /// let mut bwt = vec![];
+    /// BwtBuilder::from_reader(rdr, &std::env::temp_dir())?.build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn from_reader<R: std::io::Read>(mut rdr: R, dir: &Path) -> Result<Self> {
+        let (temp, mut file) = spill::TempFile::create(dir)?;
+        let mut wrt = std::io::BufWriter::new(&mut file);
+        std::io::copy(&mut rdr, &mut wrt)?;
+        wrt.flush()?;
+        drop(wrt);
+        if file.metadata()?.len() == 0 {
+            return Err(Error::EmptyText);
+        }
+        // SAFETY: The temporary file is owned by the builder and never modified.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Self::from_text(
+            Text::Mapped(MappedText {
+                mmap,
+                _temp: Some(temp),
+            }),
+            1,
+        )
+    }
+
+    /// Creates a new builder over a text of symbols of `stride` bytes each.
+    fn from_text(text: Text<'a>, stride: usize) -> Result<Self> {
+        if text.len() == 0 {
+            return Err(Error::EmptyText);
         }
-        let n = text.len() as f64;
-        let chunk_size = (n / n.log2()).ceil() as usize;
+        let n = (text.len() / stride) as f64;
+        let chunk_size = (n / n.log2().max(1.0)).ceil() as usize;
         let chunk_size = chunk_size.max(1);
         Ok(Self {
             text,
             chunk_size,
             progress: Progress::new(false),
+            aux: RefCell::new(AuxWriters::default()),
+            reverse_wrt: None,
+            chunk_opts: ChunkOptions {
+                stride,
+                ..ChunkOptions::default()
+            },
+            kmer_len: 0,
+            terminator: None,
+            remap_alphabet: false,
+            remap_to: None,
+            min_n_run_len: None,
+            cuts: None,
+            context_order: None,
         })
     }
 
@@ -99,7 +563,7 @@ impl<'a> BwtBuilder<'a> {
     #[doc(hidden)]
     pub fn chunk_size(mut self, chunk_size: usize) -> Result<Self> {
         if chunk_size == 0 {
-            return Err(anyhow!("chunk_size must be positive."));
+            return Err(Error::invalid_argument("chunk_size must be positive."));
         }
         self.chunk_size = chunk_size;
         Ok(self)
@@ -120,316 +584,4199 @@ impl<'a> BwtBuilder<'a> {
         self
     }
 
-    /// Builds the BWT and writes it to `wrt`.
+    /// Sets a handler to be notified of the progress, instead of printing it by [`Self::verbose`].
     ///
     /// # Arguments
     ///
-    /// * `wrt` - The writer to write the BWT.
+    /// * `handler` - The progress handler.
+    ///
+    /// # Default value
+    ///
+    /// The progress is not notified.
+    ///
+    /// # Examples
+    ///
+    /// See [`ProgressHandler`].
+    pub const fn progress_handler(mut self, handler: &'a dyn ProgressHandler) -> Self {
+        self.progress = Progress::with_handler(handler);
+        self
+    }
+
+    /// Sets a strategy to sort the suffixes in each chunk, such as [`MultikeyQuicksort`]
+    /// or one provided by a downstream crate.
+    ///
+    /// The suffix positions of a chunk are passed to the sorter as `usize`,
+    /// which may take more space than the built-in one storing them in fewer bytes.
+    /// The large buckets are not sorted in parallel even if [`Self::parallel`] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `sorter` - The suffix sorter.
+    ///
+    /// # Default value
+    ///
+    /// The built-in [`MsdRadixSort`] with the default threshold.
+    ///
+    /// # Examples
+    ///
+    /// See [`SuffixSorter`].
+    pub const fn suffix_sorter(mut self, sorter: &'a dyn SuffixSorter) -> Self {
+        self.chunk_opts.sorter = Some(sorter);
+        self
+    }
+
+    /// Sets whether to gather and sort the chunks of several cuts in parallel
+    /// using the global thread pool of [rayon](https://docs.rs/rayon).
+    /// Large buckets in each chunk are also sorted in parallel.
+    /// The output is still written in the order of cuts.
+    ///
+    /// If `parallel` is `true`, the chunks of as many cuts as the number of threads
+    /// are held in memory at once, so the working space grows proportionally.
+    ///
+    /// # Arguments
+    ///
+    /// * `parallel` - Whether to process cuts in parallel.
+    ///
+    /// # Default value
+    ///
+    /// `false`
+    #[cfg(feature = "rayon")]
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.chunk_opts.n_threads = if parallel {
+            rayon::current_num_threads()
+        } else {
+            1
+        };
+        self
+    }
+
+    /// Sets whether to remap the text to its effective alphabet `[0, σ)` before the construction,
+    /// where `σ` is the number of distinct bytes in the text.
+    ///
+    /// The mapping preserves the order of bytes, so the BWT and the auxiliary outputs
+    /// are those of the original text with every symbol replaced by its code.
+    /// The mapping is reported in [`BuildStats::alphabet`] to restore the symbols,
+    /// and its codes fit in [`Alphabet::bits_per_symbol`] bits for packed representations.
+    /// The remapped text is materialized in additional `n` bytes.
+    /// If [`Self::implicit_sentinel`] is also set, its placeholder is written as it is, without remapping.
+    ///
+    /// # Arguments
+    ///
+    /// * `remap_alphabet` - Whether to remap the text.
+    ///
+    /// # Default value
+    ///
+    /// `false`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let text = "GATTACA$";
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::new(text.as_bytes())?
+    ///     .remap_alphabet(true)
+    ///     .build(&mut bwt)?;
+    /// assert_eq!(bwt, [1, 2, 4, 3, 1, 0, 4, 1]);
+    ///
+    /// let alphabet = stats.alphabet.unwrap();
+    /// assert_eq!(alphabet.symbols(), b"$ACGT");
+    /// assert_eq!(alphabet.decode_all(&bwt), b"ACTGA$TA");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub const fn remap_alphabet(mut self, remap_alphabet: bool) -> Self {
+        self.remap_alphabet = remap_alphabet;
+        self
+    }
+
+    /// Sets the alphabet to which the text is remapped, instead of its effective alphabet
+    /// computed by [`Self::remap_alphabet`], such as [`AlphabetPreset::alphabet`].
+    ///
+    /// The codes are the same for any text, so that the BWTs of different texts are comparable.
+    /// [`Self::build`] returns an error if the text has a byte not in the alphabet.
+    ///
+    /// # Arguments
+    ///
+    /// * `alphabet` - The alphabet including all the bytes of the text.
+    ///
+    /// # Default value
+    ///
+    /// The text is not remapped unless [`Self::remap_alphabet`] is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{Alphabet, BwtBuilder};
+    ///
+    /// let alphabet = Alphabet::from_text(b"$ACGT");
+    /// let mut bwt = vec![];
+    /// BwtBuilder::new(b"GAGA$")?
+    ///     .remap_with_alphabet(alphabet.clone())
+    ///     .build(&mut bwt)?;
+    /// assert_eq!(bwt, [1, 3, 3, 1, 0]);
+    ///
+    /// let result = BwtBuilder::new(b"GANA$")?
+    ///     .remap_with_alphabet(alphabet)
+    ///     .build(&mut bwt);
+    /// assert!(result.is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn remap_with_alphabet(mut self, alphabet: Alphabet) -> Self {
+        self.remap_to = Some(alphabet);
+        self
+    }
+
+    /// Sets the minimum length of the runs of `N` handled separately, such as gaps in genomes.
+    ///
+    /// A long run of `N` makes the cut generator expand its prefixes level by level
+    /// and the sorter compare its suffixes byte by byte.
+    /// If it is set, the suffixes starting inside the maximal runs of at least `min_len` bytes
+    /// are excluded from the cuts and spliced into the output in their sorted order,
+    /// and suffixes are compared by skipping the runs, producing the same outputs.
+    /// The detected runs are reported in [`BuildStats::n_runs`].
+    ///
+    /// This is not supported for the texts given to [`Self::from_symbols`] and [`Self::from_packed_dna`].
+    ///
+    /// # Arguments
+    ///
+    /// * `min_len` - The minimum length of the runs.
+    ///
+    /// # Default value
+    ///
+    /// The runs are not handled separately.
     ///
     /// # Errors
     ///
-    /// An error is returned if `wrt` returns an error.
-    pub fn build<W: Write>(&self, wrt: W) -> Result<()> {
-        assert!(!self.text.is_empty());
-        assert_ne!(self.chunk_size, 0);
+    /// An error is returned if `min_len` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let text = "ACNNNNNNGTNNA$";
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::new(text.as_bytes())?
+    ///     .skip_n_runs(4)?
+    ///     .build(&mut bwt)?;
+    /// assert_eq!(stats.n_runs, [2..8]);
+    ///
+    /// let mut expected = vec![];
+    /// BwtBuilder::new(text.as_bytes())?.build(&mut expected)?;
+    /// assert_eq!(bwt, expected);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn skip_n_runs(mut self, min_len: usize) -> Result<Self> {
+        if min_len == 0 {
+            return Err(Error::invalid_argument("min_len must not be zero."));
+        }
+        self.min_n_run_len = Some(min_len);
+        Ok(self)
+    }
 
-        let text = self.text;
-        let chunk_size = self.chunk_size;
-        let n_expected_cuts = text.len() / chunk_size;
+    /// Sets the order `k` to build the context-bounded BWT, often called the k-BWT,
+    /// sorting the rotations of the text only by their first `k` symbols instead of the suffixes,
+    /// as [`context_bwt`].
+    ///
+    /// It takes `O(kn)` time in `16n` bytes of space without cuts,
+    /// which is faster than the full BWT for a small `k`, at the cost of compressibility.
+    /// The order is reported in [`BuildStats::context_order`] and recorded by [`BwtFileWriter`],
+    /// and the BWT is decoded by [`decode_context_bwt`].
+    /// It cannot be combined with the auxiliary writers, the reverse writer, an implicit sentinel,
+    /// skipping runs of N, a range of cuts, checkpoints, SA-IS, or prefix-free parsing,
+    /// and is not supported for the texts given to [`Self::from_symbols`] and [`Self::from_packed_dna`].
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - The number of the symbols by which the rotations are sorted.
+    ///
+    /// # Default value
+    ///
+    /// The full BWT is built.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `k` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{decode_context_bwt, BwtBuilder};
+    ///
+    /// let text = b"abracadabra$";
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::new(text)?.context_order(2)?.build(&mut bwt)?;
+    /// assert_eq!(stats.context_order, Some(2));
+    /// assert_eq!(decode_context_bwt(&bwt, stats.primary_index, 2)?, text);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn context_order(mut self, k: usize) -> Result<Self> {
+        if k == 0 {
+            return Err(Error::invalid_argument("k must not be zero."));
+        }
+        self.context_order = Some(k);
+        Ok(self)
+    }
 
-        self.progress
-            .print(&format!("Text length: {:?} MiB", to_mib(text.len())));
-        self.progress
-            .print(&format!("Chunk size: {:?} M", to_mb(chunk_size)));
-        self.progress
-            .print(&format!("Expected number of cuts: {:?}", n_expected_cuts));
+    /// Sets whether to bucket all the suffixes into chunks in a single scan of the text.
+    ///
+    /// By default, the text is scanned once per cut to gather the suffixes of its chunk,
+    /// keeping only one chunk in memory.
+    /// If `single_pass` is `true`, every suffix is assigned to its chunk by binary search on the cuts
+    /// in one scan, which reduces the construction time at the cost of storing all the `n` suffix positions.
+    ///
+    /// # Arguments
+    ///
+    /// * `single_pass` - Whether to bucket suffixes in a single scan.
+    ///
+    /// # Default value
+    ///
+    /// `false`
+    pub const fn single_pass(mut self, single_pass: bool) -> Self {
+        self.chunk_opts.single_pass = single_pass;
+        self
+    }
 
-        self.progress.print("Generating cuts...");
-        let cuts = CutGenerator::generate(text, chunk_size);
-        self.progress
-            .print(&format!("Actual number of cuts: {:?}", cuts.len()));
+    /// Sets whether to bucket the suffixes of a large chunk by their first two bytes,
+    /// i.e., into 65,536 buckets, at the top level of the chunk sort.
+    ///
+    /// This shrinks the recursion depth of the MSD radix sort and improves the locality on large chunks,
+    /// at the cost of a counter array of about 0.5 MiB per chunk being sorted.
+    /// Chunks smaller than 65,536 suffixes and the lower levels are still bucketed by one byte.
+    /// It is ignored if [`Self::suffix_sorter`] is set, where [`MsdRadixSort::wide_radix`] can be used instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `wide_radix` - Whether to use 16-bit radix at the top level.
+    ///
+    /// # Default value
+    ///
+    /// `false`
+    pub const fn wide_radix(mut self, wide_radix: bool) -> Self {
+        self.chunk_opts.wide_radix = wide_radix;
+        self
+    }
 
-        bwt_from_cuts(text, &cuts, wrt, &self.progress)
+    /// Sets whether to gather the suffixes of the next chunk in another thread
+    /// while the current chunk is sorted and written.
+    ///
+    /// This overlaps the scan of the text, which is memory-bound, with the sort, which is CPU-bound,
+    /// at the cost of holding two chunks in memory.
+    /// It applies only to the default mode gathering the chunks one by one,
+    /// and is ignored with [`Self::parallel`], [`Self::single_pass`], and [`Self::external_memory`].
+    ///
+    /// # Arguments
+    ///
+    /// * `pipeline` - Whether to gather and sort chunks in a pipeline of two threads.
+    ///
+    /// # Default value
+    ///
+    /// `false`
+    pub const fn pipeline(mut self, pipeline: bool) -> Self {
+        self.chunk_opts.pipeline = pipeline;
+        self
     }
-}
 
-fn bwt_from_cuts<W: Write>(
-    text: &[u8],
-    cuts: &[Vec<u8>],
-    mut wrt: W,
-    progress: &Progress,
-) -> Result<()> {
-    assert!(cuts[0].is_empty());
-    let mut chunks = vec![];
-    for q in 1..=cuts.len() {
-        progress.print(&format!("Generating BWT: {}/{}", q, cuts.len()));
-        progress.print(&format!("Length of the cut: {:?}", cuts[q - 1].len()));
-
-        let cut_p = cuts[q - 1].as_slice();
-        if q < cuts.len() {
-            let cut_q = cuts[q].as_slice();
-            for j in 0..text.len() {
-                let suffix = &text[j..];
-                if cut_p < suffix && suffix <= cut_q {
-                    chunks.push(j);
-                }
-            }
-        } else {
-            for j in 0..text.len() {
-                let suffix = &text[j..];
-                if cut_p < suffix {
-                    chunks.push(j);
-                }
+    /// Sets whether to sort all the suffixes at once by SA-IS instead of the small-space algorithm,
+    /// which is faster when the memory is plentiful.
+    ///
+    /// If `sais` is `true`, the suffix array is built in `O(n)` time and `O(n)` words of space,
+    /// and the options on cuts and chunks, such as [`Self::parallel`], [`Self::single_pass`],
+    /// [`Self::kmer_table`], and [`Self::external_memory`], are ignored.
+    /// The outputs are the same as those of the small-space algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `sais` - Whether to build the suffix array by SA-IS.
+    ///
+    /// # Default value
+    ///
+    /// `false`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::new(b"abracadabra$")?.sais(true).build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// assert_eq!(stats.n_cuts, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "sais")]
+    pub const fn sais(mut self, sais: bool) -> Self {
+        self.chunk_opts.sais = sais;
+        self
+    }
+
+    /// Sets the algorithm to prefix-free parsing as in Big-BWT, for highly repetitive texts
+    /// such as pangenomes and versioned documents, where the cuts and the chunk sorts degrade.
+    ///
+    /// The text is split into phrases at trigger windows of `window` bytes whose fingerprints are
+    /// divisible by `modulus`, and its suffixes are sorted from the distinct phrases and the sequence
+    /// of phrases, in space proportional to their sizes, which are much smaller than the text if it is repetitive.
+    /// The sorted suffixes are written in batches of the chunk size.
+    /// The options on cuts and chunks, such as [`Self::parallel`], [`Self::single_pass`],
+    /// [`Self::kmer_table`], and [`Self::external_memory`], are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The length of the windows, such as 10.
+    /// * `modulus` - The modulus selecting the trigger windows, such as 100,
+    ///   i.e., the expected length of phrases.
+    ///
+    /// # Default value
+    ///
+    /// The small-space algorithm with cuts is used.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `window` or `modulus` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let text = [&b"GATTACA".repeat(100)[..], b"$"].concat();
+    /// let mut expected = vec![];
+    /// BwtBuilder::new(&text)?.build(&mut expected)?;
+    ///
+    /// let mut bwt = vec![];
+    /// BwtBuilder::new(&text)?
+    ///     .prefix_free_parsing(4, 10)?
+    ///     .build(&mut bwt)?;
+    /// assert_eq!(bwt, expected);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prefix_free_parsing(mut self, window: usize, modulus: usize) -> Result<Self> {
+        if window == 0 || modulus == 0 {
+            return Err(Error::invalid_argument(
+                "window and modulus must be positive.",
+            ));
+        }
+        self.chunk_opts.pfp = Some(PfpParams {
+            window,
+            modulus,
+            batch_size: 0,
+        });
+        Ok(self)
+    }
+
+    /// Sets the length of k-mers whose frequencies are precomputed to generate cuts.
+    ///
+    /// The cuts are generated by counting the symbols following each candidate prefix,
+    /// scanning the text once per prefix length.
+    /// If `kmer_len` is positive, the frequencies of all the k-mers up to `kmer_len` are counted
+    /// in a single scan beforehand, and the prefixes shorter than `kmer_len` are answered from them.
+    /// The table takes `256^kmer_len` words, e.g., 128 MiB for `kmer_len = 3` on 64-bit machines.
+    ///
+    /// # Arguments
+    ///
+    /// * `kmer_len` - The maximum length of precomputed k-mers.
+    ///
+    /// # Default value
+    ///
+    /// `0`, i.e., no table is precomputed.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `kmer_len` is greater than 3.
+    pub fn kmer_table(mut self, kmer_len: usize) -> Result<Self> {
+        if kmer_len > MAX_KMER_LEN {
+            return Err(Error::invalid_argument(format!(
+                "kmer_len must be no greater than {MAX_KMER_LEN}, but got {kmer_len}."
+            )));
+        }
+        self.kmer_len = kmer_len;
+        Ok(self)
+    }
+
+    /// Generates the cuts partitioning the suffixes into chunks of about the chunk size,
+    /// which can be inspected, persisted, or tuned and passed to [`Self::cuts`].
+    ///
+    /// The `q`-th chunk has the suffixes greater than the `q-1`-th cut and no greater than the `q`-th one,
+    /// where the first cut is empty and the last chunk has all the suffixes greater than the last cut.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if remapping the alphabet or skipping runs of N is set,
+    /// which transforms the text before the cuts are generated,
+    /// or if a prefix occurs too often to be cut, as in a periodic text such as `a^n$`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let builder = BwtBuilder::new(b"abracadabra$")?.chunk_size(3)?;
+    /// let cuts = builder.generate_cuts()?;
+    /// assert_eq!(cuts, [&b""[..], b"ab", b"ad", b"c", b"r"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn generate_cuts(&self) -> Result<Vec<Vec<u8>>> {
+        if self.remap_alphabet || self.remap_to.is_some() || self.min_n_run_len.is_some() {
+            return Err(Error::invalid_argument(
+                "cuts cannot be generated before remapping or skipping runs of N.",
+            ));
+        }
+        let (chunk_size, kmer_len, stride) =
+            (self.chunk_size, self.kmer_len, self.chunk_opts.stride);
+        let cuts = match &self.text {
+            Text::Borrowed(text) => CutGenerator::generate(*text, chunk_size, kmer_len, stride),
+            Text::Owned(text) => {
+                CutGenerator::generate(text.as_slice(), chunk_size, kmer_len, stride)
+            }
+            #[cfg(feature = "mmap")]
+            Text::Mapped(mmap) => CutGenerator::generate(&mmap[..], chunk_size, kmer_len, stride),
+            Text::Packed(packed) => CutGenerator::generate(*packed, chunk_size, kmer_len, stride),
+        };
+        cuts.ok_or_else(|| {
+            Error::invalid_argument(format!(
+                "a prefix of {MAX_CUT_LEN} bytes occurs more than {MAX_OVERSIZE_RATIO} times the chunk size."
+            ))
+        })
+    }
+
+    /// Sets the cuts partitioning the suffixes into chunks, instead of generating them,
+    /// such as the ones from [`Self::generate_cuts`] cached for the same text.
+    ///
+    /// The chunks are as described in [`Self::generate_cuts`],
+    /// where the cuts are compared with the text after remapping the alphabet if set.
+    /// Any cuts give the same BWT, but the chunk sizes depend on them.
+    /// The cuts are ignored by SA-IS, prefix-free parsing, and the BWT of the reversed text.
+    ///
+    /// # Arguments
+    ///
+    /// * `cuts` - The cuts in strictly increasing order, starting with the empty one.
+    ///
+    /// # Default value
+    ///
+    /// The cuts are generated from the chunk size.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the first cut is not empty or the cuts are not strictly increasing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::new(b"abracadabra$")?
+    ///     .cuts(vec![b"".to_vec(), b"b".to_vec(), b"r".to_vec()])?
+    ///     .build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// assert_eq!(stats.n_cuts, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cuts(mut self, cuts: Vec<Vec<u8>>) -> Result<Self> {
+        if cuts.first().map_or(true, |cut| !cut.is_empty()) {
+            return Err(Error::invalid_argument("the first cut must be empty."));
+        }
+        if cuts.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(Error::invalid_argument(
+                "cuts must be in strictly increasing order.",
+            ));
+        }
+        self.cuts = Some(cuts);
+        Ok(self)
+    }
+
+    /// Builds only the chunks of the cuts in `range`, writing the contiguous rows of the BWT in them,
+    /// such as to resume a construction, to distribute it across processes, or to debug a chunk.
+    ///
+    /// The chunk of the `i`-th cut has the suffixes greater than it and no greater than the `i+1`-th one,
+    /// as described in [`Self::generate_cuts`], so the outputs of consecutive ranges can be concatenated.
+    /// The rows written are returned in [`BuildStats::rows`],
+    /// and the positions in the auxiliary outputs, such as the ranks of the inverse suffix array,
+    /// are those of the whole BWT.
+    /// The cuts should be fixed by [`Self::cuts`] for the ranges of several builds to be consistent.
+    ///
+    /// It takes an additional scan of the text to count the rows before the range,
+    /// and another one to find the primary index if it is out of the range.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The range of the indices of cuts, which must be within the number of cuts.
+    ///
+    /// # Default value
+    ///
+    /// The chunks of all the cuts are built.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `range` is empty.
+    /// [`Self::build`] returns an error if `range` exceeds the number of cuts,
+    /// or if the reverse writer, skipping runs of N, SA-IS, or prefix-free parsing is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let text = b"abracadabra$";
+    /// let cuts = BwtBuilder::new(text)?.chunk_size(3)?.generate_cuts()?;
+    /// assert_eq!(cuts.len(), 5);
+    ///
+    /// let mut bwt = vec![];
+    /// for range in [0..2, 2..5] {
+    ///     let stats = BwtBuilder::new(text)?
+    ///         .cuts(cuts.clone())?
+    ///         .cut_range(range)?
+    ///         .build(&mut bwt)?;
+    ///     assert_eq!(stats.primary_index, 3);
+    /// }
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cut_range(mut self, range: Range<usize>) -> Result<Self> {
+        if range.is_empty() {
+            return Err(Error::invalid_argument("range of cuts must not be empty."));
+        }
+        self.chunk_opts.cut_range = Some((range.start, range.end));
+        Ok(self)
+    }
+
+    /// Generates the cuts as a [`CutSet`] to be distributed to the workers of a construction,
+    /// each of which builds the chunks of a range of cuts by [`Self::partial`].
+    ///
+    /// # Errors
+    ///
+    /// An error is returned in the same cases as [`Self::generate_cuts`].
+    ///
+    /// # Examples
+    ///
+    /// See [`PartialBwtWriter`].
+    pub fn cut_set(&self) -> Result<CutSet> {
+        Ok(CutSet {
+            text_len: self.text.len() / self.chunk_opts.stride,
+            cuts: self.generate_cuts()?,
+        })
+    }
+
+    /// Builds the chunks of the cuts in `range` of a cut set generated by [`Self::cut_set`],
+    /// as a worker of a distributed construction, in the same way as [`Self::cuts`] and [`Self::cut_range`].
+    ///
+    /// The BWT should be written by [`PartialBwtWriter`]
+    /// so that the outputs of the workers are verified and concatenated by [`assemble_partials`].
+    ///
+    /// # Arguments
+    ///
+    /// * `cut_set` - The cut set of the construction.
+    /// * `range` - The range of the indices of cuts assigned to the worker.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the cut set is not of a text of the same length,
+    /// or if its cuts or `range` are invalid.
+    ///
+    /// # Examples
+    ///
+    /// See [`PartialBwtWriter`].
+    pub fn partial(self, cut_set: &CutSet, range: Range<usize>) -> Result<Self> {
+        let text_len = self.text.len() / self.chunk_opts.stride;
+        if cut_set.text_len != text_len {
+            return Err(Error::invalid_argument(format!(
+                "cut set must be of a text of length {text_len}, but got {}.",
+                cut_set.text_len
+            )));
+        }
+        self.cuts(cut_set.cuts.clone())?.cut_range(range)
+    }
+
+    /// Writes a [`Checkpoint`] to `path` after each chunk but the last one,
+    /// so that an interrupted construction can be continued by [`Self::resume`].
+    ///
+    /// The writer of the BWT is flushed before each checkpoint is written,
+    /// and the file is replaced atomically.
+    /// The file is left after the construction, which may be removed by the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the checkpoint file.
+    ///
+    /// # Default value
+    ///
+    /// No checkpoint is written.
+    ///
+    /// # Errors
+    ///
+    /// [`Self::build`] returns an error if the auxiliary writers, the reverse writer,
+    /// skipping runs of N, SA-IS, or prefix-free parsing is set.
+    pub const fn checkpoint(mut self, path: &'a Path) -> Self {
+        self.chunk_opts.checkpoint = Some(path);
+        self
+    }
+
+    /// Continues the construction from a checkpoint written by [`Self::checkpoint`],
+    /// building the chunks of the remaining cuts as in [`Self::cut_range`].
+    ///
+    /// The output should be truncated to [`Checkpoint::n_bytes_written`] bytes
+    /// and continued by the writer passed to [`Self::build`].
+    ///
+    /// # Arguments
+    ///
+    /// * `checkpoint` - The checkpoint of the interrupted construction of the same text.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the checkpoint is not of a text of the same length or its cuts are invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, Checkpoint};
+    ///
+    /// let text = b"abracadabra$";
+    /// let path = std::env::temp_dir().join("small-bwt-resume-example.ckpt");
+    ///
+    /// // The construction is interrupted after two chunks.
+    /// let checkpoint = Checkpoint {
+    ///     text_len: text.len(),
+    ///     cuts: BwtBuilder::new(text)?.chunk_size(3)?.generate_cuts()?,
+    ///     n_cuts_done: 2,
+    ///     n_bytes_written: 5,
+    /// };
+    /// checkpoint.save(&path)?;
+    ///
+    /// let mut bwt = b"ard$rc".to_vec();
+    /// let checkpoint = Checkpoint::load(&path)?;
+    /// bwt.truncate(checkpoint.n_bytes_written);
+    /// BwtBuilder::new(text)?.resume(&checkpoint)?.build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resume(self, checkpoint: &Checkpoint) -> Result<Self> {
+        let text_len = self.text.len() / self.chunk_opts.stride;
+        if checkpoint.text_len != text_len {
+            return Err(Error::invalid_argument(format!(
+                "checkpoint must be of a text of length {text_len}, but got {}.",
+                checkpoint.text_len
+            )));
+        }
+        let n_cuts = checkpoint.cuts.len();
+        self.cuts(checkpoint.cuts.clone())?
+            .cut_range(checkpoint.n_cuts_done..n_cuts)
+    }
+
+    /// Enables the external-memory mode, which bounds the working memory of chunks to `budget` bytes.
+    ///
+    /// The chunk size is set so that the suffix positions of a chunk and the buffer to sort them fit in `budget`.
+    /// All the suffixes are bucketed into the chunks in a single scan of the text, as in [`Self::single_pass`],
+    /// but the chunks are spilled to temporary files in `dir` and loaded one by one to be sorted.
+    /// The files take `8n` bytes in total and are removed after the construction.
+    ///
+    /// This overrides [`Self::single_pass`] and the chunk size.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to write temporary files.
+    /// * `budget` - The memory budget for chunks in bytes.
+    ///
+    /// # Default value
+    ///
+    /// The chunks are held in memory.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `budget` is too small to store two suffix positions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let dir = std::env::temp_dir();
+    /// let mut bwt = vec![];
+    /// BwtBuilder::new(b"abracadabra$")?
+    ///     .external_memory(&dir, 32)?
+    ///     .build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn external_memory(mut self, dir: &'a Path, budget: usize) -> Result<Self> {
+        let chunk_size = budget / (2 * suffix_index_bytes(self.text.len()));
+        if chunk_size == 0 {
+            return Err(Error::invalid_argument(format!(
+                "budget must be large enough to store two suffix positions, but got {budget} bytes."
+            )));
+        }
+        self.chunk_size = chunk_size;
+        self.chunk_opts.spill_dir = Some(dir);
+        Ok(self)
+    }
+
+    /// Sets a writer to output the suffix array along with the BWT.
+    /// Each suffix position is written as a little-endian integer of `width`.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the suffix array.
+    /// * `width` - The byte width of each integer.
+    ///
+    /// # Default value
+    ///
+    /// The suffix array is not written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, IntWidth};
+    ///
+    /// let text = "abracadabra$";
+    /// let mut bwt = vec![];
+    /// let mut sa = vec![];
+    /// BwtBuilder::new(text.as_bytes())?
+    ///     .suffix_array_writer(&mut sa, IntWidth::U32)
+    ///     .build(&mut bwt)?;
+    ///
+    /// let sa: Vec<u32> = sa
+    ///     .chunks(4)
+    ///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    ///     .collect();
+    /// assert_eq!(sa, vec![11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn suffix_array_writer<S: Write + 'a>(mut self, wrt: S, width: IntWidth) -> Self {
+        self.aux.get_mut().sa = Some(IntWriter::new(wrt, width));
+        self
+    }
+
+    /// Sets a writer to output the suffix array sampled at every `sample_rate` rows along with the BWT.
+    /// The `k`-th value is the starting position of the `k * sample_rate`-th smallest suffix,
+    /// written as a little-endian integer of `width`.
+    ///
+    /// The samples can be given to [`FmIndex::with_sa_samples`] to locate patterns.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the sampled suffix array.
+    /// * `width` - The byte width of each integer.
+    /// * `sample_rate` - The sampling rate of the suffix array.
+    ///
+    /// # Default value
+    ///
+    /// The sampled suffix array is not written.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `sample_rate` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, IntWidth};
+    ///
+    /// let text = "abracadabra$";
+    /// let mut bwt = vec![];
+    /// let mut samples = vec![];
+    /// BwtBuilder::new(text.as_bytes())?
+    ///     .sampled_suffix_array_writer(&mut samples, IntWidth::U32, 4)?
+    ///     .build(&mut bwt)?;
+    ///
+    /// let samples: Vec<u32> = samples
+    ///     .chunks(4)
+    ///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    ///     .collect();
+    /// assert_eq!(samples, vec![11, 3, 4]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sampled_suffix_array_writer<S: Write + 'a>(
+        mut self,
+        wrt: S,
+        width: IntWidth,
+        sample_rate: usize,
+    ) -> Result<Self> {
+        if sample_rate == 0 {
+            return Err(Error::invalid_argument("sample_rate must be positive."));
+        }
+        self.aux.get_mut().ssa = Some((IntWriter::new(wrt, width), sample_rate));
+        Ok(self)
+    }
+
+    /// Sets a writer to output the LCP array along with the BWT.
+    /// The `i`-th value is the length of the longest common prefix
+    /// between the `i-1`-th and `i`-th smallest suffixes (the first value is zero).
+    /// Each value is written as a little-endian integer of `width`.
+    ///
+    /// The LCP values are computed by comparing adjacent suffixes in the sorted chunks,
+    /// so no additional memory proportional to the text length is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the LCP array.
+    /// * `width` - The byte width of each integer.
+    ///
+    /// # Default value
+    ///
+    /// The LCP array is not written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, IntWidth};
+    ///
+    /// let text = "abracadabra$";
+    /// let mut bwt = vec![];
+    /// let mut lcp = vec![];
+    /// BwtBuilder::new(text.as_bytes())?
+    ///     .lcp_array_writer(&mut lcp, IntWidth::U32)
+    ///     .build(&mut bwt)?;
+    ///
+    /// let lcp: Vec<u32> = lcp
+    ///     .chunks(4)
+    ///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    ///     .collect();
+    /// assert_eq!(lcp, vec![0, 0, 1, 4, 1, 1, 0, 3, 0, 0, 0, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lcp_array_writer<S: Write + 'a>(mut self, wrt: S, width: IntWidth) -> Self {
+        self.aux.get_mut().lcp = Some(IntWriter::new(wrt, width));
+        self
+    }
+
+    /// Sets a writer to output the text positions of the BWT symbols along with the BWT.
+    /// The `i`-th value is the position in the text of the `i`-th symbol of the BWT,
+    /// i.e., the position preceding the `i`-th smallest suffix (cyclically),
+    /// written as a little-endian integer of `width`.
+    ///
+    /// This relates each BWT symbol back to the text without an extra LF pass over the BWT.
+    /// If [`Self::implicit_sentinel`] is set, the sentinel is at position `n`.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the text positions.
+    /// * `width` - The byte width of each integer.
+    ///
+    /// # Default value
+    ///
+    /// The text positions are not written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, IntWidth};
+    ///
+    /// let text = "abracadabra$";
+    /// let mut bwt = vec![];
+    /// let mut pos = vec![];
+    /// BwtBuilder::new(text.as_bytes())?
+    ///     .text_position_writer(&mut pos, IntWidth::U32)
+    ///     .build(&mut bwt)?;
+    ///
+    /// let pos: Vec<u32> = pos
+    ///     .chunks(4)
+    ///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    ///     .collect();
+    /// assert_eq!(pos, vec![10, 9, 6, 11, 2, 4, 7, 0, 3, 5, 8, 1]);
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn text_position_writer<S: Write + 'a>(mut self, wrt: S, width: IntWidth) -> Self {
+        self.aux.get_mut().text_pos = Some((IntWriter::new(wrt, width), 0));
+        self
+    }
+
+    /// Sets a writer to output the inverse suffix array along with the BWT.
+    /// The `j`-th value is the rank of suffix `text[j..]` among all the suffixes.
+    /// Each value is written as a little-endian integer of `width`.
+    ///
+    /// Since the ranks are produced in suffix order,
+    /// the values are written at random positions by seeking `wrt`.
+    /// Prefer a writer that supports cheap random access, such as [`std::fs::File`]
+    /// or [`std::io::Cursor`], rather than one that flushes on every seek.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the inverse suffix array.
+    /// * `width` - The byte width of each integer.
+    ///
+    /// # Default value
+    ///
+    /// The inverse suffix array is not written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::io::Cursor;
+    /// use small_bwt::{BwtBuilder, IntWidth};
+    ///
+    /// let text = "abracadabra$";
+    /// let mut bwt = vec![];
+    /// let mut isa = Cursor::new(vec![]);
+    /// BwtBuilder::new(text.as_bytes())?
+    ///     .inverse_suffix_array_writer(&mut isa, IntWidth::U32)
+    ///     .build(&mut bwt)?;
+    ///
+    /// let isa: Vec<u32> = isa
+    ///     .into_inner()
+    ///     .chunks(4)
+    ///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    ///     .collect();
+    /// assert_eq!(isa, vec![3, 7, 11, 4, 8, 5, 9, 2, 6, 10, 1, 0]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn inverse_suffix_array_writer<S: Write + Seek + 'a>(
+        mut self,
+        wrt: S,
+        width: IntWidth,
+    ) -> Self {
+        self.aux.get_mut().isa = Some(IntArrayWriter::new(wrt, width));
+        self
+    }
+
+    /// Sets a writer to output the document array along with the BWT,
+    /// assuming that the text is a concatenation of documents.
+    /// The `i`-th value is the document containing the `i`-th smallest suffix.
+    /// Each value is written as a little-endian integer of `width`.
+    ///
+    /// # Arguments
+    ///
+    /// * `starts` - The starting positions of the documents in the text,
+    ///   which must be strictly increasing and begin with zero.
+    ///   The `d`-th document spans `starts[d]..starts[d + 1]`, and the last one spans to the end.
+    /// * `wrt` - The writer to write the document array.
+    /// * `width` - The byte width of each integer.
+    ///
+    /// # Default value
+    ///
+    /// The document array is not written.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `starts` is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{BwtBuilder, IntWidth};
+    ///
+    /// let text = "abra,cad,abra$";
+    /// let mut bwt = vec![];
+    /// let mut da = vec![];
+    /// BwtBuilder::new(text.as_bytes())?
+    ///     .document_array_writer(&[0, 5, 9], &mut da, IntWidth::U32)?
+    ///     .build(&mut bwt)?;
+    ///
+    /// let da: Vec<u32> = da
+    ///     .chunks(4)
+    ///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    ///     .collect();
+    /// assert_eq!(da, vec![2, 1, 0, 2, 0, 2, 0, 1, 2, 0, 1, 1, 2, 0]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn document_array_writer<S: Write + 'a>(
+        mut self,
+        starts: &'a [usize],
+        wrt: S,
+        width: IntWidth,
+    ) -> Result<Self> {
+        if starts.first() != Some(&0) {
+            return Err(Error::invalid_argument("starts must begin with zero."));
+        }
+        if let Some(w) = starts.windows(2).find(|w| w[0] >= w[1]) {
+            return Err(Error::invalid_argument(format!(
+                "starts must be strictly increasing, but found {} followed by {}.",
+                w[0], w[1]
+            )));
+        }
+        let text_len = self.text.len() / self.chunk_opts.stride;
+        if *starts.last().unwrap() >= text_len {
+            return Err(Error::invalid_argument(format!(
+                "starts must be smaller than the text length {text_len}."
+            )));
+        }
+        self.aux.get_mut().da = Some((IntWriter::new(wrt, width), starts));
+        Ok(self)
+    }
+
+    /// Sets a writer to output the BWT of the reversed text along with the BWT.
+    /// The reversed text is the text except the terminator in reverse order,
+    /// followed by the terminator, as needed for bidirectional FM-indexes.
+    /// If [`Self::implicit_sentinel`] is set, the whole text is reversed and followed by the sentinel.
+    ///
+    /// The reversed text is materialized in additional `n` bytes,
+    /// and its BWT is constructed on another thread concurrently with the forward one.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the BWT of the reversed text.
+    ///
+    /// # Default value
+    ///
+    /// The BWT of the reversed text is not written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let text = "abracadabra$";
+    /// let mut bwt = vec![];
+    /// let mut rev_bwt = vec![];
+    /// let stats = BwtBuilder::new(text.as_bytes())?
+    ///     .reverse_writer(&mut rev_bwt)
+    ///     .build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// assert_eq!(rev_bwt, b"abdbc$rraaaa"); // BWT of "arbadacarba$"
+    /// assert_eq!(stats.reverse_primary_index, Some(5));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reverse_writer<S: Write + Send + 'a>(mut self, wrt: S) -> Self {
+        self.reverse_wrt = Some(RefCell::new(Box::new(wrt)));
+        self
+    }
+
+    /// Sets whether to build the BWT of the text followed by an implicit sentinel,
+    /// i.e., a virtual terminator smaller than every byte,
+    /// so that the text needs no terminator and may contain any byte values.
+    ///
+    /// The BWT then has `n + 1` symbols for the text length `n`,
+    /// where the sentinel is written as `placeholder` at [`BuildStats::primary_index`].
+    /// It can be decoded by [`decode_bwt_with_sentinel`].
+    /// The auxiliary outputs also cover the `n + 1` suffixes, with the empty suffix at position `n` being the smallest.
+    ///
+    /// # Arguments
+    ///
+    /// * `placeholder` - The byte written in place of the sentinel.
+    ///
+    /// # Default value
+    ///
+    /// The text must end with its own terminator satisfying [`verify_terminator`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::{decode_bwt_with_sentinel, BwtBuilder};
+    ///
+    /// let text = "abracadabra";
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::new_unchecked(text.as_bytes())?
+    ///     .implicit_sentinel(b'$')
+    ///     .build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// assert_eq!(stats.primary_index, 3);
+    ///
+    /// let decoded = decode_bwt_with_sentinel(&bwt, stats.primary_index)?;
+    /// assert_eq!(decoded, text.as_bytes());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub const fn implicit_sentinel(mut self, placeholder: u8) -> Self {
+        self.chunk_opts.sentinel = Some(placeholder);
+        self
+    }
+
+    /// Sets a flag to cancel the construction from another thread.
+    ///
+    /// The flag is checked before processing each cut.
+    /// Once it is set to `true`, [`Self::build`] stops and returns an [`Error::Cancelled`] error,
+    /// and the output written so far is incomplete and should be discarded by the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `flag` - The cancellation flag, e.g., the content of an `Arc<AtomicBool>` shared with another thread.
+    ///
+    /// # Default value
+    ///
+    /// The construction cannot be cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::atomic::AtomicBool;
+    /// use small_bwt::{BwtBuilder, Error};
+    ///
+    /// let flag = AtomicBool::new(true);
+    /// let mut bwt = vec![];
+    /// let result = BwtBuilder::new(b"abracadabra$")?
+    ///     .cancel_flag(&flag)
+    ///     .build(&mut bwt);
+    /// assert!(matches!(result, Err(Error::Cancelled)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub const fn cancel_flag(mut self, flag: &'a AtomicBool) -> Self {
+        self.chunk_opts.cancel = Some(flag);
+        self
+    }
+
+    /// Builds the BWT and writes it to `wrt`.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the BWT.
+    ///
+    /// # Returns
+    ///
+    /// The statistics of the construction, such as the primary index and the number of runs.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `wrt` or the auxiliary writers return an error,
+    /// or if an integer does not fit in the specified width.
+    /// An [`Error::Cancelled`] error is returned if the construction is cancelled by [`Self::cancel_flag`].
+    pub fn build<W: Write>(&self, wrt: W) -> Result<BuildStats> {
+        let start = Instant::now();
+        let (mut stats, alphabet) = match &self.text {
+            Text::Borrowed(text) => self.build_bytes(text, wrt)?,
+            Text::Owned(text) => self.build_bytes(text, wrt)?,
+            #[cfg(feature = "mmap")]
+            Text::Mapped(mmap) => self.build_bytes(mmap, wrt)?,
+            Text::Packed(packed) => {
+                if self.remap_alphabet
+                    || self.remap_to.is_some()
+                    || self.chunk_opts.sentinel.is_some()
+                    || self.min_n_run_len.is_some()
+                    || self.context_order.is_some()
+                {
+                    return Err(Error::invalid_argument("a packed text cannot be remapped, have an implicit sentinel, skip runs of N, or be sorted by contexts."));
+                }
+                (self.build_bwt(*packed, wrt)?, None)
+            }
+        };
+        stats.chunk_size = self.chunk_size;
+        stats.context_order = self.context_order;
+        stats.terminator = self.terminator;
+        stats.sentinel = self.chunk_opts.sentinel;
+        stats.alphabet = alphabet;
+        stats.elapsed = start.elapsed();
+        Ok(stats)
+    }
+
+    /// Returns the number of bytes of the whole BWT, i.e., the size of the buffer for [`Self::build_into`].
+    ///
+    /// It is the length of the text, plus one symbol for the implicit sentinel if any.
+    pub fn output_len(&self) -> usize {
+        let stride = self.chunk_opts.stride;
+        let n_rows = self.text.len() / stride + usize::from(self.chunk_opts.sentinel.is_some());
+        n_rows * stride
+    }
+
+    /// Builds the BWT into a buffer of the size of the whole BWT given by [`Self::output_len`],
+    /// such as a memory map shared with another process, avoiding the [`Write`] layer of the caller.
+    ///
+    /// If [`Self::cut_range`] is set, the rows built are written at their offsets in the whole BWT,
+    /// and the other bytes are left unchanged,
+    /// so that the workers of a distributed construction can fill a shared buffer in place.
+    /// It takes an additional scan of the text to find the offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The buffer to write the BWT.
+    ///
+    /// # Returns
+    ///
+    /// The statistics of the construction, such as the primary index and the number of runs.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned in the same cases as [`Self::build`],
+    /// if the length of `out` differs from [`Self::output_len`],
+    /// or if both a range of cuts and remapping the alphabet are set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let builder = BwtBuilder::new(b"abracadabra$")?;
+    /// let mut bwt = vec![0; builder.output_len()];
+    /// builder.build_into(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn build_into(&self, out: &mut [u8]) -> Result<BuildStats> {
+        let output_len = self.output_len();
+        if out.len() != output_len {
+            return Err(Error::invalid_argument(format!(
+                "buffer must have {output_len} bytes, but got {}.",
+                out.len()
+            )));
+        }
+        let first_row = match &self.text {
+            _ if self.chunk_opts.cut_range.is_none() => 0,
+            _ if self.remap_alphabet || self.remap_to.is_some() => {
+                return Err(Error::invalid_argument(
+                    "a range of cuts cannot be built into a buffer with remapping.",
+                ))
+            }
+            Text::Borrowed(text) => self.rows_before_range(*text),
+            Text::Owned(text) => self.rows_before_range(text.as_slice()),
+            #[cfg(feature = "mmap")]
+            Text::Mapped(mmap) => self.rows_before_range(&mmap[..]),
+            Text::Packed(packed) => self.rows_before_range(*packed),
+        };
+        self.build(&mut out[first_row * self.chunk_opts.stride..])
+    }
+
+    /// Returns the number of the rows before the range of cuts, if any, including the sentinel.
+    fn rows_before_range<T: TextAccess + ?Sized>(&self, text: &T) -> usize {
+        let (cuts, opts) = self.cuts_for(text, self.chunk_opts);
+        let i = opts.chunk_range(cuts.len()).start() - 1;
+        // An invalid range is reported by Self::build.
+        if i == 0 || i >= cuts.len() {
+            return 0;
+        }
+        let (n_before, _) = suffixes_up_to(text, &cuts[i], opts.stride, false);
+        n_before + usize::from(opts.sentinel.is_some())
+    }
+
+    /// Builds the BWT into the file at `path` through a writable memory map,
+    /// resizing the file to [`Self::output_len`] and creating it if it does not exist.
+    ///
+    /// As in [`Self::build_into`], the rows of [`Self::cut_range`] are written at their offsets,
+    /// so that several workers can fill the same file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the output file.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned in the same cases as [`Self::build`], or if the file cannot be mapped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let path = std::env::temp_dir().join("small_bwt_build_into_file_doctest.bwt");
+    /// BwtBuilder::new(b"abracadabra$")?.build_into_file(&path)?;
+    /// assert_eq!(std::fs::read(&path)?, b"ard$rcaaaabb");
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn build_into_file<P: AsRef<Path>>(&self, path: P) -> Result<BuildStats> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        file.set_len(self.output_len() as u64)?;
+        // SAFETY: The file must not be modified by others while it is mapped.
+        let mut mmap = unsafe { memmap2::MmapMut::map_mut(&file)? };
+        let stats = self.build_into(&mut mmap)?;
+        mmap.flush()?;
+        Ok(stats)
+    }
+
+    /// Builds the BWT and writes it to `wrt` in a dedicated thread,
+    /// so that the construction continues while earlier chunks are written to a slow writer,
+    /// such as a network stream or a compressed file.
+    ///
+    /// The bytes are passed to the writer thread through a bounded queue,
+    /// and the construction waits while the queue is full.
+    /// The auxiliary writers are still written in the calling thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the BWT.
+    /// * `queue_size` - The maximum number of bytes in the queue, roughly.
+    ///
+    /// # Returns
+    ///
+    /// The statistics of the construction, such as the primary index and the number of runs.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned in the same cases as [`Self::build`], or if `queue_size` is zero.
+    /// An error of `wrt` is returned after the writer thread is joined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let mut bwt = vec![];
+    /// BwtBuilder::new(b"abracadabra$")?.build_with_writer_thread(&mut bwt, 1 << 20)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn build_with_writer_thread<W: Write + Send>(
+        &self,
+        wrt: W,
+        queue_size: usize,
+    ) -> Result<BuildStats> {
+        // The queue never holds more than the whole BWT.
+        let queue_size = queue_size.min(self.output_len());
+        background::write_in_background(wrt, queue_size, |queue| self.build(queue))
+    }
+
+    /// Builds the BWT while `consume` processes it piece by piece in a dedicated thread,
+    /// such as for the move-to-front transform, counting runs, or hashing.
+    ///
+    /// The pieces are passed to `consume` through a bounded queue as [`BwtChunks`],
+    /// each of which has at most the chunk size of bytes,
+    /// and the construction waits while the queue is full.
+    /// The auxiliary writers are still written in the calling thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `consume` - The function consuming the pieces of the BWT.
+    ///
+    /// # Returns
+    ///
+    /// The statistics of the construction and the value returned by `consume`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned in the same cases as [`Self::build`],
+    /// in which case the pieces end early.
+    /// An error is also returned if `consume` returns before the pieces end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// // Counts the runs of the BWT while building it.
+    /// let (stats, n_runs) = BwtBuilder::new(b"abracadabra$")?.build_streaming(|chunks| {
+    ///     let mut prev = None;
+    ///     let mut n_runs = 0;
+    ///     for c in chunks.flatten() {
+    ///         n_runs += usize::from(prev != Some(c));
+    ///         prev = Some(c);
+    ///     }
+    ///     n_runs
+    /// })?;
+    /// assert_eq!(n_runs, 8);
+    /// assert_eq!(stats.primary_index, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn build_streaming<F, R>(&self, consume: F) -> Result<(BuildStats, R)>
+    where
+        F: FnOnce(BwtChunks) -> R + Send,
+        R: Send,
+    {
+        let block_size = self.chunk_size.min(self.output_len());
+        let (stats, consumed) =
+            background::pipe_to_thread(block_size, |queue| self.build(queue), consume);
+        Ok((stats?, consumed))
+    }
+
+    /// Creates a reader computing the BWT on demand as it is read, cut by cut,
+    /// so that the BWT can be passed to a consumer of [`Read`](std::io::Read) without a buffer of the whole.
+    ///
+    /// The chunks are gathered and sorted one by one in the reading thread,
+    /// so the options for parallelism, external memory, and pipelining are ignored.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if auxiliary writers, the reverse writer, remapping the alphabet,
+    /// skipping runs of N, prefix-free parsing, a range of cuts, or a context order are set,
+    /// or if the text is packed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::BwtBuilder;
+    ///
+    /// let builder = BwtBuilder::new(b"abracadabra$")?;
+    /// let mut bwt = vec![];
+    /// std::io::copy(&mut builder.reader()?, &mut bwt)?;
+    /// assert_eq!(bwt, b"ard$rcaaaabb");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reader(&self) -> Result<BwtReader<'_>> {
+        if !self.aux.borrow().is_empty()
+            || self.reverse_wrt.is_some()
+            || self.remap_alphabet
+            || self.remap_to.is_some()
+            || self.min_n_run_len.is_some()
+            || self.chunk_opts.pfp.is_some()
+            || self.chunk_opts.cut_range.is_some()
+            || self.context_order.is_some()
+        {
+            return Err(Error::invalid_argument(
+                "the BWT cannot be read lazily with auxiliary outputs, remapping, skipping runs of N, prefix-free parsing, a range of cuts, or a context order.",
+            ));
+        }
+        let text: &[u8] = match &self.text {
+            Text::Borrowed(text) => text,
+            Text::Owned(text) => text,
+            #[cfg(feature = "mmap")]
+            Text::Mapped(mmap) => mmap,
+            Text::Packed(_) => {
+                return Err(Error::invalid_argument(
+                    "the BWT of a packed text cannot be read lazily.",
+                ))
+            }
+        };
+        let (cuts, opts) = self.cuts_for(text, self.chunk_opts);
+        self.progress.cuts_generated(cuts.len());
+        Ok(BwtReader::new(text, cuts.into_owned(), opts, self.progress))
+    }
+
+    /// Returns the cuts set by [`Self::cuts`] or generated from the text,
+    /// with the options to build the BWT from them.
+    fn cuts_for<T: TextAccess + ?Sized>(
+        &self,
+        text: &T,
+        opts: ChunkOptions<'a>,
+    ) -> (Cow<'_, [Vec<u8>]>, ChunkOptions<'a>) {
+        match &self.cuts {
+            Some(cuts) if !opts.sais && opts.pfp.is_none() => (Cow::Borrowed(cuts), opts),
+            _ => {
+                let (cuts, opts) =
+                    generate_cuts(text, self.chunk_size, self.kmer_len, opts, &self.progress);
+                (Cow::Owned(cuts), opts)
+            }
+        }
+    }
+
+    /// Builds the BWT of a byte text, remapping it to its alphabet if specified.
+    fn build_bytes<W: Write>(&self, text: &[u8], wrt: W) -> Result<(BuildStats, Option<Alphabet>)> {
+        let alphabet = match &self.remap_to {
+            Some(alphabet) => {
+                alphabet.validate(text)?;
+                Some(alphabet.clone())
+            }
+            None if self.remap_alphabet => Some(Alphabet::from_text(text)),
+            None => None,
+        };
+        let remapped = alphabet.as_ref().map(|a| a.encode_all(text));
+        let text = remapped.as_deref().unwrap_or(text);
+        if let Some(k) = self.context_order {
+            return Ok((self.build_context_bwt(text, k, wrt)?, alphabet));
+        }
+        let min_len = match self.min_n_run_len {
+            Some(min_len) => min_len,
+            None => return Ok((self.build_bwt(text, wrt)?, alphabet)),
+        };
+        if self.chunk_opts.stride != 1 {
+            return Err(Error::invalid_argument(
+                "runs of N cannot be skipped in a text of symbols.",
+            ));
+        }
+        let n = alphabet.as_ref().map_or(Some(b'N'), |a| a.encode(b'N'));
+        let stats = match n {
+            Some(n) => {
+                let text = NRunText::new(Cow::Borrowed(text), n, min_len);
+                let n_runs = text.runs().to_vec();
+                let mut stats = self.build_bwt(&text, wrt)?;
+                stats.n_runs = n_runs;
+                stats
+            }
+            // N does not occur in the remapped text.
+            None => self.build_bwt(text, wrt)?,
+        };
+        Ok((stats, alphabet))
+    }
+
+    /// Builds the context-bounded BWT of a byte text of order `k`.
+    fn build_context_bwt<W: Write>(&self, text: &[u8], k: usize, mut wrt: W) -> Result<BuildStats> {
+        let opts = &self.chunk_opts;
+        if !self.aux.borrow().is_empty()
+            || self.reverse_wrt.is_some()
+            || opts.sentinel.is_some()
+            || opts.stride != 1
+            || self.min_n_run_len.is_some()
+            || opts.cut_range.is_some()
+            || opts.checkpoint.is_some()
+            || opts.sais
+            || opts.pfp.is_some()
+        {
+            return Err(Error::invalid_argument(
+                "the context-bounded BWT cannot be built with auxiliary outputs, the reverse writer, an implicit sentinel, symbols, skipping runs of N, a range of cuts, checkpoints, SA-IS, or prefix-free parsing.",
+            ));
+        }
+        opts.check_cancelled()?;
+        self.progress
+            .print(&format!("Sorting the rotations by {k} symbols..."));
+        let (bwt, primary_index) = context_bwt(text, k)?;
+        wrt.write_all(&bwt)?;
+        wrt.flush()?;
+        Ok(BuildStats {
+            text_len: text.len(),
+            // The chunk size and the elapsed time are set by BwtBuilder::build.
+            chunk_size: 0,
+            n_cuts: 0,
+            max_chunk_len: 0,
+            n_bytes_written: bwt.len(),
+            elapsed: Duration::ZERO,
+            primary_index,
+            runs: RunStats::from_bwt(&bwt),
+            reverse_primary_index: None,
+            context_order: None,
+            terminator: None,
+            sentinel: None,
+            alphabet: None,
+            n_runs: vec![],
+            rows: 0..bwt.len(),
+        })
+    }
+
+    fn build_bwt<T: TextAccess + ?Sized, W: Write>(&self, text: &T, wrt: W) -> Result<BuildStats> {
+        assert_ne!(text.len(), 0);
+        assert_ne!(self.chunk_size, 0);
+
+        let chunk_size = self.chunk_size;
+        let stride = self.chunk_opts.stride;
+        let n_expected_cuts = text.len() / stride / chunk_size;
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!("build", text_len = text.len(), chunk_size);
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+
+        self.progress
+            .print(&format!("Text length: {:?} MiB", to_mib(text.len())));
+        self.progress
+            .print(&format!("Chunk size: {:?} M", to_mb(chunk_size)));
+        self.progress
+            .print(&format!("Expected number of cuts: {:?}", n_expected_cuts));
+
+        self.progress.print("Generating cuts...");
+        let kmer_len = self.kmer_len;
+        let mut chunk_opts = self.chunk_opts;
+        if let Some(pfp) = chunk_opts.pfp.as_mut() {
+            pfp.batch_size = chunk_size;
+        }
+        let (cuts, opts) = {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("generate_cuts", kmer_len).entered();
+            self.cuts_for(text, chunk_opts)
+        };
+        self.progress.cuts_generated(cuts.len());
+        if let Some((_, end)) = opts.cut_range {
+            if opts.sais || opts.pfp.is_some() {
+                return Err(Error::invalid_argument(
+                    "a range of cuts cannot be built by SA-IS or prefix-free parsing.",
+                ));
+            }
+            if end > cuts.len() {
+                return Err(Error::invalid_argument(format!(
+                    "range of cuts must end within the number of cuts {}, but got {end}.",
+                    cuts.len()
+                )));
+            }
+            if self.reverse_wrt.is_some() || self.min_n_run_len.is_some() {
+                return Err(Error::invalid_argument(
+                    "a range of cuts cannot be built with the reverse writer or skipping runs of N.",
+                ));
+            }
+        }
+        if opts.checkpoint.is_some()
+            && (opts.sais
+                || opts.pfp.is_some()
+                || self.reverse_wrt.is_some()
+                || self.min_n_run_len.is_some()
+                || !self.aux.borrow().is_empty())
+        {
+            return Err(Error::invalid_argument(
+                "checkpoints cannot be written with auxiliary outputs, the reverse writer, skipping runs of N, SA-IS, or prefix-free parsing.",
+            ));
+        }
+
+        let mut aux = self.aux.borrow_mut();
+        let reverse_wrt = match self.reverse_wrt.as_ref() {
+            Some(reverse_wrt) => reverse_wrt,
+            None => return bwt_from_cuts(text, &cuts, wrt, &mut aux, &self.progress, opts),
+        };
+
+        self.progress
+            .print("Building the BWT of the reversed text concurrently...");
+        let mut reverse_wrt = reverse_wrt.borrow_mut();
+        let reverse_wrt: &mut (dyn Write + Send) = &mut **reverse_wrt;
+        let progress = self.progress;
+        std::thread::scope(|s| {
+            #[cfg(feature = "tracing")]
+            let span = &span;
+            let handle = s.spawn(move || {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::info_span!(parent: span, "build_reverse").entered();
+                let reversed = text.reversed(stride, chunk_opts.sentinel.is_some());
+                let (cuts, opts) =
+                    generate_cuts(&*reversed, chunk_size, kmer_len, chunk_opts, &progress);
+                bwt_from_cuts(
+                    &*reversed,
+                    &cuts,
+                    reverse_wrt,
+                    &mut AuxWriters::default(),
+                    &Progress::new(false),
+                    opts,
+                )
+            });
+            let mut stats = bwt_from_cuts(text, &cuts, wrt, &mut aux, &self.progress, opts)?;
+            let reverse_stats = handle
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e))?;
+            stats.reverse_primary_index = Some(reverse_stats.primary_index);
+            Ok(stats)
+        })
+    }
+}
+
+/// Generates the cuts, or only the empty one for the algorithms sorting all the suffixes without cuts,
+/// returning them with the options to build the BWT from them.
+///
+/// If the cut generation degenerates on a pathological text, such as `a^n$` or a long periodic one,
+/// it falls back to sorting all the suffixes at once by SA-IS with a warning,
+/// instead of sorting a chunk of almost all the suffixes sharing long prefixes.
+fn generate_cuts<'o, T: TextAccess + ?Sized>(
+    text: &T,
+    chunk_size: usize,
+    kmer_len: usize,
+    opts: ChunkOptions<'o>,
+    progress: &Progress,
+) -> (Vec<Vec<u8>>, ChunkOptions<'o>) {
+    if opts.sais || opts.pfp.is_some() {
+        return (vec![vec![]], opts);
+    }
+    CutGenerator::generate(text, chunk_size, kmer_len, opts.stride).map_or_else(
+        || {
+            progress.warn(&format!(
+                "A prefix of {MAX_CUT_LEN} bytes occurs more than {MAX_OVERSIZE_RATIO} times \
+                 the chunk size in the degenerate text, \
+                 so all the suffixes are sorted at once by SA-IS in O(n) words of space."
+            ));
+            (vec![vec![]], ChunkOptions { sais: true, ..opts })
+        },
+        |cuts| (cuts, opts),
+    )
+}
+
+impl std::str::FromStr for BwtBuilder<'_> {
+    type Err = Error;
+
+    /// Creates a new builder over a copy of a string followed by `\0` as the terminator,
+    /// which is reported in [`BuildStats::terminator`],
+    /// so that the BWT is decoded by [`decode_bwt_string`].
+    ///
+    /// If the string contains `\0`, the text is followed by an implicit sentinel instead,
+    /// which is written as [`STR_SENTINEL`] never occurring in UTF-8, as in [`Self::implicit_sentinel`].
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `text` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::str::FromStr;
+    ///
+    /// use small_bwt::{decode_bwt_string, BwtBuilder};
+    ///
+    /// let mut bwt = vec![];
+    /// let stats = BwtBuilder::from_str("abracadabra")?.build(&mut bwt)?;
+    /// assert_eq!(bwt, b"ard\0rcaaaabb");
+    /// assert_eq!(stats.terminator, Some(0));
+    /// assert_eq!(decode_bwt_string(&bwt)?, "abracadabra");
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn from_str(text: &str) -> Result<Self> {
+        if !text.contains('\0') {
+            return Self::with_terminator(text.as_bytes());
+        }
+        let text = Text::Owned(text.as_bytes().to_vec());
+        Ok(Self::from_text(text, 1)?.implicit_sentinel(STR_SENTINEL))
+    }
+}
+
+/// Text to be transformed, either borrowed, owned, memory-mapped, or packed.
+enum Text<'a> {
+    Borrowed(&'a [u8]),
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(MappedText),
+    Packed(&'a PackedDna),
+}
+
+impl Text<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Self::Borrowed(text) => text.len(),
+            Self::Owned(text) => text.len(),
+            #[cfg(feature = "mmap")]
+            Self::Mapped(mmap) => mmap.len(),
+            Self::Packed(packed) => packed.len(),
+        }
+    }
+}
+
+/// Memory-mapped text, which may be a temporary copy of a stream.
+#[cfg(feature = "mmap")]
+struct MappedText {
+    mmap: memmap2::Mmap,
+    // Dropped after the map to remove the temporary file.
+    _temp: Option<spill::TempFile>,
+}
+
+#[cfg(feature = "mmap")]
+impl std::ops::Deref for MappedText {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.mmap
+    }
+}
+
+/// Returns the text except the terminator in reverse order of symbols of `stride` bytes,
+/// followed by the terminator, or the whole text in reverse order if it has an implicit sentinel.
+fn reversed_text(text: &[u8], stride: usize, sentinel: bool) -> Vec<u8> {
+    let body_len = if sentinel {
+        text.len()
+    } else {
+        text.len() - stride
+    };
+    let mut reversed = Vec::with_capacity(text.len());
+    for c in text[..body_len].chunks(stride).rev() {
+        reversed.extend_from_slice(c);
+    }
+    reversed.extend_from_slice(&text[body_len..]);
+    reversed
+}
+
+/// Auxiliary outputs written along with the BWT.
+#[derive(Default)]
+struct AuxWriters<'a> {
+    sa: Option<IntWriter<'a>>,
+    // The writer of the sampled suffix array and the sampling rate.
+    ssa: Option<(IntWriter<'a>, usize)>,
+    lcp: Option<IntWriter<'a>>,
+    // The writer of the text positions and the number of rows, which is set when building.
+    text_pos: Option<(IntWriter<'a>, usize)>,
+    isa: Option<IntArrayWriter<'a>>,
+    // The writer of the document array and the starting positions of the documents.
+    da: Option<(IntWriter<'a>, &'a [usize])>,
+    // The last suffix of the previous chunk, used to compute LCP values across chunks.
+    prev_suffix: Option<usize>,
+}
+
+impl<'a> AuxWriters<'a> {
+    /// Writes the outputs for the sorted suffixes `chunks` of a text of symbols of `stride` bytes,
+    /// where `offset` is the rank of the first suffix.
+    /// The positions and lengths are written in symbols.
+    fn write_chunk<I: SuffixIndex, T: TextAccess + ?Sized>(
+        &mut self,
+        text: &T,
+        stride: usize,
+        offset: usize,
+        chunks: &[I],
+    ) -> Result<()> {
+        if let Some(sa) = self.sa.as_mut() {
+            for &j in chunks {
+                sa.write(j.to_usize() / stride)?;
+            }
+        }
+        if let Some((ssa, sample_rate)) = self.ssa.as_mut() {
+            for (i, &j) in chunks.iter().enumerate() {
+                if (offset + i) % *sample_rate == 0 {
+                    ssa.write(j.to_usize() / stride)?;
+                }
+            }
+        }
+        if let Some(lcp) = self.lcp.as_mut() {
+            let mut prev = self.prev_suffix;
+            for &j in chunks {
+                let j = j.to_usize();
+                let l = prev.map_or(0, |i| text.lcp(i, j));
+                lcp.write(l / stride)?;
+                prev = Some(j);
+            }
+        }
+        if let Some((text_pos, n_rows)) = self.text_pos.as_mut() {
+            for &j in chunks {
+                text_pos.write((j.to_usize() / stride + *n_rows - 1) % *n_rows)?;
+            }
+        }
+        if let Some(isa) = self.isa.as_mut() {
+            for (i, &j) in chunks.iter().enumerate() {
+                isa.write_at(j.to_usize() / stride, offset + i)?;
+            }
+        }
+        if let Some((da, starts)) = self.da.as_mut() {
+            for &j in chunks {
+                let j = j.to_usize() / stride;
+                da.write(starts.partition_point(|&s| s <= j) - 1)?;
+            }
+        }
+        if let Some(&last) = chunks.last() {
+            self.prev_suffix = Some(last.to_usize());
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if no output is set.
+    const fn is_empty(&self) -> bool {
+        self.sa.is_none()
+            && self.ssa.is_none()
+            && self.lcp.is_none()
+            && self.text_pos.is_none()
+            && self.isa.is_none()
+            && self.da.is_none()
+    }
+
+    /// Returns the number of bytes written to all the outputs for `n` suffixes.
+    fn n_bytes(&self, n: usize) -> usize {
+        let per_suffix = self.sa.as_ref().map_or(0, |w| w.width().bytes())
+            + self.lcp.as_ref().map_or(0, |w| w.width().bytes())
+            + self.text_pos.as_ref().map_or(0, |(w, _)| w.width().bytes())
+            + self.isa.as_ref().map_or(0, |w| w.width().bytes())
+            + self.da.as_ref().map_or(0, |(w, _)| w.width().bytes());
+        let sampled = self.ssa.as_ref().map_or(0, |(w, sample_rate)| {
+            (n / sample_rate + usize::from(n % sample_rate != 0)) * w.width().bytes()
+        });
+        n * per_suffix + sampled
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.prev_suffix = None;
+        if let Some(sa) = self.sa.as_mut() {
+            sa.flush()?;
+        }
+        if let Some((ssa, _)) = self.ssa.as_mut() {
+            ssa.flush()?;
+        }
+        if let Some(lcp) = self.lcp.as_mut() {
+            lcp.flush()?;
+        }
+        if let Some((text_pos, _)) = self.text_pos.as_mut() {
+            text_pos.flush()?;
+        }
+        if let Some(isa) = self.isa.as_mut() {
+            isa.flush()?;
+        }
+        if let Some((da, _)) = self.da.as_mut() {
+            da.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the BWT from the cuts, storing suffix positions in the smallest of `u32`, [`U40`], and `usize`
+/// that can represent them.
+fn bwt_from_cuts<T: TextAccess + ?Sized, W: Write>(
+    text: &T,
+    cuts: &[Vec<u8>],
+    wrt: W,
+    aux: &mut AuxWriters,
+    progress: &Progress,
+    opts: ChunkOptions,
+) -> Result<BuildStats> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("bwt_from_cuts", n_cuts = cuts.len()).entered();
+    // The largest position is that of the implicit sentinel, if any.
+    let max_pos = text.len() - usize::from(opts.sentinel.is_none());
+    if max_pos <= u32::MAX_POS {
+        bwt_from_cuts_with::<u32, T, W>(text, cuts, wrt, aux, progress, opts)
+    } else if max_pos <= U40::MAX_POS {
+        bwt_from_cuts_with::<U40, T, W>(text, cuts, wrt, aux, progress, opts)
+    } else {
+        bwt_from_cuts_with::<usize, T, W>(text, cuts, wrt, aux, progress, opts)
+    }
+}
+
+fn bwt_from_cuts_with<I: SuffixIndex, T: TextAccess + ?Sized, W: Write>(
+    text: &T,
+    cuts: &[Vec<u8>],
+    mut wrt: W,
+    aux: &mut AuxWriters,
+    progress: &Progress,
+    opts: ChunkOptions,
+) -> Result<BuildStats> {
+    assert!(cuts[0].is_empty());
+    let stride = opts.stride;
+    let n_rows = text.len() / stride + usize::from(opts.sentinel.is_some());
+    if let Some((_, text_pos_rows)) = aux.text_pos.as_mut() {
+        *text_pos_rows = n_rows;
+    }
+    let mut runs = RunCounter::default();
+    let mut primary = usize::MAX;
+    let chunk_range = opts.chunk_range(cuts.len());
+    // The rows before the range of cuts, preceded by the sentinel if any,
+    // where the last one is kept to compute the LCP value across the range.
+    let first_row = if *chunk_range.start() == 1 {
+        0
+    } else {
+        let cut = &cuts[chunk_range.start() - 1];
+        let (n_before, last) = suffixes_up_to(text, cut, stride, aux.lcp.is_some());
+        aux.prev_suffix = last;
+        n_before + usize::from(opts.sentinel.is_some())
+    };
+    let mut offset = first_row;
+    let first_cut = chunk_range.start() - 1;
+    let mut n_chunks_done = 0;
+    let mut max_chunk_len = 0;
+    let mut splice = SpliceCursor::default();
+    // The suffixes are written in batches without cuts by prefix-free parsing.
+    let n_chunks = opts.pfp.map_or_else(
+        || chunk_range.clone().count(),
+        |pfp| {
+            let n = text.len() / stride;
+            n / pfp.batch_size + usize::from(n % pfp.batch_size != 0)
+        },
+    );
+    // Returns the buffer of the chunk to be reused.
+    let mut write_chunk = |mut chunks: Vec<I>| -> Result<Vec<I>> {
+        if offset == 0 && opts.sentinel.is_some() {
+            // The empty suffix before the sentinel is the smallest one.
+            chunks.insert(0, I::from_usize(text.len()));
+        }
+        // The suffixes sorted without cuts already have the excluded ones.
+        let chunks = if opts.sais || opts.pfp.is_some() {
+            chunks
+        } else {
+            text.splice_excluded(chunks, &mut splice, n_chunks_done + 1 == cuts.len())
+        };
+        for (i, &j) in chunks.iter().enumerate() {
+            let j = j.to_usize();
+            if j == 0 {
+                primary = offset + i;
+            }
+            for_each_preceding_byte(text, j, stride, opts.sentinel, |c| {
+                runs.push(c);
+                wrt.write_all(&[c])
+            })?;
+        }
+        aux.write_chunk(text, stride, offset, &chunks)?;
+        offset += chunks.len();
+        n_chunks_done += 1;
+        max_chunk_len = max_chunk_len.max(chunks.len());
+        progress.chunk_done(&ChunkProgress {
+            n_chunks_done,
+            n_chunks,
+            chunk_len: chunks.len(),
+            n_bytes_written: offset * stride,
+            n_bytes: n_rows * stride,
+        });
+        if let Some(path) = opts.checkpoint {
+            let n_cuts_done = first_cut + n_chunks_done;
+            if n_cuts_done < cuts.len() {
+                wrt.flush()?;
+                let checkpoint = Checkpoint {
+                    text_len: text.len() / stride,
+                    cuts: cuts.to_vec(),
+                    n_cuts_done,
+                    n_bytes_written: offset * stride,
+                };
+                checkpoint.save(path)?;
+            }
+        }
+        Ok(chunks)
+    };
+    if opts.sais {
+        progress.print("Generating BWT by SA-IS...");
+        let sa = sais::suffix_array(text);
+        write_chunk(
+            sa.into_iter()
+                .filter(|&j| j % stride == 0)
+                .map(I::from_usize)
+                .collect(),
+        )?;
+    } else if let Some(params) = opts.pfp {
+        progress.print("Parsing the text into phrases...");
+        let pfp = PrefixFreeParse::new(text, params.window, params.modulus);
+        progress.print(&format!(
+            "Number of phrases: {:?} (distinct: {:?})",
+            pfp.parse_len(),
+            pfp.n_phrases()
+        ));
+        progress.print("Generating BWT by prefix-free parsing...");
+        // The batch size can exceed the text length if a large chunk size is set.
+        let capacity = params.batch_size.min(text.len() / stride);
+        let mut batch = Vec::with_capacity(capacity);
+        pfp.for_each_sorted_suffix(|j| {
+            if j % stride != 0 {
+                return Ok(());
+            }
+            batch.push(I::from_usize(j));
+            if batch.len() == params.batch_size {
+                opts.check_cancelled()?;
+                write_chunk(std::mem::replace(&mut batch, Vec::with_capacity(capacity)))?;
+            }
+            Ok(())
+        })?;
+        if !batch.is_empty() {
+            write_chunk(batch)?;
+        }
+    } else if let Some(dir) = opts.spill_dir {
+        let spilled = SpilledChunks::bucket(text, cuts, stride, dir, progress)?;
+        let mut chunk = vec![];
+        let mut buffers = SortBuffers::default();
+        for q in chunk_range {
+            opts.check_cancelled()?;
+            progress.print(&format!("Generating BWT: {}/{}", q, cuts.len()));
+            spilled.load(q, &mut chunk)?;
+            let depth = shared_prefix_len(cuts, q);
+            let parallel = opts.n_threads > 1;
+            sort_suffixes(text, &mut chunk, depth, parallel, opts, &mut buffers);
+            chunk = write_chunk(chunk)?;
+        }
+    } else if opts.batch_size(cuts.len()) == 1 && !opts.single_pass && opts.pipeline {
+        // The next chunk is gathered by another thread while the current one is sorted and written,
+        // where the buffers are passed back to be reused.
+        // The rendezvous channel keeps at most two chunks in memory.
+        let mut buffers = SortBuffers::default();
+        std::thread::scope(|s| -> Result<()> {
+            let (chunk_tx, chunk_rx) = mpsc::sync_channel::<Vec<I>>(0);
+            let (reuse_tx, reuse_rx) = mpsc::sync_channel::<Vec<I>>(1);
+            let gathered = chunk_range.clone();
+            s.spawn(move || {
+                for q in gathered {
+                    let mut chunk = reuse_rx.try_recv().unwrap_or_default();
+                    gather_chunk(text, cuts, q, stride, progress, &mut chunk);
+                    // The receiver is dropped if the construction fails.
+                    if chunk_tx.send(chunk).is_err() {
+                        break;
+                    }
+                }
+            });
+            for q in chunk_range {
+                opts.check_cancelled()?;
+                let mut chunk = match chunk_rx.recv() {
+                    Ok(chunk) => chunk,
+                    // The gathering thread has panicked, which is resumed by the scope.
+                    Err(_) => break,
+                };
+                let depth = shared_prefix_len(cuts, q);
+                sort_suffixes(text, &mut chunk, depth, false, opts, &mut buffers);
+                let _ = reuse_tx.try_send(write_chunk(chunk)?);
+            }
+            Ok(())
+        })?;
+    } else if opts.batch_size(cuts.len()) == 1 && !opts.single_pass {
+        // The chunks are gathered and sorted one by one, reusing the buffers across the cuts.
+        let mut chunk = vec![];
+        let mut buffers = SortBuffers::default();
+        for q in chunk_range {
+            opts.check_cancelled()?;
+            gather_chunk(text, cuts, q, stride, progress, &mut chunk);
+            let depth = shared_prefix_len(cuts, q);
+            sort_suffixes(text, &mut chunk, depth, false, opts, &mut buffers);
+            chunk = write_chunk(chunk)?;
+        }
+    } else {
+        let batch_size = opts.batch_size(cuts.len());
+        let end = chunk_range.end() + 1;
+        for q in chunk_range.step_by(batch_size) {
+            opts.check_cancelled()?;
+            let qs = q..(q + batch_size).min(end);
+            for chunks in sort_chunks::<I, T>(text, cuts, qs, progress, opts) {
+                write_chunk(chunks)?;
+            }
+        }
+    }
+    if primary == usize::MAX {
+        // The whole text is out of the range of cuts.
+        primary = rank_of_suffix(text, 0, stride) + usize::from(opts.sentinel.is_some());
+    }
+    let rows = first_row..offset;
+    aux.flush()?;
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        primary_index = primary,
+        n_runs = runs.stats().n_runs,
+        "built the BWT"
+    );
+    Ok(BuildStats {
+        text_len: text.len() / stride,
+        // The chunk size and the elapsed time are set by BwtBuilder::build.
+        chunk_size: 0,
+        n_cuts: cuts.len(),
+        max_chunk_len,
+        n_bytes_written: rows.len() * stride + aux.n_bytes(rows.len()),
+        elapsed: Duration::ZERO,
+        primary_index: primary,
+        runs: runs.stats(),
+        reverse_primary_index: None,
+        context_order: None,
+        terminator: None,
+        sentinel: None,
+        alphabet: None,
+        n_runs: vec![],
+        rows,
+    })
+}
+
+/// Returns the number of the suffixes bucketed by the cuts that are no greater than `cut`,
+/// with the largest one of them if `find_last` is `true`.
+fn suffixes_up_to<T: TextAccess + ?Sized>(
+    text: &T,
+    cut: &[u8],
+    stride: usize,
+    find_last: bool,
+) -> (usize, Option<usize>) {
+    let mut n = 0;
+    let mut last: Option<usize> = None;
+    for j in suffix_positions(text, stride) {
+        if text.cmp_suffix(j, cut).is_gt() {
+            continue;
+        }
+        n += 1;
+        if find_last && last.map_or(true, |i| text.cmp_suffixes(i, j).is_lt()) {
+            last = Some(j);
+        }
+    }
+    (n, last)
+}
+
+/// Returns the number of the suffixes bucketed by the cuts that are smaller than the one at `i`.
+fn rank_of_suffix<T: TextAccess + ?Sized>(text: &T, i: usize, stride: usize) -> usize {
+    suffix_positions(text, stride)
+        .filter(|&j| text.cmp_suffixes(j, i).is_lt())
+        .count()
+}
+
+/// Calls `f` with the bytes of the symbol preceding the suffix at `j` in a text of symbols of `stride` bytes,
+/// which is the last symbol for the whole text, or the implicit sentinel repeated if any.
+fn for_each_preceding_byte<T, F>(
+    text: &T,
+    j: usize,
+    stride: usize,
+    sentinel: Option<u8>,
+    mut f: F,
+) -> std::io::Result<()>
+where
+    T: TextAccess + ?Sized,
+    F: FnMut(u8) -> std::io::Result<()>,
+{
+    if j == 0 {
+        if let Some(sentinel) = sentinel {
+            return (0..stride).try_for_each(|_| f(sentinel));
+        }
+    }
+    let start = if j == 0 { text.len() } else { j } - stride;
+    (start..start + stride).try_for_each(|k| f(text.get(k)))
+}
+
+/// Returns the number of bytes to store a suffix position of a text of length `n` in a chunk,
+/// following the index type chosen by [`bwt_from_cuts`].
+const fn suffix_index_bytes(n: usize) -> usize {
+    if n - 1 <= u32::MAX_POS {
+        std::mem::size_of::<u32>()
+    } else if n - 1 <= U40::MAX_POS {
+        std::mem::size_of::<U40>()
+    } else {
+        std::mem::size_of::<usize>()
+    }
+}
+
+/// Options to gather and sort chunks.
+#[derive(Clone, Copy)]
+struct ChunkOptions<'a> {
+    // The number of threads to sort chunks.
+    n_threads: usize,
+    // Whether to bucket all the suffixes into chunks in a single scan.
+    single_pass: bool,
+    // The directory to spill the chunks in the external-memory mode.
+    spill_dir: Option<&'a Path>,
+    // The flag to cancel the construction.
+    cancel: Option<&'a AtomicBool>,
+    // The byte written for the implicit sentinel, if the text has no terminator.
+    sentinel: Option<u8>,
+    // The number of bytes per symbol of the text.
+    stride: usize,
+    // Whether to sort all the suffixes at once by SA-IS.
+    sais: bool,
+    // The sorter of the suffixes in each chunk, or the built-in one if None.
+    sorter: Option<&'a dyn SuffixSorter>,
+    // Whether the built-in sorter buckets large chunks by two bytes at the top level.
+    wide_radix: bool,
+    // Whether to gather the next chunk in another thread while sorting the current one.
+    pipeline: bool,
+    // The parameters of prefix-free parsing, if it is used instead of cuts.
+    pfp: Option<PfpParams>,
+    // The range of cuts whose chunks are built, or None for all of them.
+    cut_range: Option<(usize, usize)>,
+    // The file to write a checkpoint after each chunk.
+    checkpoint: Option<&'a Path>,
+}
+
+impl Default for ChunkOptions<'_> {
+    fn default() -> Self {
+        Self {
+            n_threads: 1,
+            single_pass: false,
+            spill_dir: None,
+            cancel: None,
+            sentinel: None,
+            stride: 1,
+            sais: false,
+            sorter: None,
+            wide_radix: false,
+            pipeline: false,
+            pfp: None,
+            cut_range: None,
+            checkpoint: None,
+        }
+    }
+}
+
+impl ChunkOptions<'_> {
+    /// Returns an [`Error::Cancelled`] error if the construction has been cancelled.
+    fn check_cancelled(&self) -> Result<()> {
+        match self.cancel {
+            Some(flag) if flag.load(AtomicOrdering::Relaxed) => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the indices of the chunks to be built among `n_cuts` ones, starting from 1.
+    const fn chunk_range(&self, n_cuts: usize) -> RangeInclusive<usize> {
+        match self.cut_range {
+            Some((i, j)) => i + 1..=j,
+            None => 1..=n_cuts,
+        }
+    }
+
+    /// Returns the number of cuts whose chunks are sorted at once.
+    fn batch_size(&self, n_cuts: usize) -> usize {
+        if self.single_pass {
+            n_cuts.max(1)
+        } else {
+            self.n_threads.max(1)
+        }
+    }
+}
+
+/// Gathers and sorts the chunks of the cuts in `qs`.
+fn sort_chunks<I: SuffixIndex, T: TextAccess + ?Sized>(
+    text: &T,
+    cuts: &[Vec<u8>],
+    qs: Range<usize>,
+    progress: &Progress,
+    opts: ChunkOptions,
+) -> Vec<Vec<I>> {
+    if opts.single_pass {
+        let mut chunks = bucket_suffixes(text, cuts, qs.clone(), opts.stride, progress);
+        #[cfg(feature = "rayon")]
+        if opts.n_threads > 1 {
+            use rayon::prelude::*;
+            chunks.par_iter_mut().zip(qs).for_each_init(
+                SortBuffers::default,
+                |buffers, (chunk, q)| {
+                    let depth = shared_prefix_len(cuts, q);
+                    sort_suffixes(text, chunk, depth, true, opts, buffers);
+                },
+            );
+            return chunks;
+        }
+        let mut buffers = SortBuffers::default();
+        for (chunk, q) in chunks.iter_mut().zip(qs) {
+            let depth = shared_prefix_len(cuts, q);
+            sort_suffixes(text, chunk, depth, false, opts, &mut buffers);
+        }
+        return chunks;
+    }
+    #[cfg(feature = "rayon")]
+    if opts.n_threads > 1 {
+        use rayon::prelude::*;
+        return qs
+            .into_par_iter()
+            .map_init(SortBuffers::default, |buffers, q| {
+                let mut chunk = vec![];
+                gather_chunk(text, cuts, q, opts.stride, progress, &mut chunk);
+                let depth = shared_prefix_len(cuts, q);
+                sort_suffixes(text, &mut chunk, depth, true, opts, buffers);
+                chunk
+            })
+            .collect();
+    }
+    let mut buffers = SortBuffers::default();
+    qs.map(|q| {
+        let mut chunk = vec![];
+        gather_chunk(text, cuts, q, opts.stride, progress, &mut chunk);
+        let depth = shared_prefix_len(cuts, q);
+        sort_suffixes(text, &mut chunk, depth, false, opts, &mut buffers);
+        chunk
+    })
+    .collect()
+}
+
+/// Gathers the suffixes between the `q-1`-th and `q`-th cuts into `chunk`,
+/// starting at the boundaries of symbols of `stride` bytes.
+fn gather_chunk<I: SuffixIndex, T: TextAccess + ?Sized>(
+    text: &T,
+    cuts: &[Vec<u8>],
+    q: usize,
+    stride: usize,
+    progress: &Progress,
+    chunk: &mut Vec<I>,
+) {
+    progress.print(&format!("Generating BWT: {}/{}", q, cuts.len()));
+    progress.print(&format!("Length of the cut: {:?}", cuts[q - 1].len()));
+
+    chunk.clear();
+    let cut_p = cuts[q - 1].as_slice();
+    if q < cuts.len() {
+        let cut_q = cuts[q].as_slice();
+        for j in suffix_positions(text, stride) {
+            if text.cmp_suffix(j, cut_p) == Ordering::Greater
+                && text.cmp_suffix(j, cut_q) != Ordering::Greater
+            {
+                chunk.push(I::from_usize(j));
+            }
+        }
+    } else {
+        for j in suffix_positions(text, stride) {
+            if text.cmp_suffix(j, cut_p) == Ordering::Greater {
+                chunk.push(I::from_usize(j));
+            }
+        }
+    }
+
+    progress.print(&format!("Length of the chunks: {:?}", chunk.len()));
+}
+
+/// Buckets the suffixes into the chunks of the cuts in `qs` in a single scan,
+/// by binary search on the cuts.
+fn bucket_suffixes<I: SuffixIndex, T: TextAccess + ?Sized>(
+    text: &T,
+    cuts: &[Vec<u8>],
+    qs: Range<usize>,
+    stride: usize,
+    progress: &Progress,
+) -> Vec<Vec<I>> {
+    progress.print(&format!(
+        "Bucketing suffixes into chunks: {}..={}/{}",
+        qs.start,
+        qs.end - 1,
+        cuts.len()
+    ));
+    let mut chunks = vec![vec![]; qs.len()];
+    for j in suffix_positions(text, stride) {
+        // The first cut not smaller than the suffix, which is never the empty cut.
+        let q = cuts.partition_point(|cut| text.cmp_suffix(j, cut).is_gt());
+        if qs.contains(&q) {
+            chunks[q - qs.start].push(I::from_usize(j));
+        }
+    }
+    chunks
+}
+
+/// Returns the length of the prefix shared by the suffixes between the `q-1`-th and `q`-th cuts,
+/// i.e., the longest common prefix of the cuts, where the last chunk is unbounded.
+fn shared_prefix_len(cuts: &[Vec<u8>], q: usize) -> usize {
+    cuts.get(q).map_or(0, |upper| {
+        cuts[q - 1]
+            .iter()
+            .zip(upper)
+            .take_while(|(a, b)| a == b)
+            .count()
+    })
+}
+
+/// Buffers to sort the chunks, reused across the cuts to avoid allocations in steady state.
+struct SortBuffers<I> {
+    // The positions of the suffixes given to the sorter set by the user.
+    positions: Vec<usize>,
+    radix: SortWorkspace<I>,
+}
+
+impl<I> Default for SortBuffers<I> {
+    fn default() -> Self {
+        Self {
+            positions: vec![],
+            radix: SortWorkspace::default(),
+        }
+    }
+}
+
+/// Sorts the suffixes sharing the first `depth` bytes by the sorter in `opts` if given,
+/// or by the built-in MSD radix sort starting at `depth`, in parallel if `parallel` is `true`.
+#[cfg_attr(not(feature = "rayon"), allow(unused_variables))]
+fn sort_suffixes<I: SuffixIndex, T: TextAccess + ?Sized>(
+    text: &T,
+    suffixes: &mut [I],
+    depth: usize,
+    parallel: bool,
+    opts: ChunkOptions,
+    buffers: &mut SortBuffers<I>,
+) {
+    if let Some(sorter) = opts.sorter {
+        let positions = &mut buffers.positions;
+        positions.clear();
+        positions.extend(suffixes.iter().map(|s| s.to_usize()));
+        sorter.sort_from_depth(&TextView(text), positions, depth);
+        for (s, &p) in suffixes.iter_mut().zip(positions.iter()) {
+            *s = I::from_usize(p);
+        }
+        return;
+    }
+    let config = MsdRadixSort::default().wide_radix(opts.wide_radix);
+    #[cfg(feature = "rayon")]
+    if parallel {
+        MsdRadixSorter::sort_parallel(text, suffixes, config, depth, &mut buffers.radix);
+        return;
+    }
+    MsdRadixSorter::sort_slice(text, suffixes, config, depth, &mut buffers.radix);
+}
+
+fn to_mb(bytes: usize) -> f64 {
+    bytes as f64 / 1000.0 / 1000.0
+}
+
+fn to_mib(bytes: usize) -> f64 {
+    bytes as f64 / 1024.0 / 1024.0
+}
+
+/// Placeholder of the implicit sentinel in the BWT of a string containing `\0`
+/// built by [`BwtBuilder::from_str`](BwtBuilder), which never occurs in UTF-8.
+pub const STR_SENTINEL: u8 = 0xff;
+
+/// Chooses a terminator to be appended to the text, i.e., a byte smaller than all the bytes in the text.
+///
+/// The smallest byte `0x00` is chosen unless it occurs in the text.
+///
+/// # Arguments
+///
+/// * `text` - The text without a terminator.
+///
+/// # Returns
+///
+/// The terminator, or `None` if the text contains `0x00` and thus no byte can be a terminator.
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::choose_terminator;
+///
+/// assert_eq!(choose_terminator(b"abracadabra"), Some(0));
+/// assert_eq!(choose_terminator(b"abra\0cadabra"), None);
+/// ```
+pub fn choose_terminator(text: &[u8]) -> Option<u8> {
+    if text.contains(&0) {
+        None
+    } else {
+        Some(0)
+    }
+}
+
+/// Verifies that the smallest character appears only at the end of the text.
+///
+/// # Arguments
+///
+/// * `text` - The text to be verified.
+///
+/// # Errors
+///
+/// An error is returned if the smallest character does not appear only at the end of the text.
+///
+/// # Examples
+///
+/// ```
+/// use small_bwt::verify_terminator;
+///
+/// let text = "abracadabra$";
+/// let result = verify_terminator(text.as_bytes());
+/// assert!(result.is_ok());
+///
+/// let text = "abrac$dabra$";
+/// let result = verify_terminator(text.as_bytes());
+/// assert!(result.is_err());
+/// ```
+pub fn verify_terminator(text: &[u8]) -> Result<()> {
+    if text.is_empty() {
+        return Err(Error::EmptyText);
+    }
+    let smallest = *text.last().unwrap();
+    for (i, &c) in text[..text.len() - 1].iter().enumerate() {
+        if c <= smallest {
+            return Err(Error::InvalidTerminator { pos: i, byte: c });
+        }
+    }
+    Ok(())
+}
+
+/// Report of [`diagnose_terminator`] on how a text violates the requirement of [`verify_terminator`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TerminatorReport {
+    /// The last byte of the text, which is expected to be the terminator.
+    pub terminator: u8,
+    /// The smallest byte of the text.
+    pub smallest: u8,
+    /// The number of occurrences of the smallest byte.
+    pub n_smallest: usize,
+    /// The number of positions before the end whose bytes are no greater than the terminator.
+    pub n_violations: usize,
+    /// The first positions of the violations, up to the limit given to [`diagnose_terminator`].
+    pub violations: Vec<usize>,
+    /// The bytes smaller than all the bytes of the text except the last one, in increasing order,
+    /// any of which can be appended or substituted for the last byte as the terminator.
+    /// It is empty if the text contains `0x00` before the end,
+    /// in which case [`TextPreparer`] or [`BwtBuilder::implicit_sentinel`] can be used instead.
+    pub free_terminators: Vec<u8>,
+}
+
+impl TerminatorReport {
+    /// Returns `true` if the text satisfies the requirement of [`verify_terminator`].
+    pub const fn is_valid(&self) -> bool {
+        self.n_violations == 0
+    }
+}
+
+/// Diagnoses the terminator of a text in a single scan, reporting all the violations of
+/// the requirement of [`verify_terminator`] instead of stopping at the first one.
+///
+/// # Arguments
+///
+/// * `text` - The text to be diagnosed.
+/// * `max_violations` - The maximum number of positions of the violations to be recorded.
+///
+/// # Errors
+///
+/// An error is returned if `text` is empty.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::diagnose_terminator;
+///
+/// let report = diagnose_terminator(b"abrac$dabra$$", 2)?;
+/// assert!(!report.is_valid());
+/// assert_eq!(report.smallest, b'$');
+/// assert_eq!(report.n_smallest, 3);
+/// assert_eq!(report.n_violations, 2);
+/// assert_eq!(report.violations, [5, 11]);
+/// assert_eq!(report.free_terminators, (0..b'$').collect::<Vec<_>>());
+/// # Ok(())
+/// # }
+/// ```
+pub fn diagnose_terminator(text: &[u8], max_violations: usize) -> Result<TerminatorReport> {
+    let (&terminator, body) = text.split_last().ok_or(Error::EmptyText)?;
+    let mut counts = [0; 256];
+    let mut n_violations = 0;
+    let mut violations = vec![];
+    for (i, &c) in body.iter().enumerate() {
+        counts[c as usize] += 1;
+        if c <= terminator {
+            n_violations += 1;
+            if violations.len() < max_violations {
+                violations.push(i);
+            }
+        }
+    }
+    counts[terminator as usize] += 1;
+    let smallest = (0..=255).find(|&c| counts[c as usize] != 0).unwrap();
+    // The bytes before the smallest one occurring except at the end.
+    let free_terminators = (0..=255)
+        .take_while(|&c| counts[c as usize] <= usize::from(c == terminator))
+        .collect();
+    Ok(TerminatorReport {
+        terminator,
+        smallest,
+        n_smallest: counts[smallest as usize],
+        n_violations,
+        violations,
+        free_terminators,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bwt_builder() {
+        let text = "abracadabra$";
+        let mut bwt = vec![];
+        let stats = BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        let bwt_str = String::from_utf8_lossy(&bwt);
+        assert_eq!(bwt_str, "ard$rcaaaabb");
+        assert_eq!(stats.primary_index, 3);
+        assert_eq!(stats.runs, RunStats::from_bwt(&bwt));
+    }
+
+    #[test]
+    fn test_bwt_builder_primary_single() {
+        let text = "$";
+        let mut bwt = vec![];
+        let stats = BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, b"$");
+        assert_eq!(stats.primary_index, 0);
+        assert_eq!(stats.runs.n_runs, 1);
+    }
+
+    #[test]
+    fn test_bwt_builder_3() {
+        let text = "abracadabra$";
+        let mut bwt = vec![];
+        BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .chunk_size(3)
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        let bwt_str = String::from_utf8_lossy(&bwt);
+        assert_eq!(bwt_str, "ard$rcaaaabb");
+    }
+
+    #[test]
+    fn test_bwt_builder_4() {
+        let text = "abracadabra$";
+        let mut bwt = vec![];
+        BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .chunk_size(4)
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        let bwt_str = String::from_utf8_lossy(&bwt);
+        assert_eq!(bwt_str, "ard$rcaaaabb");
+    }
+
+    #[test]
+    fn test_bwt_builder_suffix_array_u64() {
+        let text = "abracadabra$";
+        let mut bwt = vec![];
+        let mut sa = vec![];
+        BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .chunk_size(3)
+            .unwrap()
+            .suffix_array_writer(&mut sa, IntWidth::U64)
+            .build(&mut bwt)
+            .unwrap();
+        let sa: Vec<u64> = sa
+            .chunks(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(sa, vec![11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
+        assert_eq!(String::from_utf8_lossy(&bwt), "ard$rcaaaabb");
+    }
+
+    #[test]
+    fn test_bwt_builder_sampled_suffix_array() {
+        let text = "abracadabra$";
+        let sa = [11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2];
+        for sample_rate in 1..=text.len() + 1 {
+            let mut bwt = vec![];
+            let mut samples = vec![];
+            let stats = BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .chunk_size(3)
+                .unwrap()
+                .sampled_suffix_array_writer(&mut samples, IntWidth::U64, sample_rate)
+                .unwrap()
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(stats.n_bytes_written, bwt.len() + samples.len());
+            let samples: Vec<u64> = samples
+                .chunks(8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            let expected: Vec<u64> = sa.iter().step_by(sample_rate).copied().collect();
+            assert_eq!(samples, expected);
+        }
+        let e = BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .sampled_suffix_array_writer(vec![], IntWidth::U64, 0);
+        assert!(e.is_err());
+    }
+
+    #[test]
+    fn test_bwt_builder_lcp_array() {
+        let text = "abracadabra$";
+        for chunk_size in 1..=text.len() {
+            let mut bwt = vec![];
+            let mut lcp = vec![];
+            BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .chunk_size(chunk_size)
+                .unwrap()
+                .lcp_array_writer(&mut lcp, IntWidth::U64)
+                .build(&mut bwt)
+                .unwrap();
+            let lcp: Vec<u64> = lcp
+                .chunks(8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(lcp, vec![0, 0, 1, 4, 1, 1, 0, 3, 0, 0, 0, 2]);
+        }
+    }
+
+    #[test]
+    fn test_bwt_builder_inverse_suffix_array() {
+        let text = "abracadabra$";
+        for chunk_size in 1..=text.len() {
+            let mut bwt = vec![];
+            let mut isa = std::io::Cursor::new(vec![]);
+            BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .chunk_size(chunk_size)
+                .unwrap()
+                .inverse_suffix_array_writer(&mut isa, IntWidth::U64)
+                .build(&mut bwt)
+                .unwrap();
+            let isa: Vec<u64> = isa
+                .into_inner()
+                .chunks(8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(isa, vec![3, 7, 11, 4, 8, 5, 9, 2, 6, 10, 1, 0]);
+        }
+    }
+
+    #[test]
+    fn test_bwt_builder_text_position() {
+        let text = b"mississippi$";
+        for chunk_size in 1..=text.len() {
+            let mut bwt = vec![];
+            let mut pos = vec![];
+            let stats = BwtBuilder::new(text)
+                .unwrap()
+                .chunk_size(chunk_size)
+                .unwrap()
+                .text_position_writer(&mut pos, IntWidth::U32)
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(stats.n_bytes_written, 5 * text.len());
+            let pos: Vec<usize> = pos
+                .chunks(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+                .collect();
+            assert_eq!(pos, vec![10, 9, 6, 3, 0, 11, 8, 7, 5, 2, 4, 1]);
+            for (i, &p) in pos.iter().enumerate() {
+                assert_eq!(bwt[i], text[p]);
+            }
+        }
+        // The sentinel is at position n.
+        let mut bwt = vec![];
+        let mut pos = vec![];
+        BwtBuilder::new_unchecked(b"banana")
+            .unwrap()
+            .implicit_sentinel(b'$')
+            .text_position_writer(&mut pos, IntWidth::U64)
+            .build(&mut bwt)
+            .unwrap();
+        let pos: Vec<u64> = pos
+            .chunks(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(bwt, b"annb$aa");
+        assert_eq!(pos, vec![5, 4, 2, 0, 6, 3, 1]);
+    }
+
+    #[test]
+    fn test_bwt_builder_document_array() {
+        let text = "ab,ab,b$";
+        let mut bwt = vec![];
+        let mut da = vec![];
+        BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .chunk_size(2)
+            .unwrap()
+            .document_array_writer(&[0, 3, 6], &mut da, IntWidth::U64)
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        let da: Vec<u64> = da
+            .chunks(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        // Sorted suffixes: $, ,ab,b$, ,b$, ab,ab,b$, ab,b$, b$, b,ab,b$, b,b$
+        assert_eq!(da, vec![2, 0, 1, 0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn test_bwt_builder_document_array_invalid() {
+        let text = "ab,ab,b$";
+        let builder = || BwtBuilder::new(text.as_bytes()).unwrap();
+        let da_for = |starts| builder().document_array_writer(starts, vec![], IntWidth::U32);
+        assert!(da_for(&[]).is_err());
+        assert!(da_for(&[1, 3]).is_err());
+        assert!(da_for(&[0, 3, 3]).is_err());
+        assert!(da_for(&[0, 8]).is_err());
+        assert!(da_for(&[0, 7]).is_ok());
+    }
+
+    #[test]
+    fn test_bwt_builder_reverse() {
+        let text = "mississippi$";
+        let mut bwt = vec![];
+        let mut rev_bwt = vec![];
+        let stats = BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .chunk_size(3)
+            .unwrap()
+            .reverse_writer(&mut rev_bwt)
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, b"ipssm$pissii");
+        assert_eq!(decode_bwt(&rev_bwt).unwrap(), b"ippississim$");
+        let mut expected = vec![];
+        let expected_stats = BwtBuilder::new(b"ippississim$")
+            .unwrap()
+            .build(&mut expected)
+            .unwrap();
+        assert_eq!(rev_bwt, expected);
+        assert_eq!(
+            stats.reverse_primary_index,
+            Some(expected_stats.primary_index)
+        );
+    }
+
+    #[test]
+    fn test_bwt_builder_implicit_sentinel() {
+        // The text contains every byte value, and its last byte is not the smallest.
+        let text: Vec<u8> = (0..600u32)
+            .map(|i| (i * i % 256) as u8)
+            .chain(b"abab".iter().copied())
+            .collect();
+        let n = text.len();
+        let mut sa: Vec<usize> = (0..=n).collect();
+        sa.sort_by_key(|&i| &text[i..]);
+        let expected: Vec<u8> = sa
+            .iter()
+            .map(|&i| if i == 0 { b'?' } else { text[i - 1] })
+            .collect();
+        for chunk_size in [1, 7, 100, n] {
+            let mut bwt = vec![];
+            let mut sa_bytes = vec![];
+            let mut rev_bwt = vec![];
+            let stats = BwtBuilder::new_unchecked(&text)
+                .unwrap()
+                .chunk_size(chunk_size)
+                .unwrap()
+                .implicit_sentinel(b'?')
+                .suffix_array_writer(&mut sa_bytes, IntWidth::U32)
+                .reverse_writer(&mut rev_bwt)
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(bwt, expected);
+            assert_eq!(stats.text_len, n);
+            assert_eq!(
+                stats.primary_index,
+                sa.iter().position(|&i| i == 0).unwrap()
+            );
+            assert_eq!(stats.n_bytes_written, 5 * (n + 1));
+            let sa_values: Vec<usize> = sa_bytes
+                .chunks(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+                .collect();
+            assert_eq!(sa_values, sa);
+            verify_suffix_array(&text, sa_values).unwrap();
+            let reversed: Vec<u8> = text.iter().rev().copied().collect();
+            assert_eq!(
+                decode_bwt_with_sentinel(&rev_bwt, stats.reverse_primary_index.unwrap()).unwrap(),
+                reversed
+            );
+        }
+    }
+
+    #[test]
+    fn test_bwt_builder_binary_content() {
+        // Binary content with embedded zeros and every byte value.
+        let text: Vec<u8> = (0..3000u32)
+            .map(|i| {
+                if i % 5 == 0 {
+                    0
+                } else {
+                    (i * 7919 % 256) as u8
+                }
+            })
+            .chain([0, 0, 0])
+            .collect();
+        let build = |builder: BwtBuilder| {
+            let mut bwt = vec![];
+            let stats = builder
+                .chunk_size(50)
+                .unwrap()
+                .implicit_sentinel(0)
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(
+                decode_bwt_with_sentinel(&bwt, stats.primary_index).unwrap(),
+                text
+            );
+            bwt
+        };
+        let expected = build(BwtBuilder::new_unchecked(&text).unwrap());
+        let dir = std::env::temp_dir();
+        let builders = [
+            BwtBuilder::new_unchecked(&text).unwrap().single_pass(true),
+            BwtBuilder::new_unchecked(&text)
+                .unwrap()
+                .kmer_table(2)
+                .unwrap(),
+            BwtBuilder::new_unchecked(&text)
+                .unwrap()
+                .external_memory(&dir, 1 << 10)
+                .unwrap(),
+        ];
+        for builder in builders {
+            assert_eq!(build(builder), expected);
+        }
+        #[cfg(feature = "rayon")]
+        assert_eq!(
+            build(BwtBuilder::new_unchecked(&text).unwrap().parallel(true)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_bwt_builder_remap_alphabet() {
+        let text = b"mississippi$";
+        let mut expected = vec![];
+        let mut expected_sa = vec![];
+        BwtBuilder::new(text)
+            .unwrap()
+            .suffix_array_writer(&mut expected_sa, IntWidth::U32)
+            .build(&mut expected)
+            .unwrap();
+        for chunk_size in 1..=text.len() {
+            let mut bwt = vec![];
+            let mut sa = vec![];
+            let stats = BwtBuilder::new(text)
+                .unwrap()
+                .chunk_size(chunk_size)
+                .unwrap()
+                .remap_alphabet(true)
+                .suffix_array_writer(&mut sa, IntWidth::U32)
+                .build(&mut bwt)
+                .unwrap();
+            let alphabet = stats.alphabet.unwrap();
+            assert_eq!(alphabet.sigma(), 5);
+            assert!(bwt.iter().all(|&k| (k as usize) < alphabet.sigma()));
+            assert_eq!(alphabet.decode_all(&bwt), expected);
+            assert_eq!(sa, expected_sa);
+            assert_eq!(stats.primary_index, 5);
+        }
+        let stats = BwtBuilder::new(text).unwrap().build(&mut vec![]).unwrap();
+        assert_eq!(stats.alphabet, None);
+    }
+
+    #[test]
+    fn test_bwt_builder_from_symbols() {
+        // Symbols larger than bytes, with many repeats of shared high bytes.
+        let text: Vec<u16> = (0..500u32)
+            .map(|i| (0x100 + i * i % 37 * 0x101) as u16)
+            .chain([0])
+            .collect();
+        let n = text.len();
+        let mut expected_sa: Vec<usize> = (0..n).collect();
+        expected_sa.sort_by(|&i, &j| text[i..].cmp(&text[j..]));
+        let expected: Vec<u16> = expected_sa.iter().map(|&j| text[(j + n - 1) % n]).collect();
+        let mut expected_lcp = vec![0];
+        for w in expected_sa.windows(2) {
+            let l = text[w[0]..]
+                .iter()
+                .zip(&text[w[1]..])
+                .take_while(|(a, b)| a == b)
+                .count();
+            expected_lcp.push(l);
+        }
+        let dir = std::env::temp_dir();
+        for chunk_size in [1, 7, 64, n] {
+            for mode in 0..4 {
+                let builder = BwtBuilder::from_symbols(&text).unwrap();
+                let builder = match mode {
+                    0 => builder,
+                    1 => builder.single_pass(true),
+                    2 => builder.kmer_table(1).unwrap(),
+                    _ => builder.external_memory(&dir, 1 << 10).unwrap(),
+                };
+                let mut bwt = vec![];
+                let mut sa = vec![];
+                let mut lcp = vec![];
+                let stats = builder
+                    .chunk_size(chunk_size)
+                    .unwrap()
+                    .suffix_array_writer(&mut sa, IntWidth::U32)
+                    .lcp_array_writer(&mut lcp, IntWidth::U32)
+                    .build(&mut bwt)
+                    .unwrap();
+                let bwt: Vec<u16> = bwt.chunks(2).map(u16::from_be_slice).collect();
+                assert_eq!(bwt, expected);
+                let to_usizes = |bytes: &[u8]| -> Vec<usize> {
+                    bytes
+                        .chunks(4)
+                        .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+                        .collect()
+                };
+                assert_eq!(to_usizes(&sa), expected_sa);
+                assert_eq!(to_usizes(&lcp), expected_lcp);
+                assert_eq!(stats.text_len, n);
+                assert_eq!(stats.n_bytes_written, n * 2 + n * 4 * 2);
+                assert_eq!(
+                    stats.primary_index,
+                    expected_sa.iter().position(|&j| j == 0).unwrap()
+                );
+            }
+        }
+        // Without a terminator through the implicit sentinel.
+        let text: Vec<u32> = vec![70000, 3, 70000, 3, 70000];
+        let mut bwt = vec![];
+        let stats = BwtBuilder::from_symbols(&text)
+            .unwrap()
+            .implicit_sentinel(0)
+            .build(&mut bwt)
+            .unwrap();
+        let bwt: Vec<u32> = bwt.chunks(4).map(u32::from_be_slice).collect();
+        assert_eq!(bwt, [70000, 70000, 70000, 3, 3, 0]);
+        assert_eq!(stats.primary_index, 5);
+    }
+
+    #[test]
+    fn test_bwt_builder_from_packed_dna() {
+        // Repetitive bases so that suffixes share prefixes longer than a word.
+        let seq: Vec<u8> = (0..3000u32)
+            .map(|i| b"ACGT"[(i % 97 * i % 89 % 4) as usize])
+            .collect();
+        let packed = PackedDna::from_acgt(&seq).unwrap();
+        let text = packed.to_bytes();
+        let dir = std::env::temp_dir();
+        let build = |mode: usize| {
+            let builder = match mode {
+                0 => BwtBuilder::new(&text).unwrap(),
+                1 => BwtBuilder::from_packed_dna(&packed).unwrap(),
+                2 => BwtBuilder::from_packed_dna(&packed)
+                    .unwrap()
+                    .single_pass(true),
+                3 => BwtBuilder::from_packed_dna(&packed)
+                    .unwrap()
+                    .kmer_table(2)
+                    .unwrap(),
+                4 => BwtBuilder::from_packed_dna(&packed)
+                    .unwrap()
+                    .external_memory(&dir, 1 << 10)
+                    .unwrap(),
+                _ => BwtBuilder::from_packed_dna(&packed).unwrap(),
+            };
+            #[cfg(feature = "rayon")]
+            let builder = builder.parallel(mode == 5);
+            let mut bwt = vec![];
+            let mut rev_bwt = vec![];
+            let mut lcp = vec![];
+            let stats = builder
+                .chunk_size(100)
+                .unwrap()
+                .lcp_array_writer(&mut lcp, IntWidth::U32)
+                .reverse_writer(&mut rev_bwt)
+                .build(&mut bwt)
+                .unwrap();
+            (bwt, rev_bwt, lcp, stats.primary_index)
+        };
+        let expected = build(0);
+        assert_eq!(decode_bwt(&expected.0).unwrap(), text);
+        for mode in 1..5 {
+            assert_eq!(build(mode), expected);
+        }
+        #[cfg(feature = "rayon")]
+        assert_eq!(build(5), expected);
+        assert!(BwtBuilder::from_packed_dna(&packed)
+            .unwrap()
+            .remap_alphabet(true)
+            .build(&mut vec![])
+            .is_err());
+    }
+
+    #[test]
+    fn test_bwt_builder_skip_n_runs() {
+        // Long runs of N followed by bytes smaller and larger than N, and at the end.
+        let mut text = vec![];
+        for i in 0..40usize {
+            text.extend_from_slice(b"ACGTAGGCTA"[..i % 7 + 1].as_ref());
+            text.extend(std::iter::repeat(b'N').take(i * 37 % 200));
+            text.push(b"ATCN"[i % 4]);
+        }
+        text.extend(std::iter::repeat(b'N').take(300));
+        let terminated = [&text[..], b"$"].concat();
+        let dir = std::env::temp_dir();
+        let build = |text: &[u8], mode: usize, skip: bool| {
+            let builder = BwtBuilder::new(text).unwrap().chunk_size(50).unwrap();
+            let builder = if skip {
+                builder.skip_n_runs(50).unwrap()
+            } else {
+                builder
+            };
+            let builder = match mode {
+                0 => builder,
+                1 => builder.single_pass(true),
+                2 => builder.kmer_table(2).unwrap(),
+                3 => builder.remap_alphabet(true),
+                _ => builder.external_memory(&dir, 1 << 10).unwrap(),
+            };
+            let mut bwt = vec![];
+            let mut rev_bwt = vec![];
+            let mut sa = vec![];
+            let mut lcp = vec![];
+            let stats = builder
+                .suffix_array_writer(&mut sa, IntWidth::U32)
+                .lcp_array_writer(&mut lcp, IntWidth::U32)
+                .reverse_writer(&mut rev_bwt)
+                .build(&mut bwt)
+                .unwrap();
+            ((bwt, rev_bwt, sa, lcp, stats.primary_index), stats.n_runs)
+        };
+        for mode in 0..5 {
+            let (expected, n_runs) = build(&terminated, mode, false);
+            assert!(n_runs.is_empty());
+            let (actual, n_runs) = build(&terminated, mode, true);
+            assert_eq!(actual, expected);
+            assert_eq!(n_runs.len(), 30);
+            assert!(n_runs.iter().all(|r| r.len() >= 50));
+            assert!(n_runs
+                .iter()
+                .all(|r| terminated[r.clone()].iter().all(|&c| c == b'N')));
+        }
+        let mut expected = vec![];
+        let mut bwt = vec![];
+        BwtBuilder::new_unchecked(&text)
+            .unwrap()
+            .implicit_sentinel(b'$')
+            .build(&mut expected)
+            .unwrap();
+        BwtBuilder::new_unchecked(&text)
+            .unwrap()
+            .implicit_sentinel(b'$')
+            .skip_n_runs(50)
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, expected);
+        assert!(BwtBuilder::new_unchecked(&text)
+            .unwrap()
+            .skip_n_runs(0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_bwt_builder_skip_short_n_runs() {
+        let texts: &[&[u8]] = &[b"N$", b"NAN$", b"ANNANAN$", b"NNANNNTNANT$", b"CNTNNANNGN$"];
+        for &text in texts {
+            let mut sa: Vec<usize> = (0..text.len()).collect();
+            sa.sort_by_key(|&i| &text[i..]);
+            let expected_bwt: Vec<u8> = sa
+                .iter()
+                .map(|&i| text[(i + text.len() - 1) % text.len()])
+                .collect();
+            let expected_sa: Vec<u8> = sa.iter().flat_map(|&i| (i as u32).to_le_bytes()).collect();
+            for min_len in 1..=2 {
+                let mut bwt = vec![];
+                let mut sa = vec![];
+                BwtBuilder::new(text)
+                    .unwrap()
+                    .skip_n_runs(min_len)
+                    .unwrap()
+                    .suffix_array_writer(&mut sa, IntWidth::U32)
+                    .build(&mut bwt)
+                    .unwrap();
+                assert_eq!(bwt, expected_bwt, "{text:?} min_len={min_len}");
+                assert_eq!(sa, expected_sa, "{text:?} min_len={min_len}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_reversed_text() {
+        assert_eq!(reversed_text(b"abc$", 1, false), b"cba$");
+        assert_eq!(reversed_text(b"$", 1, false), b"$");
+        assert_eq!(reversed_text(b"abc", 1, true), b"cba");
+        assert_eq!(reversed_text(b"abcdef\0\0", 2, false), b"efcdab\0\0");
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_bwt_builder_parallel() {
+        let text = "abracadabra$";
+        for chunk_size in 1..=text.len() {
+            let mut bwt = vec![];
+            let mut sa = vec![];
+            let stats = BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .chunk_size(chunk_size)
+                .unwrap()
+                .parallel(true)
+                .suffix_array_writer(&mut sa, IntWidth::U32)
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(bwt, b"ard$rcaaaabb");
+            assert_eq!(stats.primary_index, 3);
+            assert_eq!(sa.len(), text.len() * 4);
+        }
+    }
+
+    #[test]
+    fn test_shared_prefix_len() {
+        let cuts = vec![
+            b"".to_vec(),
+            b"ab".to_vec(),
+            b"abra".to_vec(),
+            b"ac".to_vec(),
+        ];
+        assert_eq!(shared_prefix_len(&cuts, 1), 0);
+        assert_eq!(shared_prefix_len(&cuts, 2), 2);
+        assert_eq!(shared_prefix_len(&cuts, 3), 1);
+        assert_eq!(shared_prefix_len(&cuts, 4), 0);
+    }
+
+    #[test]
+    fn test_bwt_from_cuts_batch() {
+        let text: &[u8] = b"abracadabra$";
+        let cuts = &[b"".to_vec(), b"ab".to_vec(), b"b".to_vec(), b"r".to_vec()];
+        for (n_threads, single_pass) in (1..=5).flat_map(|t| [(t, false), (t, true)]) {
+            let opts = ChunkOptions {
+                n_threads,
+                single_pass,
+                ..Default::default()
+            };
+            let mut bwt = vec![];
+            bwt_from_cuts(
+                text,
+                cuts,
+                &mut bwt,
+                &mut AuxWriters::default(),
+                &Progress::new(false),
+                opts,
+            )
+            .unwrap();
+            assert_eq!(bwt, b"ard$rcaaaabb");
+        }
+    }
+
+    #[test]
+    fn test_bwt_builder_single_pass() {
+        let text = "abracadabra$";
+        for chunk_size in 1..=text.len() {
+            let mut bwt = vec![];
+            let stats = BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .chunk_size(chunk_size)
+                .unwrap()
+                .single_pass(true)
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(bwt, b"ard$rcaaaabb");
+            assert_eq!(stats.primary_index, 3);
+        }
+    }
+
+    #[test]
+    fn test_bwt_builder_wide_radix() {
+        let text: Vec<u8> = (0..70000u32)
+            .map(|i| b"acgt"[(i.wrapping_mul(2654435761) >> 30) as usize])
+            .chain([b'$'])
+            .collect();
+        let mut expected = vec![];
+        BwtBuilder::new(&text)
+            .unwrap()
+            .chunk_size(text.len())
+            .unwrap()
+            .build(&mut expected)
+            .unwrap();
+        let mut bwt = vec![];
+        BwtBuilder::new(&text)
+            .unwrap()
+            .chunk_size(text.len())
+            .unwrap()
+            .wide_radix(true)
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, expected);
+        assert_eq!(decode_bwt(&bwt).unwrap(), text);
+    }
+
+    #[test]
+    fn test_bwt_builder_pipeline() {
+        let text = "abracadabra$";
+        for chunk_size in 1..=text.len() {
+            let mut bwt = vec![];
+            let mut sa = vec![];
+            let stats = BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .chunk_size(chunk_size)
+                .unwrap()
+                .pipeline(true)
+                .suffix_array_writer(&mut sa, IntWidth::U32)
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(bwt, b"ard$rcaaaabb");
+            assert_eq!(stats.primary_index, 3);
+            let sa: Vec<u32> = sa
+                .chunks(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(sa, [11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
+        }
+
+        // The gathering thread stops when the construction is cancelled.
+        let flag = AtomicBool::new(true);
+        let e = BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .chunk_size(2)
+            .unwrap()
+            .pipeline(true)
+            .cancel_flag(&flag)
+            .build(vec![])
+            .unwrap_err();
+        assert!(matches!(e, Error::Cancelled));
+    }
+
+    #[test]
+    fn test_bwt_builder_writer_thread() {
+        let text = "abracadabra$";
+        for queue_size in [1, 5, 1 << 20] {
+            let mut bwt = vec![];
+            let stats = BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .chunk_size(3)
+                .unwrap()
+                .build_with_writer_thread(&mut bwt, queue_size)
+                .unwrap();
+            assert_eq!(bwt, b"ard$rcaaaabb");
+            assert_eq!(stats.primary_index, 3);
+        }
+
+        struct FailingWriter;
+        impl Write for FailingWriter {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let e = BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .build_with_writer_thread(FailingWriter, 4)
+            .unwrap_err();
+        assert!(matches!(e, Error::Io(e) if e.to_string() == "disk full"));
+    }
+
+    #[test]
+    fn test_bwt_builder_streaming() {
+        let text = "abracadabra$";
+        for chunk_size in [1, 3, 100] {
+            let (stats, chunks) = BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .chunk_size(chunk_size)
+                .unwrap()
+                .build_streaming(|chunks| chunks.collect::<Vec<_>>())
+                .unwrap();
+            assert!(chunks
+                .iter()
+                .all(|c| !c.is_empty() && c.len() <= chunk_size));
+            assert_eq!(chunks.concat(), b"ard$rcaaaabb");
+            assert_eq!(stats.primary_index, 3);
+        }
+
+        // The construction fails if the consumer stops early.
+        let e = BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .chunk_size(1)
+            .unwrap()
+            .build_streaming(|mut chunks| chunks.next())
+            .unwrap_err();
+        assert!(matches!(e, Error::Io(e) if e.kind() == std::io::ErrorKind::BrokenPipe));
+    }
+
+    #[test]
+    fn test_bwt_builder_reader() {
+        use std::io::Read;
+
+        let mut text = vec![];
+        for i in 0..200usize {
+            text.extend_from_slice(&b"ACGTTGCAACGT"[i % 5..i % 7 + 5]);
+        }
+        text.push(b'$');
+        let mut expected = vec![];
+        let stats = BwtBuilder::new(&text)
+            .unwrap()
+            .chunk_size(50)
+            .unwrap()
+            .build(&mut expected)
+            .unwrap();
+        let builder = BwtBuilder::new(&text).unwrap().chunk_size(50).unwrap();
+        let mut rdr = builder.reader().unwrap();
+        let mut bwt = vec![];
+        rdr.read_to_end(&mut bwt).unwrap();
+        assert_eq!(bwt, expected);
+        assert_eq!(rdr.primary_index(), Some(stats.primary_index));
+
+        // A text of symbols.
+        let symbols: Vec<u16> = vec![300, 301, 317, 300, 302, 300, 303, 300, 301, 317, 300, 0];
+        let builder = BwtBuilder::from_symbols(&symbols).unwrap();
+        let mut bwt = vec![];
+        builder.reader().unwrap().read_to_end(&mut bwt).unwrap();
+        let bwt: Vec<u16> = bwt.chunks(2).map(u16::from_be_slice).collect();
+        assert_eq!(
+            bwt,
+            [300, 317, 303, 0, 317, 302, 300, 300, 300, 300, 301, 301]
+        );
+
+        let mut sa = vec![];
+        let builder = BwtBuilder::new(&text)
+            .unwrap()
+            .suffix_array_writer(&mut sa, IntWidth::U32);
+        assert!(builder.reader().is_err());
+    }
+
+    #[test]
+    fn test_bwt_builder_cuts() {
+        let mut text = vec![];
+        for i in 0..200usize {
+            text.extend_from_slice(&b"ACGTTGCAACGT"[i % 5..i % 7 + 5]);
+        }
+        text.push(b'$');
+        let builder = BwtBuilder::new(&text).unwrap().chunk_size(50).unwrap();
+        let cuts = builder.generate_cuts().unwrap();
+        let mut expected = vec![];
+        let stats = builder.build(&mut expected).unwrap();
+        assert_eq!(stats.n_cuts, cuts.len());
+
+        // The cuts are reused with another chunk size, or tuned by hand.
+        let tuned = vec![b"".to_vec(), b"AC".to_vec(), b"G".to_vec(), b"TT".to_vec()];
+        for cuts in [cuts, tuned] {
+            let n_cuts = cuts.len();
+            let builder = BwtBuilder::new(&text)
+                .unwrap()
+                .chunk_size(1000)
+                .unwrap()
+                .cuts(cuts)
+                .unwrap();
+            let mut bwt = vec![];
+            let stats = builder.build(&mut bwt).unwrap();
+            assert_eq!(bwt, expected);
+            assert_eq!(stats.n_cuts, n_cuts);
+            let mut bwt = vec![];
+            std::io::copy(&mut builder.reader().unwrap(), &mut bwt).unwrap();
+            assert_eq!(bwt, expected);
+        }
+
+        let builder = || BwtBuilder::new(&text).unwrap();
+        assert!(builder().cuts(vec![]).is_err());
+        assert!(builder().cuts(vec![b"A".to_vec()]).is_err());
+        assert!(builder()
+            .cuts(vec![b"".to_vec(), b"G".to_vec(), b"C".to_vec()])
+            .is_err());
+        assert!(builder()
+            .cuts(vec![b"".to_vec(), b"G".to_vec(), b"G".to_vec()])
+            .is_err());
+        assert!(builder().remap_alphabet(true).generate_cuts().is_err());
+
+        // A periodic text cannot be cut.
+        let text = [&[b'a'; 1000][..], b"$"].concat();
+        let builder = BwtBuilder::new(&text).unwrap().chunk_size(10).unwrap();
+        assert!(builder.generate_cuts().is_err());
+    }
+
+    #[test]
+    fn test_bwt_builder_cut_range() {
+        let mut text = vec![];
+        for i in 0..200usize {
+            text.extend_from_slice(&b"ACGTTGCAACGT"[i % 5..i % 7 + 5]);
+        }
+        for terminated in [true, false] {
+            let text = if terminated {
+                [&text[..], b"$"].concat()
+            } else {
+                text.clone()
+            };
+            // The builder lives as long as the writers given to it.
+            fn new_builder(text: &[u8], terminated: bool) -> BwtBuilder<'_> {
+                let builder = BwtBuilder::new_unchecked(text)
+                    .unwrap()
+                    .chunk_size(50)
+                    .unwrap();
+                if terminated {
+                    builder
+                } else {
+                    builder.implicit_sentinel(b'$')
+                }
+            }
+            let builder = || new_builder(&text, terminated);
+            let cuts = builder().generate_cuts().unwrap();
+            assert!(cuts.len() > 4);
+            let (mut bwt, mut sa, mut lcp) = (vec![], vec![], vec![]);
+            let stats = new_builder(&text, terminated)
+                .suffix_array_writer(&mut sa, IntWidth::U32)
+                .lcp_array_writer(&mut lcp, IntWidth::U32)
+                .build(&mut bwt)
+                .unwrap();
+
+            for single_pass in [false, true] {
+                let (mut parts, mut part_sa, mut part_lcp) = (vec![], vec![], vec![]);
+                let mut next_row = 0;
+                for range in [0..1, 1..3, 3..cuts.len()] {
+                    let part = new_builder(&text, terminated)
+                        .cuts(cuts.clone())
+                        .unwrap()
+                        .cut_range(range)
+                        .unwrap()
+                        .single_pass(single_pass)
+                        .suffix_array_writer(&mut part_sa, IntWidth::U32)
+                        .lcp_array_writer(&mut part_lcp, IntWidth::U32)
+                        .build(&mut parts)
+                        .unwrap();
+                    assert_eq!(part.rows.start, next_row);
+                    assert_eq!(part.primary_index, stats.primary_index);
+                    next_row = part.rows.end;
+                }
+                assert_eq!(next_row, stats.rows.end);
+                assert_eq!(parts, bwt);
+                assert_eq!(part_sa, sa);
+                assert_eq!(part_lcp, lcp);
+            }
+        }
+
+        let builder = || {
+            BwtBuilder::new(b"abracadabra$")
+                .unwrap()
+                .chunk_size(3)
+                .unwrap()
+        };
+        let mut bwt = vec![];
+        assert!(builder().cut_range(2..2).is_err());
+        assert!(builder().cut_range(0..6).unwrap().build(&mut bwt).is_err());
+        #[cfg(feature = "sais")]
+        assert!(builder()
+            .cut_range(0..1)
+            .unwrap()
+            .sais(true)
+            .build(&mut bwt)
+            .is_err());
+        assert!(builder().cut_range(0..1).unwrap().reader().is_err());
+    }
+
+    #[test]
+    fn test_bwt_builder_checkpoint() {
+        // Cancels the construction after a number of chunks.
+        struct Interrupt<'a>(&'a AtomicBool, usize);
+        impl ProgressHandler for Interrupt<'_> {
+            fn on_chunk_done(&self, progress: &ChunkProgress) {
+                if progress.n_chunks_done == self.1 {
+                    self.0.store(true, AtomicOrdering::Relaxed);
+                }
+            }
+        }
+
+        let mut text = vec![];
+        for i in 0..200usize {
+            text.extend_from_slice(&b"ACGTTGCAACGT"[i % 5..i % 7 + 5]);
+        }
+        text.push(b'$');
+        let mut expected = vec![];
+        BwtBuilder::new(&text)
+            .unwrap()
+            .build(&mut expected)
+            .unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "small-bwt-test-checkpoint-{}.ckpt",
+            std::process::id()
+        ));
+
+        let flag = AtomicBool::new(false);
+        let interrupt = Interrupt(&flag, 2);
+        let mut bwt = vec![];
+        let e = BwtBuilder::new(&text)
+            .unwrap()
+            .chunk_size(50)
+            .unwrap()
+            .checkpoint(&path)
+            .progress_handler(&interrupt)
+            .cancel_flag(&flag)
+            .build(&mut bwt)
+            .unwrap_err();
+        assert!(matches!(e, Error::Cancelled));
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        assert_eq!(checkpoint.n_cuts_done, 2);
+        assert_eq!(checkpoint.text_len, text.len());
+        assert_eq!(checkpoint.n_bytes_written, bwt.len());
+
+        // The resumed construction is interrupted again.
+        let flag = AtomicBool::new(false);
+        let interrupt = Interrupt(&flag, 1);
+        let e = BwtBuilder::new(&text)
+            .unwrap()
+            .resume(&checkpoint)
+            .unwrap()
+            .checkpoint(&path)
+            .progress_handler(&interrupt)
+            .cancel_flag(&flag)
+            .build(&mut bwt)
+            .unwrap_err();
+        assert!(matches!(e, Error::Cancelled));
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        assert_eq!(checkpoint.n_cuts_done, 3);
+        assert_eq!(checkpoint.n_bytes_written, bwt.len());
+
+        let stats = BwtBuilder::new(&text)
+            .unwrap()
+            .resume(&checkpoint)
+            .unwrap()
+            .checkpoint(&path)
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, expected);
+        assert_eq!(stats.rows.end, text.len());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(BwtBuilder::new(&text[1..])
+            .unwrap()
+            .resume(&checkpoint)
+            .is_err());
+        let mut sa = vec![];
+        assert!(BwtBuilder::new(&text)
+            .unwrap()
+            .checkpoint(&path)
+            .suffix_array_writer(&mut sa, IntWidth::U32)
+            .build(&mut bwt)
+            .is_err());
+    }
+
+    #[test]
+    fn test_bwt_builder_suffix_sorter() {
+        let mut text = vec![];
+        for i in 0..60usize {
+            text.extend_from_slice(&b"ACGTTGCANNNNNNNNNN"[i % 5..i % 7 + 8]);
+        }
+        text.push(b'$');
+        let dir = std::env::temp_dir();
+        let build = |sorter: Option<&dyn SuffixSorter>, mode: usize| {
+            let builder = BwtBuilder::new(&text).unwrap().chunk_size(50).unwrap();
+            let builder = match sorter {
+                Some(sorter) => builder.suffix_sorter(sorter),
+                None => builder,
+            };
+            let builder = match mode {
+                0 => builder,
+                1 => builder.single_pass(true),
+                2 => builder.skip_n_runs(5).unwrap(),
+                _ => builder.external_memory(&dir, 1 << 10).unwrap(),
+            };
+            let mut bwt = vec![];
+            let mut sa = vec![];
+            let stats = builder
+                .suffix_array_writer(&mut sa, IntWidth::U32)
+                .build(&mut bwt)
+                .unwrap();
+            (bwt, sa, stats.primary_index)
+        };
+        for mode in 0..4 {
+            let expected = build(None, mode);
+            for sorter in [
+                &MsdRadixSort::new(1) as &dyn SuffixSorter,
+                &MultikeyQuicksort::default(),
+            ] {
+                assert_eq!(build(Some(sorter), mode), expected);
             }
         }
+    }
 
-        progress.print(&format!("Length of the chunks: {:?}", chunks.len()));
-        chunks = MsdRadixSorter::sort(text, chunks, 256);
-
-        for &j in &chunks {
-            let c = if j == 0 {
-                *text.last().unwrap()
+    #[test]
+    fn test_bwt_builder_prefix_free_parsing() {
+        // Copies of a sequence with a few mutations and runs of N.
+        let base: Vec<u8> = (0..200u32)
+            .map(|i| b"ACGT"[(i.wrapping_mul(2654435761) >> 30) as usize])
+            .collect();
+        let mut text = vec![];
+        for i in 0..8 {
+            let mut copy = base.clone();
+            copy[i * 23 % base.len()] = b'T';
+            copy[i * 71 % base.len()] = b'G';
+            text.extend_from_slice(&copy);
+            text.extend(std::iter::repeat(b'N').take(i * 5));
+        }
+        let build = |text: &[u8], pfp: bool, sentinel: bool, skip: bool| {
+            let builder = BwtBuilder::new_unchecked(text)
+                .unwrap()
+                .chunk_size(100)
+                .unwrap();
+            let builder = if pfp {
+                builder.prefix_free_parsing(6, 20).unwrap()
+            } else {
+                builder
+            };
+            let builder = if sentinel {
+                builder.implicit_sentinel(0)
             } else {
-                text[j - 1]
+                builder
             };
-            wrt.write_all(&[c])?;
+            let builder = if skip {
+                builder.skip_n_runs(10).unwrap()
+            } else {
+                builder
+            };
+            let mut bwt = vec![];
+            let mut rev_bwt = vec![];
+            let mut sa = vec![];
+            let mut lcp = vec![];
+            let stats = builder
+                .suffix_array_writer(&mut sa, IntWidth::U32)
+                .lcp_array_writer(&mut lcp, IntWidth::U32)
+                .reverse_writer(&mut rev_bwt)
+                .build(&mut bwt)
+                .unwrap();
+            (bwt, rev_bwt, sa, lcp, stats.primary_index)
+        };
+        let terminated = [&text[..], b"$"].concat();
+        for (text, sentinel) in [(&text[..], true), (&terminated[..], false)] {
+            for skip in [false, true] {
+                assert_eq!(
+                    build(text, true, sentinel, skip),
+                    build(text, false, sentinel, skip)
+                );
+            }
         }
-        chunks.clear();
-    }
-    Ok(())
-}
 
-struct CutGenerator<'a> {
-    text: &'a [u8],
-    chunk_size: usize,
-    cuts: Vec<Vec<u8>>,
-    lens: Vec<usize>,
-}
+        // A text of symbols, whose suffixes are sorted at symbol boundaries.
+        let symbols: Vec<u16> = terminated.iter().map(|&c| (c as u16) << 8 | 1).collect();
+        let build_symbols = |pfp: bool| {
+            let builder = BwtBuilder::from_symbols(&symbols).unwrap();
+            let builder = if pfp {
+                builder.prefix_free_parsing(4, 10).unwrap()
+            } else {
+                builder
+            };
+            let mut bwt = vec![];
+            let stats = builder.build(&mut bwt).unwrap();
+            (bwt, stats.primary_index)
+        };
+        assert_eq!(build_symbols(true), build_symbols(false));
 
-impl<'a> CutGenerator<'a> {
-    fn generate(text: &'a [u8], chunk_size: usize) -> Vec<Vec<u8>> {
-        let mut builder = Self {
-            text,
-            chunk_size,
-            cuts: vec![vec![]],
-            lens: vec![],
+        assert!(BwtBuilder::new(&terminated)
+            .unwrap()
+            .prefix_free_parsing(0, 10)
+            .is_err());
+        assert!(BwtBuilder::new(&terminated)
+            .unwrap()
+            .prefix_free_parsing(10, 0)
+            .is_err());
+    }
+
+    #[cfg(feature = "sais")]
+    #[test]
+    fn test_bwt_builder_sais() {
+        // Cross-validates the small-space algorithm with SA-IS.
+        let mut x = 31415u64;
+        let mut random_text = |n: usize, sigma: u64| -> Vec<u8> {
+            (0..n)
+                .map(|_| {
+                    x ^= x << 13;
+                    x ^= x >> 7;
+                    x ^= x << 17;
+                    b'A' + (x % sigma) as u8
+                })
+                .collect()
         };
-        builder.expand(vec![]);
-        builder.cuts
-    }
-
-    fn expand(&mut self, mut cut: Vec<u8>) {
-        let freqs = symbol_freqs(self.text, &cut);
-        cut.push(0); // dummy last symbol
-        for (symbol, &freq) in freqs.iter().enumerate() {
-            if freq == 0 {
-                continue;
-            }
-            *cut.last_mut().unwrap() = symbol as u8;
-            if freq <= self.chunk_size {
-                if self.lens.is_empty() || *self.lens.last().unwrap() + freq > self.chunk_size {
-                    self.cuts.push(vec![]);
-                    self.lens.push(0);
-                }
-                *self.cuts.last_mut().unwrap() = cut.clone();
-                *self.lens.last_mut().unwrap() += freq;
+        let mut texts = vec![];
+        for (n, sigma) in [(1, 1), (100, 1), (100, 2), (1000, 4), (1000, 26)] {
+            texts.push(random_text(n, sigma));
+        }
+        texts.push(b"NNNNNNNNACGTNNNNNNNNNNNNTTNNNNNNNNN".to_vec());
+        let build = |text: &[u8], sais: bool, sentinel: bool, skip: bool| {
+            let builder = BwtBuilder::new_unchecked(text).unwrap().sais(sais);
+            let builder = if sentinel {
+                builder.implicit_sentinel(0)
+            } else {
+                builder
+            };
+            let builder = if skip {
+                builder.skip_n_runs(4).unwrap()
             } else {
-                self.expand(cut.clone());
+                builder
+            };
+            let mut bwt = vec![];
+            let mut rev_bwt = vec![];
+            let mut sa = vec![];
+            let mut lcp = vec![];
+            let stats = builder
+                .suffix_array_writer(&mut sa, IntWidth::U32)
+                .lcp_array_writer(&mut lcp, IntWidth::U32)
+                .reverse_writer(&mut rev_bwt)
+                .build(&mut bwt)
+                .unwrap();
+            (bwt, rev_bwt, sa, lcp, stats.primary_index)
+        };
+        for text in &texts {
+            let terminated = [&text[..], b"$"].concat();
+            for (text, sentinel) in [(&text[..], true), (&terminated[..], false)] {
+                for skip in [false, true] {
+                    assert_eq!(
+                        build(text, true, sentinel, skip),
+                        build(text, false, sentinel, skip)
+                    );
+                }
             }
         }
-    }
-}
 
-/// Computes the frequencies of symbols following cut in text.
-fn symbol_freqs(text: &[u8], cut: &[u8]) -> Vec<usize> {
-    let mut freqs = vec![0; 256];
-    for j in cut.len()..text.len() {
-        let i = j - cut.len();
-        if cut == &text[i..j] {
-            freqs[text[j] as usize] += 1;
-        }
+        // A text of symbols, whose suffixes are sorted at symbol boundaries.
+        let symbols: Vec<u16> = random_text(500, 3)
+            .iter()
+            .map(|&c| (c as u16) << 8 | 0x41)
+            .chain([0])
+            .collect();
+        let build_symbols = |sais: bool| {
+            let mut bwt = vec![];
+            let stats = BwtBuilder::from_symbols(&symbols)
+                .unwrap()
+                .sais(sais)
+                .build(&mut bwt)
+                .unwrap();
+            (bwt, stats.primary_index)
+        };
+        assert_eq!(build_symbols(true), build_symbols(false));
     }
-    freqs
-}
-
-struct Progress {
-    verbose: bool,
-}
 
-impl Progress {
-    const fn new(verbose: bool) -> Self {
-        Self { verbose }
-    }
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_bwt_builder_from_mmap() {
+        let path = std::env::temp_dir().join("small_bwt_test_from_mmap.txt");
+        std::fs::write(&path, "abracadabra$").unwrap();
+        let mut bwt = vec![];
+        let stats = BwtBuilder::from_mmap(&path)
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, b"ard$rcaaaabb");
+        assert_eq!(stats.primary_index, 3);
 
-    fn print(&self, msg: &str) {
-        if self.verbose {
-            eprintln!("[INFO] {}", msg);
-        }
+        std::fs::write(&path, "").unwrap();
+        assert!(BwtBuilder::from_mmap(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
     }
-}
-
-fn to_mb(bytes: usize) -> f64 {
-    bytes as f64 / 1000.0 / 1000.0
-}
 
-fn to_mib(bytes: usize) -> f64 {
-    bytes as f64 / 1024.0 / 1024.0
-}
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_bwt_builder_from_reader() {
+        let dir =
+            std::env::temp_dir().join(format!("small_bwt_test_from_reader_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let builder = BwtBuilder::from_reader("abracadabra$".as_bytes(), &dir)
+            .unwrap()
+            .chunk_size(3)
+            .unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let mut bwt = vec![];
+        builder.build(&mut bwt).unwrap();
+        assert_eq!(bwt, b"ard$rcaaaabb");
+        // The temporary file is removed with the builder.
+        drop(builder);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
 
-/// Verifies that the smallest character appears only at the end of the text.
-///
-/// # Arguments
-///
-/// * `text` - The text to be verified.
-///
-/// # Errors
-///
-/// An error is returned if the smallest character does not appear only at the end of the text.
-///
-/// # Examples
-///
-/// ```
-/// use small_bwt::verify_terminator;
-///
-/// let text = "abracadabra$";
-/// let result = verify_terminator(text.as_bytes());
-/// assert!(result.is_ok());
-///
-/// let text = "abrac$dabra$";
-/// let result = verify_terminator(text.as_bytes());
-/// assert!(result.is_err());
-/// ```
-pub fn verify_terminator(text: &[u8]) -> Result<()> {
-    if text.is_empty() {
-        return Err(anyhow!("text must not be empty."));
-    }
-    let smallest = *text.last().unwrap();
-    for (i, &c) in text[..text.len() - 1].iter().enumerate() {
-        if c <= smallest {
-            return Err(anyhow!(
-                "text must have the smallest special character only at the end, but found {c:?} at position {i}."
-            ));
-        }
+        assert!(matches!(
+            BwtBuilder::from_reader(&b""[..], &dir),
+            Err(Error::EmptyText)
+        ));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
     }
-    Ok(())
-}
 
-/// Decodes the original text from a given BWT.
-///
-/// It runs in `O(n)` time and `O(n log n)` bits of space,
-/// where `n` is the length of the text.
-///
-/// # Arguments
-///
-/// * `bwt` - The Burrows-Wheeler transform of a text.
-///
-/// # Errors
-///
-/// An error is returned if the Burrows-Wheeler transform is invalid.
-///
-/// # Examples
-///
-/// ```
-/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-/// use small_bwt::decode_bwt;
-///
-/// let bwt = "ard$rcaaaabb";
-/// let decoded = decode_bwt(bwt.as_bytes())?;
-/// assert_eq!(decoded, "abracadabra$".as_bytes());
-/// # Ok(())
-/// # }
-/// ```
-pub fn decode_bwt(bwt: &[u8]) -> Result<Vec<u8>> {
-    if bwt.is_empty() {
-        return Err(anyhow!("bwt must not be empty."));
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_bwt_builder_build_into_file() {
+        let text = b"abracadabra$";
+        let path = std::env::temp_dir().join("small_bwt_test_build_into_file.bwt");
+        let cuts = BwtBuilder::new(text)
+            .unwrap()
+            .chunk_size(3)
+            .unwrap()
+            .generate_cuts()
+            .unwrap();
+        // Two workers fill the same file in any order.
+        for range in [2..5, 0..2] {
+            BwtBuilder::new(text)
+                .unwrap()
+                .cuts(cuts.clone())
+                .unwrap()
+                .cut_range(range)
+                .unwrap()
+                .build_into_file(&path)
+                .unwrap();
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), b"ard$rcaaaabb");
+        std::fs::remove_file(&path).unwrap();
     }
 
-    let (counts, ranks) = {
-        let mut counts = vec![0; 256];
-        let mut ranks = vec![0; bwt.len()];
-        for (&c, r) in bwt.iter().zip(ranks.iter_mut()) {
-            *r = counts[c as usize];
-            counts[c as usize] += 1;
-        }
-        (counts, ranks)
-    };
+    #[test]
+    fn test_bwt_builder_build_into() {
+        let text = b"abracadabra$";
+        let builder = BwtBuilder::new(text).unwrap();
+        assert_eq!(builder.output_len(), 12);
+        let mut bwt = vec![0; 12];
+        let stats = builder.build_into(&mut bwt).unwrap();
+        assert_eq!(bwt, b"ard$rcaaaabb");
+        assert_eq!(stats.primary_index, 3);
+        assert!(builder.build_into(&mut [0; 11]).is_err());
+        assert!(builder.build_into(&mut [0; 13]).is_err());
 
-    let occ = {
-        let mut occ = vec![0; 256];
-        let mut rank = 0;
-        for i in 0..256 {
-            occ[i] = rank;
-            rank += counts[i];
-        }
-        occ
-    };
+        // The rows of each range of cuts are written at their offsets.
+        let cuts = BwtBuilder::new(text)
+            .unwrap()
+            .chunk_size(3)
+            .unwrap()
+            .generate_cuts()
+            .unwrap();
+        let mut bwt = vec![b'_'; 12];
+        let stats = BwtBuilder::new(text)
+            .unwrap()
+            .cuts(cuts)
+            .unwrap()
+            .cut_range(2..3)
+            .unwrap()
+            .build_into(&mut bwt)
+            .unwrap();
+        assert_eq!(stats.rows, 5..8);
+        assert_eq!(bwt, b"_____caa____");
 
-    let terminator = counts.iter().position(|&c| c != 0).unwrap();
-    if counts[terminator] != 1 {
-        return Err(anyhow!(
-            "bwt must have exactly one terminator character, but found {:x} {} times.",
-            terminator,
-            counts[terminator]
-        ));
+        let text = b"abracadabra";
+        let builder = BwtBuilder::new_unchecked(text)
+            .unwrap()
+            .implicit_sentinel(b'$');
+        let mut bwt = vec![0; builder.output_len()];
+        builder.build_into(&mut bwt).unwrap();
+        assert_eq!(bwt, b"ard$rcaaaabb");
     }
-    let terminator = terminator as u8;
 
-    let mut decoded = Vec::with_capacity(bwt.len());
-    decoded.push(terminator);
-
-    let mut i = 0;
-    while bwt[i] != terminator {
-        assert!(decoded.len() < bwt.len());
-        decoded.push(bwt[i]);
-        i = occ[bwt[i] as usize] + ranks[i];
+    #[test]
+    fn test_bwt_builder_external_memory() {
+        let text = "abracadabra$";
+        let dir = std::env::temp_dir();
+        for budget in 8..=64 {
+            let mut bwt = vec![];
+            let mut sa = vec![];
+            let stats = BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .external_memory(&dir, budget)
+                .unwrap()
+                .suffix_array_writer(&mut sa, IntWidth::U32)
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(bwt, b"ard$rcaaaabb");
+            assert_eq!(stats.primary_index, 3);
+            let sa: Vec<u32> = sa
+                .chunks(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(sa, vec![11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
+        }
+        assert!(BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .external_memory(&dir, 7)
+            .is_err());
     }
-    decoded.reverse();
-
-    Ok(decoded)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
     #[test]
-    fn test_bwt_builder() {
+    fn test_bwt_builder_cancel_flag() {
         let text = "abracadabra$";
+        let flag = AtomicBool::new(false);
         let mut bwt = vec![];
         BwtBuilder::new(text.as_bytes())
             .unwrap()
+            .cancel_flag(&flag)
             .build(&mut bwt)
             .unwrap();
-        let bwt_str = String::from_utf8_lossy(&bwt);
-        assert_eq!(bwt_str, "ard$rcaaaabb");
+        assert_eq!(bwt, b"ard$rcaaaabb");
+
+        flag.store(true, AtomicOrdering::Relaxed);
+        let mut rev_bwt = vec![];
+        let e = BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .reverse_writer(&mut rev_bwt)
+            .cancel_flag(&flag)
+            .build(vec![])
+            .unwrap_err();
+        assert!(matches!(e, Error::Cancelled));
     }
 
     #[test]
-    fn test_bwt_builder_3() {
+    fn test_bwt_builder_progress_handler() {
+        #[derive(Default)]
+        struct Recorder {
+            n_cuts: std::sync::Mutex<usize>,
+            chunks: std::sync::Mutex<Vec<ChunkProgress>>,
+        }
+        impl ProgressHandler for Recorder {
+            fn on_cuts_generated(&self, n_cuts: usize) {
+                *self.n_cuts.lock().unwrap() = n_cuts;
+            }
+            fn on_chunk_done(&self, progress: &ChunkProgress) {
+                self.chunks.lock().unwrap().push(*progress);
+            }
+        }
         let text = "abracadabra$";
+        let recorder = Recorder::default();
         let mut bwt = vec![];
         BwtBuilder::new(text.as_bytes())
             .unwrap()
-            .chunk_size(3)
+            .chunk_size(4)
             .unwrap()
+            .progress_handler(&recorder)
             .build(&mut bwt)
             .unwrap();
-        let bwt_str = String::from_utf8_lossy(&bwt);
-        assert_eq!(bwt_str, "ard$rcaaaabb");
+        let n_cuts = *recorder.n_cuts.lock().unwrap();
+        let chunks = recorder.chunks.lock().unwrap();
+        assert_eq!(n_cuts, 3);
+        assert_eq!(chunks.len(), n_cuts);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.n_chunks_done, i + 1);
+            assert_eq!(chunk.n_chunks, n_cuts);
+            assert_eq!(chunk.n_bytes, text.len());
+        }
+        assert_eq!(chunks.last().unwrap().n_bytes_written, text.len());
     }
 
     #[test]
-    fn test_bwt_builder_4() {
+    fn test_bwt_builder_degenerate_cuts() {
+        #[derive(Default)]
+        struct Recorder {
+            warnings: std::sync::Mutex<Vec<String>>,
+        }
+        impl ProgressHandler for Recorder {
+            fn on_warning(&self, msg: &str) {
+                self.warnings.lock().unwrap().push(msg.to_string());
+            }
+        }
+        let periodic = [&b"ab".repeat(5000)[..], b"$"].concat();
+        let unary = [&b"a".repeat(3000)[..], b"$"].concat();
+        for text in [periodic, unary] {
+            let recorder = Recorder::default();
+            let mut bwt = vec![];
+            let mut rev_bwt = vec![];
+            let stats = BwtBuilder::new(&text)
+                .unwrap()
+                .progress_handler(&recorder)
+                .reverse_writer(&mut rev_bwt)
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(stats.n_cuts, 1);
+            assert_eq!(decode_bwt(&bwt).unwrap(), text);
+            let mut reversed = text[..text.len() - 1].to_vec();
+            reversed.reverse();
+            reversed.push(b'$');
+            assert_eq!(decode_bwt(&rev_bwt).unwrap(), reversed);
+            // Warned for both the text and its reverse.
+            let warnings = recorder.warnings.lock().unwrap();
+            assert_eq!(warnings.len(), 2);
+            assert!(warnings[0].contains("SA-IS"));
+        }
+
+        // A typical text does not fall back.
+        let recorder = Recorder::default();
+        let stats = BwtBuilder::new(b"abracadabra$")
+            .unwrap()
+            .chunk_size(1)
+            .unwrap()
+            .progress_handler(&recorder)
+            .build(&mut vec![])
+            .unwrap();
+        assert!(stats.n_cuts > 1);
+        assert!(recorder.warnings.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_bwt_builder_stats() {
         let text = "abracadabra$";
         let mut bwt = vec![];
-        BwtBuilder::new(text.as_bytes())
+        let mut sa = vec![];
+        let stats = BwtBuilder::new(text.as_bytes())
             .unwrap()
             .chunk_size(4)
             .unwrap()
+            .suffix_array_writer(&mut sa, IntWidth::U32)
             .build(&mut bwt)
             .unwrap();
-        let bwt_str = String::from_utf8_lossy(&bwt);
-        assert_eq!(bwt_str, "ard$rcaaaabb");
+        assert_eq!(stats.text_len, 12);
+        assert_eq!(stats.chunk_size, 4);
+        assert_eq!(stats.n_cuts, 3);
+        assert_eq!(stats.max_chunk_len, 4);
+        assert_eq!(stats.n_bytes_written, 12 + 12 * 4);
+        assert_eq!(stats.n_bytes_written, bwt.len() + sa.len());
+    }
+
+    #[test]
+    fn test_bwt_builder_kmer_table() {
+        let text = "abracadabra$";
+        for kmer_len in 0..=3 {
+            let mut bwt = vec![];
+            let stats = BwtBuilder::new(text.as_bytes())
+                .unwrap()
+                .chunk_size(2)
+                .unwrap()
+                .kmer_table(kmer_len)
+                .unwrap()
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(bwt, b"ard$rcaaaabb");
+            assert_eq!(stats.primary_index, 3);
+        }
+        assert!(BwtBuilder::new(text.as_bytes())
+            .unwrap()
+            .kmer_table(4)
+            .is_err());
+    }
+
+    #[test]
+    fn test_bucket_suffixes() {
+        let text: &[u8] = b"abracadabra$";
+        let cuts = &[b"".to_vec(), b"ab".to_vec(), b"b".to_vec(), b"r".to_vec()];
+        let chunks = bucket_suffixes::<usize, _>(text, cuts, 1..4, 1, &Progress::new(false));
+        assert_eq!(
+            chunks,
+            vec![vec![10, 11], vec![0, 3, 5, 7], vec![1, 4, 6, 8]]
+        );
+        let chunks = bucket_suffixes::<u32, _>(text, cuts, 2..5, 1, &Progress::new(false));
+        assert_eq!(chunks, vec![vec![0, 3, 5, 7], vec![1, 4, 6, 8], vec![2, 9]]);
+        // Only the suffixes at even positions for symbols of two bytes.
+        let chunks = bucket_suffixes::<u32, _>(text, cuts, 1..5, 2, &Progress::new(false));
+        assert_eq!(chunks, vec![vec![10], vec![0], vec![4, 6, 8], vec![2]]);
     }
 
     #[test]
@@ -441,7 +4788,7 @@ mod tests {
 
     #[test]
     fn test_bwt_from_cuts_3() {
-        let text = b"abracadabra$";
+        let text: &[u8] = b"abracadabra$";
         let cuts = &[
             b"".to_vec(),
             b"a$".to_vec(),
@@ -451,45 +4798,137 @@ mod tests {
             b"r".to_vec(),
         ];
         let mut bwt = vec![];
-        bwt_from_cuts(text, cuts, &mut bwt, &Progress::new(false)).unwrap();
+        let stats = bwt_from_cuts(
+            text,
+            cuts,
+            &mut bwt,
+            &mut AuxWriters::default(),
+            &Progress::new(false),
+            ChunkOptions::default(),
+        )
+        .unwrap();
         let bwt_str = String::from_utf8_lossy(&bwt);
         assert_eq!(bwt_str, "ard$rcaaaabb");
+        assert_eq!(stats.primary_index, 3);
     }
 
     #[test]
-    fn test_bwt_from_cuts_4() {
+    fn test_bwt_from_cuts_with_index_types() {
+        fn bwt_with<I: SuffixIndex>(text: &[u8], cuts: &[Vec<u8>]) -> (Vec<u8>, Vec<u8>) {
+            let mut bwt = vec![];
+            let mut sa = vec![];
+            let mut aux = AuxWriters {
+                sa: Some(IntWriter::new(&mut sa, IntWidth::U64)),
+                ..Default::default()
+            };
+            bwt_from_cuts_with::<I, _, _>(
+                text,
+                cuts,
+                &mut bwt,
+                &mut aux,
+                &Progress::new(false),
+                ChunkOptions::default(),
+            )
+            .unwrap();
+            drop(aux);
+            (bwt, sa)
+        }
         let text = b"abracadabra$";
         let cuts = &[b"".to_vec(), b"ab".to_vec(), b"b".to_vec(), b"r".to_vec()];
+        let expected = bwt_with::<usize>(text, cuts);
+        assert_eq!(expected.0, b"ard$rcaaaabb");
+        assert_eq!(bwt_with::<u32>(text, cuts), expected);
+        assert_eq!(bwt_with::<U40>(text, cuts), expected);
+    }
+
+    #[test]
+    fn test_bwt_from_cuts_4() {
+        let text: &[u8] = b"abracadabra$";
+        let cuts = &[b"".to_vec(), b"ab".to_vec(), b"b".to_vec(), b"r".to_vec()];
         let mut bwt = vec![];
-        bwt_from_cuts(text, cuts, &mut bwt, &Progress::new(false)).unwrap();
+        let stats = bwt_from_cuts(
+            text,
+            cuts,
+            &mut bwt,
+            &mut AuxWriters::default(),
+            &Progress::new(false),
+            ChunkOptions::default(),
+        )
+        .unwrap();
         let bwt_str = String::from_utf8_lossy(&bwt);
         assert_eq!(bwt_str, "ard$rcaaaabb");
+        assert_eq!(stats.primary_index, 3);
     }
 
     #[test]
-    fn test_symbol_freqs() {
-        let text = b"abracadabra$";
-        let cut = b"ra";
-        let freqs = symbol_freqs(text, cut);
-        let mut expected = vec![0; 256];
-        expected[b'$' as usize] = 1;
-        expected[b'c' as usize] = 1;
-        assert_eq!(freqs, expected);
+    fn test_bwt_builder_with_terminator() {
+        let text = b"mississippi";
+        let mut bwt = vec![];
+        let stats = BwtBuilder::with_terminator(text)
+            .unwrap()
+            .chunk_size(3)
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, b"ipssm\0pissii");
+        assert_eq!(stats.terminator, Some(0));
+        assert_eq!(decode_bwt(&bwt).unwrap(), b"mississippi\0");
+
+        let stats = BwtBuilder::new(b"mississippi$")
+            .unwrap()
+            .build(&mut vec![])
+            .unwrap();
+        assert_eq!(stats.terminator, None);
+        assert!(BwtBuilder::with_terminator(b"").is_err());
+        assert!(BwtBuilder::with_terminator(b"ab\0c").is_err());
     }
 
     #[test]
-    fn test_symbol_freqs_empty() {
-        let text = b"abracadabra$";
-        let cut = b"";
-        let freqs = symbol_freqs(text, cut);
-        let mut expected = vec![0; 256];
-        expected[b'$' as usize] = 1;
-        expected[b'a' as usize] = 5;
-        expected[b'b' as usize] = 2;
-        expected[b'c' as usize] = 1;
-        expected[b'd' as usize] = 1;
-        expected[b'r' as usize] = 2;
-        assert_eq!(freqs, expected);
+    fn test_diagnose_terminator() {
+        let report = diagnose_terminator(b"abracadabra$", 10).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.terminator, b'$');
+        assert_eq!(report.n_smallest, 1);
+        assert!(report.violations.is_empty());
+        assert_eq!(report.free_terminators, (0..b'a').collect::<Vec<_>>());
+
+        let report = diagnose_terminator(b"ab\0ra#a\0a$", 1).unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(report.smallest, 0);
+        assert_eq!(report.n_smallest, 2);
+        assert_eq!(report.n_violations, 3);
+        assert_eq!(report.violations, [2]);
+        assert!(report.free_terminators.is_empty());
+
+        let report = diagnose_terminator(b"x", 0).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.free_terminators.len(), 256);
+        assert!(diagnose_terminator(b"", 10).is_err());
+    }
+
+    #[test]
+    fn test_bwt_builder_from_str() {
+        use std::str::FromStr;
+
+        for text in ["mississippi", "今日は良い天気", "a\0b", "\0\0", "x"] {
+            let mut bwt = vec![];
+            let stats = BwtBuilder::from_str(text)
+                .unwrap()
+                .chunk_size(3)
+                .unwrap()
+                .build(&mut bwt)
+                .unwrap();
+            assert_eq!(bwt.len(), text.len() + 1);
+            if text.contains('\0') {
+                assert_eq!(stats.sentinel, Some(STR_SENTINEL));
+                assert_eq!(bwt[stats.primary_index], STR_SENTINEL);
+            } else {
+                assert_eq!(stats.terminator, Some(0));
+            }
+            assert_eq!(decode_bwt_string(&bwt).unwrap(), text);
+        }
+        assert!(BwtBuilder::from_str("").is_err());
+        assert!(decode_bwt_string(b"\xfe\0").is_err());
     }
 
     #[test]
@@ -500,23 +4939,109 @@ mod tests {
     }
 
     #[test]
-    fn test_decode_bwt_single() {
-        let bwt = "$";
-        let decoded = decode_bwt(bwt.as_bytes()).unwrap();
-        assert_eq!(decoded, "$".as_bytes());
+    fn test_bwt_builder_new_verifies_terminator() {
+        assert!(matches!(BwtBuilder::new(b""), Err(Error::EmptyText)));
+        assert!(matches!(
+            BwtBuilder::new(b"abracadabra"),
+            Err(Error::InvalidTerminator { pos: 0, byte: b'a' })
+        ));
+        assert!(BwtBuilder::new_unchecked(b"").is_err());
+        let mut bwt = vec![];
+        BwtBuilder::new_unchecked(b"abracadabra")
+            .unwrap()
+            .implicit_sentinel(b'$')
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, b"ard$rcaaaabb");
     }
 
     #[test]
-    fn test_decode_bwt_empty() {
-        let bwt = "";
-        let e = decode_bwt(bwt.as_bytes());
-        assert!(e.is_err());
+    fn test_bwt_builder_extreme_parameters() {
+        let mut bwt = vec![];
+        BwtBuilder::new(b"$")
+            .unwrap()
+            .prefix_free_parsing(1, 1)
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, b"$");
+
+        let text = b"abracadabra$";
+        let builder = || {
+            BwtBuilder::new(text)
+                .unwrap()
+                .chunk_size(usize::MAX)
+                .unwrap()
+        };
+        let mut bwt = vec![];
+        builder()
+            .prefix_free_parsing(usize::MAX, usize::MAX)
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, b"ard$rcaaaabb");
+        let (_, bwt) = builder()
+            .build_streaming(|chunks| chunks.flatten().collect::<Vec<u8>>())
+            .unwrap();
+        assert_eq!(bwt, b"ard$rcaaaabb");
+        let mut bwt = vec![];
+        builder()
+            .build_with_writer_thread(&mut bwt, usize::MAX)
+            .unwrap();
+        assert_eq!(bwt, b"ard$rcaaaabb");
+        let mut samples = vec![];
+        builder()
+            .sampled_suffix_array_writer(&mut samples, IntWidth::U32, usize::MAX)
+            .unwrap()
+            .build(&mut vec![])
+            .unwrap();
+        assert_eq!(samples, 11u32.to_le_bytes());
     }
 
     #[test]
-    fn test_decode_bwt_invalid_terminator() {
-        let bwt = "ard$rcaaa$bb";
-        let e = decode_bwt(bwt.as_bytes());
-        assert!(e.is_err());
+    fn test_bwt_builder_context_order() {
+        let text = b"abracadabra$";
+        for k in [1, 2, 4, 12, 100] {
+            let builder = BwtBuilder::new(text).unwrap().context_order(k).unwrap();
+            let mut bwt = vec![];
+            let stats = builder.build(&mut bwt).unwrap();
+            let (expected, primary_index) = context_bwt(text, k).unwrap();
+            assert_eq!(bwt, expected);
+            assert_eq!(stats.primary_index, primary_index);
+            assert_eq!(stats.context_order, Some(k));
+            assert_eq!(stats.runs, RunStats::from_bwt(&bwt));
+            assert_eq!(stats.rows, 0..text.len());
+
+            let mut out = vec![0; builder.output_len()];
+            builder.build_into(&mut out).unwrap();
+            assert_eq!(out, expected);
+        }
+        // The order of n with a unique terminator gives the full BWT.
+        let mut bwt = vec![];
+        BwtBuilder::new(text)
+            .unwrap()
+            .context_order(text.len())
+            .unwrap()
+            .build(&mut bwt)
+            .unwrap();
+        assert_eq!(bwt, b"ard$rcaaaabb");
+
+        assert!(BwtBuilder::new(text).unwrap().context_order(0).is_err());
+        let builder = || BwtBuilder::new(text).unwrap().context_order(2).unwrap();
+        let mut sa = vec![];
+        assert!(builder()
+            .suffix_array_writer(&mut sa, IntWidth::U32)
+            .build(&mut vec![])
+            .is_err());
+        assert!(builder().reverse_writer(vec![]).build(&mut vec![]).is_err());
+        assert!(builder().implicit_sentinel(0).build(&mut vec![]).is_err());
+        assert!(builder().reader().is_err());
+        let packed = PackedDna::from_acgt(b"ACGT").unwrap();
+        assert!(BwtBuilder::from_packed_dna(&packed)
+            .unwrap()
+            .context_order(2)
+            .unwrap()
+            .build(&mut vec![])
+            .is_err());
     }
 }
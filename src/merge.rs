@@ -0,0 +1,203 @@
+use std::cmp::Ordering;
+
+use crate::error::{Error, Result};
+
+use crate::decode_bwt;
+use crate::rank::OccTable;
+
+/// Merges the BWTs of two texts into the BWT of their concatenation.
+///
+/// Given the BWTs of `A$` and `B$`, where `$` denotes the terminators,
+/// it computes the BWT of `AB$`, i.e., the text obtained by dropping the terminator of the first text.
+///
+/// The first text is decoded from `bwt_a`, and the suffixes of the concatenation starting in `A`
+/// are interleaved with those of `B` using the gap-array approach:
+/// the rank of each suffix `A[i..]B` among the suffixes of `B` is computed by backward search
+/// over `bwt_b`, so the second text is never decoded.
+/// It uses `O(|A| log n)` bits plus the rank support of `bwt_b`.
+///
+/// # Arguments
+///
+/// * `bwt_a` - The BWT of the first text.
+/// * `bwt_b` - The BWT of the second text.
+///
+/// # Errors
+///
+/// An error is returned if
+///
+/// - either BWT is invalid (see [`decode_bwt`]), or
+/// - the terminator of `bwt_b` is not smaller than every symbol of the first text.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{merge_bwts, BwtBuilder};
+///
+/// let mut bwt_a = vec![];
+/// BwtBuilder::new(b"abra$")?.build(&mut bwt_a)?;
+/// let mut bwt_b = vec![];
+/// BwtBuilder::new(b"cadabra$")?.build(&mut bwt_b)?;
+///
+/// let merged = merge_bwts(&bwt_a, &bwt_b)?;
+/// assert_eq!(merged, b"ard$rcaaaabb");
+/// # Ok(())
+/// # }
+/// ```
+pub fn merge_bwts(bwt_a: &[u8], bwt_b: &[u8]) -> Result<Vec<u8>> {
+    let mut text_a = decode_bwt(bwt_a)?;
+    text_a.pop(); // Drops the terminator.
+    Ok(MergeState::new(&text_a, bwt_b)?.merge())
+}
+
+struct MergeState<'a> {
+    text_a: &'a [u8],
+    bwt_b: &'a [u8],
+    // The position of the terminator in bwt_b, i.e., the rank of B among its suffixes.
+    primary_b: usize,
+    // gaps[i] is the number of suffixes of B smaller than A[i..]B.
+    gaps: Vec<usize>,
+}
+
+impl<'a> MergeState<'a> {
+    fn new(text_a: &'a [u8], bwt_b: &'a [u8]) -> Result<Self> {
+        if bwt_b.is_empty() {
+            return Err(Error::invalid_bwt("bwt must not be empty."));
+        }
+        let terminator = *bwt_b.iter().min().unwrap();
+        let primary_b = bwt_b.iter().position(|&c| c == terminator).unwrap();
+        if bwt_b[primary_b + 1..].contains(&terminator) {
+            return Err(Error::invalid_bwt(format!(
+                "bwt must have exactly one terminator character, but found {terminator:x} more than once."
+            )));
+        }
+        if let Some(i) = text_a.iter().position(|&c| c <= terminator) {
+            return Err(Error::invalid_argument(format!(
+                "terminator of the second bwt must be smaller than the symbols of the first text, but found {:?} at position {i}.",
+                text_a[i]
+            )));
+        }
+
+        let occ = OccTable::new(bwt_b);
+        let mut gaps = vec![0; text_a.len()];
+        let mut gap = primary_b;
+        for (i, &c) in text_a.iter().enumerate().rev() {
+            gap = occ.c(c) + occ.rank(bwt_b, c, gap);
+            gaps[i] = gap;
+        }
+        Ok(Self {
+            text_a,
+            bwt_b,
+            primary_b,
+            gaps,
+        })
+    }
+
+    /// Returns a key such that a smaller key implies a smaller suffix.
+    /// The suffix at position `text_a.len()` is B itself.
+    fn key(&self, i: usize) -> usize {
+        if i == self.text_a.len() {
+            2 * self.primary_b + 1
+        } else {
+            2 * self.gaps[i]
+        }
+    }
+
+    /// Compares suffixes `A[i..]B` and `A[j..]B`.
+    fn compare(&self, mut i: usize, mut j: usize) -> Ordering {
+        loop {
+            let ord = self.key(i).cmp(&self.key(j));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            // Both are in A since B's key is odd.
+            let ord = self.text_a[i].cmp(&self.text_a[j]);
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+
+    fn merge(self) -> Vec<u8> {
+        let n_a = self.text_a.len();
+        let mut order: Vec<usize> = (0..n_a).collect();
+        order.sort_unstable_by(|&i, &j| self.compare(i, j));
+
+        let terminator = self.bwt_b[self.primary_b];
+        let mut merged = Vec::with_capacity(n_a + self.bwt_b.len());
+        let mut order = order.into_iter().peekable();
+        for (k, &c) in self.bwt_b.iter().enumerate() {
+            while let Some(i) = order.next_if(|&i| self.gaps[i] == k) {
+                merged.push(if i == 0 {
+                    terminator
+                } else {
+                    self.text_a[i - 1]
+                });
+            }
+            if k == self.primary_b && n_a != 0 {
+                merged.push(self.text_a[n_a - 1]);
+            } else {
+                merged.push(c);
+            }
+        }
+        for i in order {
+            merged.push(if i == 0 {
+                terminator
+            } else {
+                self.text_a[i - 1]
+            });
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BwtBuilder;
+
+    fn bwt(text: &[u8]) -> Vec<u8> {
+        let mut bwt = vec![];
+        BwtBuilder::new(text).unwrap().build(&mut bwt).unwrap();
+        bwt
+    }
+
+    #[test]
+    fn test_merge_bwts() {
+        let texts: &[&[u8]] = &[
+            b"",
+            b"a",
+            b"ab",
+            b"aaaa",
+            b"abab",
+            b"mississippi",
+            b"banana",
+        ];
+        for &a in texts {
+            for &b in texts {
+                let a_text = [a, b"$"].concat();
+                let b_text = [b, b"$"].concat();
+                let ab_text = [a, b, b"$"].concat();
+                let merged = merge_bwts(&bwt(&a_text), &bwt(&b_text)).unwrap();
+                assert_eq!(merged, bwt(&ab_text), "a={a:?}, b={b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_bwts_invalid_terminator() {
+        let bwt_a = bwt(b"ab\x01");
+        let bwt_b = bwt(b"cd\x02");
+        assert!(merge_bwts(&bwt_a, &bwt_b).is_ok());
+        let bwt_a = bwt(b"a\x02b\x01");
+        assert!(merge_bwts(&bwt_a, &bwt_b).is_err());
+    }
+
+    #[test]
+    fn test_merge_bwts_empty() {
+        assert!(merge_bwts(b"", b"$").is_err());
+        assert!(merge_bwts(b"$", b"").is_err());
+    }
+}
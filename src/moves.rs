@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Balancing parameter of the move structure.
+/// Every output interval overlaps less than `2 * BALANCE` input intervals after balancing.
+const BALANCE: usize = 2;
+
+/// Move structure over the run-length encoded BWT,
+/// supporting LF steps in constant time and `O(r)` words of space,
+/// where `r` is the number of runs in the BWT.
+///
+/// The rows of the BWT are partitioned into intervals, each of which is contained in a run,
+/// so that the LF mapping moves each interval to a contiguous range of rows.
+/// The intervals are split from the runs so that each moved range overlaps
+/// less than four intervals, which bounds the cost of finding the interval of the moved row.
+/// The number of intervals is at most `2r`.
+///
+/// A position in the BWT is represented by the pair of its row and the index of its interval.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BwtBuilder, MoveStructureBuilder};
+///
+/// let mut wrt = MoveStructureBuilder::new();
+/// BwtBuilder::new(b"abracadabra$")?.build(&mut wrt)?;
+/// let ms = wrt.finish();
+///
+/// // Walks the LF mapping from row 0, i.e., the suffix "$", to decode the text backward.
+/// let mut pos = (0, ms.interval_of(0));
+/// let mut decoded = vec![];
+/// for _ in 1..ms.len() {
+///     decoded.push(ms.symbol(pos.1));
+///     pos = ms.lf(pos);
+/// }
+/// decoded.reverse();
+/// assert_eq!(decoded, b"abracadabra");
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveStructure {
+    intervals: Vec<MoveInterval>,
+    len: usize,
+}
+
+/// Interval of rows in the move structure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct MoveInterval {
+    // The first row of the interval.
+    start: usize,
+    // The row to which the first row is moved by the LF mapping.
+    out: usize,
+    // The index of the interval containing the row `out`.
+    out_idx: usize,
+    // The symbol of the run containing the interval.
+    symbol: u8,
+}
+
+impl MoveStructure {
+    /// Returns the length of the BWT.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the BWT is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of intervals.
+    pub fn n_intervals(&self) -> usize {
+        self.intervals.len()
+    }
+
+    /// Returns the index of the interval containing `row`, in `O(log r)` time.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - The row, which must be less than the length of the BWT.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `row` is not less than the length of the BWT.
+    pub fn interval_of(&self, row: usize) -> usize {
+        assert!(row < self.len, "row {row} must be less than {}.", self.len);
+        self.intervals.partition_point(|iv| iv.start <= row) - 1
+    }
+
+    /// Returns the BWT symbol of the rows in the interval `interval`.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The index of the interval.
+    pub fn symbol(&self, interval: usize) -> u8 {
+        self.intervals[interval].symbol
+    }
+
+    /// Returns the position moved from `pos` by the LF mapping in constant time.
+    ///
+    /// # Arguments
+    ///
+    /// * `pos` - The pair of a row and the index of the interval containing it.
+    pub fn lf(&self, pos: (usize, usize)) -> (usize, usize) {
+        let (row, interval) = pos;
+        let iv = &self.intervals[interval];
+        let row = iv.out + (row - iv.start);
+        let mut interval = iv.out_idx;
+        // Fast-forwards less than 2 * BALANCE intervals.
+        while interval + 1 < self.intervals.len() && self.intervals[interval + 1].start <= row {
+            interval += 1;
+        }
+        (row, interval)
+    }
+}
+
+/// Writer adapter building a [`MoveStructure`] from a byte stream of the BWT,
+/// holding only its runs.
+///
+/// The runs can also be given by [`MoveStructureBuilder::push_run`],
+/// e.g., from [`RlbwtReader`](crate::RlbwtReader).
+pub struct MoveStructureBuilder {
+    runs: Vec<(u8, usize)>,
+}
+
+impl MoveStructureBuilder {
+    /// Creates a new builder.
+    pub const fn new() -> Self {
+        Self { runs: vec![] }
+    }
+
+    /// Appends `len` copies of `symbol` to the BWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol.
+    /// * `len` - The run length.
+    pub fn push_run(&mut self, symbol: u8, len: usize) {
+        if len == 0 {
+            return;
+        }
+        match self.runs.last_mut() {
+            Some((c, l)) if *c == symbol => *l += len,
+            _ => self.runs.push((symbol, len)),
+        }
+    }
+
+    /// Builds the move structure over the BWT written so far,
+    /// in `O(r log r)` time.
+    pub fn finish(self) -> MoveStructure {
+        let mut counts = vec![0; 256];
+        for &(c, len) in &self.runs {
+            counts[c as usize] += len;
+        }
+        let mut occ = vec![0; 256];
+        let mut rank = 0;
+        for c in 0..256 {
+            occ[c] = rank;
+            rank += counts[c];
+        }
+        let len = rank;
+
+        // inputs[p] = (q, c) for the interval starting at row p moved to row q with symbol c.
+        let mut inputs = BTreeMap::new();
+        let mut p = 0;
+        for &(c, l) in &self.runs {
+            inputs.insert(p, (occ[c as usize], c));
+            occ[c as usize] += l;
+            p += l;
+        }
+        balance(&mut inputs, len);
+
+        let starts: Vec<_> = inputs.keys().copied().collect();
+        let intervals = inputs
+            .iter()
+            .map(|(&start, &(out, symbol))| MoveInterval {
+                start,
+                out,
+                out_idx: starts.partition_point(|&s| s <= out) - 1,
+                symbol,
+            })
+            .collect();
+        MoveStructure { intervals, len }
+    }
+}
+
+impl Default for MoveStructureBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for MoveStructureBuilder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &c in buf {
+            self.push_run(c, 1);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Splits the intervals until every output interval contains less than `2 * BALANCE` input starts.
+fn balance(inputs: &mut BTreeMap<usize, (usize, u8)>, len: usize) {
+    // The input start of the interval moved to each output start.
+    let mut outputs: BTreeMap<usize, usize> = inputs.iter().map(|(&p, &(q, _))| (q, p)).collect();
+    let mut stack: Vec<usize> = inputs.keys().copied().collect();
+    while let Some(p) = stack.pop() {
+        let (q, c) = inputs[&p];
+        let end = inputs.range(p + 1..).next().map_or(len, |(&s, _)| s);
+        let out_end = q + (end - p);
+        // Finds the (2 * BALANCE)-th input start within the output interval, if any.
+        let starts: Vec<_> = inputs
+            .range(q..out_end)
+            .take(2 * BALANCE)
+            .map(|(&s, _)| s)
+            .collect();
+        if starts.len() < 2 * BALANCE {
+            continue;
+        }
+        // Splits the interval so that its first part contains exactly BALANCE input starts.
+        let mid = starts[BALANCE];
+        let new_p = p + (mid - q);
+        inputs.insert(new_p, (mid, c));
+        outputs.insert(mid, new_p);
+        stack.push(p);
+        stack.push(new_p);
+        // The output interval containing the new input start may become heavy.
+        if let Some((_, &owner)) = outputs.range(..=new_p).next_back() {
+            stack.push(owner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rank::OccTable;
+    use crate::BwtBuilder;
+
+    fn build(text: &[u8]) -> (Vec<u8>, MoveStructure) {
+        let mut bwt = vec![];
+        BwtBuilder::new(text).unwrap().build(&mut bwt).unwrap();
+        let mut builder = MoveStructureBuilder::new();
+        builder.write_all(&bwt).unwrap();
+        (bwt, builder.finish())
+    }
+
+    #[test]
+    fn test_move_structure_lf() {
+        let text: Vec<u8> = (0..3000u32)
+            .map(|i| b"ab"[(i.count_ones() % 2) as usize])
+            .chain([b'$'])
+            .collect();
+        let (bwt, ms) = build(&text);
+        let occ = OccTable::new(&bwt);
+        let n_runs = 1 + bwt.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!(ms.n_intervals() <= 2 * n_runs);
+        for row in 0..bwt.len() {
+            let k = ms.interval_of(row);
+            assert_eq!(ms.symbol(k), bwt[row]);
+            let (next, l) = ms.lf((row, k));
+            assert_eq!(next, occ.lf(&bwt, row));
+            assert_eq!(l, ms.interval_of(next));
+        }
+    }
+
+    #[test]
+    fn test_move_structure_balanced() {
+        let text: Vec<u8> = (0..5000u32)
+            .map(|i| b"abc"[(i.count_ones() % 3) as usize])
+            .chain([b'$'])
+            .collect();
+        let (bwt, ms) = build(&text);
+        let n_runs = 1 + bwt.windows(2).filter(|w| w[0] != w[1]).count();
+        // Some runs must be split.
+        assert!(n_runs < ms.n_intervals() && ms.n_intervals() <= 2 * n_runs);
+        for (k, iv) in ms.intervals.iter().enumerate() {
+            let end = ms.intervals.get(k + 1).map_or(ms.len(), |next| next.start);
+            let out_end = iv.out + (end - iv.start);
+            let n_starts = ms
+                .intervals
+                .iter()
+                .filter(|other| iv.out <= other.start && other.start < out_end)
+                .count();
+            assert!(n_starts < 2 * BALANCE);
+        }
+    }
+
+    #[test]
+    fn test_move_structure_push_run() {
+        let mut builder = MoveStructureBuilder::new();
+        for (c, len) in [
+            (b'a', 1),
+            (b'r', 1),
+            (b'd', 1),
+            (b'$', 1),
+            (b'r', 1),
+            (b'c', 1),
+        ] {
+            builder.push_run(c, len);
+        }
+        builder.push_run(b'a', 2);
+        builder.push_run(b'a', 2);
+        builder.push_run(b'b', 0);
+        builder.push_run(b'b', 2);
+        let ms = builder.finish();
+        let (bwt, expected) = build(b"abracadabra$");
+        assert_eq!(bwt, b"ard$rcaaaabb");
+        assert_eq!(ms.len(), expected.len());
+        assert_eq!(ms.intervals, expected.intervals);
+    }
+
+    #[test]
+    fn test_move_structure_empty() {
+        let ms = MoveStructureBuilder::new().finish();
+        assert!(ms.is_empty());
+        assert_eq!(ms.n_intervals(), 0);
+    }
+}
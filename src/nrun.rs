@@ -0,0 +1,329 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::index::SuffixIndex;
+use crate::text::TextAccess;
+
+/// Byte text whose long runs of `N`, as gaps in genomic references, are handled separately.
+///
+/// The suffixes starting inside a run, i.e., those preceded by `N`, are excluded from the cuts
+/// and spliced into the sorted chunks by [`SpliceCursor`], since their order is determined by the runs:
+/// `N^k X` for a run followed by `X` precedes `N^(k+1) X` if the first byte of `X` is smaller than `N`,
+/// and follows it otherwise.
+/// Suffixes are compared by skipping the runs instead of scanning them.
+pub struct NRunText<'t> {
+    text: Cow<'t, [u8]>,
+    // The byte of the runs, which is `N` unless the text is remapped.
+    n: u8,
+    min_len: usize,
+    // The maximal runs of at least min_len bytes in order.
+    runs: Vec<Range<usize>>,
+    // The runs followed by a byte smaller than n or the end of the text,
+    // in the order of the suffixes following them.
+    low: Vec<usize>,
+    // The other runs in the order of the suffixes following them.
+    high: Vec<usize>,
+    // The lengths of the runs in high in decreasing order.
+    high_lens: Vec<usize>,
+}
+
+impl<'t> NRunText<'t> {
+    /// Detects the maximal runs of `n` of at least `min_len` bytes.
+    pub fn new(text: Cow<'t, [u8]>, n: u8, min_len: usize) -> Self {
+        assert_ne!(min_len, 0);
+        let mut runs = vec![];
+        let mut i = 0;
+        while i < text.len() {
+            if text[i] != n {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < text.len() && text[i] == n {
+                i += 1;
+            }
+            if i - start >= min_len {
+                runs.push(start..i);
+            }
+        }
+        let mut nrun = Self {
+            text,
+            n,
+            min_len,
+            runs,
+            low: vec![],
+            high: vec![],
+            high_lens: vec![],
+        };
+        let (mut low, mut high): (Vec<_>, Vec<_>) = (0..nrun.runs.len())
+            .partition(|&r| nrun.text.get(nrun.runs[r].end).map_or(true, |&c| c < n));
+        low.sort_by(|&a, &b| nrun.cmp_suffixes(nrun.runs[a].end, nrun.runs[b].end));
+        high.sort_by(|&a, &b| nrun.cmp_suffixes(nrun.runs[a].end, nrun.runs[b].end));
+        let mut high_lens: Vec<_> = high.iter().map(|&r| nrun.runs[r].len()).collect();
+        high_lens.sort_by_key(|&l| std::cmp::Reverse(l));
+        nrun.low = low;
+        nrun.high = high;
+        nrun.high_lens = high_lens;
+        nrun
+    }
+
+    /// Returns the detected runs in order.
+    pub fn runs(&self) -> &[Range<usize>] {
+        &self.runs
+    }
+
+    /// Returns the number of `n` at the beginning of the suffix starting at `i`.
+    fn n_prefix(&self, i: usize) -> usize {
+        let r = self.runs.partition_point(|run| run.end <= i);
+        match self.runs.get(r) {
+            Some(run) if run.start <= i => run.end - i,
+            // Shorter than min_len.
+            _ => self.text[i..].iter().take_while(|&&c| c == self.n).count(),
+        }
+    }
+
+    /// Compares the suffix starting at `i` whose first `a` bytes are `n` with one starting with more `n`,
+    /// which is decided by the byte following the former's `n`.
+    fn cmp_fewer_n(&self, i: usize, a: usize) -> Ordering {
+        match self.text.get(i + a) {
+            Some(&c) if c > self.n => Ordering::Greater,
+            // Smaller than n, or the end of the text.
+            _ => Ordering::Less,
+        }
+    }
+}
+
+impl TextAccess for NRunText<'_> {
+    fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    fn get(&self, i: usize) -> u8 {
+        self.text[i]
+    }
+
+    fn cmp_suffixes(&self, mut i: usize, mut j: usize) -> Ordering {
+        if i == j {
+            return Ordering::Equal;
+        }
+        loop {
+            let (a, b) = (self.n_prefix(i), self.n_prefix(j));
+            match a.cmp(&b) {
+                Ordering::Less => return self.cmp_fewer_n(i, a),
+                Ordering::Greater => return self.cmp_fewer_n(j, b).reverse(),
+                Ordering::Equal => {}
+            }
+            i += a;
+            j += a;
+            // Compares the bytes up to the next run.
+            loop {
+                match (self.text.get(i), self.text.get(j)) {
+                    (None, _) => return Ordering::Less,
+                    (_, None) => return Ordering::Greater,
+                    (Some(x), Some(y)) if x != y => return x.cmp(y),
+                    (Some(&x), _) if x == self.n => break,
+                    _ => {
+                        i += 1;
+                        j += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn lcp(&self, mut i: usize, mut j: usize) -> usize {
+        let mut l = 0;
+        loop {
+            let (a, b) = (self.n_prefix(i), self.n_prefix(j));
+            if a != b {
+                return l + a.min(b);
+            }
+            i += a;
+            j += a;
+            l += a;
+            loop {
+                match (self.text.get(i), self.text.get(j)) {
+                    (Some(x), Some(y)) if x == y => {
+                        if *x == self.n {
+                            break;
+                        }
+                        i += 1;
+                        j += 1;
+                        l += 1;
+                    }
+                    _ => return l,
+                }
+            }
+        }
+    }
+
+    fn reversed(&self, stride: usize, sentinel: bool) -> Box<Self> {
+        let reversed = crate::reversed_text(&self.text, stride, sentinel);
+        Box::new(Self::new(Cow::Owned(reversed), self.n, self.min_len))
+    }
+
+    fn cmp_prefix(&self, i: usize, pattern: &[u8]) -> Ordering {
+        self.text[..].cmp_prefix(i, pattern)
+    }
+
+    fn cmp_suffix(&self, i: usize, pattern: &[u8]) -> Ordering {
+        self.text[..].cmp_suffix(i, pattern)
+    }
+
+    fn suffix_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = vec![];
+        let mut start = 0;
+        for run in &self.runs {
+            // The suffix at the first byte of a run is preceded by another byte.
+            ranges.push(start..run.start + 1);
+            start = run.end;
+        }
+        ranges.push(start..self.text.len());
+        ranges
+    }
+
+    fn splice_excluded<I: SuffixIndex>(
+        &self,
+        chunk: Vec<I>,
+        cursor: &mut SpliceCursor,
+        last: bool,
+    ) -> Vec<I> {
+        let mut merged = Vec::with_capacity(chunk.len());
+        for s in chunk {
+            while let Some(p) = cursor.peek(self) {
+                if self.cmp_suffixes(p, s.to_usize()) == Ordering::Greater {
+                    break;
+                }
+                merged.push(I::from_usize(p));
+                cursor.idx += 1;
+            }
+            merged.push(s);
+        }
+        if last {
+            while let Some(p) = cursor.peek(self) {
+                merged.push(I::from_usize(p));
+                cursor.idx += 1;
+            }
+        }
+        merged
+    }
+}
+
+/// Cursor over the suffixes starting inside the runs of an [`NRunText`] in sorted order,
+/// which are `N^k X` for the runs longer than `k` followed by `X`.
+///
+/// Those followed by a byte smaller than `N` come first for increasing `k`,
+/// and the others follow for decreasing `k`, where the runs of the same `k` are in the order of `X`.
+#[derive(Default)]
+pub struct SpliceCursor {
+    started: bool,
+    high: bool,
+    k: usize,
+    // The runs longer than k in the current group and the next one in them.
+    active: Vec<usize>,
+    idx: usize,
+    // The number of runs in high longer than k.
+    n_high_active: usize,
+}
+
+impl SpliceCursor {
+    /// Returns the position of the next suffix, or `None` if all the suffixes are visited.
+    fn peek(&mut self, text: &NRunText) -> Option<usize> {
+        if !self.started {
+            self.started = true;
+            self.k = 1;
+            // A run of a single byte has no suffix starting inside it.
+            self.active = text
+                .low
+                .iter()
+                .copied()
+                .filter(|&r| text.runs[r].len() > 1)
+                .collect();
+        }
+        while self.idx == self.active.len() {
+            if self.high && self.k <= 1 {
+                return None;
+            }
+            self.idx = 0;
+            if !self.high {
+                self.k += 1;
+                let k = self.k;
+                self.active.retain(|&r| text.runs[r].len() > k);
+                if self.active.is_empty() {
+                    self.high = true;
+                    self.k = text.high_lens.first().map_or(0, |&l| l);
+                }
+            } else {
+                self.k -= 1;
+                let n_active = text.high_lens.partition_point(|&l| l > self.k);
+                if n_active != self.n_high_active {
+                    self.n_high_active = n_active;
+                    let k = self.k;
+                    self.active = text
+                        .high
+                        .iter()
+                        .copied()
+                        .filter(|&r| text.runs[r].len() > k)
+                        .collect();
+                }
+            }
+        }
+        Some(text.runs[self.active[self.idx]].end - self.k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nrun_text() {
+        let text: &[u8] = b"ACNNNNGTNNNACNNNNNTANNNNNNNNA$";
+        let nrun = NRunText::new(Cow::Borrowed(text), b'N', 3);
+        assert_eq!(nrun.runs(), [2..6, 8..11, 13..18, 20..28]);
+        assert_eq!(nrun.suffix_ranges(), [0..3, 6..9, 11..14, 18..21, 28..30]);
+        for i in 0..text.len() {
+            for j in 0..text.len() {
+                assert_eq!(nrun.cmp_suffixes(i, j), text.cmp_suffixes(i, j));
+                if i != j {
+                    assert_eq!(nrun.lcp(i, j), text.lcp(i, j));
+                }
+            }
+        }
+
+        let mut sa: Vec<usize> = (0..text.len()).collect();
+        sa.sort_by_key(|&i| &text[i..]);
+        let included: Vec<usize> = nrun.suffix_ranges().into_iter().flatten().collect();
+        let main: Vec<usize> = sa
+            .iter()
+            .copied()
+            .filter(|i| included.contains(i))
+            .collect();
+        let mut cursor = SpliceCursor::default();
+        let mut spliced = nrun.splice_excluded(main[..10].to_vec(), &mut cursor, false);
+        spliced.extend(nrun.splice_excluded(main[10..].to_vec(), &mut cursor, true));
+        assert_eq!(spliced, sa);
+    }
+
+    #[test]
+    fn test_nrun_text_short_runs() {
+        let texts: &[&[u8]] = &[b"N$", b"NAN$", b"ANNANAN$", b"NNANNNTNANT$", b"NTNNANN$"];
+        for &text in texts {
+            let mut sa: Vec<usize> = (0..text.len()).collect();
+            sa.sort_by_key(|&i| &text[i..]);
+            for min_len in 1..=3 {
+                let nrun = NRunText::new(Cow::Borrowed(text), b'N', min_len);
+                let included: Vec<usize> = nrun.suffix_ranges().into_iter().flatten().collect();
+                let main: Vec<usize> = sa
+                    .iter()
+                    .copied()
+                    .filter(|i| included.contains(i))
+                    .collect();
+                let mut cursor = SpliceCursor::default();
+                let spliced = nrun.splice_excluded(main, &mut cursor, true);
+                assert_eq!(spliced, sa, "{text:?} min_len={min_len}");
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::cmp::Ordering;
+
+use crate::error::{Error, Result};
+
+use crate::text::TextAccess;
+
+/// Terminator following the bases of a [`PackedDna`], which is the null byte.
+pub const PACKED_DNA_TERMINATOR: u8 = b'\0';
+
+/// Bases in the order of their 2-bit codes.
+const BASES: [u8; 4] = *b"ACGT";
+
+/// Number of bases packed in a word.
+const BASES_PER_WORD: usize = 32;
+
+/// Nucleotide text packed in 2 bits per base, taking a quarter of the bytes of the plain text.
+///
+/// It represents the bases `A`, `C`, `G`, and `T` followed by [`PACKED_DNA_TERMINATOR`],
+/// and is transformed by [`BwtBuilder::from_packed_dna`](crate::BwtBuilder::from_packed_dna)
+/// without unpacking it, where suffixes are compared 32 bases at a time.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::PackedDna;
+///
+/// let mut packed = PackedDna::from_acgt(b"GATT")?;
+/// packed.extend_acgt(b"aca")?;
+/// assert_eq!(packed.len(), 8);
+/// assert_eq!(packed.get(4), b'A');
+/// assert_eq!(packed.get(7), b'\0');
+/// assert_eq!(packed.to_bytes(), b"GATTACA\0");
+/// assert!(PackedDna::from_acgt(b"GATTNCA").is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackedDna {
+    // The bases from the most significant bits of each word,
+    // followed by a zero word so that any 32 bases can be read from two words.
+    words: Vec<u64>,
+    // The number of bases, excluding the terminator.
+    n_bases: usize,
+}
+
+impl PackedDna {
+    /// Packs a nucleotide sequence followed by the terminator.
+    ///
+    /// # Arguments
+    ///
+    /// * `seq` - The sequence of `A`, `C`, `G`, and `T`, case-insensitively.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `seq` contains another byte, such as `N`.
+    pub fn from_acgt(seq: &[u8]) -> Result<Self> {
+        let mut packed = Self::default();
+        packed.extend_acgt(seq)?;
+        Ok(packed)
+    }
+
+    /// Appends a nucleotide sequence before the terminator,
+    /// so that a large text can be packed piece by piece.
+    ///
+    /// # Arguments
+    ///
+    /// * `seq` - The sequence of `A`, `C`, `G`, and `T`, case-insensitively.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `seq` contains another byte, such as `N`,
+    /// in which case the bases before it have been appended.
+    pub fn extend_acgt(&mut self, seq: &[u8]) -> Result<()> {
+        self.words.pop();
+        self.words
+            .reserve((self.n_bases + seq.len()) / BASES_PER_WORD + 2 - self.words.len());
+        let result = seq.iter().enumerate().try_for_each(|(pos, &c)| {
+            let code = match c {
+                b'A' | b'a' => 0,
+                b'C' | b'c' => 1,
+                b'G' | b'g' => 2,
+                b'T' | b't' => 3,
+                _ => return Err(Error::InvalidSymbol { pos, byte: c }),
+            };
+            self.push_code(code);
+            Ok(())
+        });
+        self.words.push(0);
+        result
+    }
+
+    /// Returns the length of the text, including the terminator.
+    #[allow(clippy::len_without_is_empty)]
+    pub const fn len(&self) -> usize {
+        self.n_bases + 1
+    }
+
+    /// Returns the byte at position `i`.
+    ///
+    /// # Arguments
+    ///
+    /// * `i` - The position, which must be less than [`Self::len`].
+    ///
+    /// # Panics
+    ///
+    /// It panics if `i` is not less than [`Self::len`].
+    pub fn get(&self, i: usize) -> u8 {
+        assert!(i < self.len());
+        if i == self.n_bases {
+            PACKED_DNA_TERMINATOR
+        } else {
+            BASES[self.code(i)]
+        }
+    }
+
+    /// Unpacks the text into bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        (0..self.len()).map(|i| self.get(i)).collect()
+    }
+
+    /// Returns the number of bytes of the packed bases.
+    pub fn size_in_bytes(&self) -> usize {
+        self.words.len() * std::mem::size_of::<u64>()
+    }
+
+    /// Appends the base of `code`, without the trailing zero word.
+    fn push_code(&mut self, code: u64) {
+        let off = self.n_bases % BASES_PER_WORD;
+        if off == 0 {
+            self.words.push(0);
+        }
+        *self.words.last_mut().unwrap() |= code << (62 - 2 * off);
+        self.n_bases += 1;
+    }
+
+    fn code(&self, i: usize) -> usize {
+        (self.words[i / BASES_PER_WORD] >> (62 - 2 * (i % BASES_PER_WORD))) as usize & 3
+    }
+
+    /// Returns the 32 bases starting at position `i` from the most significant bits,
+    /// where the bases past the end are zeros.
+    fn word_at(&self, i: usize) -> u64 {
+        let (w, off) = (i / BASES_PER_WORD, i % BASES_PER_WORD);
+        if off == 0 {
+            self.words[w]
+        } else {
+            self.words[w] << (2 * off) | self.words[w + 1] >> (64 - 2 * off)
+        }
+    }
+
+    /// Returns the pair of words at positions `i + k` and `j + k` masked to their first `l` bases.
+    fn masked_words(&self, i: usize, j: usize, k: usize, l: usize) -> (u64, u64) {
+        let mask = !0 << (64 - 2 * l);
+        (self.word_at(i + k) & mask, self.word_at(j + k) & mask)
+    }
+}
+
+impl Default for PackedDna {
+    /// Creates an empty sequence, i.e., the text of only the terminator.
+    fn default() -> Self {
+        Self {
+            words: vec![0],
+            n_bases: 0,
+        }
+    }
+}
+
+impl TextAccess for PackedDna {
+    fn len(&self) -> usize {
+        Self::len(self)
+    }
+
+    fn get(&self, i: usize) -> u8 {
+        Self::get(self, i)
+    }
+
+    fn cmp_suffixes(&self, i: usize, j: usize) -> Ordering {
+        let (ri, rj) = (self.n_bases - i, self.n_bases - j);
+        let m = ri.min(rj);
+        for k in (0..m).step_by(BASES_PER_WORD) {
+            let (a, b) = self.masked_words(i, j, k, (m - k).min(BASES_PER_WORD));
+            if a != b {
+                return a.cmp(&b);
+            }
+        }
+        // The shorter suffix reaches the terminator, which is the smallest byte.
+        ri.cmp(&rj)
+    }
+
+    fn lcp(&self, i: usize, j: usize) -> usize {
+        let m = (self.n_bases - i).min(self.n_bases - j);
+        for k in (0..m).step_by(BASES_PER_WORD) {
+            let (a, b) = self.masked_words(i, j, k, (m - k).min(BASES_PER_WORD));
+            if a != b {
+                return k + (a ^ b).leading_zeros() as usize / 2;
+            }
+        }
+        m
+    }
+
+    fn reversed(&self, _stride: usize, _sentinel: bool) -> Box<Self> {
+        let mut reversed = Self {
+            words: Vec::with_capacity(self.words.len()),
+            n_bases: 0,
+        };
+        for i in (0..self.n_bases).rev() {
+            reversed.push_code(self.code(i) as u64);
+        }
+        reversed.words.push(0);
+        Box::new(reversed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packed_dna() {
+        let seq: Vec<u8> = (0..200u32)
+            .map(|i| BASES[(i * i % 7 % 4) as usize])
+            .collect();
+        let packed = PackedDna::from_acgt(&seq).unwrap();
+        let text = [&seq[..], &[PACKED_DNA_TERMINATOR]].concat();
+        assert_eq!(packed.to_bytes(), text);
+        assert_eq!(packed.size_in_bytes(), 8 * 8);
+        for i in 0..text.len() {
+            for j in 0..text.len() {
+                assert_eq!(packed.cmp_suffixes(i, j), text[..].cmp_suffixes(i, j));
+                if i != j {
+                    assert_eq!(packed.lcp(i, j), text[..].lcp(i, j));
+                }
+            }
+            assert_eq!(
+                packed.cmp_suffix(i, b"ACG\0"),
+                text[..].cmp_suffix(i, b"ACG\0")
+            );
+        }
+        let reversed = packed.reversed(1, false);
+        assert_eq!(reversed.to_bytes(), crate::reversed_text(&text, 1, false));
+    }
+
+    #[test]
+    fn test_packed_dna_extend() {
+        let mut packed = PackedDna::default();
+        assert_eq!(packed.to_bytes(), b"\0");
+        for _ in 0..10 {
+            packed.extend_acgt(b"ACGTTGCA").unwrap();
+        }
+        assert_eq!(
+            packed.to_bytes(),
+            [&b"ACGTTGCA".repeat(10)[..], b"\0"].concat()
+        );
+        assert!(matches!(
+            packed.extend_acgt(b"ACN"),
+            Err(Error::InvalidSymbol { pos: 2, byte: b'N' })
+        ));
+        assert_eq!(packed.len(), 83);
+    }
+}
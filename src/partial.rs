@@ -0,0 +1,590 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::checkpoint::{read_cuts, read_usize, save_atomically, write_cuts};
+use crate::container::crc32_update;
+use crate::error::{Error, Result};
+use crate::stats::RunCounter;
+use crate::BuildStats;
+
+/// Magic bytes at the beginning of a cut set file.
+const CUT_SET_MAGIC: &[u8; 4] = b"SBCS";
+/// Magic bytes at the beginning of a partial BWT file.
+const PARTIAL_MAGIC: &[u8; 4] = b"SBPT";
+/// Version of the file formats.
+const VERSION: u8 = 1;
+/// Length of the header of a partial BWT file in bytes.
+const PARTIAL_HEADER_LEN: usize = 104;
+
+const FLAG_TERMINATOR: u8 = 1 << 0;
+const FLAG_SENTINEL: u8 = 1 << 1;
+
+/// Cuts shared by the workers of a distributed construction.
+///
+/// They are generated by [`BwtBuilder::cut_set`](crate::BwtBuilder::cut_set)
+/// and passed to [`BwtBuilder::partial`](crate::BwtBuilder::partial) on each worker.
+///
+/// The file is stored in little-endian order:
+///
+/// | Size | Content |
+/// |-----:|---------|
+/// | 4 | Magic `SBCS` |
+/// | 1 | Version `1` |
+/// | 8 | Length of the text |
+/// | 8 | Number of cuts |
+///
+/// followed by the length in 8 bytes and the bytes of each cut.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CutSet {
+    /// The length of the text in symbols.
+    pub text_len: usize,
+    /// The cuts, starting with the empty one.
+    pub cuts: Vec<Vec<u8>>,
+}
+
+impl CutSet {
+    /// Reads a cut set from `rdr`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the cut set is malformed or `rdr` returns an error.
+    pub fn read_from<R: Read>(mut rdr: R) -> Result<Self> {
+        read_magic(&mut rdr, CUT_SET_MAGIC, "cut set")?;
+        let text_len = read_usize(&mut rdr)?;
+        let cuts = read_cuts(&mut rdr)?;
+        Ok(Self { text_len, cuts })
+    }
+
+    /// Writes the cut set to `wrt`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `wrt` returns an error.
+    pub fn write_to<W: Write>(&self, mut wrt: W) -> Result<()> {
+        wrt.write_all(CUT_SET_MAGIC)?;
+        wrt.write_all(&[VERSION])?;
+        wrt.write_all(&(self.text_len as u64).to_le_bytes())?;
+        write_cuts(&mut wrt, &self.cuts)?;
+        wrt.flush()?;
+        Ok(())
+    }
+
+    /// Loads a cut set from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the file cannot be read or is malformed.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Saves the cut set to the file at `path`, replacing it atomically.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the file cannot be written.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        save_atomically(path.as_ref(), |wrt| self.write_to(wrt))
+    }
+
+    /// Returns the CRC-32 checksum of the serialized cut set,
+    /// recorded in the partial outputs to tell whether they are built from the same cuts.
+    pub fn checksum(&self) -> u32 {
+        let mut buf = vec![];
+        // Writing to a vector never fails.
+        self.write_to(&mut buf).unwrap();
+        crc32_update(0, &buf)
+    }
+}
+
+/// Header of a partial BWT file written by [`PartialBwtWriter`],
+/// describing the rows of the BWT following it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartialHeader {
+    /// The length of the text in symbols.
+    pub text_len: usize,
+    /// The number of cuts of the cut set.
+    pub n_cuts: usize,
+    /// The checksum of the cut set, given by [`CutSet::checksum`].
+    pub cut_set_checksum: u32,
+    /// The range of the indices of cuts whose chunks are built.
+    pub cut_range: Range<usize>,
+    /// The rows of the BWT following the header.
+    pub rows: Range<usize>,
+    /// The number of bytes of the BWT following the header.
+    pub len: usize,
+    /// The CRC-32 checksum of the BWT following the header.
+    pub checksum: u32,
+    /// The primary index of the whole BWT.
+    pub primary_index: usize,
+    /// The chunk size used to generate cuts.
+    pub chunk_size: usize,
+    /// The number of suffixes in the largest chunk of the range.
+    pub max_chunk_len: usize,
+    /// The wall-clock time of the partial construction.
+    pub elapsed: Duration,
+    /// The terminator appended to the text, if any.
+    pub terminator: Option<u8>,
+    /// The placeholder written at the implicit sentinel, if any.
+    pub sentinel: Option<u8>,
+}
+
+impl PartialHeader {
+    /// Reads the header of a partial BWT file from `rdr`, which is left at the beginning of the BWT.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the header is malformed or `rdr` returns an error.
+    pub fn read_from<R: Read>(mut rdr: R) -> Result<Self> {
+        read_magic(&mut rdr, PARTIAL_MAGIC, "partial BWT")?;
+        let mut bytes = [0; 3];
+        rdr.read_exact(&mut bytes)?;
+        let [flags, terminator, sentinel] = bytes;
+        let text_len = read_usize(&mut rdr)?;
+        let n_cuts = read_usize(&mut rdr)?;
+        let cut_start = read_usize(&mut rdr)?;
+        let cut_end = read_usize(&mut rdr)?;
+        let row_start = read_usize(&mut rdr)?;
+        let row_end = read_usize(&mut rdr)?;
+        let len = read_usize(&mut rdr)?;
+        let primary_index = read_usize(&mut rdr)?;
+        let chunk_size = read_usize(&mut rdr)?;
+        let max_chunk_len = read_usize(&mut rdr)?;
+        let elapsed_ns = read_usize(&mut rdr)?;
+        let mut crcs = [0; 8];
+        rdr.read_exact(&mut crcs)?;
+        let header = Self {
+            text_len,
+            n_cuts,
+            cut_set_checksum: u32::from_le_bytes(crcs[..4].try_into().unwrap()),
+            cut_range: cut_start..cut_end,
+            rows: row_start..row_end,
+            len,
+            checksum: u32::from_le_bytes(crcs[4..].try_into().unwrap()),
+            primary_index,
+            chunk_size,
+            max_chunk_len,
+            elapsed: Duration::from_nanos(elapsed_ns as u64),
+            terminator: (flags & FLAG_TERMINATOR != 0).then_some(terminator),
+            sentinel: (flags & FLAG_SENTINEL != 0).then_some(sentinel),
+        };
+        if header.cut_range.is_empty() || header.cut_range.end > header.n_cuts {
+            return Err(Error::invalid_format(format!(
+                "range of cuts must be non-empty within {}, but got {:?}.",
+                header.n_cuts, header.cut_range
+            )));
+        }
+        if header.rows.start > header.rows.end {
+            return Err(Error::invalid_format(format!(
+                "rows must be a range, but got {:?}.",
+                header.rows
+            )));
+        }
+        Ok(header)
+    }
+
+    fn to_bytes(&self) -> [u8; PARTIAL_HEADER_LEN] {
+        let mut bytes = [0; PARTIAL_HEADER_LEN];
+        bytes[..4].copy_from_slice(PARTIAL_MAGIC);
+        bytes[4] = VERSION;
+        if let Some(terminator) = self.terminator {
+            bytes[5] |= FLAG_TERMINATOR;
+            bytes[6] = terminator;
+        }
+        if let Some(sentinel) = self.sentinel {
+            bytes[5] |= FLAG_SENTINEL;
+            bytes[7] = sentinel;
+        }
+        let xs = [
+            self.text_len,
+            self.n_cuts,
+            self.cut_range.start,
+            self.cut_range.end,
+            self.rows.start,
+            self.rows.end,
+            self.len,
+            self.primary_index,
+            self.chunk_size,
+            self.max_chunk_len,
+            self.elapsed.as_nanos() as usize,
+        ];
+        for (i, x) in xs.into_iter().enumerate() {
+            bytes[8 + i * 8..16 + i * 8].copy_from_slice(&(x as u64).to_le_bytes());
+        }
+        bytes[96..100].copy_from_slice(&self.cut_set_checksum.to_le_bytes());
+        bytes[100..].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes
+    }
+}
+
+/// Writer of the output of a worker in a distributed construction,
+/// prefixing the rows of the BWT with a [`PartialHeader`]
+/// so that the outputs are verified and concatenated by [`assemble_partials`].
+///
+/// The header is reserved when the writer is created,
+/// and filled by [`Self::finish`] after the construction.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::io::Cursor;
+///
+/// use small_bwt::{assemble_partials, BwtBuilder, PartialBwtWriter};
+///
+/// let text = b"abracadabra$";
+/// // The coordinator generates the cuts and distributes them.
+/// let cut_set = BwtBuilder::new(text)?.chunk_size(3)?.cut_set()?;
+/// assert_eq!(cut_set.cuts.len(), 5);
+///
+/// // Each worker builds the chunks of a range of cuts.
+/// let mut parts = vec![];
+/// for range in [0..2, 2..5] {
+///     let builder = BwtBuilder::new(text)?.partial(&cut_set, range.clone())?;
+///     let mut wrt = PartialBwtWriter::new(Cursor::new(vec![]), &cut_set, range)?;
+///     let stats = builder.build(&mut wrt)?;
+///     parts.push(wrt.finish(&stats)?.into_inner());
+/// }
+///
+/// // The partial outputs are verified and concatenated in the order of the ranges.
+/// let mut bwt = vec![];
+/// let stats = assemble_partials(parts.iter().map(|part| part.as_slice()), &mut bwt)?;
+/// assert_eq!(bwt, b"ard$rcaaaabb");
+/// assert_eq!(stats.primary_index, 3);
+/// # Ok(())
+/// # }
+/// ```
+pub struct PartialBwtWriter<W: Write + Seek> {
+    wrt: W,
+    // The position of the header in wrt.
+    start: u64,
+    len: usize,
+    crc: u32,
+    n_cuts: usize,
+    cut_set_checksum: u32,
+    cut_range: Range<usize>,
+}
+
+impl<W: Write + Seek> PartialBwtWriter<W> {
+    /// Creates a new writer of the BWT of the cuts in `cut_range` of `cut_set`,
+    /// reserving the header at the current position of `wrt`.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the file.
+    /// * `cut_set` - The cut set of the construction.
+    /// * `cut_range` - The range of cuts given to [`BwtBuilder::partial`](crate::BwtBuilder::partial).
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `cut_range` is empty or exceeds the number of cuts,
+    /// or if `wrt` returns an error.
+    pub fn new(mut wrt: W, cut_set: &CutSet, cut_range: Range<usize>) -> Result<Self> {
+        let n_cuts = cut_set.cuts.len();
+        if cut_range.is_empty() || cut_range.end > n_cuts {
+            return Err(Error::invalid_argument(format!(
+                "range of cuts must be non-empty within {n_cuts}, but got {cut_range:?}."
+            )));
+        }
+        let start = wrt.stream_position()?;
+        wrt.write_all(&[0; PARTIAL_HEADER_LEN])?;
+        Ok(Self {
+            wrt,
+            start,
+            len: 0,
+            crc: 0,
+            n_cuts,
+            cut_set_checksum: cut_set.checksum(),
+            cut_range,
+        })
+    }
+
+    /// Writes the header from the statistics of the construction and returns the underlying writer,
+    /// positioned at the end of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `stats` - The statistics returned by [`BwtBuilder::build`](crate::BwtBuilder::build).
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `stats` is not of a construction with the cut set,
+    /// or if the underlying writer returns an error.
+    pub fn finish(mut self, stats: &BuildStats) -> Result<W> {
+        if stats.n_cuts != self.n_cuts {
+            return Err(Error::invalid_argument(format!(
+                "construction must have {} cuts, but got {}.",
+                self.n_cuts, stats.n_cuts
+            )));
+        }
+        let header = PartialHeader {
+            text_len: stats.text_len,
+            n_cuts: self.n_cuts,
+            cut_set_checksum: self.cut_set_checksum,
+            cut_range: self.cut_range.clone(),
+            rows: stats.rows.clone(),
+            len: self.len,
+            checksum: self.crc,
+            primary_index: stats.primary_index,
+            chunk_size: stats.chunk_size,
+            max_chunk_len: stats.max_chunk_len,
+            elapsed: stats.elapsed,
+            terminator: stats.terminator,
+            sentinel: stats.sentinel,
+        };
+        let end = self.wrt.stream_position()?;
+        self.wrt.seek(SeekFrom::Start(self.start))?;
+        self.wrt.write_all(&header.to_bytes())?;
+        self.wrt.seek(SeekFrom::Start(end))?;
+        self.wrt.flush()?;
+        Ok(self.wrt)
+    }
+}
+
+impl<W: Write + Seek> Write for PartialBwtWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.wrt.write(buf)?;
+        self.len += n;
+        self.crc = crc32_update(self.crc, &buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.wrt.flush()
+    }
+}
+
+/// Concatenates the partial BWT files written by [`PartialBwtWriter`] into the whole BWT,
+/// verifying that they are built from the same cut set and cover all the cuts in order.
+///
+/// The checksum of each part is verified while it is copied,
+/// so `wrt` may have received some bytes when an error is returned.
+///
+/// # Arguments
+///
+/// * `parts` - The readers of the partial files in the order of their ranges of cuts.
+/// * `wrt` - The writer of the whole BWT, such as [`BwtFileWriter`](crate::BwtFileWriter).
+///
+/// # Returns
+///
+/// The statistics of the whole construction, where the elapsed time is the longest one of the parts.
+///
+/// # Errors
+///
+/// An error is returned if
+///
+/// - a part is malformed or its BWT differs from its checksum,
+/// - the parts are of different texts or cut sets,
+/// - the ranges of cuts of the parts are not contiguous from the first cut to the last one, or
+/// - a reader or `wrt` returns an error.
+///
+/// # Examples
+///
+/// See [`PartialBwtWriter`].
+pub fn assemble_partials<R, I, W>(parts: I, mut wrt: W) -> Result<BuildStats>
+where
+    R: Read,
+    I: IntoIterator<Item = R>,
+    W: Write,
+{
+    let mut first: Option<PartialHeader> = None;
+    let (mut next_cut, mut next_row) = (0, 0);
+    let (mut max_chunk_len, mut n_bytes_written, mut elapsed) = (0, 0, Duration::ZERO);
+    let mut runs = RunCounter::default();
+    let mut buf = vec![0; 1 << 16];
+    for mut part in parts {
+        let header = PartialHeader::read_from(&mut part)?;
+        let first = first.get_or_insert_with(|| header.clone());
+        if (
+            header.text_len,
+            header.n_cuts,
+            header.cut_set_checksum,
+            header.primary_index,
+            header.terminator,
+            header.sentinel,
+        ) != (
+            first.text_len,
+            first.n_cuts,
+            first.cut_set_checksum,
+            first.primary_index,
+            first.terminator,
+            first.sentinel,
+        ) {
+            return Err(Error::invalid_format(format!(
+                "parts must be of the same text and cut set, but the one of cuts {:?} differs.",
+                header.cut_range
+            )));
+        }
+        if header.cut_range.start != next_cut || header.rows.start != next_row {
+            return Err(Error::invalid_format(format!(
+                "part must start at cut {next_cut} and row {next_row}, but got cut {} and row {}.",
+                header.cut_range.start, header.rows.start
+            )));
+        }
+        let mut crc = 0;
+        let mut rest = header.len;
+        while rest != 0 {
+            let len = rest.min(buf.len());
+            part.read_exact(&mut buf[..len])?;
+            crc = crc32_update(crc, &buf[..len]);
+            runs.extend(&buf[..len]);
+            wrt.write_all(&buf[..len])?;
+            rest -= len;
+        }
+        if crc != header.checksum {
+            return Err(Error::invalid_format(format!(
+                "checksum of the part of cuts {:?} must be {:#010x}, but got {crc:#010x}.",
+                header.cut_range, header.checksum
+            )));
+        }
+        next_cut = header.cut_range.end;
+        next_row = header.rows.end;
+        max_chunk_len = max_chunk_len.max(header.max_chunk_len);
+        n_bytes_written += header.len;
+        elapsed = elapsed.max(header.elapsed);
+    }
+    let first = first.ok_or_else(|| Error::invalid_argument("parts must not be empty."))?;
+    let n_rows = first.text_len + usize::from(first.sentinel.is_some());
+    if next_cut != first.n_cuts || next_row != n_rows {
+        return Err(Error::invalid_format(format!(
+            "parts must end at cut {} and row {n_rows}, but got cut {next_cut} and row {next_row}.",
+            first.n_cuts
+        )));
+    }
+    wrt.flush()?;
+    Ok(BuildStats {
+        text_len: first.text_len,
+        chunk_size: first.chunk_size,
+        n_cuts: first.n_cuts,
+        max_chunk_len,
+        n_bytes_written,
+        elapsed,
+        primary_index: first.primary_index,
+        runs: runs.stats(),
+        reverse_primary_index: None,
+        context_order: None,
+        terminator: first.terminator,
+        sentinel: first.sentinel,
+        alphabet: None,
+        n_runs: vec![],
+        rows: 0..n_rows,
+    })
+}
+
+fn read_magic<R: Read>(rdr: &mut R, magic: &[u8; 4], name: &str) -> Result<()> {
+    let mut head = [0; 5];
+    rdr.read_exact(&mut head)?;
+    if &head[..4] != magic {
+        return Err(Error::invalid_format(format!(
+            "not a {name} file, since the magic is missing."
+        )));
+    }
+    if head[4] != VERSION {
+        return Err(Error::invalid_format(format!(
+            "version must be {VERSION}, but got {}.",
+            head[4]
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::BwtBuilder;
+
+    /// Builds the parts of the ranges of cuts between consecutive `bounds`.
+    fn build_parts(text: &[u8], chunk_size: usize, bounds: &[usize]) -> Vec<Vec<u8>> {
+        let cut_set = BwtBuilder::new(text)
+            .unwrap()
+            .chunk_size(chunk_size)
+            .unwrap()
+            .cut_set()
+            .unwrap();
+        bounds
+            .windows(2)
+            .map(|w| {
+                let range = w[0]..w[1];
+                let builder = BwtBuilder::new(text)
+                    .unwrap()
+                    .partial(&cut_set, range.clone())
+                    .unwrap();
+                let mut wrt =
+                    PartialBwtWriter::new(Cursor::new(vec![]), &cut_set, range.clone()).unwrap();
+                let stats = builder.build(&mut wrt).unwrap();
+                wrt.finish(&stats).unwrap().into_inner()
+            })
+            .collect()
+    }
+
+    fn assemble(parts: &[Vec<u8>]) -> Result<(BuildStats, Vec<u8>)> {
+        let mut bwt = vec![];
+        let stats = assemble_partials(parts.iter().map(|part| part.as_slice()), &mut bwt)?;
+        Ok((stats, bwt))
+    }
+
+    #[test]
+    fn test_cut_set_roundtrip() {
+        let cut_set = CutSet {
+            text_len: 12,
+            cuts: vec![b"".to_vec(), b"ab".to_vec(), b"ad".to_vec()],
+        };
+        let mut buf = vec![];
+        cut_set.write_to(&mut buf).unwrap();
+        assert_eq!(&buf[..5], b"SBCS\x01");
+        assert_eq!(CutSet::read_from(buf.as_slice()).unwrap(), cut_set);
+        for len in [0, 4, 13, buf.len() - 1] {
+            assert!(CutSet::read_from(&buf[..len]).is_err());
+        }
+
+        let mut other = cut_set.clone();
+        other.cuts[2] = b"ac".to_vec();
+        assert_ne!(cut_set.checksum(), other.checksum());
+    }
+
+    #[test]
+    fn test_assemble_partials() {
+        let text = b"abracadabra$";
+        let expected = BwtBuilder::new(text)
+            .unwrap()
+            .chunk_size(3)
+            .unwrap()
+            .build(vec![])
+            .unwrap();
+        for bounds in [&[0, 5][..], &[0, 1, 5], &[0, 2, 3, 5]] {
+            let parts = build_parts(text, 3, bounds);
+            let header = PartialHeader::read_from(parts[0].as_slice()).unwrap();
+            assert_eq!(header.cut_range, bounds[0]..bounds[1]);
+            assert_eq!(header.len, header.rows.len());
+            let (stats, bwt) = assemble(&parts).unwrap();
+            assert_eq!(bwt, b"ard$rcaaaabb");
+            assert_eq!(stats.primary_index, 3);
+            assert_eq!(stats.runs, expected.runs);
+            assert_eq!(stats.rows, 0..12);
+        }
+    }
+
+    #[test]
+    fn test_assemble_partials_invalid() {
+        let text = b"abracadabra$";
+        let parts = build_parts(text, 3, &[0, 2, 3, 5]);
+        assert!(assemble(&[]).is_err());
+        // Missing, duplicated, and swapped parts.
+        assert!(assemble(&parts[..2]).is_err());
+        assert!(assemble(&[parts[0].clone(), parts[0].clone()]).is_err());
+        assert!(assemble(&[parts[1].clone(), parts[0].clone(), parts[2].clone()]).is_err());
+        // A corrupted part.
+        let mut broken = parts.clone();
+        *broken[1].last_mut().unwrap() ^= 1;
+        assert!(assemble(&broken).is_err());
+        // A part of another cut set.
+        let other = build_parts(text, 100, &[0, 1]);
+        assert!(assemble(&[parts[0].clone(), other[0].clone()]).is_err());
+    }
+}
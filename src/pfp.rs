@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::sais::sais;
+use crate::text::TextAccess;
+
+/// Base of the Karp-Rabin fingerprints of windows.
+const HASH_BASE: u64 = 0x100000001b3;
+
+/// Parameters of prefix-free parsing, set by
+/// [`BwtBuilder::prefix_free_parsing`](crate::BwtBuilder::prefix_free_parsing).
+#[derive(Clone, Copy, Debug)]
+pub struct PfpParams {
+    // The length of the windows.
+    pub window: usize,
+    // The modulus selecting the trigger windows.
+    pub modulus: usize,
+    // The number of suffixes written at once, which is the chunk size.
+    pub batch_size: usize,
+}
+
+/// Prefix-free parsing of a text as in Big-BWT of Boucher et al.,
+/// splitting the text into phrases at trigger windows whose fingerprints are divisible by the modulus.
+///
+/// Consecutive phrases overlap by a window, so the phrase suffixes longer than a window are prefix-free,
+/// and the suffixes of the text are sorted by the suffixes of the dictionary and the parse,
+/// in `O(|D| + |P|)` words of space for the dictionary `D` and the parse `P`.
+/// For a repetitive text, both are much smaller than the text.
+pub struct PrefixFreeParse {
+    // The distinct phrases in increasing order, where the k-th one has rank k + 1.
+    // The last phrase of the text is kept separately from the same phrase in the middle.
+    phrases: Vec<Vec<u8>>,
+    // The rank of the last phrase of the text.
+    last_rank: usize,
+    // The ranks of the phrases in the text, followed by 0.
+    parse: Vec<usize>,
+    // The starting positions of the phrases in the text.
+    starts: Vec<usize>,
+    window: usize,
+}
+
+impl PrefixFreeParse {
+    /// Parses the text with windows of `window` bytes, where a window is a trigger
+    /// if its fingerprint is divisible by `modulus`.
+    pub fn new<T: TextAccess + ?Sized>(text: &T, window: usize, modulus: usize) -> Self {
+        assert_ne!(window, 0);
+        assert_ne!(modulus, 0);
+        let n = text.len();
+        let mut starts = vec![0];
+        if n.saturating_sub(window) > 1 {
+            let top = (0..window).fold(1u64, |acc, _| acc.wrapping_mul(HASH_BASE));
+            let symbol = |i: usize| text.get(i) as u64 + 1;
+            let mut hash = (0..window).fold(0u64, |h, i| {
+                h.wrapping_mul(HASH_BASE).wrapping_add(symbol(i))
+            });
+            // The windows at 0 and ending at the last byte are not triggers,
+            // so that every phrase is longer than a window.
+            for i in 1..n - window {
+                hash = hash
+                    .wrapping_mul(HASH_BASE)
+                    .wrapping_sub(top.wrapping_mul(symbol(i - 1)))
+                    .wrapping_add(symbol(i + window - 1));
+                if (hash.wrapping_mul(0x9e3779b97f4a7c15) >> 32) % modulus as u64 == 0 {
+                    starts.push(i);
+                }
+            }
+        }
+
+        // Collects the distinct phrases, where the last one is always distinct.
+        let phrase_at = |k: usize| -> Vec<u8> {
+            let end = starts.get(k + 1).map_or(n, |&s| s + window);
+            (starts[k]..end).map(|i| text.get(i)).collect()
+        };
+        let mut ids = HashMap::new();
+        let mut phrases = vec![];
+        let mut parse = Vec::with_capacity(starts.len() + 1);
+        for k in 0..starts.len() - 1 {
+            let phrase = phrase_at(k);
+            let id = *ids.entry(phrase).or_insert_with_key(|phrase| {
+                phrases.push(phrase.clone());
+                phrases.len() - 1
+            });
+            parse.push(id);
+        }
+        drop(ids);
+        phrases.push(phrase_at(starts.len() - 1));
+        parse.push(phrases.len() - 1);
+
+        // Ranks the phrases, where the last one precedes the same phrase in the middle
+        // since the text ends there.
+        let last_id = phrases.len() - 1;
+        let mut order: Vec<usize> = (0..phrases.len()).collect();
+        order.sort_unstable_by(|&a, &b| {
+            phrases[a]
+                .cmp(&phrases[b])
+                .then_with(|| (b == last_id).cmp(&(a == last_id)))
+        });
+        let mut ranks = vec![0; phrases.len()];
+        for (r, &id) in order.iter().enumerate() {
+            ranks[id] = r + 1;
+        }
+        for id in parse.iter_mut() {
+            *id = ranks[*id];
+        }
+        parse.push(0);
+        let mut sorted = vec![vec![]; phrases.len()];
+        for (id, phrase) in phrases.into_iter().enumerate() {
+            sorted[ranks[id] - 1] = phrase;
+        }
+        Self {
+            phrases: sorted,
+            last_rank: ranks[last_id],
+            parse,
+            starts,
+            window,
+        }
+    }
+
+    /// Returns the number of distinct phrases.
+    pub fn n_phrases(&self) -> usize {
+        self.phrases.len()
+    }
+
+    /// Returns the number of phrases in the text.
+    pub fn parse_len(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Visits the starting positions of all the suffixes of the text in increasing order of the suffixes.
+    pub fn for_each_sorted_suffix<F: FnMut(usize) -> Result<()>>(&self, mut f: F) -> Result<()> {
+        // occs[r - 1] has the occurrences k of the phrase of rank r
+        // with the ranks of the parse suffixes at k + 1 in increasing order.
+        let sa = sais(&self.parse, self.phrases.len() + 1);
+        let mut occs = vec![vec![]; self.phrases.len()];
+        for (r, &k) in sa.iter().enumerate() {
+            if k != 0 {
+                occs[self.parse[k - 1] - 1].push((r, k - 1));
+            }
+        }
+        drop(sa);
+
+        // The phrase suffixes longer than a window, or any suffix of the last phrase.
+        let suffix = |(q, o): (usize, usize)| &self.phrases[q][o..];
+        let mut suffixes = vec![];
+        for (q, phrase) in self.phrases.iter().enumerate() {
+            let end = if q + 1 == self.last_rank {
+                phrase.len()
+            } else {
+                phrase.len() - self.window
+            };
+            suffixes.extend((0..end).map(|o| (q, o)));
+        }
+        suffixes.sort_unstable_by(|&a, &b| suffix(a).cmp(suffix(b)));
+
+        // Equal phrase suffixes are ordered by the parse suffixes following them.
+        let mut merged = vec![];
+        let mut i = 0;
+        while i < suffixes.len() {
+            let mut j = i + 1;
+            while j < suffixes.len() && suffix(suffixes[j]) == suffix(suffixes[i]) {
+                j += 1;
+            }
+            let group = &suffixes[i..j];
+            i = j;
+            if let [(q, o)] = *group {
+                for &(_, k) in &occs[q] {
+                    f(self.starts[k] + o)?;
+                }
+                continue;
+            }
+            merged.clear();
+            for &(q, o) in group {
+                merged.extend(occs[q].iter().map(|&(r, k)| (r, self.starts[k] + o)));
+            }
+            merged.sort_unstable();
+            for &(_, pos) in &merged {
+                f(pos)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted_suffixes(text: &[u8], window: usize, modulus: usize) -> Vec<usize> {
+        let pfp = PrefixFreeParse::new(text, window, modulus);
+        let mut sa = vec![];
+        pfp.for_each_sorted_suffix(|j| {
+            sa.push(j);
+            Ok(())
+        })
+        .unwrap();
+        sa
+    }
+
+    fn naive_suffix_array(text: &[u8]) -> Vec<usize> {
+        let mut sa: Vec<usize> = (0..text.len()).collect();
+        sa.sort_by(|&a, &b| text[a..].cmp(&text[b..]));
+        sa
+    }
+
+    #[test]
+    fn test_prefix_free_parse() {
+        let text = b"abracadabra$";
+        assert_eq!(
+            sorted_suffixes(text, 2, 2),
+            [11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]
+        );
+    }
+
+    #[test]
+    fn test_prefix_free_parse_repetitive() {
+        // Copies of a random sequence with a few mutations.
+        let mut x = 2718u64;
+        let mut next = || {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        };
+        let base: Vec<u8> = (0..300).map(|_| b"ACGT"[(next() % 4) as usize]).collect();
+        let mut text = vec![];
+        for _ in 0..10 {
+            let mut copy = base.clone();
+            for _ in 0..3 {
+                let i = (next() % copy.len() as u64) as usize;
+                copy[i] = b"ACGT"[(next() % 4) as usize];
+            }
+            text.extend_from_slice(&copy);
+        }
+        let terminated = [&text[..], b"$"].concat();
+        for (window, modulus) in [(1, 1), (1, 3), (4, 8), (10, 20), (10, 100000)] {
+            for text in [&text[..], &terminated[..]] {
+                let pfp = PrefixFreeParse::new(text, window, modulus);
+                assert_eq!(pfp.starts.len() + 1, pfp.parse.len());
+                assert_eq!(
+                    sorted_suffixes(text, window, modulus),
+                    naive_suffix_array(text)
+                );
+            }
+        }
+        // The phrases are shared by the copies.
+        let pfp = PrefixFreeParse::new(&text[..], 6, 30);
+        assert!(pfp.n_phrases() < pfp.parse_len() / 2);
+    }
+
+    #[test]
+    fn test_prefix_free_parse_short() {
+        for text in [&b"a"[..], b"ab", b"aaaaaaa", b"abababab", b"ba"] {
+            for window in [1, 2, 3, usize::MAX] {
+                assert_eq!(sorted_suffixes(text, window, 1), naive_suffix_array(text));
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use crate::error::{Error, Result};
+
+/// Preparer of arbitrary bytes into a text with a unique terminator for [`BwtBuilder`](crate::BwtBuilder),
+/// reversibly escaping the occurrences of the terminator in the bytes.
+///
+/// With the terminator `t`, the escape byte is `t + 1`,
+/// and each occurrence of `t` or `t + 1` in the bytes is written as `t + 1` followed by the byte plus one.
+/// The terminator is then appended, which is the smallest byte occurring only at the end,
+/// as required by [`verify_terminator`](crate::verify_terminator).
+/// The text grows by one byte for each escaped byte.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{decode_bwt, BwtBuilder, TextPreparer};
+///
+/// let preparer = TextPreparer::default();
+/// let text = preparer.prepare(b"ab\0ra\x01")?;
+/// assert_eq!(text, b"ab\x01\x01ra\x01\x02\0");
+///
+/// let mut bwt = vec![];
+/// BwtBuilder::new(&text)?.build(&mut bwt)?;
+/// let decoded = decode_bwt(&bwt)?;
+/// assert_eq!(preparer.restore(&decoded)?, b"ab\0ra\x01");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextPreparer {
+    terminator: u8,
+}
+
+impl Default for TextPreparer {
+    /// Creates a preparer with the terminator `0x00`.
+    fn default() -> Self {
+        Self { terminator: 0 }
+    }
+}
+
+impl TextPreparer {
+    /// Creates a preparer with a given terminator.
+    ///
+    /// # Arguments
+    ///
+    /// * `terminator` - The terminator, which must be no greater than `0xfd`
+    ///   so that the escaped bytes fit in a byte.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `terminator` is greater than `0xfd`.
+    pub fn new(terminator: u8) -> Result<Self> {
+        if terminator > 0xfd {
+            return Err(Error::invalid_argument(format!(
+                "terminator must be no greater than 0xfd, but got {terminator:#04x}."
+            )));
+        }
+        Ok(Self { terminator })
+    }
+
+    /// Returns the terminator.
+    pub const fn terminator(&self) -> u8 {
+        self.terminator
+    }
+
+    /// Returns the escape byte, i.e., the terminator plus one.
+    pub const fn escape(&self) -> u8 {
+        self.terminator + 1
+    }
+
+    /// Escapes the terminator in `bytes` and appends the terminator.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes to be prepared.
+    ///
+    /// # Errors
+    ///
+    /// An [`Error::InvalidSymbol`] error is returned if `bytes` has a byte smaller than the terminator,
+    /// which could not be ordered after it.
+    pub fn prepare(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let (terminator, escape) = (self.terminator, self.escape());
+        if let Some(pos) = bytes.iter().position(|&c| c < terminator) {
+            return Err(Error::InvalidSymbol {
+                pos,
+                byte: bytes[pos],
+            });
+        }
+        let n_escaped = bytes.iter().filter(|&&c| c <= escape).count();
+        let mut text = Vec::with_capacity(bytes.len() + n_escaped + 1);
+        for &c in bytes {
+            if c <= escape {
+                text.extend_from_slice(&[escape, c + 1]);
+            } else {
+                text.push(c);
+            }
+        }
+        text.push(terminator);
+        Ok(text)
+    }
+
+    /// Restores the original bytes from a text prepared by [`Self::prepare`],
+    /// such as the one decoded from the BWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The prepared text, optionally followed by the terminator.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `text` has the terminator before its end,
+    /// or the escape byte not followed by an escaped byte.
+    pub fn restore(&self, text: &[u8]) -> Result<Vec<u8>> {
+        let (terminator, escape) = (self.terminator, self.escape());
+        let body = text.strip_suffix(&[terminator]).unwrap_or(text);
+        let mut bytes = Vec::with_capacity(body.len());
+        let mut iter = body.iter().enumerate();
+        while let Some((pos, &c)) = iter.next() {
+            if c == terminator {
+                return Err(Error::invalid_format(format!(
+                    "terminator must be only at the end, but found at position {pos}."
+                )));
+            }
+            if c != escape {
+                bytes.push(c);
+                continue;
+            }
+            match iter.next() {
+                Some((_, &d)) if d == terminator + 1 || d == escape + 1 => bytes.push(d - 1),
+                _ => {
+                    return Err(Error::invalid_format(format!(
+                        "escape byte must be followed by {:#04x} or {:#04x}, but not at position {pos}.",
+                        terminator + 1,
+                        escape + 1
+                    )))
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{decode_bwt, verify_terminator, BwtBuilder};
+
+    #[test]
+    fn test_text_preparer_roundtrip() {
+        let all: Vec<u8> = (0..=255).chain((0..=255).rev()).collect();
+        for terminator in [0, 1, b'$', 0xfd] {
+            let preparer = TextPreparer::new(terminator).unwrap();
+            let valid: Vec<u8> = all.iter().copied().filter(|&c| c >= terminator).collect();
+            for bytes in [
+                &valid[..],
+                &[],
+                &[terminator],
+                &[terminator + 1, terminator],
+            ] {
+                let text = preparer.prepare(bytes).unwrap();
+                verify_terminator(&text).unwrap();
+                let mut bwt = vec![];
+                BwtBuilder::new(&text).unwrap().build(&mut bwt).unwrap();
+                let decoded = decode_bwt(&bwt).unwrap();
+                assert_eq!(preparer.restore(&decoded).unwrap(), bytes);
+                assert_eq!(preparer.restore(&text[..text.len() - 1]).unwrap(), bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_preparer_invalid() {
+        assert!(TextPreparer::new(0xfe).is_err());
+        let preparer = TextPreparer::new(b'$').unwrap();
+        assert!(matches!(
+            preparer.prepare(b"ab#"),
+            Err(Error::InvalidSymbol { pos: 2, byte: b'#' })
+        ));
+        assert!(preparer.restore(b"ab$c$").is_err());
+        assert!(preparer.restore(b"ab%").is_err());
+        assert!(preparer.restore(b"ab%a$").is_err());
+        assert_eq!(preparer.restore(b"ab%%%&$").unwrap(), b"ab$%");
+    }
+}
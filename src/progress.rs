@@ -0,0 +1,174 @@
+/// Handler of the progress of the BWT construction,
+/// set by [`BwtBuilder::progress_handler`](crate::BwtBuilder::progress_handler).
+///
+/// All the methods do nothing by default.
+/// They may be called from several threads when the construction is parallelized.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use small_bwt::{BwtBuilder, ChunkProgress, ProgressHandler};
+///
+/// #[derive(Default)]
+/// struct Counter(AtomicUsize);
+///
+/// impl ProgressHandler for Counter {
+///     fn on_chunk_done(&self, progress: &ChunkProgress) {
+///         self.0.fetch_add(progress.chunk_len, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let counter = Counter::default();
+/// let mut bwt = vec![];
+/// BwtBuilder::new(b"abracadabra$")?
+///     .progress_handler(&counter)
+///     .build(&mut bwt)?;
+/// assert_eq!(counter.0.load(Ordering::Relaxed), 12);
+/// # Ok(())
+/// # }
+/// ```
+pub trait ProgressHandler: Sync {
+    /// Called when the cuts are generated.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_cuts` - The number of cuts, i.e., the number of chunks to be processed.
+    fn on_cuts_generated(&self, n_cuts: usize) {
+        let _ = n_cuts;
+    }
+
+    /// Called when the BWT of a chunk is written.
+    ///
+    /// # Arguments
+    ///
+    /// * `progress` - The progress after the chunk.
+    fn on_chunk_done(&self, progress: &ChunkProgress) {
+        let _ = progress;
+    }
+
+    /// Called with a human-readable message on the construction, such as parameters and steps.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The message.
+    fn on_message(&self, msg: &str) {
+        let _ = msg;
+    }
+
+    /// Called with a human-readable warning, such as when the construction falls back
+    /// to another algorithm on a pathological text.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The warning.
+    fn on_warning(&self, msg: &str) {
+        let _ = msg;
+    }
+}
+
+/// Progress of the BWT construction after a chunk, passed to [`ProgressHandler::on_chunk_done`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkProgress {
+    /// The number of chunks done, starting from 1.
+    pub n_chunks_done: usize,
+    /// The number of chunks in total.
+    pub n_chunks: usize,
+    /// The number of suffixes in the chunk.
+    pub chunk_len: usize,
+    /// The number of bytes of the BWT written so far.
+    pub n_bytes_written: usize,
+    /// The number of bytes of the BWT in total, i.e., the text length.
+    pub n_bytes: usize,
+}
+
+/// Handler printing the progress to stderr, used by [`BwtBuilder::verbose`](crate::BwtBuilder::verbose).
+struct StderrProgress;
+
+impl ProgressHandler for StderrProgress {
+    fn on_cuts_generated(&self, n_cuts: usize) {
+        eprintln!("[INFO] Actual number of cuts: {:?}", n_cuts);
+    }
+
+    fn on_chunk_done(&self, progress: &ChunkProgress) {
+        eprintln!(
+            "[INFO] Done chunk {}/{}: {} suffixes, {}/{} bytes written",
+            progress.n_chunks_done,
+            progress.n_chunks,
+            progress.chunk_len,
+            progress.n_bytes_written,
+            progress.n_bytes
+        );
+    }
+
+    fn on_message(&self, msg: &str) {
+        eprintln!("[INFO] {}", msg);
+    }
+
+    fn on_warning(&self, msg: &str) {
+        eprintln!("[WARN] {}", msg);
+    }
+}
+
+/// Reference to the progress handler, if any.
+///
+/// With the `tracing` feature, the progress is also emitted as `tracing` events regardless of the handler.
+#[derive(Clone, Copy)]
+pub struct Progress<'a> {
+    handler: Option<&'a dyn ProgressHandler>,
+}
+
+impl<'a> Progress<'a> {
+    pub const fn new(verbose: bool) -> Self {
+        Self {
+            handler: if verbose { Some(&StderrProgress) } else { None },
+        }
+    }
+
+    pub const fn with_handler(handler: &'a dyn ProgressHandler) -> Self {
+        Self {
+            handler: Some(handler),
+        }
+    }
+
+    pub fn print(&self, msg: &str) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("{}", msg);
+        if let Some(handler) = self.handler {
+            handler.on_message(msg);
+        }
+    }
+
+    pub fn warn(&self, msg: &str) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!("{}", msg);
+        if let Some(handler) = self.handler {
+            handler.on_warning(msg);
+        }
+    }
+
+    pub fn cuts_generated(&self, n_cuts: usize) {
+        #[cfg(feature = "tracing")]
+        tracing::info!(n_cuts, "generated cuts");
+        if let Some(handler) = self.handler {
+            handler.on_cuts_generated(n_cuts);
+        }
+    }
+
+    pub fn chunk_done(&self, progress: &ChunkProgress) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            n_chunks_done = progress.n_chunks_done,
+            n_chunks = progress.n_chunks,
+            chunk_len = progress.chunk_len,
+            n_bytes_written = progress.n_bytes_written,
+            n_bytes = progress.n_bytes,
+            "chunk done"
+        );
+        if let Some(handler) = self.handler {
+            handler.on_chunk_done(progress);
+        }
+    }
+}
@@ -1,111 +1,460 @@
-pub struct MsdRadixSorter<'a> {
-    text: &'a [u8],
-    suffixes: Vec<usize>,
+use crate::index::SuffixIndex;
+use crate::sorter::{multikey_quicksort, MsdRadixSort, MultikeyQuicksort, SuffixText};
+
+/// Minimum number of suffixes for which buckets are sorted in parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_CUTOFF: usize = 1 << 14;
+
+/// Minimum number of suffixes for which the top level is bucketed by two bytes.
+const WIDE_CUTOFF: usize = 1 << 16;
+
+/// Number of buckets of two bytes, i.e., one for the suffix ending before the level,
+/// and 257 for each first byte, including the suffix ending after it.
+const WIDE_BUCKETS: usize = 1 + 256 * 257;
+
+/// Buffers of the counting sort, reused across the buckets and the chunks to avoid allocations.
+pub struct SortWorkspace<I> {
+    counts: Vec<usize>,
+    scratch: Vec<I>,
+}
+
+impl<I> Default for SortWorkspace<I> {
+    fn default() -> Self {
+        Self {
+            counts: vec![],
+            scratch: vec![],
+        }
+    }
+}
+
+pub struct MsdRadixSorter<'a, T: SuffixText + ?Sized> {
+    text: &'a T,
     threshold: usize,
+    // Whether to bucket large ranges by two bytes at the top level.
+    wide: bool,
+    // The threshold of multikey quicksort for small ranges, or None for comparison sort.
+    multikey: Option<usize>,
 }
 
-impl<'a> MsdRadixSorter<'a> {
-    // Assumes that text has a special terminator.
-    pub fn sort(text: &'a [u8], suffixes: Vec<usize>, threshold: usize) -> Vec<usize> {
-        let n_suffixes = suffixes.len();
-        let threshold = threshold.max(1);
-        let mut sorter = Self {
+impl<'a, T: SuffixText + ?Sized> MsdRadixSorter<'a, T> {
+    // A suffix that is a prefix of another one is sorted before it,
+    // as if the text were followed by a sentinel smaller than every symbol.
+    // The suffixes must share the first `level` bytes, which are skipped.
+    // If `config.wide` is true, ranges larger than WIDE_CUTOFF are bucketed by two bytes at the top level.
+    #[cfg(test)]
+    pub fn sort<I: SuffixIndex>(
+        text: &'a T,
+        mut suffixes: Vec<I>,
+        config: MsdRadixSort,
+        level: usize,
+    ) -> Vec<I> {
+        let ws = &mut SortWorkspace::default();
+        Self::sort_slice(text, &mut suffixes, config, level, ws);
+        suffixes
+    }
+
+    pub fn sort_slice<I: SuffixIndex>(
+        text: &'a T,
+        suffixes: &mut [I],
+        config: MsdRadixSort,
+        level: usize,
+        ws: &mut SortWorkspace<I>,
+    ) {
+        let sorter = Self::new(text, config);
+        if !sorter.wide_bucket_sort(suffixes, level, ws) {
+            sorter.sort_range(suffixes, level, ws);
+            return;
+        }
+        let mut rest = suffixes;
+        while !rest.is_empty() {
+            let len = sorter.wide_bucket_len(rest, level);
+            let (bucket, tail) = std::mem::take(&mut rest).split_at_mut(len);
+            sorter.sort_range(bucket, level + 2, ws);
+            rest = tail;
+        }
+    }
+
+    // Buckets larger than PARALLEL_CUTOFF are recursively sorted in parallel,
+    // where each task has its own workspace.
+    #[cfg(feature = "rayon")]
+    pub fn sort_parallel<I: SuffixIndex>(
+        text: &'a T,
+        suffixes: &mut [I],
+        config: MsdRadixSort,
+        level: usize,
+        ws: &mut SortWorkspace<I>,
+    ) {
+        let sorter = Self::new(text, config);
+        if !sorter.wide_bucket_sort(suffixes, level, ws) {
+            sorter.sort_range_parallel(suffixes, level, ws);
+            return;
+        }
+        rayon::scope(|s| {
+            let sorter = &sorter;
+            let mut rest = suffixes;
+            while !rest.is_empty() {
+                let len = sorter.wide_bucket_len(rest, level);
+                let (bucket, tail) = std::mem::take(&mut rest).split_at_mut(len);
+                s.spawn(move |_| {
+                    let ws = &mut SortWorkspace::default();
+                    sorter.sort_range_parallel(bucket, level + 2, ws);
+                });
+                rest = tail;
+            }
+        });
+    }
+
+    fn new(text: &'a T, config: MsdRadixSort) -> Self {
+        let threshold = config.threshold.max(1);
+        let multikey = config
+            .multikey
+            .then_some(MultikeyQuicksort::default().threshold);
+        Self {
             text,
-            suffixes,
             threshold,
-        };
-        sorter.sort_range(0, n_suffixes, 0);
-        sorter.suffixes
+            wide: config.wide,
+            multikey,
+        }
     }
 
-    fn sort_range(&mut self, start: usize, end: usize, level: usize) {
-        if end - start <= self.threshold {
-            // Sorts small ranges with comparison sort.
-            self.suffixes[start..end].sort_unstable_by(|&a, &b| {
-                self.text[a..].cmp(&self.text[b..]).then_with(|| a.cmp(&b))
-            });
+    fn sort_range<I: SuffixIndex>(
+        &self,
+        suffixes: &mut [I],
+        level: usize,
+        ws: &mut SortWorkspace<I>,
+    ) {
+        if suffixes.len() <= self.threshold {
+            self.comparison_sort(suffixes, level);
             return;
         }
+        self.bucket_sort(suffixes, level, ws);
 
-        {
-            // Counts occurrences at this level.
-            let mut counts = vec![0; 256];
-            for i in start..end {
-                let c = self.text[self.suffixes[i] + level];
-                counts[c as usize] += 1;
+        // Recursively sort each bucket.
+        let mut rest = suffixes;
+        while !rest.is_empty() {
+            let len = self.bucket_len(rest, level);
+            let (bucket, tail) = std::mem::take(&mut rest).split_at_mut(len);
+            if !self.is_ended(bucket, level) {
+                self.sort_range(bucket, level + 1, ws);
             }
+            rest = tail;
+        }
+    }
 
-            // Computes cumulative sums
-            for i in 1..256 {
-                counts[i] += counts[i - 1];
-            }
+    #[cfg(feature = "rayon")]
+    fn sort_range_parallel<I: SuffixIndex>(
+        &self,
+        suffixes: &mut [I],
+        level: usize,
+        ws: &mut SortWorkspace<I>,
+    ) {
+        if suffixes.len() <= self.threshold.max(PARALLEL_CUTOFF) {
+            self.sort_range(suffixes, level, ws);
+            return;
+        }
+        self.bucket_sort(suffixes, level, ws);
 
-            // Bucket sort.
-            let mut sorted = vec![0; end - start];
-            for i in (start..end).rev() {
-                let c = self.text[self.suffixes[i] + level];
-                counts[c as usize] -= 1;
-                sorted[counts[c as usize]] = self.suffixes[i];
+        // Recursively sort each bucket in parallel.
+        rayon::scope(|s| {
+            let mut rest = suffixes;
+            while !rest.is_empty() {
+                let len = self.bucket_len(rest, level);
+                let (bucket, tail) = std::mem::take(&mut rest).split_at_mut(len);
+                if !self.is_ended(bucket, level) {
+                    s.spawn(move |_| {
+                        let ws = &mut SortWorkspace::default();
+                        self.sort_range_parallel(bucket, level + 1, ws);
+                    });
+                }
+                rest = tail;
             }
+        });
+    }
 
-            self.suffixes[start..end].copy_from_slice(&sorted[..]);
+    // Sorts small ranges with comparison sort, or multikey quicksort if enabled.
+    // The suffixes share the first `level` bytes, so they are compared from there.
+    // A suffix of length `level` is compared as the empty suffix at the end of the text.
+    fn comparison_sort<I: SuffixIndex>(&self, suffixes: &mut [I], level: usize) {
+        if let Some(threshold) = self.multikey {
+            multikey_quicksort(self.text, suffixes, level, threshold);
+            return;
         }
+        suffixes.sort_unstable_by(|&a, &b| {
+            self.text
+                .cmp_suffixes(a.to_usize() + level, b.to_usize() + level)
+                .then_with(|| a.cmp(&b))
+        });
+    }
 
-        // Recursively sort each bucket.
-        let mut i = start;
-        while i < end {
-            let c = self.text[self.suffixes[i] + level];
-            let mut j = i + 1;
-            while j < end && self.text[self.suffixes[j] + level] == c {
-                j += 1;
-            }
-            self.sort_range(i, j, level + 1);
-            i = j;
+    // Returns the bucket of the suffix at the level, where zero is for the suffix ending before it.
+    // Distinct suffixes of a bucket cannot end at the same level, so the bucket of zero is never split.
+    fn bucket<I: SuffixIndex>(&self, s: I, level: usize) -> usize {
+        let pos = s.to_usize() + level;
+        if pos < self.text.len() {
+            self.text.get(pos) as usize + 1
+        } else {
+            0
+        }
+    }
+
+    // Returns the bucket of the suffix by the two characters at the level and the next one.
+    // The suffix ending before the level or after one character is alone in its bucket.
+    fn wide_bucket<I: SuffixIndex>(&self, s: I, level: usize) -> usize {
+        match self.bucket(s, level) {
+            0 => 0,
+            b => 1 + (b - 1) * 257 + self.bucket(s, level + 1),
+        }
+    }
+
+    // Sorts suffixes by their characters at the level.
+    fn bucket_sort<I: SuffixIndex>(
+        &self,
+        suffixes: &mut [I],
+        level: usize,
+        ws: &mut SortWorkspace<I>,
+    ) {
+        Self::counting_sort(suffixes, 257, |s| self.bucket(s, level), ws);
+    }
+
+    // Sorts suffixes by their two characters at the level if the range is large enough,
+    // shrinking the recursion depth, and returns whether they are sorted.
+    fn wide_bucket_sort<I: SuffixIndex>(
+        &self,
+        suffixes: &mut [I],
+        level: usize,
+        ws: &mut SortWorkspace<I>,
+    ) -> bool {
+        if !self.wide || suffixes.len() < WIDE_CUTOFF.max(self.threshold + 1) {
+            return false;
+        }
+        Self::counting_sort(suffixes, WIDE_BUCKETS, |s| self.wide_bucket(s, level), ws);
+        true
+    }
+
+    fn counting_sort<I: SuffixIndex, F: Fn(I) -> usize>(
+        suffixes: &mut [I],
+        n_buckets: usize,
+        bucket: F,
+        ws: &mut SortWorkspace<I>,
+    ) {
+        // Counts occurrences at this level.
+        let counts = &mut ws.counts;
+        counts.clear();
+        counts.resize(n_buckets, 0);
+        for &s in suffixes.iter() {
+            counts[bucket(s)] += 1;
+        }
+
+        // Computes cumulative sums
+        for i in 1..n_buckets {
+            counts[i] += counts[i - 1];
+        }
+
+        // Bucket sort from a copy of the suffixes.
+        ws.scratch.clear();
+        ws.scratch.extend_from_slice(suffixes);
+        for &s in ws.scratch.iter().rev() {
+            let b = bucket(s);
+            counts[b] -= 1;
+            suffixes[counts[b]] = s;
         }
     }
+
+    // Returns whether the suffixes of a bucket end before the level,
+    // where they are duplicates of one position and need no more sorting.
+    fn is_ended<I: SuffixIndex>(&self, bucket: &[I], level: usize) -> bool {
+        self.bucket(bucket[0], level) == 0
+    }
+
+    // Returns the length of the first bucket of suffixes sharing the character at the level.
+    fn bucket_len<I: SuffixIndex>(&self, suffixes: &[I], level: usize) -> usize {
+        let b = self.bucket(suffixes[0], level);
+        suffixes
+            .iter()
+            .position(|&s| self.bucket(s, level) != b)
+            .unwrap_or(suffixes.len())
+    }
+
+    // Returns the length of the first bucket of suffixes sharing the two characters at the level.
+    fn wide_bucket_len<I: SuffixIndex>(&self, suffixes: &[I], level: usize) -> usize {
+        let b = self.wide_bucket(suffixes[0], level);
+        suffixes
+            .iter()
+            .position(|&s| self.wide_bucket(s, level) != b)
+            .unwrap_or(suffixes.len())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::index::U40;
 
     #[test]
     fn test_msd_radix_sorter_1() {
-        let text = b"abracadabra$";
-        let suffixes = (0..text.len()).collect();
-        let suffixes = MsdRadixSorter::sort(text, suffixes, 1);
+        let text: &[u8] = b"abracadabra$";
+        let suffixes: Vec<usize> = (0..text.len()).collect();
+        let suffixes = MsdRadixSorter::sort(text, suffixes, MsdRadixSort::new(1), 0);
         assert_eq!(suffixes, vec![11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
     }
 
     #[test]
     fn test_msd_radix_sorter_2() {
-        let text = b"abracadabra$";
-        let suffixes = (0..text.len()).collect();
-        let suffixes = MsdRadixSorter::sort(text, suffixes, 2);
+        let text: &[u8] = b"abracadabra$";
+        let suffixes: Vec<usize> = (0..text.len()).collect();
+        let suffixes = MsdRadixSorter::sort(text, suffixes, MsdRadixSort::new(2), 0);
         assert_eq!(suffixes, vec![11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
     }
 
     #[test]
     fn test_msd_radix_sorter_4() {
-        let text = b"abracadabra$";
-        let suffixes = (0..text.len()).collect();
-        let suffixes = MsdRadixSorter::sort(text, suffixes, 4);
+        let text: &[u8] = b"abracadabra$";
+        let suffixes: Vec<usize> = (0..text.len()).collect();
+        let suffixes = MsdRadixSorter::sort(text, suffixes, MsdRadixSort::new(4), 0);
         assert_eq!(suffixes, vec![11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
     }
 
     #[test]
     fn test_msd_radix_sorter_part_1() {
-        let text = b"abracadabra$";
-        let suffixes = vec![1, 3, 4, 7, 10];
-        let suffixes = MsdRadixSorter::sort(text, suffixes, 1);
+        let text: &[u8] = b"abracadabra$";
+        let suffixes: Vec<usize> = vec![1, 3, 4, 7, 10];
+        let suffixes = MsdRadixSorter::sort(text, suffixes, MsdRadixSort::new(1), 0);
         assert_eq!(suffixes, vec![10, 7, 3, 1, 4]);
     }
 
+    #[test]
+    fn test_msd_radix_sorter_duplicates() {
+        let text: &[u8] = b"abracadabra";
+        let suffixes: Vec<usize> = vec![10, 3, 10, 0, 7, 3, 10];
+        let expected = vec![10, 10, 10, 7, 0, 3, 3];
+        for config in [MsdRadixSort::new(1), MsdRadixSort::new(1).wide_radix(true)] {
+            let sorted = MsdRadixSorter::sort(text, suffixes.clone(), config, 0);
+            assert_eq!(sorted, expected);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_msd_radix_sorter_parallel() {
+        let text: Vec<u8> = (0..100000u32)
+            .map(|i| b"ab"[(i.count_ones() % 2) as usize])
+            .chain([b'$'])
+            .collect();
+        let suffixes: Vec<usize> = (0..text.len()).collect();
+        let expected = MsdRadixSorter::sort(&text[..], suffixes.clone(), MsdRadixSort::new(256), 0);
+        let mut suffixes = suffixes;
+        let ws = &mut SortWorkspace::default();
+        MsdRadixSorter::sort_parallel(&text[..], &mut suffixes, MsdRadixSort::new(256), 0, ws);
+        assert_eq!(suffixes, expected);
+    }
+
+    #[test]
+    fn test_msd_radix_sorter_u32() {
+        let text: &[u8] = b"abracadabra$";
+        let suffixes: Vec<u32> = (0..text.len() as u32).collect();
+        let suffixes = MsdRadixSorter::sort(text, suffixes, MsdRadixSort::new(2), 0);
+        assert_eq!(suffixes, vec![11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
+    }
+
+    #[test]
+    fn test_msd_radix_sorter_u40() {
+        let text: &[u8] = b"abracadabra$";
+        let suffixes: Vec<U40> = (0..text.len()).map(U40::from_usize).collect();
+        let suffixes = MsdRadixSorter::sort(text, suffixes, MsdRadixSort::new(2), 0);
+        let suffixes: Vec<usize> = suffixes.into_iter().map(U40::to_usize).collect();
+        assert_eq!(suffixes, vec![11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
+    }
+
+    #[test]
+    fn test_msd_radix_sorter_no_terminator() {
+        let text: &[u8] = b"abaababaab";
+        let suffixes: Vec<usize> = (0..text.len()).collect();
+        let suffixes = MsdRadixSorter::sort(text, suffixes, MsdRadixSort::new(1), 0);
+        let mut expected: Vec<usize> = (0..text.len()).collect();
+        expected.sort_by_key(|&i| &text[i..]);
+        assert_eq!(suffixes, expected);
+    }
+
+    #[test]
+    fn test_msd_radix_sorter_deep_buckets() {
+        // Copies of a block with a mutation make buckets of near-identical suffixes.
+        let block: Vec<u8> = (0..200u32)
+            .map(|i| b"acgt"[(i.wrapping_mul(2654435761) >> 30) as usize])
+            .collect();
+        let mut text = vec![];
+        for k in 0..8 {
+            let mut copy = block.clone();
+            copy[(k * 37) % 200] = b'n';
+            text.extend_from_slice(&copy);
+        }
+        text.push(b'$');
+        let suffixes: Vec<usize> = (0..text.len()).collect();
+        let mut expected = suffixes.clone();
+        expected.sort_by_key(|&i| &text[i..]);
+        for threshold in [1, 2, 4, 16, 256] {
+            for multikey in [false, true] {
+                let config = MsdRadixSort::new(threshold).multikey_fallback(multikey);
+                let sorted = MsdRadixSorter::sort(&text[..], suffixes.clone(), config, 0);
+                assert_eq!(sorted, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_msd_radix_sorter_wide() {
+        let text: Vec<u8> = (0..100000u32)
+            .map(|i| b"acgt"[(i.wrapping_mul(2654435761) >> 30) as usize])
+            .chain(*b"a$")
+            .collect();
+        let suffixes: Vec<usize> = (0..text.len()).collect();
+        let expected = MsdRadixSorter::sort(&text[..], suffixes.clone(), MsdRadixSort::new(256), 0);
+        let sorted = MsdRadixSorter::sort(
+            &text[..],
+            suffixes.clone(),
+            MsdRadixSort::new(256).wide_radix(true),
+            0,
+        );
+        assert_eq!(sorted, expected);
+        #[cfg(feature = "rayon")]
+        {
+            let mut sorted = suffixes;
+            let config = MsdRadixSort::new(256).wide_radix(true);
+            let ws = &mut SortWorkspace::default();
+            MsdRadixSorter::sort_parallel(&text[..], &mut sorted, config, 0, ws);
+            assert_eq!(sorted, expected);
+        }
+    }
+
+    #[test]
+    fn test_msd_radix_sorter_workspace_reuse() {
+        let text: &[u8] = b"abracadabra$";
+        let ws = &mut SortWorkspace::default();
+        let config = MsdRadixSort::new(1);
+        let mut suffixes: Vec<usize> = (0..text.len()).collect();
+        MsdRadixSorter::sort_slice(text, &mut suffixes, config, 0, ws);
+        assert_eq!(suffixes, vec![11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]);
+        let mut suffixes: Vec<usize> = vec![1, 3, 4, 7, 10];
+        MsdRadixSorter::sort_slice(text, &mut suffixes, config, 0, ws);
+        assert_eq!(suffixes, vec![10, 7, 3, 1, 4]);
+    }
+
+    #[test]
+    fn test_msd_radix_sorter_from_level() {
+        let text: &[u8] = b"abracadabra$";
+        // The suffixes starting with "ab" or "ac".
+        let suffixes: Vec<usize> = vec![3, 0, 7];
+        for threshold in [1, 256] {
+            let sorted =
+                MsdRadixSorter::sort(text, suffixes.clone(), MsdRadixSort::new(threshold), 1);
+            assert_eq!(sorted, vec![7, 0, 3]);
+        }
+    }
+
     #[test]
     fn test_msd_radix_sorter_part_2() {
-        let text = b"abracadabra$";
-        let suffixes = vec![1, 3, 4, 7, 10];
-        let suffixes = MsdRadixSorter::sort(text, suffixes, 2);
+        let text: &[u8] = b"abracadabra$";
+        let suffixes: Vec<usize> = vec![1, 3, 4, 7, 10];
+        let suffixes = MsdRadixSorter::sort(text, suffixes, MsdRadixSort::new(2), 0);
         assert_eq!(suffixes, vec![10, 7, 3, 1, 4]);
     }
 }
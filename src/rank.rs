@@ -0,0 +1,163 @@
+/// Sampling interval of the occurrence counts.
+const SAMPLE_INTERVAL: usize = 256;
+
+/// Rank support over a byte sequence such as the BWT,
+/// storing cumulative counts of symbols and sampled occurrence counts.
+///
+/// The sequence itself is not stored and must be given to [`OccTable::rank`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OccTable {
+    // Index of each symbol in the effective alphabet, or usize::MAX if it does not appear.
+    codes: Vec<usize>,
+    // c[s] is the number of symbols smaller than s.
+    c: Vec<usize>,
+    // samples[b * sigma + k] is the number of k-th symbols in bwt[..b * SAMPLE_INTERVAL].
+    samples: Vec<usize>,
+    sigma: usize,
+}
+
+impl OccTable {
+    pub fn new(bwt: &[u8]) -> Self {
+        let mut counts = vec![0; 256];
+        for &c in bwt {
+            counts[c as usize] += 1;
+        }
+        let mut codes = vec![usize::MAX; 256];
+        let mut sigma = 0;
+        for (s, &count) in counts.iter().enumerate() {
+            if count != 0 {
+                codes[s] = sigma;
+                sigma += 1;
+            }
+        }
+        let mut c = vec![0; 257];
+        for s in 0..256 {
+            c[s + 1] = c[s] + counts[s];
+        }
+        let mut samples = Vec::with_capacity((bwt.len() / SAMPLE_INTERVAL + 1) * sigma);
+        let mut occ = vec![0; sigma];
+        for (i, &s) in bwt.iter().enumerate() {
+            if i % SAMPLE_INTERVAL == 0 {
+                samples.extend_from_slice(&occ);
+            }
+            occ[codes[s as usize]] += 1;
+        }
+        if bwt.len() % SAMPLE_INTERVAL == 0 {
+            samples.extend_from_slice(&occ);
+        }
+        Self {
+            codes,
+            c,
+            samples,
+            sigma,
+        }
+    }
+
+    /// Returns the number of symbols smaller than `s`.
+    pub fn c(&self, s: u8) -> usize {
+        self.c[s as usize]
+    }
+
+    /// Returns the number of occurrences of `s` in `bwt[..i]`,
+    /// where `bwt` must be the sequence given to [`OccTable::new`].
+    pub fn rank(&self, bwt: &[u8], s: u8, i: usize) -> usize {
+        let code = self.codes[s as usize];
+        if code == usize::MAX {
+            return 0;
+        }
+        let b = i / SAMPLE_INTERVAL;
+        let base = b * SAMPLE_INTERVAL;
+        let sampled = self.samples[b * self.sigma + code];
+        sampled + bwt[base..i].iter().filter(|&&t| t == s).count()
+    }
+
+    /// Returns the number of symbols smaller than `s` in `bwt[..i]`,
+    /// where `bwt` must be the sequence given to [`OccTable::new`].
+    pub fn rank_less(&self, bwt: &[u8], s: u8, i: usize) -> usize {
+        // The number of symbols appearing in bwt that are smaller than s.
+        let k = self.codes[..s as usize]
+            .iter()
+            .filter(|&&code| code != usize::MAX)
+            .count();
+        let b = i / SAMPLE_INTERVAL;
+        let base = b * SAMPLE_INTERVAL;
+        let sampled: usize = self.samples[b * self.sigma..b * self.sigma + k]
+            .iter()
+            .sum();
+        sampled + bwt[base..i].iter().filter(|&&t| t < s).count()
+    }
+
+    /// Returns the row of the suffix preceding the suffix at row `r`, i.e., the LF mapping,
+    /// where `bwt` must be the sequence given to [`OccTable::new`].
+    pub fn lf(&self, bwt: &[u8], r: usize) -> usize {
+        let s = bwt[r];
+        self.c(s) + self.rank(bwt, s, r)
+    }
+
+    /// Returns the range of rows whose suffixes start with `s` followed by the suffixes at rows `lo..hi`,
+    /// where `bwt` must be the sequence given to [`OccTable::new`].
+    /// The range is clamped to `0..bwt.len()`, so it is empty if `lo..hi` is.
+    pub fn backward_step(&self, bwt: &[u8], lo: usize, hi: usize, s: u8) -> (usize, usize) {
+        let hi = hi.min(bwt.len());
+        let lo = lo.min(hi);
+        let lo = self.c(s) + self.rank(bwt, s, lo);
+        let hi = self.c(s) + self.rank(bwt, s, hi);
+        if lo < hi {
+            (lo, hi)
+        } else {
+            (lo, lo)
+        }
+    }
+
+    /// Returns the first symbol of the suffix at row `r`,
+    /// i.e., the symbol `s` such that `c(s) <= r < c(s + 1)`.
+    pub fn first_symbol(&self, r: usize) -> u8 {
+        (self.c[1..].partition_point(|&c| c <= r)) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_occ_table() {
+        let bwt = b"ard$rcaaaabb";
+        let occ = OccTable::new(bwt);
+        for s in 0..=255u8 {
+            for i in 0..=bwt.len() {
+                let expected = bwt[..i].iter().filter(|&&t| t == s).count();
+                assert_eq!(occ.rank(bwt, s, i), expected);
+            }
+        }
+        assert_eq!(occ.c(b'$'), 0);
+        assert_eq!(occ.c(b'a'), 1);
+        assert_eq!(occ.c(b'b'), 6);
+        let first = b"$aaaaabbcdrr";
+        for (r, &s) in first.iter().enumerate() {
+            assert_eq!(occ.first_symbol(r), s);
+        }
+        for s in 0..=255u8 {
+            for i in 0..=bwt.len() {
+                let expected = bwt[..i].iter().filter(|&&t| t < s).count();
+                assert_eq!(occ.rank_less(bwt, s, i), expected);
+            }
+        }
+        assert_eq!(occ.lf(bwt, 0), 1);
+        assert_eq!(occ.lf(bwt, 3), 0);
+    }
+
+    #[test]
+    fn test_occ_table_long() {
+        let bwt: Vec<u8> = (0..1000).map(|i| (i * 7 % 5) as u8).collect();
+        let occ = OccTable::new(&bwt);
+        for s in 0..5u8 {
+            for i in [0, 1, 255, 256, 257, 511, 512, 999, 1000] {
+                let expected = bwt[..i].iter().filter(|&&t| t == s).count();
+                assert_eq!(occ.rank(&bwt, s, i), expected);
+                let expected = bwt[..i].iter().filter(|&&t| t < s).count();
+                assert_eq!(occ.rank_less(&bwt, s, i), expected);
+            }
+        }
+    }
+}
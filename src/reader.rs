@@ -0,0 +1,242 @@
+use std::io::Read;
+
+use crate::error::Result;
+use crate::index::{SuffixIndex, U40};
+use crate::{
+    for_each_preceding_byte, gather_chunk, sais, shared_prefix_len, sort_suffixes, ChunkOptions,
+    Progress, SortBuffers,
+};
+
+/// Reader computing the BWT on demand as it is read, created by
+/// [`BwtBuilder::reader`](crate::BwtBuilder::reader).
+///
+/// The cuts are generated when the reader is created,
+/// and the chunk of each cut is gathered and sorted when the previous one has been read,
+/// so at most one chunk is held in memory.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::io::Read;
+///
+/// use small_bwt::BwtBuilder;
+///
+/// let builder = BwtBuilder::new(b"abracadabra$")?.chunk_size(3)?;
+/// let mut rdr = builder.reader()?;
+/// let mut bwt = vec![];
+/// rdr.read_to_end(&mut bwt)?;
+/// assert_eq!(bwt, b"ard$rcaaaabb");
+/// assert_eq!(rdr.primary_index(), Some(3));
+/// # Ok(())
+/// # }
+/// ```
+pub struct BwtReader<'b> {
+    chunks: Box<dyn ChunkSource + 'b>,
+    // The bytes of the current chunk and the number of them already read.
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<'b> BwtReader<'b> {
+    /// Creates a reader of the BWT of a byte text of symbols of `opts.stride` bytes from the cuts,
+    /// storing suffix positions in the smallest index type as in `bwt_from_cuts`.
+    pub(crate) fn new(
+        text: &'b [u8],
+        cuts: Vec<Vec<u8>>,
+        opts: ChunkOptions<'b>,
+        progress: Progress<'b>,
+    ) -> Self {
+        let max_pos = text.len() - usize::from(opts.sentinel.is_none());
+        let chunks: Box<dyn ChunkSource + 'b> = if max_pos <= u32::MAX_POS {
+            Box::new(ChunkCursor::<u32>::new(text, cuts, opts, progress))
+        } else if max_pos <= U40::MAX_POS {
+            Box::new(ChunkCursor::<U40>::new(text, cuts, opts, progress))
+        } else {
+            Box::new(ChunkCursor::<usize>::new(text, cuts, opts, progress))
+        };
+        Self {
+            chunks,
+            buf: vec![],
+            pos: 0,
+        }
+    }
+
+    /// Returns the primary index, i.e., the position of the last symbol of the text in the BWT,
+    /// or `None` if it has not been read yet.
+    pub fn primary_index(&self) -> Option<usize> {
+        self.chunks.primary_index()
+    }
+}
+
+impl Read for BwtReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // A chunk may be empty.
+        while self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+            if !self.chunks.next_chunk(&mut self.buf)? {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.buf.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Source of the BWT chunk by chunk, hiding the index type of the suffix positions.
+trait ChunkSource {
+    /// Appends the BWT of the next chunk to `bwt`, returning `false` if all the chunks have been read.
+    fn next_chunk(&mut self, bwt: &mut Vec<u8>) -> Result<bool>;
+
+    fn primary_index(&self) -> Option<usize>;
+}
+
+/// Gathers and sorts the chunks one by one, reusing the buffers across the cuts.
+struct ChunkCursor<'b, I> {
+    text: &'b [u8],
+    cuts: Vec<Vec<u8>>,
+    opts: ChunkOptions<'b>,
+    progress: Progress<'b>,
+    // The index of the cut of the next chunk, starting from 1.
+    q: usize,
+    // The rank of the first suffix of the next chunk.
+    offset: usize,
+    primary: Option<usize>,
+    chunk: Vec<I>,
+    buffers: SortBuffers<I>,
+}
+
+impl<'b, I: SuffixIndex> ChunkCursor<'b, I> {
+    fn new(
+        text: &'b [u8],
+        cuts: Vec<Vec<u8>>,
+        opts: ChunkOptions<'b>,
+        progress: Progress<'b>,
+    ) -> Self {
+        assert!(cuts[0].is_empty());
+        Self {
+            text,
+            cuts,
+            opts,
+            progress,
+            q: 1,
+            offset: 0,
+            primary: None,
+            chunk: vec![],
+            buffers: SortBuffers::default(),
+        }
+    }
+}
+
+impl<I: SuffixIndex> ChunkSource for ChunkCursor<'_, I> {
+    fn next_chunk(&mut self, bwt: &mut Vec<u8>) -> Result<bool> {
+        if self.q > self.cuts.len() {
+            return Ok(false);
+        }
+        self.opts.check_cancelled()?;
+        let (text, stride) = (self.text, self.opts.stride);
+        if self.opts.sais {
+            // The only chunk has all the suffixes.
+            self.progress.print("Generating BWT by SA-IS...");
+            self.chunk.clear();
+            self.chunk.extend(
+                sais::suffix_array(text)
+                    .into_iter()
+                    .filter(|&j| j % stride == 0)
+                    .map(I::from_usize),
+            );
+        } else {
+            gather_chunk(
+                text,
+                &self.cuts,
+                self.q,
+                stride,
+                &self.progress,
+                &mut self.chunk,
+            );
+            let depth = shared_prefix_len(&self.cuts, self.q);
+            sort_suffixes(
+                text,
+                &mut self.chunk,
+                depth,
+                false,
+                self.opts,
+                &mut self.buffers,
+            );
+        }
+        if self.offset == 0 && self.opts.sentinel.is_some() {
+            // The empty suffix before the sentinel is the smallest one.
+            self.chunk.insert(0, I::from_usize(text.len()));
+        }
+        bwt.reserve(self.chunk.len() * stride);
+        for (i, &j) in self.chunk.iter().enumerate() {
+            let j = j.to_usize();
+            if j == 0 {
+                self.primary = Some(self.offset + i);
+            }
+            for_each_preceding_byte(text, j, stride, self.opts.sentinel, |c| {
+                bwt.push(c);
+                Ok(())
+            })?;
+        }
+        self.offset += self.chunk.len();
+        self.q += 1;
+        Ok(true)
+    }
+
+    fn primary_index(&self) -> Option<usize> {
+        self.primary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_cuts;
+
+    fn new_reader<'b>(text: &'b [u8], chunk_size: usize, opts: ChunkOptions<'b>) -> BwtReader<'b> {
+        let progress = Progress::new(false);
+        let (cuts, opts) = generate_cuts(text, chunk_size, 2, opts, &progress);
+        BwtReader::new(text, cuts, opts, progress)
+    }
+
+    fn read_all(rdr: &mut BwtReader, buf_len: usize) -> Vec<u8> {
+        let mut bwt = vec![];
+        let mut buf = vec![0; buf_len];
+        loop {
+            let len = rdr.read(&mut buf).unwrap();
+            if len == 0 {
+                return bwt;
+            }
+            bwt.extend_from_slice(&buf[..len]);
+        }
+    }
+
+    #[test]
+    fn test_bwt_reader() {
+        let text = b"abracadabra$";
+        for chunk_size in [1, 3, 100] {
+            for buf_len in [1, 5, 100] {
+                let opts = ChunkOptions::default();
+                let mut rdr = new_reader(text, chunk_size, opts);
+                assert_eq!(rdr.primary_index(), None);
+                assert_eq!(read_all(&mut rdr, buf_len), b"ard$rcaaaabb");
+                assert_eq!(rdr.primary_index(), Some(3));
+            }
+        }
+    }
+
+    #[test]
+    fn test_bwt_reader_sentinel() {
+        let opts = ChunkOptions {
+            sentinel: Some(b'$'),
+            ..Default::default()
+        };
+        let mut rdr = new_reader(b"abracadabra", 3, opts);
+        assert_eq!(read_all(&mut rdr, 4), b"ard$rcaaaabb");
+        assert_eq!(rdr.primary_index(), Some(3));
+    }
+}
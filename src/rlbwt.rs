@@ -0,0 +1,157 @@
+use std::io::{Read, Write};
+
+use crate::error::{Error, Result};
+
+use crate::intio::{read_varint, write_varint};
+
+/// Writer adapter that run-length encodes a byte stream such as the BWT.
+///
+/// Each maximal run of a symbol is emitted as the symbol byte followed by
+/// the run length in the unsigned LEB128 encoding.
+/// The pending run is emitted only by [`RlbwtWriter::finish`],
+/// so it must be called after all bytes are written.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BwtBuilder, RlbwtReader, RlbwtWriter};
+///
+/// let text = "abracadabra$";
+/// let mut wrt = RlbwtWriter::new(vec![]);
+/// BwtBuilder::new(text.as_bytes())?.build(&mut wrt)?;
+/// let rlbwt = wrt.finish()?;
+///
+/// let runs = RlbwtReader::new(rlbwt.as_slice()).collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(runs[..4], [(b'a', 1), (b'r', 1), (b'd', 1), (b'$', 1)]);
+/// assert_eq!(runs[6..], [(b'a', 4), (b'b', 2)]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct RlbwtWriter<W: Write> {
+    wrt: W,
+    // The pending run as (symbol, length).
+    run: Option<(u8, usize)>,
+}
+
+impl<W: Write> RlbwtWriter<W> {
+    /// Creates a new writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the run-length encoded stream.
+    pub const fn new(wrt: W) -> Self {
+        Self { wrt, run: None }
+    }
+
+    /// Emits the pending run and returns the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the underlying writer returns an error.
+    pub fn finish(mut self) -> Result<W> {
+        if let Some((c, len)) = self.run.take() {
+            write_run(&mut self.wrt, c, len)?;
+        }
+        self.wrt.flush()?;
+        Ok(self.wrt)
+    }
+}
+
+impl<W: Write> Write for RlbwtWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &c in buf {
+            match self.run.as_mut() {
+                Some((d, len)) if *d == c => *len += 1,
+                _ => {
+                    if let Some((d, len)) = self.run.replace((c, 1)) {
+                        write_run(&mut self.wrt, d, len)?;
+                    }
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.wrt.flush()
+    }
+}
+
+fn write_run<W: Write>(wrt: &mut W, c: u8, len: usize) -> std::io::Result<()> {
+    wrt.write_all(&[c])?;
+    write_varint(wrt, len)
+}
+
+/// Iterator over the runs of a stream written by [`RlbwtWriter`].
+///
+/// Each item is a pair of the symbol and its run length.
+pub struct RlbwtReader<R: Read> {
+    rdr: R,
+}
+
+impl<R: Read> RlbwtReader<R> {
+    /// Creates a new reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `rdr` - The reader of the run-length encoded stream.
+    pub const fn new(rdr: R) -> Self {
+        Self { rdr }
+    }
+
+    fn read_run(&mut self) -> Result<Option<(u8, usize)>> {
+        let mut buf = [0; 1];
+        if self.rdr.read(&mut buf)? == 0 {
+            return Ok(None);
+        }
+        let c = buf[0];
+        let len = read_varint(&mut self.rdr)?;
+        if len == 0 {
+            return Err(Error::invalid_format("run length must be positive."));
+        }
+        Ok(Some((c, len)))
+    }
+}
+
+impl<R: Read> Iterator for RlbwtReader<R> {
+    type Item = Result<(u8, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_run().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rlbwt_roundtrip() {
+        let bwt = [&[b'a'; 3][..], &[b'b'; 130], b"c"].concat();
+        let mut wrt = RlbwtWriter::new(vec![]);
+        wrt.write_all(&bwt[..10]).unwrap();
+        wrt.write_all(&bwt[10..]).unwrap();
+        let rlbwt = wrt.finish().unwrap();
+        let runs = RlbwtReader::new(rlbwt.as_slice())
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(runs, vec![(b'a', 3), (b'b', 130), (b'c', 1)]);
+        assert_eq!(rlbwt, vec![b'a', 3, b'b', 0x82, 0x01, b'c', 1]);
+    }
+
+    #[test]
+    fn test_rlbwt_empty() {
+        let wrt = RlbwtWriter::new(vec![]);
+        let rlbwt = wrt.finish().unwrap();
+        assert!(rlbwt.is_empty());
+        assert_eq!(RlbwtReader::new(rlbwt.as_slice()).count(), 0);
+    }
+
+    #[test]
+    fn test_rlbwt_reader_truncated() {
+        let rlbwt = [b'a', 0x82];
+        let mut rdr = RlbwtReader::new(&rlbwt[..]);
+        assert!(rdr.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use std::io::{Read, Write};
+
+use crate::error::{Error, Result};
+
+/// Symbols of ropebwt2 in the order of their codes, where `$` is the sentinel of every sequence.
+const NT6_SYMBOLS: &[u8; 6] = b"$ACGTN";
+
+/// Maximum run length encoded in one run, i.e., in eight bytes.
+const MAX_RUN_LEN: usize = (1 << 43) - 1;
+
+/// Returns the ropebwt2 code of a byte of the BWT, mapping both `\0` and `$` to the sentinel.
+const fn nt6_code(c: u8) -> Option<u8> {
+    match c {
+        b'\0' | b'$' => Some(0),
+        b'A' | b'a' => Some(1),
+        b'C' | b'c' => Some(2),
+        b'G' | b'g' => Some(3),
+        b'T' | b't' => Some(4),
+        b'N' | b'n' => Some(5),
+        _ => None,
+    }
+}
+
+/// Writer adapter that encodes a nucleotide BWT in the run-length encoding of ropebwt2,
+/// used in its leaves and dumped BWT files.
+///
+/// Each symbol is mapped to a code in `$ACGTN`, where both `\0` and `$` are mapped to the sentinel `$`
+/// and the bases are case-insensitive.
+/// Each maximal run of a code `c` of length `l` is emitted in a UTF-8-like encoding:
+///
+/// - `l < 2^4`: one byte `l << 3 | c`,
+/// - `l < 2^8`: two bytes starting with `0b110`,
+/// - `l < 2^19`: four bytes starting with `0b1110`,
+/// - otherwise: eight bytes starting with `0b1111`, splitting runs longer than `2^43 - 1`,
+///
+/// where the following bytes hold six bits of `l` each after `0b10`.
+/// The pending run is emitted only by [`RopebwtRleWriter::finish`],
+/// so it must be called after all bytes are written.
+///
+/// Since all the sentinels are mapped to `$`, the BWT of a text with separators, such as the one
+/// from [`parse_fasta`](crate::parse_fasta), cannot be decoded after a round trip by this crate.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BwtBuilder, RopebwtRleReader, RopebwtRleWriter};
+///
+/// let text = "GATTACA$";
+/// let mut wrt = RopebwtRleWriter::new(vec![]);
+/// BwtBuilder::new(text.as_bytes())?.build(&mut wrt)?;
+/// let rle = wrt.finish()?;
+///
+/// let mut bwt = vec![];
+/// for run in RopebwtRleReader::new(rle.as_slice()) {
+///     let (c, len) = run?;
+///     bwt.resize(bwt.len() + len, c);
+/// }
+/// assert_eq!(bwt, b"ACTGA$TA");
+/// # Ok(())
+/// # }
+/// ```
+pub struct RopebwtRleWriter<W: Write> {
+    wrt: W,
+    // The pending run as (code, length).
+    run: Option<(u8, usize)>,
+    // The number of bytes written, to report the position of an invalid byte.
+    pos: usize,
+}
+
+impl<W: Write> RopebwtRleWriter<W> {
+    /// Creates a new writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrt` - The writer to write the run-length encoded stream.
+    pub const fn new(wrt: W) -> Self {
+        Self {
+            wrt,
+            run: None,
+            pos: 0,
+        }
+    }
+
+    /// Emits the pending run and returns the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the underlying writer returns an error.
+    pub fn finish(mut self) -> Result<W> {
+        if let Some((c, len)) = self.run.take() {
+            write_run(&mut self.wrt, c, len)?;
+        }
+        self.wrt.flush()?;
+        Ok(self.wrt)
+    }
+}
+
+impl<W: Write> Write for RopebwtRleWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            let c = nt6_code(byte).ok_or(Error::InvalidSymbol {
+                pos: self.pos,
+                byte,
+            })?;
+            self.pos += 1;
+            match self.run.as_mut() {
+                Some((d, len)) if *d == c && *len < MAX_RUN_LEN => *len += 1,
+                _ => {
+                    if let Some((d, len)) = self.run.replace((c, 1)) {
+                        write_run(&mut self.wrt, d, len)?;
+                    }
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.wrt.flush()
+    }
+}
+
+fn write_run<W: Write>(wrt: &mut W, c: u8, len: usize) -> std::io::Result<()> {
+    let c = c as u64;
+    let l = len as u64;
+    if l < 1 << 4 {
+        wrt.write_all(&[(l << 3 | c) as u8])
+    } else if l < 1 << 8 {
+        wrt.write_all(&[(0xc0 | l >> 6 << 3 | c) as u8, (0x80 | (l & 0x3f)) as u8])
+    } else {
+        let (head, n_tails) = if l < 1 << 19 { (0xe0, 3) } else { (0xf0, 7) };
+        let mut buf = [0; 8];
+        buf[0] = (head | l >> (6 * n_tails) << 3 | c) as u8;
+        for (i, b) in buf[1..=n_tails].iter_mut().enumerate() {
+            *b = (0x80 | (l >> (6 * (n_tails - 1 - i)) & 0x3f)) as u8;
+        }
+        wrt.write_all(&buf[..=n_tails])
+    }
+}
+
+/// Iterator over the runs of a stream in the run-length encoding of ropebwt2,
+/// such as the one written by [`RopebwtRleWriter`].
+///
+/// Each item is a pair of the symbol in `$ACGTN` and its run length.
+pub struct RopebwtRleReader<R: Read> {
+    rdr: R,
+}
+
+impl<R: Read> RopebwtRleReader<R> {
+    /// Creates a new reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `rdr` - The reader of the run-length encoded stream.
+    pub const fn new(rdr: R) -> Self {
+        Self { rdr }
+    }
+
+    fn read_run(&mut self) -> Result<Option<(u8, usize)>> {
+        let mut buf = [0; 1];
+        if self.rdr.read(&mut buf)? == 0 {
+            return Ok(None);
+        }
+        let head = buf[0];
+        let c = head & 7;
+        if c as usize >= NT6_SYMBOLS.len() {
+            return Err(Error::invalid_format(format!(
+                "symbol code must be less than {}, but got {c}.",
+                NT6_SYMBOLS.len()
+            )));
+        }
+        let (mut len, n_tails) = match head >> 4 {
+            0b0000..=0b0111 => ((head >> 3) as usize, 0),
+            0b1100 | 0b1101 => ((head >> 3 & 3) as usize, 1),
+            0b1110 => ((head >> 3 & 1) as usize, 3),
+            0b1111 => ((head >> 3 & 1) as usize, 7),
+            _ => {
+                return Err(Error::invalid_format(format!(
+                    "run must not start with a continuation byte {head:#04x}."
+                )))
+            }
+        };
+        for _ in 0..n_tails {
+            if self.rdr.read(&mut buf)? == 0 {
+                return Err(Error::invalid_format(
+                    "unexpected end of the stream in a run.",
+                ));
+            }
+            if buf[0] >> 6 != 0b10 {
+                return Err(Error::invalid_format(format!(
+                    "continuation byte must start with 0b10, but got {:#04x}.",
+                    buf[0]
+                )));
+            }
+            len = len << 6 | (buf[0] & 0x3f) as usize;
+        }
+        if len == 0 {
+            return Err(Error::invalid_format("run length must be positive."));
+        }
+        Ok(Some((NT6_SYMBOLS[c as usize], len)))
+    }
+}
+
+impl<R: Read> Iterator for RopebwtRleReader<R> {
+    type Item = Result<(u8, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_run().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs of C, A, $, G, and T with their encodings in ropebwt2.
+    const FIXTURE: &[u8] = &[
+        0x1a, // (C, 3)
+        0xc1, 0x94, // (A, 20)
+        0x08, // ($, 1)
+        0xe3, 0x80, 0x84, 0xac, // (G, 300)
+        0xf4, 0x80, 0x80, 0x81, 0x80, 0x80, 0x80, 0x80, // (T, 1 << 24)
+    ];
+
+    #[test]
+    fn test_ropebwt_rle_fixture() {
+        let runs = RopebwtRleReader::new(FIXTURE)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            runs,
+            vec![
+                (b'C', 3),
+                (b'A', 20),
+                (b'$', 1),
+                (b'G', 300),
+                (b'T', 1 << 24)
+            ]
+        );
+
+        let mut wrt = RopebwtRleWriter::new(vec![]);
+        for (c, len) in runs {
+            let run = if c == b'$' { vec![b'\0'] } else { vec![c; len] };
+            wrt.write_all(&run).unwrap();
+        }
+        assert_eq!(wrt.finish().unwrap(), FIXTURE);
+    }
+
+    #[test]
+    fn test_ropebwt_rle_roundtrip() {
+        let bwt = [&b"acgt"[..], &[b'N'; 200], b"$", &[b'T'; 70000], b"GG"].concat();
+        let mut wrt = RopebwtRleWriter::new(vec![]);
+        wrt.write_all(&bwt[..100]).unwrap();
+        wrt.write_all(&bwt[100..]).unwrap();
+        let rle = wrt.finish().unwrap();
+        let mut decoded = vec![];
+        for run in RopebwtRleReader::new(rle.as_slice()) {
+            let (c, len) = run.unwrap();
+            decoded.resize(decoded.len() + len, c);
+        }
+        assert_eq!(decoded, bwt.to_ascii_uppercase());
+    }
+
+    #[test]
+    fn test_ropebwt_rle_invalid() {
+        let mut wrt = RopebwtRleWriter::new(vec![]);
+        let e = wrt.write_all(b"ACX").unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(RopebwtRleReader::new(&[0x0f][..]).next().unwrap().is_err());
+        assert!(RopebwtRleReader::new(&[0xc1][..]).next().unwrap().is_err());
+        assert!(RopebwtRleReader::new(&[0x01][..]).next().unwrap().is_err());
+        assert!(RopebwtRleReader::new(&[0x94][..]).next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Suffix array construction by induced sorting (SA-IS) of Nong, Zhang, and Chan,
+//! used as an alternative backend when the memory is plentiful.
+
+/// Placeholder of an empty slot of the suffix array.
+const EMPTY: usize = usize::MAX;
+
+/// Computes the suffix array of `text` by SA-IS in `O(n)` time and `O(n)` words of space,
+/// as if the text were followed by a sentinel smaller than every symbol.
+pub fn suffix_array<T: crate::text::TextAccess + ?Sized>(text: &T) -> Vec<usize> {
+    let n = text.len();
+    let mut s = Vec::with_capacity(n + 1);
+    s.extend((0..n).map(|i| text.get(i) as usize + 1));
+    s.push(0);
+    let mut sa = sais(&s, 257);
+    // The first one is the sentinel.
+    sa.remove(0);
+    sa
+}
+
+/// Computes the suffix array of `s` whose symbols are less than `sigma`,
+/// where the last symbol is a unique smallest one.
+pub fn sais(s: &[usize], sigma: usize) -> Vec<usize> {
+    let n = s.len();
+    if n == 1 {
+        return vec![0];
+    }
+    // stype[i] is true if the suffix at i is smaller than the one at i + 1.
+    let mut stype = vec![false; n];
+    stype[n - 1] = true;
+    for i in (0..n - 1).rev() {
+        stype[i] = s[i] < s[i + 1] || (s[i] == s[i + 1] && stype[i + 1]);
+    }
+    let is_lms = |i: usize| i > 0 && stype[i] && !stype[i - 1];
+    let mut counts = vec![0; sigma];
+    for &c in s {
+        counts[c] += 1;
+    }
+
+    // Sorts the LMS substrings by inducing from the LMS suffixes at the bucket tails.
+    let mut sa = vec![EMPTY; n];
+    let mut tails = bucket_tails(&counts);
+    for i in (1..n).filter(|&i| is_lms(i)) {
+        tails[s[i]] -= 1;
+        sa[tails[s[i]]] = i;
+    }
+    induce(s, &mut sa, &stype, &counts);
+
+    // Names the LMS substrings in sorted order, storing the name of position p at sa[m + p / 2].
+    let mut m = 0;
+    for k in 0..n {
+        if is_lms(sa[k]) {
+            sa[m] = sa[k];
+            m += 1;
+        }
+    }
+    sa[m..].fill(EMPTY);
+    let mut n_names = 0;
+    let mut prev = EMPTY;
+    for k in 0..m {
+        let p = sa[k];
+        if prev == EMPTY || !lms_substrings_eq(s, &stype, prev, p) {
+            n_names += 1;
+        }
+        prev = p;
+        sa[m + p / 2] = n_names - 1;
+    }
+    let reduced: Vec<usize> = sa[m..].iter().copied().filter(|&x| x != EMPTY).collect();
+
+    // Sorts the LMS suffixes recursively unless their names are distinct.
+    let reduced_sa = if n_names < m {
+        sais(&reduced, n_names)
+    } else {
+        let mut reduced_sa = vec![0; m];
+        for (i, &x) in reduced.iter().enumerate() {
+            reduced_sa[x] = i;
+        }
+        reduced_sa
+    };
+
+    // Induces the suffix array from the sorted LMS suffixes.
+    let lms: Vec<usize> = (1..n).filter(|&i| is_lms(i)).collect();
+    sa.fill(EMPTY);
+    let mut tails = bucket_tails(&counts);
+    for &k in reduced_sa.iter().rev() {
+        let p = lms[k];
+        tails[s[p]] -= 1;
+        sa[tails[s[p]]] = p;
+    }
+    induce(s, &mut sa, &stype, &counts);
+    sa
+}
+
+/// Induces the L-type suffixes from left to right and then the S-type ones from right to left.
+fn induce(s: &[usize], sa: &mut [usize], stype: &[bool], counts: &[usize]) {
+    let mut heads = bucket_heads(counts);
+    for k in 0..sa.len() {
+        let p = sa[k];
+        if p != EMPTY && p > 0 && !stype[p - 1] {
+            sa[heads[s[p - 1]]] = p - 1;
+            heads[s[p - 1]] += 1;
+        }
+    }
+    let mut tails = bucket_tails(counts);
+    for k in (0..sa.len()).rev() {
+        let p = sa[k];
+        if p != EMPTY && p > 0 && stype[p - 1] {
+            tails[s[p - 1]] -= 1;
+            sa[tails[s[p - 1]]] = p - 1;
+        }
+    }
+}
+
+/// Checks if the LMS substrings starting at `a` and `b` are equal in their symbols and types.
+fn lms_substrings_eq(s: &[usize], stype: &[bool], a: usize, b: usize) -> bool {
+    let n = s.len();
+    // The sentinel is unique.
+    if a == n - 1 || b == n - 1 {
+        return a == b;
+    }
+    let is_lms = |i: usize| stype[i] && !stype[i - 1];
+    for i in 0.. {
+        let (x, y) = (a + i, b + i);
+        if s[x] != s[y] || stype[x] != stype[y] {
+            return false;
+        }
+        if i > 0 && (is_lms(x) || is_lms(y)) {
+            return is_lms(x) && is_lms(y);
+        }
+    }
+    unreachable!()
+}
+
+fn bucket_heads(counts: &[usize]) -> Vec<usize> {
+    let mut sum = 0;
+    counts
+        .iter()
+        .map(|&c| {
+            sum += c;
+            sum - c
+        })
+        .collect()
+}
+
+fn bucket_tails(counts: &[usize]) -> Vec<usize> {
+    let mut sum = 0;
+    counts
+        .iter()
+        .map(|&c| {
+            sum += c;
+            sum
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_suffix_array(text: &[u8]) -> Vec<usize> {
+        let mut sa: Vec<usize> = (0..text.len()).collect();
+        sa.sort_by(|&a, &b| text[a..].cmp(&text[b..]));
+        sa
+    }
+
+    #[test]
+    fn test_suffix_array() {
+        assert_eq!(
+            suffix_array(&b"abracadabra$"[..]),
+            [11, 10, 7, 0, 3, 5, 8, 1, 4, 6, 9, 2]
+        );
+        assert_eq!(suffix_array(&b"a"[..]), [0]);
+        assert_eq!(suffix_array(&b""[..]), Vec::<usize>::new());
+        for text in [
+            &b"aaaaaaaa"[..],
+            b"abababab",
+            b"mississippi",
+            b"\xff\x00\xff\x00",
+        ] {
+            assert_eq!(suffix_array(text), naive_suffix_array(text));
+        }
+    }
+
+    #[test]
+    fn test_suffix_array_random() {
+        let mut x = 12345u64;
+        for sigma in [2, 4, 256] {
+            for n in [10, 100, 1000] {
+                let text: Vec<u8> = (0..n)
+                    .map(|_| {
+                        x ^= x << 13;
+                        x ^= x >> 7;
+                        x ^= x << 17;
+                        (x % sigma) as u8
+                    })
+                    .collect();
+                assert_eq!(suffix_array(&text[..]), naive_suffix_array(&text));
+            }
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+
+use crate::error::{Error, Result};
+use crate::index::SuffixIndex;
+use crate::radixsort::{MsdRadixSorter, SortWorkspace};
+use crate::text::TextAccess;
+
+/// Read-only view of a text given to a [`SuffixSorter`].
+///
+/// A suffix that is a prefix of another one is smaller than it,
+/// as if the text were followed by a sentinel smaller than every byte.
+pub trait SuffixText: Sync {
+    /// Returns the length of the text in bytes.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if the text is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the byte at position `i`.
+    fn get(&self, i: usize) -> u8;
+
+    /// Compares the suffixes starting at `i` and `j`.
+    fn cmp_suffixes(&self, i: usize, j: usize) -> Ordering;
+
+    /// Returns the length of the longest common prefix of the suffixes starting at `i` and `j`.
+    fn lcp(&self, i: usize, j: usize) -> usize;
+}
+
+impl<T: TextAccess + ?Sized> SuffixText for T {
+    fn len(&self) -> usize {
+        TextAccess::len(self)
+    }
+
+    fn get(&self, i: usize) -> u8 {
+        TextAccess::get(self, i)
+    }
+
+    fn cmp_suffixes(&self, i: usize, j: usize) -> Ordering {
+        TextAccess::cmp_suffixes(self, i, j)
+    }
+
+    fn lcp(&self, i: usize, j: usize) -> usize {
+        TextAccess::lcp(self, i, j)
+    }
+}
+
+/// View of a possibly unsized text, such as `[u8]`, to be passed as `&dyn SuffixText`.
+pub struct TextView<'t, T: ?Sized>(pub &'t T);
+
+impl<T: TextAccess + ?Sized> SuffixText for TextView<'_, T> {
+    fn len(&self) -> usize {
+        TextAccess::len(self.0)
+    }
+
+    fn get(&self, i: usize) -> u8 {
+        TextAccess::get(self.0, i)
+    }
+
+    fn cmp_suffixes(&self, i: usize, j: usize) -> Ordering {
+        TextAccess::cmp_suffixes(self.0, i, j)
+    }
+
+    fn lcp(&self, i: usize, j: usize) -> usize {
+        TextAccess::lcp(self.0, i, j)
+    }
+}
+
+/// Strategy to sort the suffixes in each chunk,
+/// set by [`BwtBuilder::suffix_sorter`](crate::BwtBuilder::suffix_sorter).
+///
+/// It may be called from several threads when the construction is parallelized.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BwtBuilder, SuffixSorter, SuffixText};
+///
+/// // Sorts the suffixes by comparing them naively.
+/// struct NaiveSort;
+///
+/// impl SuffixSorter for NaiveSort {
+///     fn sort(&self, text: &dyn SuffixText, suffixes: &mut [usize]) {
+///         suffixes.sort_unstable_by(|&a, &b| text.cmp_suffixes(a, b));
+///     }
+/// }
+///
+/// let mut bwt = vec![];
+/// BwtBuilder::new(b"abracadabra$")?
+///     .suffix_sorter(&NaiveSort)
+///     .build(&mut bwt)?;
+/// assert_eq!(bwt, b"ard$rcaaaabb");
+/// # Ok(())
+/// # }
+/// ```
+pub trait SuffixSorter: Sync {
+    /// Sorts the starting positions of suffixes in increasing order of the suffixes.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    /// * `suffixes` - The distinct starting positions of suffixes, which are less than `text.len()`.
+    fn sort(&self, text: &dyn SuffixText, suffixes: &mut [usize]);
+
+    /// Sorts the starting positions of suffixes sharing the first `depth` bytes,
+    /// which is called for the suffixes between two cuts sharing a prefix of length `depth`.
+    ///
+    /// The default implementation ignores `depth` and calls [`Self::sort`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    /// * `suffixes` - The distinct starting positions of suffixes, which are less than `text.len()`.
+    /// * `depth` - The length of the prefix shared by the suffixes, which need not be compared.
+    fn sort_from_depth(&self, text: &dyn SuffixText, suffixes: &mut [usize], depth: usize) {
+        let _ = depth;
+        self.sort(text, suffixes);
+    }
+}
+
+/// MSD radix sort on the bytes of suffixes, falling back to comparison sort for small buckets.
+///
+/// This is the strategy used by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MsdRadixSort {
+    pub(crate) threshold: usize,
+    pub(crate) wide: bool,
+    pub(crate) multikey: bool,
+}
+
+impl MsdRadixSort {
+    /// Creates a new sorter.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The maximum size of buckets sorted by comparison sort.
+    pub const fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            wide: false,
+            multikey: false,
+        }
+    }
+
+    /// Sets whether to bucket large ranges by the first two bytes at the top level,
+    /// as in [`BwtBuilder::wide_radix`](crate::BwtBuilder::wide_radix).
+    ///
+    /// # Arguments
+    ///
+    /// * `wide` - Whether to use 16-bit radix at the top level.
+    pub const fn wide_radix(mut self, wide: bool) -> Self {
+        self.wide = wide;
+        self
+    }
+
+    /// Sets whether to sort the buckets of at most `threshold` suffixes by multikey quicksort
+    /// as in [`MultikeyQuicksort`], instead of comparison sort on whole suffixes.
+    ///
+    /// Multikey quicksort inspects each byte of the shared prefixes once,
+    /// which pays off for buckets of suffixes with long common prefixes.
+    ///
+    /// # Arguments
+    ///
+    /// * `multikey` - Whether to use multikey quicksort for small buckets.
+    pub const fn multikey_fallback(mut self, multikey: bool) -> Self {
+        self.multikey = multikey;
+        self
+    }
+
+    /// Sorts arbitrary starting positions of suffixes of a byte text in increasing order of the suffixes,
+    /// i.e., sparse suffix sorting.
+    ///
+    /// The text need not end with a terminator.
+    /// A suffix that is a prefix of another one is smaller than it,
+    /// as if the text were followed by a sentinel smaller than every byte.
+    /// Duplicate positions are allowed and kept next to each other.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    /// * `suffixes` - The starting positions of suffixes, which are sorted in place.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if a position is not less than the length of `text`,
+    /// in which case `suffixes` is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use small_bwt::MsdRadixSort;
+    ///
+    /// // The suffixes of "abracadabra" starting at even positions.
+    /// let mut suffixes = vec![0, 2, 4, 6, 8, 10];
+    /// MsdRadixSort::default().sort_sparse(b"abracadabra", &mut suffixes)?;
+    /// assert_eq!(suffixes, [10, 0, 8, 4, 6, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sort_sparse(&self, text: &[u8], suffixes: &mut [usize]) -> Result<()> {
+        if let Some(&pos) = suffixes.iter().find(|&&pos| pos >= text.len()) {
+            return Err(Error::invalid_argument(format!(
+                "suffix position must be less than the text length {}, but got {pos}.",
+                text.len()
+            )));
+        }
+        self.sort(&TextView(text), suffixes);
+        Ok(())
+    }
+}
+
+impl Default for MsdRadixSort {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl SuffixSorter for MsdRadixSort {
+    fn sort(&self, text: &dyn SuffixText, suffixes: &mut [usize]) {
+        self.sort_from_depth(text, suffixes, 0);
+    }
+
+    fn sort_from_depth(&self, text: &dyn SuffixText, suffixes: &mut [usize], depth: usize) {
+        thread_local! {
+            static WORKSPACE: RefCell<SortWorkspace<usize>> = RefCell::default();
+        }
+        WORKSPACE.with(|ws| {
+            MsdRadixSorter::sort_slice(text, suffixes, *self, depth, &mut ws.borrow_mut());
+        });
+    }
+}
+
+/// Multikey quicksort of Bentley and Sedgewick, partitioning suffixes three-way on their bytes,
+/// falling back to comparison sort for small partitions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MultikeyQuicksort {
+    pub(crate) threshold: usize,
+}
+
+impl MultikeyQuicksort {
+    /// Creates a new sorter.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - The maximum size of partitions sorted by comparison sort.
+    pub const fn new(threshold: usize) -> Self {
+        Self { threshold }
+    }
+}
+
+impl Default for MultikeyQuicksort {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl SuffixSorter for MultikeyQuicksort {
+    fn sort(&self, text: &dyn SuffixText, suffixes: &mut [usize]) {
+        self.sort_from_depth(text, suffixes, 0);
+    }
+
+    fn sort_from_depth(&self, text: &dyn SuffixText, suffixes: &mut [usize], depth: usize) {
+        multikey_quicksort(text, suffixes, depth, self.threshold);
+    }
+}
+
+/// Sorts the suffixes sharing the first `depth` bytes by multikey quicksort,
+/// falling back to comparison sort for partitions of at most `threshold` suffixes.
+pub fn multikey_quicksort<I: SuffixIndex, T: SuffixText + ?Sized>(
+    text: &T,
+    mut suffixes: &mut [I],
+    mut depth: usize,
+    threshold: usize,
+) {
+    // Returns the byte of the suffix at the depth, where zero is for the suffix ending before it.
+    let key = |s: I, depth: usize| {
+        let pos = s.to_usize() + depth;
+        if pos < text.len() {
+            text.get(pos) as usize + 1
+        } else {
+            0
+        }
+    };
+    while suffixes.len() > threshold.max(1) {
+        let pivot = key(suffixes[suffixes.len() / 2], depth);
+        // Partitions into [lt, eq, gt) in the manner of the Dutch national flag.
+        let (mut lt, mut i, mut gt) = (0, 0, suffixes.len());
+        while i < gt {
+            match key(suffixes[i], depth).cmp(&pivot) {
+                Ordering::Less => {
+                    suffixes.swap(lt, i);
+                    lt += 1;
+                    i += 1;
+                }
+                Ordering::Equal => i += 1,
+                Ordering::Greater => {
+                    gt -= 1;
+                    suffixes.swap(i, gt);
+                }
+            }
+        }
+        let (less, rest) = std::mem::take(&mut suffixes).split_at_mut(lt);
+        let (equal, greater) = rest.split_at_mut(gt - lt);
+        // At most one suffix can end at the depth, so the equal part is done if the pivot is the end.
+        let equal_depth = if pivot == 0 { None } else { Some(depth + 1) };
+        // Recurses into the smaller parts and loops on the largest one to bound the stack depth.
+        let mut parts = [
+            (less, Some(depth)),
+            (equal, equal_depth),
+            (greater, Some(depth)),
+        ];
+        parts.sort_unstable_by_key(|(part, _)| part.len());
+        let [a, b, c] = parts;
+        for (part, d) in [a, b] {
+            if let Some(d) = d {
+                multikey_quicksort(text, part, d, threshold);
+            }
+        }
+        match c {
+            (part, Some(d)) => {
+                suffixes = part;
+                depth = d;
+            }
+            (_, None) => return,
+        }
+    }
+    // The suffixes share the first `depth` bytes, so they are compared from there.
+    suffixes.sort_unstable_by(|&a, &b| {
+        text.cmp_suffixes(a.to_usize() + depth, b.to_usize() + depth)
+            .then_with(|| a.cmp(&b))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_sort(text: &[u8], mut suffixes: Vec<usize>) -> Vec<usize> {
+        suffixes.sort_by(|&a, &b| text[a..].cmp(&text[b..]));
+        suffixes
+    }
+
+    #[test]
+    fn test_suffix_sorters() {
+        let sorters: [&dyn SuffixSorter; 6] = [
+            &MsdRadixSort::default(),
+            &MsdRadixSort::new(1),
+            &MsdRadixSort::new(1).wide_radix(true),
+            &MsdRadixSort::new(64).multikey_fallback(true),
+            &MultikeyQuicksort::default(),
+            &MultikeyQuicksort::new(1),
+        ];
+        let mut texts = vec![
+            b"abracadabra$".to_vec(),
+            b"abaababaab".to_vec(),
+            vec![b'a'; 100],
+        ];
+        texts.push(
+            (0..3000u32)
+                .map(|i| b"acgt"[(i.wrapping_mul(2654435761) >> 30) as usize])
+                .collect(),
+        );
+        for text in &texts {
+            let all: Vec<usize> = (0..text.len()).collect();
+            let part: Vec<usize> = (0..text.len()).step_by(3).collect();
+            for suffixes in [all, part] {
+                let expected = naive_sort(text, suffixes.clone());
+                for sorter in sorters {
+                    let mut sorted = suffixes.clone();
+                    sorter.sort(&TextView(text.as_slice()), &mut sorted);
+                    assert_eq!(sorted, expected);
+                    // The suffixes starting with "a".
+                    let mut sorted: Vec<usize> = suffixes
+                        .iter()
+                        .copied()
+                        .filter(|&i| text[i] == b'a')
+                        .collect();
+                    sorter.sort_from_depth(&TextView(text.as_slice()), &mut sorted, 1);
+                    let expected: Vec<usize> = expected
+                        .iter()
+                        .copied()
+                        .filter(|&i| text[i] == b'a')
+                        .collect();
+                    assert_eq!(sorted, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_msd_radix_sort_sparse() {
+        let text = b"mississippi";
+        let suffixes = vec![10, 1, 4, 7, 7, 0, 3];
+        for sorter in [
+            MsdRadixSort::default(),
+            MsdRadixSort::new(1),
+            MsdRadixSort::new(1).multikey_fallback(true),
+        ] {
+            let mut sorted = suffixes.clone();
+            sorter.sort_sparse(text, &mut sorted).unwrap();
+            assert_eq!(sorted, [10, 7, 7, 4, 1, 0, 3]);
+            assert_eq!(sorted, naive_sort(text, suffixes.clone()));
+        }
+
+        let mut suffixes = vec![3, 11, 0];
+        assert!(MsdRadixSort::default()
+            .sort_sparse(text, &mut suffixes)
+            .is_err());
+        assert_eq!(suffixes, [3, 11, 0]);
+        let mut empty = vec![];
+        MsdRadixSort::default()
+            .sort_sparse(b"", &mut empty)
+            .unwrap();
+    }
+}
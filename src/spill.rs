@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::Result;
+
+use crate::index::SuffixIndex;
+use crate::intio::{IntWidth, IntWriter};
+use crate::text::{suffix_positions, TextAccess};
+use crate::Progress;
+
+/// Counter to name the temporary files uniquely among constructions in the process.
+static SPILL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Chunks of suffix positions spilled to temporary files in the external-memory mode,
+/// which are removed when dropped.
+pub struct SpilledChunks {
+    // paths[q - 1] is the file of the chunk between the `q-1`-th and `q`-th cuts.
+    paths: Vec<PathBuf>,
+}
+
+impl SpilledChunks {
+    /// Buckets the suffixes starting at multiples of `stride` into the chunks in a single scan,
+    /// writing them to files in `dir`.
+    pub fn bucket<T: TextAccess + ?Sized>(
+        text: &T,
+        cuts: &[Vec<u8>],
+        stride: usize,
+        dir: &Path,
+        progress: &Progress,
+    ) -> Result<Self> {
+        progress.print(&format!(
+            "Spilling suffixes into {} chunks in {}",
+            cuts.len(),
+            dir.display()
+        ));
+        let id = SPILL_ID.fetch_add(1, Ordering::Relaxed);
+        let mut spilled = Self { paths: vec![] };
+        let mut writers = Vec::with_capacity(cuts.len());
+        for q in 1..=cuts.len() {
+            let path = dir.join(format!("small-bwt-{}-{id}-{q}.tmp", std::process::id()));
+            let file = File::create(&path)?;
+            spilled.paths.push(path);
+            writers.push(IntWriter::new(BufWriter::new(file), IntWidth::U64));
+        }
+        for j in suffix_positions(text, stride) {
+            // The first cut not smaller than the suffix, which is never the empty cut.
+            let q = cuts.partition_point(|cut| text.cmp_suffix(j, cut).is_gt());
+            writers[q - 1].write(j)?;
+        }
+        for wrt in &mut writers {
+            wrt.flush()?;
+        }
+        Ok(spilled)
+    }
+
+    /// Loads the suffixes between the `q-1`-th and `q`-th cuts into `chunk`, replacing its contents.
+    pub fn load<I: SuffixIndex>(&self, q: usize, chunk: &mut Vec<I>) -> Result<()> {
+        let path = &self.paths[q - 1];
+        let len = std::fs::metadata(path)?.len() as usize / 8;
+        let mut rdr = BufReader::new(File::open(path)?);
+        chunk.clear();
+        chunk.reserve(len);
+        let mut buf = [0; 8];
+        for _ in 0..len {
+            rdr.read_exact(&mut buf)?;
+            chunk.push(I::from_usize(u64::from_le_bytes(buf) as usize));
+        }
+        Ok(())
+    }
+}
+
+/// Temporary file in a directory, which is removed when dropped.
+#[cfg(feature = "mmap")]
+pub struct TempFile {
+    path: PathBuf,
+}
+
+#[cfg(feature = "mmap")]
+impl TempFile {
+    /// Creates an empty temporary file in `dir`, opened for reading and writing.
+    pub fn create(dir: &Path) -> Result<(Self, File)> {
+        let id = SPILL_ID.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("small-bwt-{}-{id}.tmp", std::process::id()));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok((Self { path }, file))
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Drop for SpilledChunks {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spilled_chunks() {
+        let text: &[u8] = b"abracadabra$";
+        let cuts = &[b"".to_vec(), b"ab".to_vec(), b"b".to_vec(), b"r".to_vec()];
+        let dir = std::env::temp_dir();
+        let spilled = SpilledChunks::bucket(text, cuts, 1, &dir, &Progress::new(false)).unwrap();
+        // The buffer is reused across the chunks.
+        let mut chunk: Vec<u32> = vec![];
+        spilled.load(1, &mut chunk).unwrap();
+        assert_eq!(chunk, vec![10, 11]);
+        spilled.load(2, &mut chunk).unwrap();
+        assert_eq!(chunk, vec![0, 3, 5, 7]);
+        let mut chunk: Vec<usize> = vec![];
+        spilled.load(3, &mut chunk).unwrap();
+        assert_eq!(chunk, vec![1, 4, 6, 8]);
+        spilled.load(4, &mut chunk).unwrap();
+        assert_eq!(chunk, vec![2, 9]);
+        let paths = spilled.paths.clone();
+        drop(spilled);
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
+}
@@ -0,0 +1,495 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::Alphabet;
+
+/// Statistics of the BWT construction returned by [`BwtBuilder::build`](crate::BwtBuilder::build).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BuildStats {
+    /// The length of the text.
+    pub text_len: usize,
+    /// The chunk size used to generate cuts.
+    pub chunk_size: usize,
+    /// The number of cuts, i.e., the number of chunks.
+    pub n_cuts: usize,
+    /// The number of suffixes in the largest chunk.
+    pub max_chunk_len: usize,
+    /// The number of bytes written to the BWT writer and the auxiliary writers,
+    /// excluding the BWT of the reversed text.
+    pub n_bytes_written: usize,
+    /// The wall-clock time of the construction.
+    pub elapsed: Duration,
+    /// The primary index, i.e., the row of the whole text in the sorted rotation matrix.
+    /// It is the position of the terminator in the BWT.
+    pub primary_index: usize,
+    /// The run statistics of the BWT.
+    pub runs: RunStats,
+    /// The primary index of the BWT of the reversed text,
+    /// if it is built by [`BwtBuilder::reverse_writer`](crate::BwtBuilder::reverse_writer).
+    pub reverse_primary_index: Option<usize>,
+    /// The number of the symbols by which the rotations are sorted
+    /// if the context-bounded BWT is built by [`BwtBuilder::context_order`](crate::BwtBuilder::context_order).
+    pub context_order: Option<usize>,
+    /// The terminator appended to the text
+    /// if it is built by [`BwtBuilder::with_terminator`](crate::BwtBuilder::with_terminator).
+    pub terminator: Option<u8>,
+    /// The placeholder written at the implicit sentinel
+    /// if [`BwtBuilder::implicit_sentinel`](crate::BwtBuilder::implicit_sentinel) is set.
+    pub sentinel: Option<u8>,
+    /// The effective alphabet to which the text is remapped
+    /// if [`BwtBuilder::remap_alphabet`](crate::BwtBuilder::remap_alphabet) is set.
+    pub alphabet: Option<Alphabet>,
+    /// The runs of `N` whose suffixes are spliced into the output
+    /// if [`BwtBuilder::skip_n_runs`](crate::BwtBuilder::skip_n_runs) is set.
+    pub n_runs: Vec<Range<usize>>,
+    /// The rows of the BWT written, which are all the rows
+    /// unless [`BwtBuilder::cut_range`](crate::BwtBuilder::cut_range) is set.
+    pub rows: Range<usize>,
+}
+
+/// Statistics of the runs of equal symbols in a BWT.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunStats {
+    /// The length of the BWT.
+    pub len: usize,
+    /// The number of maximal runs, often denoted by `r`.
+    pub n_runs: usize,
+    /// The length of the longest run.
+    pub max_run_len: usize,
+}
+
+impl RunStats {
+    /// Computes the run statistics of a given BWT.
+    ///
+    /// # Arguments
+    ///
+    /// * `bwt` - The Burrows-Wheeler transform of a text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use small_bwt::RunStats;
+    ///
+    /// let stats = RunStats::from_bwt(b"ard$rcaaaabb");
+    /// assert_eq!(stats.n_runs, 8);
+    /// assert_eq!(stats.max_run_len, 4);
+    /// assert_eq!(stats.mean_run_len(), 1.5);
+    /// ```
+    pub fn from_bwt(bwt: &[u8]) -> Self {
+        let mut counter = RunCounter::default();
+        counter.extend(bwt);
+        counter.stats()
+    }
+
+    /// Returns the mean length of the runs, or zero if there are no runs.
+    pub fn mean_run_len(&self) -> f64 {
+        if self.n_runs == 0 {
+            0.0
+        } else {
+            self.len as f64 / self.n_runs as f64
+        }
+    }
+
+    /// Returns an upper bound of the number of bytes of the BWT compressed by a simple run-length coder,
+    /// which writes each run as its symbol in a byte followed by its length in Elias gamma code.
+    ///
+    /// The gamma codes take at most `r (2 log(n/r) + 1)` bits in total by the concavity of the logarithm.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use small_bwt::RunStats;
+    ///
+    /// let stats = RunStats::from_bwt(&[b'a'; 1024]);
+    /// // 8 bits for 'a' and 21 bits for the length.
+    /// assert_eq!(stats.run_length_size(), 4);
+    /// ```
+    pub fn run_length_size(&self) -> usize {
+        if self.n_runs == 0 {
+            return 0;
+        }
+        let r = self.n_runs as f64;
+        let bits = r * 2.0f64.mul_add((self.len as f64 / r).log2(), 9.0);
+        (bits / 8.0).ceil() as usize
+    }
+}
+
+/// Distribution of the lengths of the runs of equal symbols in a BWT per symbol,
+/// created by [`bwt_run_histogram`] or [`RunHistogramBuilder`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunHistogram {
+    // counts[c][len] is the number of runs of symbol c with length len.
+    counts: Vec<BTreeMap<usize, usize>>,
+}
+
+impl RunHistogram {
+    /// Returns the pairs of a run length and the number of runs of symbol `c` with the length,
+    /// in increasing order of the length.
+    pub fn runs(&self, c: u8) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.counts[c as usize].iter().map(|(&len, &n)| (len, n))
+    }
+
+    /// Returns the pairs of a run length and the number of runs of any symbol with the length,
+    /// in increasing order of the length.
+    pub fn all_runs(&self) -> Vec<(usize, usize)> {
+        let mut merged = BTreeMap::new();
+        for counts in &self.counts {
+            for (&len, &n) in counts {
+                *merged.entry(len).or_default() += n;
+            }
+        }
+        merged.into_iter().collect()
+    }
+
+    /// Returns the symbols having runs in increasing order.
+    pub fn symbols(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=255).filter(|&c| !self.counts[c as usize].is_empty())
+    }
+
+    /// Returns the number of runs of symbol `c`.
+    pub fn n_runs(&self, c: u8) -> usize {
+        self.counts[c as usize].values().sum()
+    }
+
+    /// Returns the number of runs, often denoted by `r`.
+    pub fn total_runs(&self) -> usize {
+        self.counts.iter().flat_map(|counts| counts.values()).sum()
+    }
+}
+
+/// Builder of [`RunHistogram`] over a BWT written in a streaming manner,
+/// such as the output of [`BwtBuilder::build`](crate::BwtBuilder::build).
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BwtBuilder, RunHistogramBuilder};
+///
+/// let mut builder = RunHistogramBuilder::new();
+/// BwtBuilder::new(b"abracadabra$")?.build(&mut builder)?;
+/// let hist = builder.finish();
+///
+/// // The BWT is "ard$rcaaaabb".
+/// assert_eq!(hist.runs(b'a').collect::<Vec<_>>(), vec![(1, 1), (4, 1)]);
+/// assert_eq!(hist.runs(b'b').collect::<Vec<_>>(), vec![(2, 1)]);
+/// assert_eq!(hist.total_runs(), 8);
+/// # Ok(())
+/// # }
+/// ```
+pub struct RunHistogramBuilder {
+    counts: Vec<BTreeMap<usize, usize>>,
+    last: Option<u8>,
+    run_len: usize,
+}
+
+impl RunHistogramBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        Self {
+            counts: vec![BTreeMap::new(); 256],
+            last: None,
+            run_len: 0,
+        }
+    }
+
+    /// Appends a symbol to the BWT.
+    pub fn push(&mut self, c: u8) {
+        if self.last == Some(c) {
+            self.run_len += 1;
+            return;
+        }
+        self.close_run();
+        self.last = Some(c);
+        self.run_len = 1;
+    }
+
+    /// Builds the histogram of the BWT written so far.
+    pub fn finish(mut self) -> RunHistogram {
+        self.close_run();
+        RunHistogram {
+            counts: self.counts,
+        }
+    }
+
+    fn close_run(&mut self) {
+        if let Some(c) = self.last {
+            *self.counts[c as usize].entry(self.run_len).or_default() += 1;
+        }
+    }
+}
+
+impl Default for RunHistogramBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for RunHistogramBuilder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &c in buf {
+            self.push(c);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Computes the distribution of the run lengths per symbol of a BWT read from a stream.
+///
+/// # Arguments
+///
+/// * `rdr` - The reader of the Burrows-Wheeler transform.
+///
+/// # Errors
+///
+/// An error is returned if `rdr` returns an error.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::bwt_run_histogram;
+///
+/// let hist = bwt_run_histogram(&b"aabaaab"[..])?;
+/// assert_eq!(hist.runs(b'a').collect::<Vec<_>>(), vec![(2, 1), (3, 1)]);
+/// assert_eq!(hist.all_runs(), vec![(1, 2), (2, 1), (3, 1)]);
+/// assert_eq!(hist.symbols().collect::<Vec<_>>(), vec![b'a', b'b']);
+/// # Ok(())
+/// # }
+/// ```
+pub fn bwt_run_histogram<R: Read>(mut rdr: R) -> Result<RunHistogram> {
+    let mut builder = RunHistogramBuilder::new();
+    std::io::copy(&mut rdr, &mut builder)?;
+    Ok(builder.finish())
+}
+
+/// Empirical entropy statistics of a text, which bound the sizes of the BWT compressed by entropy coders.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntropyStats {
+    /// The length of the text.
+    pub len: usize,
+    /// The order `k` of [`Self::hk`].
+    pub order: usize,
+    /// The zeroth-order empirical entropy in bits per symbol.
+    pub h0: f64,
+    /// The `k`-th order empirical entropy in bits per symbol.
+    pub hk: f64,
+}
+
+impl EntropyStats {
+    /// Computes the empirical entropy statistics of a given text.
+    ///
+    /// The context of each position is the `k` symbols following it, truncated at the end of the text.
+    /// They group the symbols as the BWT does in blocks of rows sharing their first `k` symbols,
+    /// so `nHk` is the size an order-0 coder achieves on the BWT split into the blocks.
+    ///
+    /// It takes `O(n)` expected time and memory proportional to the number of distinct contexts.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text.
+    /// * `k` - The order of the context.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use small_bwt::EntropyStats;
+    ///
+    /// let stats = EntropyStats::from_text(b"abababab", 1);
+    /// assert_eq!(stats.h0, 1.0);
+    /// // Each symbol is determined by the following one, except the last.
+    /// assert_eq!(stats.hk, 0.0);
+    /// assert_eq!(stats.order0_size(), 1);
+    /// ```
+    pub fn from_text(text: &[u8], k: usize) -> Self {
+        let n = text.len();
+        let mut counts = [0usize; 256];
+        for &c in text {
+            counts[c as usize] += 1;
+        }
+        let h0 = entropy_bits(counts.iter().copied(), n);
+        let hk = if k == 0 {
+            h0
+        } else {
+            let mut contexts: HashMap<&[u8], HashMap<u8, usize>> = HashMap::new();
+            for (i, &c) in text.iter().enumerate() {
+                let context = &text[i + 1..n.min(i + 1 + k)];
+                *contexts.entry(context).or_default().entry(c).or_default() += 1;
+            }
+            contexts
+                .values()
+                .map(|counts| entropy_bits(counts.values().copied(), counts.values().sum()))
+                .sum()
+        };
+        let per_symbol = |bits: f64| if n == 0 { 0.0 } else { bits / n as f64 };
+        Self {
+            len: n,
+            order: k,
+            h0: per_symbol(h0),
+            hk: per_symbol(hk),
+        }
+    }
+
+    /// Returns the number of bytes of the text or its BWT compressed by an ideal order-0 coder,
+    /// i.e., `nH0` bits rounded up to bytes.
+    pub fn order0_size(&self) -> usize {
+        (self.len as f64 * self.h0 / 8.0).ceil() as usize
+    }
+
+    /// Returns the number of bytes of the BWT compressed by an ideal order-0 coder
+    /// on each block of rows sharing their first `k` symbols, i.e., `nHk` bits rounded up to bytes.
+    pub fn orderk_size(&self) -> usize {
+        (self.len as f64 * self.hk / 8.0).ceil() as usize
+    }
+}
+
+/// Returns `n H0` in bits for the symbol counts summing to `n`.
+fn entropy_bits<I>(counts: I, n: usize) -> f64
+where
+    I: IntoIterator<Item = usize>,
+{
+    counts
+        .into_iter()
+        .filter(|&c| c != 0)
+        .map(|c| c as f64 * (n as f64 / c as f64).log2())
+        .sum()
+}
+
+/// Incremental counter of [`RunStats`].
+#[derive(Default)]
+pub struct RunCounter {
+    stats: RunStats,
+    last: Option<u8>,
+    run_len: usize,
+}
+
+impl RunCounter {
+    pub fn push(&mut self, c: u8) {
+        if self.last == Some(c) {
+            self.run_len += 1;
+        } else {
+            self.last = Some(c);
+            self.run_len = 1;
+            self.stats.n_runs += 1;
+        }
+        self.stats.len += 1;
+        self.stats.max_run_len = self.stats.max_run_len.max(self.run_len);
+    }
+
+    pub fn extend(&mut self, bwt: &[u8]) {
+        for &c in bwt {
+            self.push(c);
+        }
+    }
+
+    pub const fn stats(&self) -> RunStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_stats_empty() {
+        let stats = RunStats::from_bwt(b"");
+        assert_eq!(stats, RunStats::default());
+        assert_eq!(stats.mean_run_len(), 0.0);
+    }
+
+    #[test]
+    fn test_run_length_size() {
+        assert_eq!(RunStats::default().run_length_size(), 0);
+        // 4 runs of 8 bits and 1-bit lengths.
+        assert_eq!(RunStats::from_bwt(b"abab").run_length_size(), 5);
+    }
+
+    #[test]
+    fn test_run_histogram() {
+        let hist = bwt_run_histogram(&b""[..]).unwrap();
+        assert_eq!(hist.total_runs(), 0);
+        assert_eq!(hist.symbols().count(), 0);
+        assert!(hist.all_runs().is_empty());
+
+        let bwt = b"mmiissssssippiiiiimmmp";
+        let hist = bwt_run_histogram(&bwt[..]).unwrap();
+        assert_eq!(hist.total_runs(), RunStats::from_bwt(bwt).n_runs);
+        assert_eq!(
+            hist.runs(b'i').collect::<Vec<_>>(),
+            vec![(1, 1), (2, 1), (5, 1)]
+        );
+        assert_eq!(hist.runs(b'm').collect::<Vec<_>>(), vec![(2, 1), (3, 1)]);
+        assert_eq!(hist.runs(b'p').collect::<Vec<_>>(), vec![(1, 1), (2, 1)]);
+        assert_eq!(hist.runs(b's').collect::<Vec<_>>(), vec![(6, 1)]);
+        assert_eq!(hist.n_runs(b'i'), 3);
+        assert_eq!(hist.n_runs(b'x'), 0);
+        let total: usize = hist.all_runs().iter().map(|&(len, n)| len * n).sum();
+        assert_eq!(total, bwt.len());
+    }
+
+    #[test]
+    fn test_run_histogram_builder_split() {
+        let mut builder = RunHistogramBuilder::new();
+        builder.write_all(b"aab").unwrap();
+        builder.write_all(b"bbb").unwrap();
+        let hist = builder.finish();
+        assert_eq!(hist.all_runs(), vec![(2, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn test_entropy_stats() {
+        let stats = EntropyStats::from_text(b"", 2);
+        assert_eq!(
+            stats,
+            EntropyStats {
+                order: 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats.order0_size(), 0);
+
+        let stats = EntropyStats::from_text(b"aaaa", 0);
+        assert_eq!((stats.h0, stats.hk), (0.0, 0.0));
+
+        let stats = EntropyStats::from_text(b"abcd", 0);
+        assert_eq!((stats.h0, stats.hk), (2.0, 2.0));
+        assert_eq!(stats.order0_size(), 1);
+
+        // Contexts: "a" -> "rcdr", "b" -> "aa", "c" -> "a", "d" -> "a", "r" -> "bb", "" -> "a".
+        let stats = EntropyStats::from_text(b"abracadabra", 1);
+        let expected = 4.0 * (0.5 * 2f64.log2() + 2.0 * 0.25 * 4f64.log2()) / 11.0;
+        assert!((stats.hk - expected).abs() < 1e-12);
+        assert!(stats.hk <= stats.h0);
+        assert!(stats.orderk_size() <= stats.order0_size());
+
+        // Higher orders never increase the entropy.
+        let text = b"mississippimississippi";
+        let hks: Vec<_> = (0..6)
+            .map(|k| EntropyStats::from_text(text, k).hk)
+            .collect();
+        assert!(hks.windows(2).all(|w| w[1] <= w[0] + 1e-12), "{hks:?}");
+    }
+
+    #[test]
+    fn test_run_counter_split() {
+        let mut counter = RunCounter::default();
+        counter.extend(b"aab");
+        counter.extend(b"bbb");
+        let stats = counter.stats();
+        assert_eq!(stats.len, 6);
+        assert_eq!(stats.n_runs, 2);
+        assert_eq!(stats.max_run_len, 4);
+    }
+}
@@ -0,0 +1,55 @@
+/// Unsigned integer type of the symbols of a text given to
+/// [`BwtBuilder::from_symbols`](crate::BwtBuilder::from_symbols).
+///
+/// A symbol is represented by its big-endian bytes,
+/// so that the suffixes of the symbols are in the same order as those of their bytes.
+pub trait Symbol: Copy + Ord {
+    /// The number of bytes of a symbol.
+    const BYTES: usize;
+
+    /// Appends the big-endian bytes of the symbol to `bytes`.
+    fn extend_be_bytes(self, bytes: &mut Vec<u8>);
+
+    /// Restores a symbol from its big-endian bytes.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the length of `bytes` is not [`Self::BYTES`].
+    fn from_be_slice(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_symbol {
+    ($($t:ty),*) => {
+        $(
+            impl Symbol for $t {
+                const BYTES: usize = std::mem::size_of::<$t>();
+
+                fn extend_be_bytes(self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn from_be_slice(bytes: &[u8]) -> Self {
+                    Self::from_be_bytes(bytes.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+impl_symbol!(u8, u16, u32, u64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol() {
+        let mut bytes = vec![];
+        0x1234u16.extend_be_bytes(&mut bytes);
+        0x56789abcu32.extend_be_bytes(&mut bytes);
+        assert_eq!(bytes, [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc]);
+        assert_eq!(u16::from_be_slice(&bytes[..2]), 0x1234);
+        assert_eq!(u32::from_be_slice(&bytes[2..]), 0x56789abc);
+        assert_eq!(<u64 as Symbol>::BYTES, 8);
+    }
+}
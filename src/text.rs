@@ -0,0 +1,160 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::index::SuffixIndex;
+use crate::nrun::SpliceCursor;
+
+/// Random access to a text whose suffixes are sorted,
+/// either a byte slice or a [`PackedDna`](crate::PackedDna).
+pub trait TextAccess: Sync {
+    /// Returns the length of the text.
+    fn len(&self) -> usize;
+
+    /// Returns the byte at position `i`.
+    fn get(&self, i: usize) -> u8;
+
+    /// Compares the suffixes starting at `i` and `j`.
+    fn cmp_suffixes(&self, i: usize, j: usize) -> Ordering;
+
+    /// Returns the length of the longest common prefix of the suffixes starting at `i` and `j`.
+    fn lcp(&self, i: usize, j: usize) -> usize;
+
+    /// Returns the text in reverse order for the reverse writer,
+    /// where `stride` and `sentinel` are as in the byte text case.
+    fn reversed(&self, stride: usize, sentinel: bool) -> Box<Self>;
+
+    /// Compares the prefix of the suffix starting at `i`, truncated to the length of `pattern`, with `pattern`.
+    fn cmp_prefix(&self, i: usize, pattern: &[u8]) -> Ordering {
+        let m = pattern.len().min(self.len() - i);
+        for (k, &c) in pattern[..m].iter().enumerate() {
+            match self.get(i + k).cmp(&c) {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
+        }
+        m.cmp(&pattern.len())
+    }
+
+    /// Compares the suffix starting at `i` with `pattern`.
+    fn cmp_suffix(&self, i: usize, pattern: &[u8]) -> Ordering {
+        self.cmp_prefix(i, pattern)
+            .then_with(|| (self.len() - i).cmp(&pattern.len()))
+    }
+
+    /// Returns the ranges of the starting positions of the suffixes bucketed by the cuts,
+    /// which are all the positions unless some suffixes are spliced by [`Self::splice_excluded`].
+    #[allow(clippy::single_range_in_vec_init)]
+    fn suffix_ranges(&self) -> Vec<Range<usize>> {
+        vec![0..self.len()]
+    }
+
+    /// Merges the suffixes excluded from [`Self::suffix_ranges`] into the next sorted chunk,
+    /// visiting them in sorted order with `cursor`. The remaining ones are appended if `last` is `true`.
+    fn splice_excluded<I: SuffixIndex>(
+        &self,
+        chunk: Vec<I>,
+        _cursor: &mut SpliceCursor,
+        _last: bool,
+    ) -> Vec<I> {
+        chunk
+    }
+}
+
+/// Returns the starting positions of the suffixes bucketed by the cuts at multiples of `stride`
+/// in increasing order.
+pub fn suffix_positions<T: TextAccess + ?Sized>(
+    text: &T,
+    stride: usize,
+) -> impl Iterator<Item = usize> {
+    text.suffix_ranges()
+        .into_iter()
+        .flat_map(move |r| r.step_by(stride))
+}
+
+impl TextAccess for [u8] {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn get(&self, i: usize) -> u8 {
+        self[i]
+    }
+
+    fn cmp_suffixes(&self, i: usize, j: usize) -> Ordering {
+        self[i..].cmp(&self[j..])
+    }
+
+    fn lcp(&self, i: usize, j: usize) -> usize {
+        self[i..]
+            .iter()
+            .zip(self[j..].iter())
+            .take_while(|(a, b)| a == b)
+            .count()
+    }
+
+    fn reversed(&self, stride: usize, sentinel: bool) -> Box<Self> {
+        crate::reversed_text(self, stride, sentinel).into_boxed_slice()
+    }
+
+    fn cmp_prefix(&self, i: usize, pattern: &[u8]) -> Ordering {
+        let end = (i + pattern.len()).min(self.len());
+        self[i..end].cmp(pattern)
+    }
+
+    fn cmp_suffix(&self, i: usize, pattern: &[u8]) -> Ordering {
+        self[i..].cmp(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lcp() {
+        let text: &[u8] = b"abracadabra$";
+        assert_eq!(text.lcp(0, 7), 4);
+        assert_eq!(text.lcp(1, 8), 3);
+        assert_eq!(text.lcp(0, 1), 0);
+        assert_eq!(text.lcp(11, 11), 1);
+    }
+
+    #[test]
+    fn test_text_access_defaults() {
+        // The default methods agree with the overrides of byte slices.
+        struct Bytes<'a>(&'a [u8]);
+
+        impl TextAccess for Bytes<'_> {
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+            fn get(&self, i: usize) -> u8 {
+                self.0[i]
+            }
+            fn cmp_suffixes(&self, i: usize, j: usize) -> Ordering {
+                self.0.cmp_suffixes(i, j)
+            }
+            fn lcp(&self, i: usize, j: usize) -> usize {
+                self.0.lcp(i, j)
+            }
+            fn reversed(&self, _: usize, _: bool) -> Box<Self> {
+                unimplemented!()
+            }
+        }
+
+        let text = b"abracadabra$";
+        for i in 0..text.len() {
+            for pattern in [&b""[..], b"a", b"ab", b"abr", b"b", b"bra$", b"bra$x", b"z"] {
+                assert_eq!(
+                    Bytes(text).cmp_prefix(i, pattern),
+                    text[..].cmp_prefix(i, pattern)
+                );
+                assert_eq!(
+                    Bytes(text).cmp_suffix(i, pattern),
+                    text[..].cmp_suffix(i, pattern)
+                );
+                assert_eq!(text[..].cmp_suffix(i, pattern), text[i..].cmp(pattern));
+            }
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use crate::error::{Error, Result};
+
+use crate::indexed::primary_index;
+
+/// Flag of a row keeping its entry in the first column.
+const HAS_F: u8 = 1;
+/// Flag of a row keeping its entry in the last column, i.e., the BWT.
+const HAS_L: u8 = 2;
+
+/// The minimum width of a block to be tunneled.
+const MIN_WIDTH: usize = 3;
+
+/// BWT with tunneled blocks, returned by [`tunnel_bwt`].
+///
+/// A block consists of `w` columns of `h` rows, where the LF mapping moves each column to the next one,
+/// and the symbols in every column except the last one are identical.
+/// Tunneling a block removes the BWT entries of all but the top row in all but the last column,
+/// and the first-column entries of all but the top row in all but the first column.
+/// The rows losing both entries are removed from the matrix,
+/// so that `(h - 1)(w - 1)` symbols are removed from the BWT.
+///
+/// The kept entries of each remaining row are recorded as flags,
+/// where bit 0 indicates the first-column entry and bit 1 indicates the BWT entry.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TunneledBwt {
+    bwt: Vec<u8>,
+    flags: Vec<u8>,
+}
+
+/// Tunnels disjoint blocks of a BWT to shrink it.
+///
+/// The blocks are greedily taken from the runs in the BWT:
+/// each run of two or more rows is extended through the LF mapping while the moved rows are in one run,
+/// and is tunneled if it spans at least three columns without overlapping the other blocks.
+///
+/// It runs in `O(n)` time and uses `O(n log n)` bits of space, where `n` is the length of the BWT.
+///
+/// # Arguments
+///
+/// * `bwt` - The Burrows-Wheeler transform of a text with a unique terminator.
+///
+/// # Errors
+///
+/// An error is returned if `bwt` is empty, does not have exactly one terminator, or is not a valid BWT.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{tunnel_bwt, BwtBuilder};
+///
+/// let mut bwt = vec![];
+/// BwtBuilder::new(b"abcabcabcabc$")?.build(&mut bwt)?;
+///
+/// let tunneled = tunnel_bwt(&bwt)?;
+/// assert!(tunneled.bwt().len() < bwt.len());
+/// assert_eq!(tunneled.detunnel()?, bwt);
+/// # Ok(())
+/// # }
+/// ```
+pub fn tunnel_bwt(bwt: &[u8]) -> Result<TunneledBwt> {
+    primary_index(bwt)?;
+    let lf = lf_mapping(bwt);
+    let mut r = 0;
+    for k in 1..=bwt.len() {
+        r = lf[r];
+        if r == 0 && k != bwt.len() {
+            return Err(Error::invalid_bwt(
+                "bwt is not a valid BWT, since the LF mapping has multiple cycles.",
+            ));
+        }
+    }
+
+    let n = bwt.len();
+    let mut flags = vec![HAS_F | HAS_L; n];
+    let mut used = vec![false; n];
+    let mut start = 0;
+    while start < n {
+        let mut end = start + 1;
+        while end < n && bwt[end] == bwt[start] {
+            end += 1;
+        }
+        if end - start >= 2 && !used[start..end].iter().any(|&u| u) {
+            // The first rows of the columns in the block.
+            let mut tops = vec![start];
+            let h = end - start;
+            used[start..end].iter_mut().for_each(|u| *u = true);
+            loop {
+                let top = *tops.last().unwrap();
+                if bwt[top..top + h].iter().any(|&c| c != bwt[top]) {
+                    break;
+                }
+                let next = lf[top];
+                if used[next..next + h].iter().any(|&u| u) {
+                    break;
+                }
+                used[next..next + h].iter_mut().for_each(|u| *u = true);
+                tops.push(next);
+            }
+            if tops.len() >= MIN_WIDTH {
+                let w = tops.len();
+                for (k, &top) in tops.iter().enumerate() {
+                    for f in &mut flags[top + 1..top + h] {
+                        if k != 0 {
+                            *f &= !HAS_F;
+                        }
+                        if k != w - 1 {
+                            *f &= !HAS_L;
+                        }
+                    }
+                }
+            } else {
+                for &top in &tops {
+                    used[top..top + h].iter_mut().for_each(|u| *u = false);
+                }
+            }
+        }
+        start = end;
+    }
+
+    let tunneled = bwt
+        .iter()
+        .zip(&flags)
+        .filter(|(_, &f)| f & HAS_L != 0)
+        .map(|(&c, _)| c)
+        .collect();
+    let flags = flags.into_iter().filter(|&f| f != 0).collect();
+    Ok(TunneledBwt {
+        bwt: tunneled,
+        flags,
+    })
+}
+
+impl TunneledBwt {
+    /// Creates the tunneled BWT from its parts, e.g., read from storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `bwt` - The tunneled BWT.
+    /// * `flags` - The flags of the remaining rows.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the flags are inconsistent with `bwt`.
+    pub fn from_parts(bwt: Vec<u8>, flags: Vec<u8>) -> Result<Self> {
+        if flags.iter().any(|&f| f == 0 || f & !(HAS_F | HAS_L) != 0) {
+            return Err(Error::invalid_argument("flags must be 1, 2, or 3."));
+        }
+        let n_l = flags.iter().filter(|&&f| f & HAS_L != 0).count();
+        let n_f = flags.iter().filter(|&&f| f & HAS_F != 0).count();
+        if n_l != bwt.len() || n_f != bwt.len() {
+            return Err(Error::invalid_argument(format!(
+                "flags must have {} entries in both columns, but got {n_l} and {n_f}.",
+                bwt.len()
+            )));
+        }
+        Ok(Self { bwt, flags })
+    }
+
+    /// Returns the tunneled BWT.
+    pub fn bwt(&self) -> &[u8] {
+        &self.bwt
+    }
+
+    /// Returns the flags of the remaining rows,
+    /// where bit 0 indicates the first-column entry and bit 1 indicates the BWT entry.
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    /// Restores the original BWT.
+    ///
+    /// The LF mapping is walked twice over the tunneled BWT,
+    /// first to find the heights of the blocks, and then to place every symbol at its original row.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the tunneled BWT is invalid.
+    pub fn detunnel(&self) -> Result<Vec<u8>> {
+        let walker = Walker::new(self)?;
+
+        // extras[r] is the number of rows removed after the remaining row r.
+        let mut extras = vec![0; self.flags.len()];
+        let mut height = None;
+        let mut n = 0;
+        walker.walk(|step| {
+            if step.offset.is_none() {
+                if let Some(h) = walker.entrance_height(step.row) {
+                    height = Some(h);
+                } else if walker.is_exit(step.row) {
+                    height = None;
+                } else if let Some(h) = height {
+                    extras[step.row] = h - 1;
+                }
+            }
+            n += 1;
+        })?;
+
+        let mut origs = Vec::with_capacity(extras.len());
+        let mut orig = 0;
+        for &extra in &extras {
+            origs.push(orig);
+            orig += 1 + extra;
+        }
+        if orig != n {
+            return Err(Error::invalid_bwt("bwt is not a valid tunneled BWT."));
+        }
+
+        let mut bwt = vec![0; n];
+        let mut valid = true;
+        walker.walk(|step| {
+            let offset = step.offset.unwrap_or(0);
+            match bwt.get_mut(origs[step.row] + offset) {
+                Some(c) => *c = step.symbol,
+                None => valid = false,
+            }
+        })?;
+        if !valid {
+            return Err(Error::invalid_bwt("bwt is not a valid tunneled BWT."));
+        }
+        Ok(bwt)
+    }
+}
+
+/// Step of the walk over the tunneled BWT.
+struct Step {
+    // The remaining row, or the top row of the column in a block.
+    row: usize,
+    // The offset from the top row, if the walk is passing through a block below its top row.
+    offset: Option<usize>,
+    symbol: u8,
+}
+
+/// Navigator of the LF mapping over the tunneled BWT.
+struct Walker<'a> {
+    bwt: &'a [u8],
+    flags: &'a [u8],
+    // The remaining rows with the first-column entries.
+    f_rows: Vec<usize>,
+    // The remaining rows with the BWT entries.
+    l_rows: Vec<usize>,
+    // l_ranks[r] is the number of BWT entries in the remaining rows before r.
+    l_ranks: Vec<usize>,
+    // lf[i] is the index of the first-column entry moved from the i-th BWT entry.
+    lf: Vec<usize>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(tunneled: &'a TunneledBwt) -> Result<Self> {
+        let TunneledBwt { bwt, flags } = tunneled;
+        if bwt.is_empty() {
+            return Err(Error::invalid_bwt("bwt must not be empty."));
+        }
+        let mut f_rows = vec![];
+        let mut l_rows = vec![];
+        let mut l_ranks = Vec::with_capacity(flags.len());
+        for (r, &f) in flags.iter().enumerate() {
+            l_ranks.push(l_rows.len());
+            if f & HAS_F != 0 {
+                f_rows.push(r);
+            }
+            if f & HAS_L != 0 {
+                l_rows.push(r);
+            }
+        }
+        Ok(Self {
+            bwt,
+            flags,
+            f_rows,
+            l_rows,
+            l_ranks,
+            lf: lf_mapping(bwt),
+        })
+    }
+
+    /// Returns the height of the block if `r` is the top row of its first column.
+    fn entrance_height(&self, r: usize) -> Option<usize> {
+        let n_lower = self.flags[r + 1..]
+            .iter()
+            .take_while(|&&f| f == HAS_F)
+            .count();
+        if n_lower == 0 {
+            None
+        } else {
+            Some(n_lower + 1)
+        }
+    }
+
+    /// Returns `true` if `r` is the top row of the last column of a block.
+    fn is_exit(&self, r: usize) -> bool {
+        self.flags.get(r + 1) == Some(&HAS_L)
+    }
+
+    /// Walks the LF mapping from the row of the smallest suffix, calling `f` for every original row.
+    fn walk<F: FnMut(Step)>(&self, mut f: F) -> Result<()> {
+        let invalid = || Error::invalid_bwt("bwt is not a valid tunneled BWT.");
+        // Every BWT entry is visited at most once per row of the remaining rows.
+        let max_steps = self.bwt.len() * self.flags.len();
+        let mut offset = None;
+        let mut i = 0;
+        for _ in 0..max_steps {
+            let mut row = self.f_rows[i];
+            let mut step_offset = offset;
+            if self.flags[row] & HAS_L == 0 {
+                // Enters a block below its top row.
+                let l = self.l_ranks[row].checked_sub(1).ok_or_else(invalid)?;
+                let top = self.l_rows[l];
+                offset = Some(row - top);
+                step_offset = offset;
+                row = top;
+            } else if let Some(j) = offset {
+                if self.is_exit(row) {
+                    // Exits the block at the same offset.
+                    row += j;
+                    if row >= self.flags.len() || self.flags[row] != HAS_L {
+                        return Err(invalid());
+                    }
+                    offset = None;
+                    step_offset = None;
+                }
+            }
+            let l = self.l_ranks[row];
+            f(Step {
+                row,
+                offset: step_offset,
+                symbol: self.bwt[l],
+            });
+            i = self.lf[l];
+            if i == 0 && offset.is_none() {
+                return Ok(());
+            }
+        }
+        Err(invalid())
+    }
+}
+
+/// Computes the LF mapping of `bwt`.
+fn lf_mapping(bwt: &[u8]) -> Vec<usize> {
+    let mut counts = vec![0; 256];
+    for &c in bwt {
+        counts[c as usize] += 1;
+    }
+    let mut occ = vec![0; 256];
+    let mut rank = 0;
+    for c in 0..256 {
+        occ[c] = rank;
+        rank += counts[c];
+    }
+    bwt.iter()
+        .map(|&c| {
+            let i = occ[c as usize];
+            occ[c as usize] += 1;
+            i
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BwtBuilder;
+
+    fn build(text: &[u8]) -> Vec<u8> {
+        let mut bwt = vec![];
+        BwtBuilder::new(text).unwrap().build(&mut bwt).unwrap();
+        bwt
+    }
+
+    #[test]
+    fn test_tunnel_bwt() {
+        let text: Vec<u8> = (0..3000u32)
+            .map(|i| b"abc"[(i.count_ones() % 3) as usize])
+            .chain([b'$'])
+            .collect();
+        let bwt = build(&text);
+        let tunneled = tunnel_bwt(&bwt).unwrap();
+        assert!(tunneled.bwt().len() < bwt.len());
+        assert_eq!(tunneled.detunnel().unwrap(), bwt);
+    }
+
+    #[test]
+    fn test_tunnel_bwt_small() {
+        for text in [
+            &b"$"[..],
+            b"a$",
+            b"abracadabra$",
+            b"aaaaaaaa$",
+            b"abababab$",
+        ] {
+            let bwt = build(text);
+            let tunneled = tunnel_bwt(&bwt).unwrap();
+            assert_eq!(tunneled.detunnel().unwrap(), bwt);
+            let parts = TunneledBwt::from_parts(tunneled.bwt().to_vec(), tunneled.flags().to_vec())
+                .unwrap();
+            assert_eq!(parts.detunnel().unwrap(), bwt);
+        }
+    }
+
+    #[test]
+    fn test_tunnel_bwt_block() {
+        // The run "cccc" is tunneled through three columns of four rows.
+        let bwt = build(b"abcabcabcabc$");
+        let tunneled = tunnel_bwt(&bwt).unwrap();
+        assert_eq!(bwt, b"cccc$aaaabbbb");
+        assert_eq!(tunneled.bwt().len(), 13 - 3 * 2);
+    }
+
+    #[test]
+    fn test_tunnel_bwt_invalid() {
+        assert!(tunnel_bwt(b"").is_err());
+        assert!(tunnel_bwt(b"ard$rcaaa$bb").is_err());
+        assert!(tunnel_bwt(b"bb$a").is_err());
+        assert!(TunneledBwt::from_parts(b"ab".to_vec(), vec![3, 1]).is_err());
+        assert!(TunneledBwt::from_parts(b"ab".to_vec(), vec![3, 4]).is_err());
+    }
+}
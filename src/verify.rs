@@ -0,0 +1,152 @@
+use std::cmp::Ordering;
+
+use crate::error::{Error, Result};
+
+/// Number of bytes compared at once to skip the common prefix of adjacent suffixes.
+const WORD_LEN: usize = 8;
+
+/// Verifies that a given sequence is the suffix array of a text,
+/// such as the one written by [`BwtBuilder::suffix_array_writer`](crate::BwtBuilder::suffix_array_writer)
+/// or by an external tool.
+///
+/// The suffix array is consumed in a single pass, holding only the previous position
+/// and a bit per position to check that it is a permutation.
+/// Each pair of adjacent suffixes is compared directly,
+/// skipping their longest common prefix a word at a time,
+/// so it runs in `O(n + L)` time, where `L` is the sum of the longest common prefixes.
+///
+/// The empty suffix at position `n` may be included, as it is
+/// with [`BwtBuilder::implicit_sentinel`](crate::BwtBuilder::implicit_sentinel),
+/// in which case it must be the first one.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+/// * `sa` - The positions of the suffixes in increasing order of the suffixes.
+///
+/// # Errors
+///
+/// An error is returned if
+///
+/// - `sa` has a position larger than `n` or the same position twice,
+/// - `sa` misses a position in `[0, n)`, or
+/// - a pair of adjacent suffixes is not in increasing order, reporting the first one.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{verify_suffix_array, BwtBuilder, IntWidth};
+///
+/// let text = b"abracadabra$";
+/// let mut sa = vec![];
+/// BwtBuilder::new(text)?
+///     .suffix_array_writer(&mut sa, IntWidth::U32)
+///     .build(&mut vec![])?;
+/// let sa = sa
+///     .chunks(4)
+///     .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize);
+/// verify_suffix_array(text, sa)?;
+///
+/// assert!(verify_suffix_array(text, [11, 10, 0, 7, 3, 5, 8, 1, 4, 6, 9, 2]).is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub fn verify_suffix_array<I>(text: &[u8], sa: I) -> Result<()>
+where
+    I: IntoIterator<Item = usize>,
+{
+    let n = text.len();
+    let mut seen = vec![0u64; n / 64 + 1];
+    let mut prev: Option<usize> = None;
+    let mut len = 0;
+    for pos in sa {
+        if pos > n {
+            return Err(Error::invalid_argument(format!(
+                "sa must have positions at most {n}, but got {pos} at index {len}."
+            )));
+        }
+        let (word, bit) = (pos / 64, 1 << (pos % 64));
+        if seen[word] & bit != 0 {
+            return Err(Error::invalid_argument(format!(
+                "sa must be a permutation, but position {pos} appears again at index {len}."
+            )));
+        }
+        seen[word] |= bit;
+        if let Some(prev) = prev {
+            if compare_suffixes(text, prev, pos) != Ordering::Less {
+                return Err(Error::invalid_argument(format!(
+                    "sa must be sorted, but the suffix at {prev} is not smaller than the one at {pos} at index {len}."
+                )));
+            }
+        }
+        prev = Some(pos);
+        len += 1;
+    }
+    let expected = n + usize::from(seen[n / 64] & 1 << (n % 64) != 0);
+    if len != expected {
+        return Err(Error::invalid_argument(format!(
+            "sa must have {expected} positions, but got {len}."
+        )));
+    }
+    Ok(())
+}
+
+/// Compares the suffixes of `text` at `i` and `j`, where a proper prefix is smaller.
+fn compare_suffixes(text: &[u8], i: usize, j: usize) -> Ordering {
+    let (a, b) = (&text[i..], &text[j..]);
+    let m = a.len().min(b.len());
+    let mut l = 0;
+    while l + WORD_LEN <= m && a[l..l + WORD_LEN] == b[l..l + WORD_LEN] {
+        l += WORD_LEN;
+    }
+    a[l..].cmp(&b[l..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_suffix_array(text: &[u8]) -> Vec<usize> {
+        let mut sa: Vec<usize> = (0..text.len()).collect();
+        sa.sort_by_key(|&i| &text[i..]);
+        sa
+    }
+
+    #[test]
+    fn test_verify_suffix_array() {
+        let texts: &[&[u8]] = &[
+            b"$",
+            b"abracadabra$",
+            b"mississippi$",
+            &[b'a'; 100],
+            b"abababababababababababababababab",
+        ];
+        for &text in texts {
+            let sa = naive_suffix_array(text);
+            verify_suffix_array(text, sa.iter().copied()).unwrap();
+            // With the empty suffix first.
+            verify_suffix_array(text, [text.len()].into_iter().chain(sa.iter().copied())).unwrap();
+
+            assert!(verify_suffix_array(text, sa[1..].iter().copied()).is_err());
+            assert!(verify_suffix_array(text, sa.iter().copied().chain([text.len()])).is_err());
+            assert!(verify_suffix_array(text, sa.iter().copied().chain([text.len() + 1])).is_err());
+            assert!(verify_suffix_array(text, sa.iter().copied().chain([sa[0]])).is_err());
+            if sa.len() > 1 {
+                let mut swapped = sa.clone();
+                swapped.swap(0, sa.len() - 1);
+                assert!(verify_suffix_array(text, swapped).is_err());
+            }
+        }
+        verify_suffix_array(b"", []).unwrap();
+    }
+
+    #[test]
+    fn test_compare_suffixes() {
+        let text = b"abcdefghijabcdefghijk";
+        assert_eq!(compare_suffixes(text, 0, 10), Ordering::Less);
+        assert_eq!(compare_suffixes(text, 10, 0), Ordering::Greater);
+        assert_eq!(compare_suffixes(text, 3, 3), Ordering::Equal);
+        assert_eq!(compare_suffixes(text, 21, 0), Ordering::Less);
+    }
+}
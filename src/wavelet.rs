@@ -0,0 +1,363 @@
+use std::io::Write;
+
+/// Number of words per block of the rank samples.
+const BLOCK_WORDS: usize = 8;
+
+/// Wavelet tree over a byte sequence such as the BWT,
+/// supporting access, rank, and select queries in `O(log σ)` time, where `σ = 256`.
+///
+/// The tree is a perfect binary tree of depth 8 over all the byte values,
+/// whose node at depth `l` stores the `l`-th most significant bits of the symbols in its subtree.
+/// It takes about `n` bytes for the bits and `n / 8` bytes for the rank samples,
+/// where `n` is the length of the sequence.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use small_bwt::{BwtBuilder, WaveletTreeBuilder};
+///
+/// let mut wrt = WaveletTreeBuilder::new();
+/// BwtBuilder::new(b"abracadabra$")?.build(&mut wrt)?;
+/// let wt = wrt.finish();
+///
+/// // The BWT is "ard$rcaaaabb".
+/// assert_eq!(wt.len(), 12);
+/// assert_eq!(wt.access(4), b'r');
+/// assert_eq!(wt.rank(b'a', 8), 3);
+/// assert_eq!(wt.select(b'b', 1), Some(11));
+/// assert_eq!(wt.select(b'b', 2), None);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WaveletTree {
+    // nodes[v - 1] is the bit vector of node v in heap order, i.e.,
+    // node 1 is the root and nodes 2v and 2v + 1 are the children of node v.
+    nodes: Vec<BitVector>,
+    len: usize,
+}
+
+impl WaveletTree {
+    /// Builds the wavelet tree over `seq`.
+    ///
+    /// # Arguments
+    ///
+    /// * `seq` - The byte sequence.
+    pub fn new(seq: &[u8]) -> Self {
+        let mut builder = WaveletTreeBuilder::new();
+        builder.push(seq);
+        builder.finish()
+    }
+
+    /// Returns the length of the sequence.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the sequence is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the `i`-th symbol.
+    ///
+    /// # Arguments
+    ///
+    /// * `i` - The position.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `i` is not less than the length of the sequence.
+    pub fn access(&self, mut i: usize) -> u8 {
+        assert!(i < self.len, "position {i} must be less than {}.", self.len);
+        let mut v = 1;
+        for _ in 0..8 {
+            let node = &self.nodes[v - 1];
+            let bit = node.get(i);
+            i = node.rank(bit, i);
+            v = 2 * v + bit as usize;
+        }
+        (v - 256) as u8
+    }
+
+    /// Returns the number of occurrences of `c` in the first `i` symbols.
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - The symbol.
+    /// * `i` - The position, which must be at most the length of the sequence.
+    ///
+    /// # Panics
+    ///
+    /// It panics if `i` is greater than the length of the sequence.
+    pub fn rank(&self, c: u8, mut i: usize) -> usize {
+        assert!(i <= self.len, "position {i} must be at most {}.", self.len);
+        let mut v = 1;
+        for l in 0..8 {
+            let bit = (c >> (7 - l)) & 1 == 1;
+            i = self.nodes[v - 1].rank(bit, i);
+            v = 2 * v + bit as usize;
+        }
+        i
+    }
+
+    /// Returns the position of the `k`-th occurrence of `c`, starting from zero,
+    /// or `None` if `c` occurs at most `k` times.
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - The symbol.
+    /// * `k` - The rank of the occurrence.
+    pub fn select(&self, c: u8, k: usize) -> Option<usize> {
+        if self.rank(c, self.len) <= k {
+            return None;
+        }
+        let mut i = k;
+        let mut v = 256 + c as usize;
+        while v > 1 {
+            let bit = v & 1 == 1;
+            v /= 2;
+            i = self.nodes[v - 1].select(bit, i);
+        }
+        Some(i)
+    }
+}
+
+/// Writer adapter building a [`WaveletTree`] from a byte stream such as the BWT,
+/// without holding the stream itself.
+///
+/// The bits of every symbol are appended to the nodes on its path as the symbol is written,
+/// and the rank samples are computed by [`WaveletTreeBuilder::finish`].
+pub struct WaveletTreeBuilder {
+    nodes: Vec<BitVectorBuilder>,
+    len: usize,
+}
+
+impl WaveletTreeBuilder {
+    /// Creates a new builder.
+    pub fn new() -> Self {
+        Self {
+            nodes: (0..255).map(|_| BitVectorBuilder::default()).collect(),
+            len: 0,
+        }
+    }
+
+    /// Builds the wavelet tree over the bytes written so far.
+    pub fn finish(self) -> WaveletTree {
+        WaveletTree {
+            nodes: self
+                .nodes
+                .into_iter()
+                .map(BitVectorBuilder::finish)
+                .collect(),
+            len: self.len,
+        }
+    }
+
+    fn push(&mut self, buf: &[u8]) {
+        for &c in buf {
+            let mut v = 1;
+            for l in 0..8 {
+                let bit = (c >> (7 - l)) & 1 == 1;
+                self.nodes[v - 1].push(bit);
+                v = 2 * v + bit as usize;
+            }
+        }
+        self.len += buf.len();
+    }
+}
+
+impl Default for WaveletTreeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for WaveletTreeBuilder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.push(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Builder of [`BitVector`] appending bits one by one.
+#[derive(Default)]
+pub struct BitVectorBuilder {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitVectorBuilder {
+    pub fn push(&mut self, bit: bool) {
+        if self.len % 64 == 0 {
+            self.words.push(0);
+        }
+        if bit {
+            *self.words.last_mut().unwrap() |= 1 << (self.len % 64);
+        }
+        self.len += 1;
+    }
+
+    pub fn finish(self) -> BitVector {
+        // blocks[b] is the number of ones in words[..b * BLOCK_WORDS].
+        let mut blocks = Vec::with_capacity(self.words.len() / BLOCK_WORDS + 2);
+        let mut ones = 0;
+        for (i, &w) in self.words.iter().enumerate() {
+            if i % BLOCK_WORDS == 0 {
+                blocks.push(ones);
+            }
+            ones += w.count_ones() as usize;
+        }
+        blocks.push(ones);
+        BitVector {
+            words: self.words,
+            blocks,
+            len: self.len,
+        }
+    }
+}
+
+/// Bit vector with rank samples per block of [`BLOCK_WORDS`] words.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitVector {
+    words: Vec<u64>,
+    blocks: Vec<usize>,
+    len: usize,
+}
+
+impl BitVector {
+    pub fn get(&self, i: usize) -> bool {
+        (self.words[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    /// Returns the number of ones in the first `i` bits.
+    pub fn rank1(&self, i: usize) -> usize {
+        let w = i / 64;
+        let b = w / BLOCK_WORDS;
+        let mut ones = self.blocks[b];
+        for &x in &self.words[b * BLOCK_WORDS..w] {
+            ones += x.count_ones() as usize;
+        }
+        if i % 64 != 0 {
+            ones += (self.words[w] & ((1 << (i % 64)) - 1)).count_ones() as usize;
+        }
+        ones
+    }
+
+    /// Returns the number of `bit`s in the first `i` bits.
+    fn rank(&self, bit: bool, i: usize) -> usize {
+        let ones = self.rank1(i);
+        if bit {
+            ones
+        } else {
+            i - ones
+        }
+    }
+
+    /// Returns the position of the `k`-th `bit`, starting from zero, which must exist.
+    fn select(&self, bit: bool, k: usize) -> usize {
+        // The number of bits in the first `i` bits, where `i` must be a multiple of 64.
+        let count = |i: usize, ones: usize| if bit { ones } else { i - ones };
+
+        // Finds the last block with at most k bits before it.
+        let mut lo = 0;
+        let mut hi = self.blocks.len();
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if count(mid * BLOCK_WORDS * 64, self.blocks[mid]) <= k {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        let mut w = lo * BLOCK_WORDS;
+        let mut k = k - count(w * 64, self.blocks[lo]);
+        loop {
+            let x = if bit { self.words[w] } else { !self.words[w] };
+            let c = x.count_ones() as usize;
+            if k < c {
+                let mut x = x;
+                for _ in 0..k {
+                    x &= x - 1;
+                }
+                let i = w * 64 + x.trailing_zeros() as usize;
+                debug_assert!(i < self.len);
+                return i;
+            }
+            k -= c;
+            w += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wavelet_tree() {
+        let seq: Vec<u8> = (0..2000u32).map(|i| (i * 7 % 13 + i / 300) as u8).collect();
+        let wt = WaveletTree::new(&seq);
+        assert_eq!(wt.len(), seq.len());
+        for (i, &c) in seq.iter().enumerate() {
+            assert_eq!(wt.access(i), c);
+        }
+        for c in 0..=20u8 {
+            let mut count = 0;
+            for i in 0..=seq.len() {
+                assert_eq!(wt.rank(c, i), count);
+                if i < seq.len() && seq[i] == c {
+                    assert_eq!(wt.select(c, count), Some(i));
+                    count += 1;
+                }
+            }
+            assert_eq!(wt.select(c, count), None);
+        }
+    }
+
+    #[test]
+    fn test_wavelet_tree_builder() {
+        let mut builder = WaveletTreeBuilder::new();
+        builder.write_all(b"ard$").unwrap();
+        builder.write_all(b"rcaaaabb").unwrap();
+        let wt = builder.finish();
+        let expected = b"ard$rcaaaabb";
+        assert_eq!(wt.len(), expected.len());
+        for (i, &c) in expected.iter().enumerate() {
+            assert_eq!(wt.access(i), c);
+        }
+        assert_eq!(wt.rank(0xff, 12), 0);
+        assert_eq!(wt.select(0xff, 0), None);
+    }
+
+    #[test]
+    fn test_wavelet_tree_empty() {
+        let wt = WaveletTree::new(&[]);
+        assert!(wt.is_empty());
+        assert_eq!(wt.rank(b'a', 0), 0);
+        assert_eq!(wt.select(b'a', 0), None);
+    }
+
+    #[test]
+    fn test_bit_vector() {
+        let mut builder = BitVectorBuilder::default();
+        let bits: Vec<bool> = (0..1500).map(|i| i % 3 == 0 || i % 7 == 0).collect();
+        for &bit in &bits {
+            builder.push(bit);
+        }
+        let bv = builder.finish();
+        let mut ones = 0;
+        for (i, &bit) in bits.iter().enumerate() {
+            assert_eq!(bv.get(i), bit);
+            assert_eq!(bv.rank(true, i), ones);
+            assert_eq!(bv.select(bit, bv.rank(bit, i)), i);
+            ones += bit as usize;
+        }
+        assert_eq!(bv.rank(true, bits.len()), ones);
+    }
+}
@@ -1,11 +1,14 @@
 use std::error::Error;
 use std::fs::File;
+use std::io::BufReader;
 use std::io::BufWriter;
 use std::io::Read;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
 use std::time::Instant;
 
 use clap::Parser;
-use small_bwt::BwtBuilder;
+use small_bwt::{AlphabetPreset, BwtBuilder, BwtFileWriter, BwtHeader, Checkpoint};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -15,8 +18,13 @@ use small_bwt::BwtBuilder;
     long_about = "Constructs the BWT of the given text."
 )]
 struct Args {
-    #[arg(short = 'i', long, help = "Path to an input text file")]
-    input_file: String,
+    #[arg(
+        short = 'i',
+        long,
+        required = true,
+        help = "Path to an input text file, given several times to concatenate files with \\x01 and add \\0, writing their ranges to <OUTPUT_FILE>.inputs"
+    )]
+    input_file: Vec<String>,
 
     #[arg(
         short = 'o',
@@ -25,38 +33,283 @@ struct Args {
     )]
     output_file: Option<String>,
 
+    #[arg(
+        long,
+        help = "Flag to write the raw BWT without the header of the self-describing format"
+    )]
+    raw: bool,
+
     #[arg(short = 't', long, help = "Flag to add a special teriminator \\0")]
     teriminator: bool,
+
+    #[arg(
+        short = 'b',
+        long,
+        help = "Flag to transform arbitrary binary content with an implicit sentinel instead of a terminator"
+    )]
+    binary: bool,
+
+    #[arg(
+        short = 'f',
+        long,
+        help = "Flag to parse the input as FASTA, concatenating sequences with $ and adding \\0"
+    )]
+    fasta: bool,
+
+    #[arg(
+        long,
+        requires = "fasta",
+        help = "Flag to follow each FASTA sequence with its reverse complement for an FMD-index"
+    )]
+    fmd: bool,
+
+    #[arg(
+        long,
+        requires = "fasta",
+        conflicts_with = "fmd",
+        help = "Flag to validate FASTA sequences as amino acids in the IUPAC codes"
+    )]
+    protein: bool,
+
+    #[arg(
+        long,
+        help = "Minimum length of the runs of N whose suffixes are sorted separately, such as gaps in genomes"
+    )]
+    skip_n_runs: Option<usize>,
+
+    #[arg(short = 'p', long, help = "Flag to process cuts in parallel")]
+    parallel: bool,
+
+    #[arg(
+        short = 's',
+        long,
+        help = "Flag to bucket suffixes in a single scan, storing all suffix positions"
+    )]
+    single_pass: bool,
+
+    #[arg(
+        long,
+        help = "Flag to bucket large chunks by their first two bytes at the top level of the sort"
+    )]
+    wide_radix: bool,
+
+    #[arg(
+        long,
+        help = "Flag to gather the next chunk in another thread while sorting the current one"
+    )]
+    pipeline: bool,
+
+    #[arg(
+        short = 'k',
+        long,
+        default_value_t = 0,
+        help = "Length of k-mers (up to 3) whose frequencies are precomputed to generate cuts"
+    )]
+    kmer_len: usize,
+
+    #[arg(
+        long,
+        help = "Directory to spill chunks to, enabling the external-memory mode"
+    )]
+    spill_dir: Option<std::path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Flag to sort all suffixes at once by SA-IS, using O(n) words of memory"
+    )]
+    sais: bool,
+
+    #[arg(
+        long,
+        help = "Flag to use prefix-free parsing with windows of 10 bytes and modulus 100, for repetitive texts"
+    )]
+    pfp: bool,
+
+    #[arg(
+        long,
+        help = "Number of symbols by which rotations are sorted, building the context-bounded BWT (k-BWT)"
+    )]
+    context_order: Option<usize>,
+
+    #[arg(
+        long,
+        default_value_t = 1024,
+        help = "Memory budget for chunks in MiB in the external-memory mode"
+    )]
+    budget_mib: usize,
+
+    #[arg(
+        long,
+        requires = "output_file",
+        help = "Flag to write a checkpoint to <OUTPUT_FILE>.ckpt after each chunk, and resume from it if it exists"
+    )]
+    resume: bool,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
-    let text = read_text(&args.input_file, args.teriminator)?;
-    small_bwt::verify_terminator(&text).map_err(|e| {
-        format!("Got error while verifying terminal character: {e} Consider using -t option.")
-    })?;
+    if args.fasta && args.input_file.len() > 1 {
+        return Err("FASTA input must be a single file.".into());
+    }
+    let text = if args.input_file.len() > 1 {
+        let files = args
+            .input_file
+            .iter()
+            .map(|path| File::open(path).map(BufReader::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        let concat = small_bwt::concat_inputs(files, b'\x01')?;
+        eprintln!("Number of input files: {}", concat.inputs.len());
+        if let Some(output_file) = args.output_file.as_ref() {
+            let mut wrt = BufWriter::new(File::create(format!("{output_file}.inputs"))?);
+            for (path, range) in args.input_file.iter().zip(&concat.inputs) {
+                writeln!(wrt, "{}\t{}\t{path}", range.start, range.end)?;
+            }
+            wrt.flush()?;
+        }
+        concat.text
+    } else if args.fasta {
+        let file = BufReader::new(File::open(&args.input_file[0])?);
+        let parsed = if args.fmd {
+            small_bwt::parse_fasta_fmd(file, b'$')?
+        } else {
+            small_bwt::parse_fasta(file, b'$')?
+        };
+        eprintln!("Number of FASTA records: {}", parsed.records.len());
+        if args.protein {
+            AlphabetPreset::AminoAcidsIupac.validate(&parsed.text)?;
+        }
+        parsed.text
+    } else {
+        read_text(&args.input_file[0], args.teriminator)?
+    };
+    if !args.binary {
+        let report = small_bwt::diagnose_terminator(&text, 10)?;
+        if !report.is_valid() {
+            eprintln!(
+                "Smallest byte: {:#04x} ({} occurrences)",
+                report.smallest, report.n_smallest
+            );
+            eprintln!(
+                "Bytes no greater than the last byte {:#04x} before the end: {} (first positions: {:?})",
+                report.terminator, report.n_violations, report.violations
+            );
+            match report.free_terminators.last() {
+                Some(&c) => eprintln!("Free terminators: 0x00 to {c:#04x}"),
+                None => eprintln!("Free terminators: none"),
+            }
+            return Err(
+                "The text does not end with a unique smallest terminator. Consider using -t or -b option."
+                    .into(),
+            );
+        }
+    }
 
-    let builder = BwtBuilder::new(&text)?.verbose(true);
-    let elapsed_ms = if let Some(output_file) = args.output_file.as_ref() {
+    let checkpoint_path = args
+        .output_file
+        .as_ref()
+        .filter(|_| args.resume)
+        .map(|output_file| PathBuf::from(format!("{output_file}.ckpt")));
+    // The text has been diagnosed above unless it is binary.
+    let mut builder = BwtBuilder::new_unchecked(&text)?;
+    if args.binary {
+        builder = builder.implicit_sentinel(b'\0');
+    }
+    let mut builder = builder
+        .verbose(true)
+        .parallel(args.parallel)
+        .single_pass(args.single_pass)
+        .wide_radix(args.wide_radix)
+        .pipeline(args.pipeline)
+        .sais(args.sais)
+        .kmer_table(args.kmer_len)?;
+    if args.pfp {
+        builder = builder.prefix_free_parsing(10, 100)?;
+    }
+    if let Some(min_len) = args.skip_n_runs {
+        builder = builder.skip_n_runs(min_len)?;
+    }
+    if let Some(k) = args.context_order {
+        builder = builder.context_order(k)?;
+    }
+    if let Some(spill_dir) = args.spill_dir.as_ref() {
+        builder = builder.external_memory(spill_dir, args.budget_mib << 20)?;
+    }
+    let mut resumed = None;
+    if let Some(path) = checkpoint_path.as_ref() {
+        builder = builder.checkpoint(path);
+        if path.exists() {
+            let checkpoint = Checkpoint::load(path)?;
+            eprintln!(
+                "Resuming from the checkpoint: {}/{} cuts done",
+                checkpoint.n_cuts_done,
+                checkpoint.cuts.len()
+            );
+            builder = builder.resume(&checkpoint)?;
+            resumed = Some(checkpoint);
+        }
+    }
+    let (elapsed_ms, stats) = if let Some(output_file) = args.output_file.as_ref() {
         let now = Instant::now();
-        let writer = BufWriter::new(File::create(output_file)?);
-        builder.build(writer)?;
-        now.elapsed().as_millis()
+        let header_len = if args.raw { 0 } else { BwtHeader::LEN as u64 };
+        let file = match resumed.as_ref() {
+            Some(checkpoint) => {
+                // Drops the bytes written after the checkpoint.
+                let mut file = File::options().read(true).write(true).open(output_file)?;
+                file.set_len(header_len + checkpoint.n_bytes_written as u64)?;
+                file.seek(SeekFrom::End(0))?;
+                file
+            }
+            None => File::create(output_file)?,
+        };
+        let writer = BufWriter::new(file);
+        let stats = if args.raw {
+            builder.build(writer)?
+        } else {
+            let mut writer = match resumed.as_ref() {
+                Some(_) => {
+                    let mut written = BufReader::new(File::open(output_file)?);
+                    written.seek(SeekFrom::Start(header_len))?;
+                    BwtFileWriter::resume(writer, written)?
+                }
+                None => BwtFileWriter::new(writer)?,
+            };
+            let stats = builder.build(&mut writer)?;
+            writer.finish(&stats)?;
+            stats
+        };
+        if let Some(path) = checkpoint_path.as_ref() {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        (now.elapsed().as_millis(), stats)
     } else {
         eprintln!("VERIFICATION MODE: The BWT will not be saved.");
         let now = Instant::now();
-        let mut bwt = Vec::with_capacity(text.len());
-        builder.build(&mut bwt)?;
+        let mut bwt = Vec::with_capacity(text.len() + 1);
+        let stats = builder.build(&mut bwt)?;
         let elapsed_ms = now.elapsed().as_millis();
-        let decoded = small_bwt::decode_bwt(&bwt)?;
+        let decoded = if args.binary {
+            small_bwt::decode_bwt_with_sentinel(&bwt, stats.primary_index)?
+        } else if let Some(k) = stats.context_order {
+            small_bwt::decode_context_bwt(&bwt, stats.primary_index, k)?
+        } else {
+            small_bwt::decode_bwt(&bwt)?
+        };
         if decoded != text {
             eprintln!("ERROR: The decoded text is different from the original text. The system will be broken.");
         } else {
             eprintln!("NO PROBLEM: The decoded text is the same as the original text. The system will be fine.");
         }
-        elapsed_ms
+        (elapsed_ms, stats)
     };
+    println!("Number of cuts: {}", stats.n_cuts);
+    println!("Largest chunk: {}", stats.max_chunk_len);
+    println!("Primary index: {}", stats.primary_index);
+    println!("Number of runs: {}", stats.runs.n_runs);
+    println!("Longest run: {}", stats.runs.max_run_len);
+    println!("Mean run length: {}", stats.runs.mean_run_len());
     println!("Elapsed sec: {}", elapsed_ms as f64 / 1000.0);
 
     Ok(())
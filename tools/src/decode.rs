@@ -1,9 +1,11 @@
 use std::error::Error;
 use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
 use std::io::Read;
-use std::io::Write;
 
 use clap::Parser;
+use small_bwt::BwtFileReader;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -18,23 +20,62 @@ struct Args {
 
     #[arg(short = 'o', long, help = "Path to an output text file")]
     output_file: String,
+
+    #[arg(
+        short = 'p',
+        long,
+        help = "Flag to decode in parallel, loading the whole BWT into memory"
+    )]
+    parallel: bool,
+
+    #[arg(
+        long,
+        help = "Flag to read the raw BWT without the header of the self-describing format"
+    )]
+    raw: bool,
+
+    #[arg(
+        long,
+        requires = "raw",
+        help = "Position of the implicit sentinel in the raw BWT built with the -b option of bwt"
+    )]
+    sentinel_index: Option<usize>,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
-    let bwt = read_text(&args.input_file)?;
-    let text = small_bwt::decode_bwt(&bwt)?;
-
-    let mut writer = File::create(&args.output_file)?;
-    writer.write_all(&text)?;
+    if !args.raw {
+        let mut reader = BwtFileReader::new(BufReader::new(File::open(&args.input_file)?))?;
+        let header = reader.header().clone();
+        let described =
+            header.implicit_sentinel || header.alphabet.is_some() || header.context_order.is_some();
+        if described || args.parallel {
+            let mut bwt = Vec::with_capacity(header.len);
+            reader.read_to_end(&mut bwt)?;
+            let text = if described {
+                header.decode(&bwt)?
+            } else {
+                small_bwt::decode_bwt_parallel(&bwt)?
+            };
+            std::fs::write(&args.output_file, text)?;
+        } else {
+            let writer = BufWriter::new(File::create(&args.output_file)?);
+            small_bwt::decode_bwt_from_reader(reader, writer)?;
+        }
+    } else if let Some(sentinel_index) = args.sentinel_index {
+        let bwt = std::fs::read(&args.input_file)?;
+        let text = small_bwt::decode_bwt_with_sentinel(&bwt, sentinel_index)?;
+        std::fs::write(&args.output_file, text)?;
+    } else if args.parallel {
+        let bwt = std::fs::read(&args.input_file)?;
+        let text = small_bwt::decode_bwt_parallel(&bwt)?;
+        std::fs::write(&args.output_file, text)?;
+    } else {
+        let reader = File::open(&args.input_file)?;
+        let writer = BufWriter::new(File::create(&args.output_file)?);
+        small_bwt::decode_bwt_from_reader(reader, writer)?;
+    }
 
     Ok(())
 }
-
-fn read_text(input_file: &str) -> Result<Vec<u8>, Box<dyn Error>> {
-    let mut file: File = File::open(input_file)?;
-    let mut text = Vec::new();
-    file.read_to_end(&mut text)?;
-    Ok(text)
-}